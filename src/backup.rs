@@ -0,0 +1,605 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    expiry::unix_timestamp_ms,
+    file::{fsync_directory, LARGE_VALUES_FOLDER, MAGIC_BYTES, SEGMENTS_FOLDER},
+    HashMap, HashSet, Keyspace,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use lsm_tree::coding::{Decode, DecodeError, Encode, EncodeError};
+use std::{
+    fs::{create_dir, create_dir_all, remove_dir_all, remove_file, rename, File},
+    hash::Hasher,
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+const BACKUPS_FOLDER: &str = "backups";
+const SHARED_FOLDER: &str = "shared";
+const CHECKPOINT_FOLDER: &str = "tmp_checkpoint";
+
+/// Backup ID
+pub type BackupId = u64;
+
+/// A file of a backup
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct FileEntry {
+    /// Path inside the keyspace folder, separated by `/`
+    path: String,
+
+    size: u64,
+
+    /// Modification time in milliseconds,
+    /// used to detect unchanged segment files without reading them
+    modified: u64,
+
+    checksum: u64,
+}
+
+impl FileEntry {
+    /// Name of the file in the shared folder
+    ///
+    /// Files are stored by content, so unchanged files are shared between backups.
+    fn shared_name(&self) -> String {
+        format!("{:016x}_{}", self.checksum, self.size)
+    }
+
+    /// Returns `true` if the file is immutable (segment, blob or large value file).
+    fn is_immutable(&self) -> bool {
+        self.path
+            .split('/')
+            .any(|x| x == SEGMENTS_FOLDER || x == LARGE_VALUES_FOLDER)
+    }
+}
+
+/// List of files of a backup
+#[derive(Debug, Eq, PartialEq)]
+struct BackupManifest {
+    timestamp: u64,
+    files: Vec<FileEntry>,
+}
+
+impl Encode for BackupManifest {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        writer.write_all(MAGIC_BYTES)?;
+
+        writer.write_u64::<BigEndian>(self.timestamp)?;
+
+        // NOTE: Truncation is okay and actually needed
+        #[allow(clippy::cast_possible_truncation)]
+        writer.write_u32::<BigEndian>(self.files.len() as u32)?;
+
+        for file in &self.files {
+            // NOTE: Truncation is okay and actually needed
+            #[allow(clippy::cast_possible_truncation)]
+            writer.write_u16::<BigEndian>(file.path.len() as u16)?;
+            writer.write_all(file.path.as_bytes())?;
+
+            writer.write_u64::<BigEndian>(file.size)?;
+            writer.write_u64::<BigEndian>(file.modified)?;
+            writer.write_u64::<BigEndian>(file.checksum)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for BackupManifest {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        let mut header = [0; MAGIC_BYTES.len()];
+        reader.read_exact(&mut header)?;
+
+        if header != MAGIC_BYTES {
+            return Err(DecodeError::InvalidHeader("BackupManifest"));
+        }
+
+        let timestamp = reader.read_u64::<BigEndian>()?;
+
+        let file_count = reader.read_u32::<BigEndian>()?;
+        let mut files = Vec::with_capacity(file_count as usize);
+
+        for _ in 0..file_count {
+            let path_len = reader.read_u16::<BigEndian>()?;
+            let mut path = vec![0; path_len.into()];
+            reader.read_exact(&mut path)?;
+            let path = std::str::from_utf8(&path)?.to_owned();
+
+            let size = reader.read_u64::<BigEndian>()?;
+            let modified = reader.read_u64::<BigEndian>()?;
+            let checksum = reader.read_u64::<BigEndian>()?;
+
+            files.push(FileEntry {
+                path,
+                size,
+                modified,
+                checksum,
+            });
+        }
+
+        Ok(Self { timestamp, files })
+    }
+}
+
+/// Information about a backup
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BackupInfo {
+    /// Backup ID
+    pub id: BackupId,
+
+    /// Unix timestamp (in milliseconds) of when the backup was created
+    pub timestamp: u64,
+
+    /// Amount of files in the backup
+    pub file_count: usize,
+
+    /// Size of the backed up keyspace in bytes
+    ///
+    /// Files are shared between backups, so the disk space used
+    /// by the backup directory may be lower than the sum of all backups.
+    pub size: u64,
+}
+
+/// Hashes a file, optionally copying it at the same time.
+fn hash_file(src: &Path, dest: Option<&Path>) -> std::io::Result<u64> {
+    let mut reader = BufReader::with_capacity(64 * 1_024, File::open(src)?);
+    let mut writer = dest.map(File::create).transpose()?.map(BufWriter::new);
+
+    let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+
+    loop {
+        let chunk = reader.fill_buf()?;
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        hasher.update(chunk);
+
+        if let Some(writer) = &mut writer {
+            writer.write_all(chunk)?;
+        }
+
+        let len = chunk.len();
+        reader.consume(len);
+    }
+
+    if let Some(writer) = writer {
+        writer.into_inner()?.sync_all()?;
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Collects all files in a folder, with their path relative to the base folder.
+#[allow(clippy::expect_used)]
+pub fn collect_files(
+    base: &Path,
+    folder: &Path,
+    files: &mut Vec<(String, PathBuf)>,
+) -> std::io::Result<()> {
+    for dirent in std::fs::read_dir(folder)? {
+        let dirent = dirent?;
+        let path = dirent.path();
+
+        if dirent.file_type()?.is_dir() {
+            collect_files(base, &path, files)?;
+        } else {
+            let relative_path = path
+                .strip_prefix(base)
+                .expect("should be inside base folder")
+                .iter()
+                .map(|x| x.to_str().expect("should be valid file name"))
+                .collect::<Vec<_>>()
+                .join("/");
+
+            files.push((relative_path, path));
+        }
+    }
+
+    Ok(())
+}
+
+/// Keeps numbered, incremental backups of a keyspace
+///
+/// Each backup is a consistent [checkpoint](Keyspace::checkpoint) of the keyspace.
+/// Files are stored by content, so unchanged segment and blob files
+/// are shared between backups, and only stored once.
+///
+/// # Examples
+///
+/// ```
+/// # use fjall::{BackupEngine, Config, PartitionCreateOptions};
+/// #
+/// # let folder = tempfile::tempdir()?;
+/// # let backup_folder = tempfile::tempdir()?;
+/// # let restore_folder = tempfile::tempdir()?;
+/// # let keyspace = Config::new(&folder).open()?;
+/// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+/// let mut backups = BackupEngine::open(&backup_folder)?;
+///
+/// partition.insert("a", "abc")?;
+/// let backup_id = backups.create_backup(&keyspace)?;
+///
+/// let restore_path = restore_folder.path().join("restored");
+/// backups.restore(backup_id, &restore_path)?;
+///
+/// let keyspace = Config::new(&restore_path).open()?;
+/// let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+/// assert_eq!(b"abc", &*partition.get("a")?.unwrap());
+/// #
+/// # Ok::<_, fjall::Error>(())
+/// ```
+pub struct BackupEngine {
+    path: PathBuf,
+}
+
+impl BackupEngine {
+    /// Opens a backup directory, creating it if it does not exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if an IO error occurred.
+    pub fn open<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+
+        create_dir_all(path.join(BACKUPS_FOLDER))?;
+        create_dir_all(path.join(SHARED_FOLDER))?;
+        fsync_directory(&path)?;
+
+        // NOTE: Remove leftovers of an interrupted backup
+        let checkpoint_folder = path.join(CHECKPOINT_FOLDER);
+        if checkpoint_folder.try_exists()? {
+            remove_dir_all(&checkpoint_folder)?;
+        }
+
+        Ok(Self { path })
+    }
+
+    fn manifest_path(&self, id: BackupId) -> PathBuf {
+        self.path.join(BACKUPS_FOLDER).join(id.to_string())
+    }
+
+    fn shared_path(&self, file: &FileEntry) -> PathBuf {
+        self.path.join(SHARED_FOLDER).join(file.shared_name())
+    }
+
+    fn backup_ids(&self) -> crate::Result<Vec<BackupId>> {
+        let mut ids = vec![];
+
+        for dirent in std::fs::read_dir(self.path.join(BACKUPS_FOLDER))? {
+            let dirent = dirent?;
+
+            // NOTE: Skip temporary manifests of interrupted backups
+            if let Some(id) = dirent.file_name().to_str().and_then(|x| x.parse().ok()) {
+                ids.push(id);
+            }
+        }
+
+        ids.sort_unstable();
+
+        Ok(ids)
+    }
+
+    fn load_manifest(&self, id: BackupId) -> crate::Result<BackupManifest> {
+        let path = self.manifest_path(id);
+
+        if !path.try_exists()? {
+            return Err(crate::Error::BackupNotFound(id));
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        Ok(BackupManifest::decode_from(&mut reader)?)
+    }
+
+    /// Lists all backups, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if an IO error occurred.
+    pub fn list_backups(&self) -> crate::Result<Vec<BackupInfo>> {
+        self.backup_ids()?
+            .into_iter()
+            .map(|id| {
+                let manifest = self.load_manifest(id)?;
+
+                Ok(BackupInfo {
+                    id,
+                    timestamp: manifest.timestamp,
+                    file_count: manifest.files.len(),
+                    size: manifest.files.iter().map(|x| x.size).sum(),
+                })
+            })
+            .collect()
+    }
+
+    /// Creates a new backup of the keyspace, returning its ID.
+    ///
+    /// Segment and blob files that are unchanged since the latest backup are not copied again.
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if an IO error occurred.
+    pub fn create_backup(&mut self, keyspace: &Keyspace) -> crate::Result<BackupId> {
+        let latest_id = self.backup_ids()?.last().copied();
+
+        let previous_files = match latest_id {
+            Some(id) => self
+                .load_manifest(id)?
+                .files
+                .into_iter()
+                .filter(FileEntry::is_immutable)
+                .map(|x| (x.path.clone(), x))
+                .collect::<HashMap<_, _>>(),
+            None => HashMap::default(),
+        };
+
+        let id = latest_id.unwrap_or_default() + 1;
+        log::info!("Creating backup {id}");
+
+        // NOTE: The checkpoint is created inside the backup directory,
+        // so its files can be moved into the shared folder
+        let checkpoint_folder = self.path.join(CHECKPOINT_FOLDER);
+        keyspace.checkpoint(&checkpoint_folder)?;
+
+        let mut checkpoint_files = vec![];
+        collect_files(
+            &checkpoint_folder,
+            &checkpoint_folder,
+            &mut checkpoint_files,
+        )?;
+
+        let mut files = Vec::with_capacity(checkpoint_files.len());
+
+        for (relative_path, path) in checkpoint_files {
+            let metadata = std::fs::metadata(&path)?;
+
+            // NOTE: Truncation is fine for the next few million years
+            #[allow(clippy::cast_possible_truncation)]
+            let modified = metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64;
+
+            let previous = previous_files.get(&relative_path).filter(|x| {
+                x.size == metadata.len()
+                    && x.modified == modified
+                    && self.shared_path(x).try_exists().unwrap_or_default()
+            });
+
+            let entry = if let Some(previous) = previous {
+                log::trace!("Backup: {relative_path} is unchanged");
+                previous.clone()
+            } else {
+                let entry = FileEntry {
+                    checksum: hash_file(&path, None)?,
+                    path: relative_path,
+                    size: metadata.len(),
+                    modified,
+                };
+
+                let shared_path = self.shared_path(&entry);
+
+                if !shared_path.try_exists()? {
+                    log::trace!(
+                        "Backup: storing {} as {}",
+                        entry.path,
+                        shared_path.display()
+                    );
+                    File::open(&path)?.sync_all()?;
+                    rename(&path, &shared_path)?;
+                }
+
+                entry
+            };
+
+            files.push(entry);
+        }
+
+        fsync_directory(self.path.join(SHARED_FOLDER))?;
+
+        let manifest = BackupManifest {
+            timestamp: unix_timestamp_ms(),
+            files,
+        };
+
+        // IMPORTANT: Write the manifest atomically, a backup only exists once its manifest does
+        let manifest_path = self.manifest_path(id);
+        let temp_path = manifest_path.with_extension("tmp");
+        {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+            manifest.encode_into(&mut writer)?;
+            writer
+                .into_inner()
+                .map_err(std::io::IntoInnerError::into_error)?
+                .sync_all()?;
+        }
+        rename(&temp_path, &manifest_path)?;
+        fsync_directory(self.path.join(BACKUPS_FOLDER))?;
+
+        remove_dir_all(&checkpoint_folder)?;
+
+        log::info!("Created backup {id}");
+
+        Ok(id)
+    }
+
+    /// Restores a backup into the given directory, which can then be opened as a keyspace.
+    ///
+    /// The directory must not exist yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if the backup does not exist, a file of the backup is corrupted,
+    /// or an IO error occurred.
+    pub fn restore<P: AsRef<Path>>(&self, id: BackupId, path: P) -> crate::Result<()> {
+        let path = path.as_ref();
+        let manifest = self.load_manifest(id)?;
+
+        log::info!("Restoring backup {id} to {}", path.display());
+
+        if let Some(parent) = path.parent() {
+            create_dir_all(parent)?;
+        }
+        create_dir(path)?;
+
+        let mut folders = HashSet::default();
+
+        for file in &manifest.files {
+            let dest = file
+                .path
+                .split('/')
+                .fold(path.to_path_buf(), |acc, x| acc.join(x));
+
+            if let Some(parent) = dest.parent() {
+                create_dir_all(parent)?;
+                folders.insert(parent.to_path_buf());
+            }
+
+            let checksum = hash_file(&self.shared_path(file), Some(&dest))?;
+
+            if checksum != file.checksum {
+                log::error!(
+                    "Backup {id}: checksum mismatch for {}, expected: {}, got: {checksum}",
+                    file.path,
+                    file.checksum,
+                );
+                return Err(crate::Error::BackupCorrupted(id));
+            }
+        }
+
+        for folder in folders {
+            fsync_directory(folder)?;
+        }
+        fsync_directory(path)?;
+
+        log::info!("Restored backup {id}");
+
+        Ok(())
+    }
+
+    /// Verifies the checksums of all files of a backup.
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if the backup does not exist, a file of the backup is corrupted,
+    /// or an IO error occurred.
+    pub fn verify(&self, id: BackupId) -> crate::Result<()> {
+        for file in self.load_manifest(id)?.files {
+            if hash_file(&self.shared_path(&file), None)? != file.checksum {
+                log::error!("Backup {id}: checksum mismatch for {}", file.path);
+                return Err(crate::Error::BackupCorrupted(id));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes a backup.
+    ///
+    /// Files that are not used by any other backup are deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if the backup does not exist, or an IO error occurred.
+    pub fn delete_backup(&mut self, id: BackupId) -> crate::Result<()> {
+        let path = self.manifest_path(id);
+
+        if !path.try_exists()? {
+            return Err(crate::Error::BackupNotFound(id));
+        }
+
+        remove_file(path)?;
+        fsync_directory(self.path.join(BACKUPS_FOLDER))?;
+
+        self.remove_unused_files()
+    }
+
+    /// Deletes the oldest backups, keeping the `n` newest ones.
+    ///
+    /// Files that are not used by any remaining backup are deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if an IO error occurred.
+    pub fn purge_old_backups(&mut self, n: usize) -> crate::Result<()> {
+        let ids = self.backup_ids()?;
+        let purge_count = ids.len().saturating_sub(n);
+
+        for id in ids.into_iter().take(purge_count) {
+            log::debug!("Purging backup {id}");
+            remove_file(self.manifest_path(id))?;
+        }
+
+        fsync_directory(self.path.join(BACKUPS_FOLDER))?;
+
+        self.remove_unused_files()
+    }
+
+    fn remove_unused_files(&self) -> crate::Result<()> {
+        let mut used = HashSet::default();
+
+        for id in self.backup_ids()? {
+            for file in self.load_manifest(id)?.files {
+                used.insert(file.shared_name());
+            }
+        }
+
+        for dirent in std::fs::read_dir(self.path.join(SHARED_FOLDER))? {
+            let dirent = dirent?;
+
+            let is_used = dirent
+                .file_name()
+                .to_str()
+                .is_some_and(|x| used.contains(x));
+
+            if !is_used {
+                log::trace!("Deleting unused backup file {}", dirent.path().display());
+                remove_file(dirent.path())?;
+            }
+        }
+
+        fsync_directory(self.path.join(SHARED_FOLDER))?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn backup_manifest_roundtrip() -> crate::Result<()> {
+        let manifest = BackupManifest {
+            timestamp: 5,
+            files: vec![
+                FileEntry {
+                    path: "partitions/default/segments/0".into(),
+                    size: 100,
+                    modified: 7,
+                    checksum: 8,
+                },
+                FileEntry {
+                    path: "version".into(),
+                    size: 5,
+                    modified: 7,
+                    checksum: 9,
+                },
+            ],
+        };
+
+        let bytes = manifest.encode_into_vec()?;
+        let decoded = BackupManifest::decode_from(&mut &bytes[..])?;
+        assert_eq!(manifest, decoded);
+
+        assert!(manifest.files[0].is_immutable());
+        assert!(!manifest.files[1].is_immutable());
+
+        Ok(())
+    }
+}
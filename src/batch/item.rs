@@ -57,16 +57,6 @@ impl Item {
         assert!(!p.is_empty());
         assert!(!k.is_empty());
 
-        assert!(u8::try_from(p.len()).is_ok(), "Partition name too long");
-        assert!(
-            u16::try_from(k.len()).is_ok(),
-            "Keys can be up to 65535 bytes long"
-        );
-        assert!(
-            u32::try_from(v.len()).is_ok(),
-            "Values can be up to 2^32 bytes long"
-        );
-
         Self {
             partition: p,
             key: k,
@@ -75,3 +65,52 @@ impl Item {
         }
     }
 }
+
+/// Range tombstone inside a batch, deleting all keys in `[start, end)`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RangeTombstoneItem {
+    /// Partition key - an arbitrary byte array
+    ///
+    /// Supports up to 2^8 bytes
+    pub partition: PartitionKey,
+
+    /// Inclusive lower bound
+    pub start: UserKey,
+
+    /// Exclusive upper bound, or unbounded if `None`
+    pub end: Option<UserKey>,
+}
+
+/// Merge operand of a key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MergeItem {
+    /// Partition key - an arbitrary byte array
+    ///
+    /// Supports up to 2^8 bytes
+    pub partition: PartitionKey,
+
+    /// User-defined key - an arbitrary byte array
+    ///
+    /// Supports up to 2^16 bytes
+    pub key: UserKey,
+
+    /// Merge operand
+    pub operand: UserValue,
+}
+
+/// Expiry timestamp of an item inside a batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExpiryItem {
+    /// Partition key - an arbitrary byte array
+    ///
+    /// Supports up to 2^8 bytes
+    pub partition: PartitionKey,
+
+    /// User-defined key - an arbitrary byte array
+    ///
+    /// Supports up to 2^16 bytes
+    pub key: UserKey,
+
+    /// Unix timestamp (in milliseconds) at which the item expires
+    pub expires_at: u64,
+}
@@ -4,12 +4,22 @@
 
 pub mod item;
 
-use crate::{Keyspace, PartitionHandle, PersistMode};
-use item::Item;
-use lsm_tree::{AbstractTree, ValueType};
+use crate::{
+    expiry::expires_at,
+    keyspace::Partitions,
+    large_value::PendingValue,
+    limits::{check_batch_items, check_item, check_key},
+    range_tombstone::{range_to_bounds, RangeTombstone},
+    subscription::ChangeKind,
+    Keyspace, PartitionHandle, PersistMode,
+};
+use item::{ExpiryItem, Item, MergeItem, RangeTombstoneItem};
+use lsm_tree::{AbstractTree, SeqNo, UserValue, ValueType};
 use std::{
     collections::{HashMap, HashSet},
+    ops::RangeBounds,
     sync::Arc,
+    time::Duration,
 };
 
 /// Partition key (a.k.a. column family, locality group)
@@ -21,6 +31,21 @@ pub type PartitionKey = Arc<str>;
 #[doc(alias = "WriteBatch")]
 pub struct Batch {
     pub(crate) data: Vec<Item>,
+    pub(crate) range_tombstones: Vec<RangeTombstoneItem>,
+    pub(crate) expiries: Vec<ExpiryItem>,
+    pub(crate) merges: Vec<MergeItem>,
+
+    /// Values of the batch that are too large for the journal and LSM-tree,
+    /// and have been written to their own files, in the form of `(partition, file, value)`
+    ///
+    /// The batch's items store the files' references instead.
+    pub(crate) large_values: Vec<(PartitionKey, PendingValue, UserValue)>,
+
+    /// Seqno to commit the batch with, instead of the next seqno of the keyspace
+    ///
+    /// Used to apply batches that were committed by a replication primary.
+    pub(crate) seqno: Option<SeqNo>,
+
     keyspace: Keyspace,
     durability: Option<PersistMode>,
 }
@@ -32,18 +57,31 @@ impl Batch {
     pub(crate) fn new(keyspace: Keyspace) -> Self {
         Self {
             data: Vec::new(),
+            range_tombstones: Vec::new(),
+            expiries: Vec::new(),
+            merges: Vec::new(),
+            large_values: Vec::new(),
+            seqno: None,
             keyspace,
             durability: None,
         }
     }
 
     /// Sets the durability level.
+    ///
+    /// Concurrent batches that are synced share a single sync of the journal (group commit).
     #[must_use]
     pub fn durability(mut self, mode: Option<PersistMode>) -> Self {
         self.durability = mode;
         self
     }
 
+    /// Removes the expiry of a previously added item of this batch.
+    fn discard_expiry(&mut self, p: &PartitionHandle, key: &[u8]) {
+        self.expiries
+            .retain(|x| x.partition != p.name || &*x.key != key);
+    }
+
     /// Inserts a key-value pair into the batch
     pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(
         &mut self,
@@ -51,6 +89,8 @@ impl Batch {
         key: K,
         value: V,
     ) {
+        self.discard_expiry(p, key.as_ref());
+
         self.data.push(Item::new(
             p.name.clone(),
             key.as_ref(),
@@ -59,8 +99,29 @@ impl Batch {
         ));
     }
 
+    /// Inserts a key-value pair into the batch, which expires after the given duration.
+    ///
+    /// See [`PartitionHandle::insert_with_ttl`].
+    pub fn insert_with_ttl<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &mut self,
+        p: &PartitionHandle,
+        key: K,
+        value: V,
+        ttl: Duration,
+    ) {
+        self.insert(p, &key, value);
+
+        self.expiries.push(ExpiryItem {
+            partition: p.name.clone(),
+            key: key.as_ref().into(),
+            expires_at: expires_at(ttl),
+        });
+    }
+
     /// Adds a tombstone marker for a key
     pub fn remove<K: AsRef<[u8]>>(&mut self, p: &PartitionHandle, key: K) {
+        self.discard_expiry(p, key.as_ref());
+
         self.data.push(Item::new(
             p.name.clone(),
             key.as_ref(),
@@ -69,12 +130,117 @@ impl Batch {
         ));
     }
 
+    /// Adds a range tombstone, removing all items in the given key range.
+    ///
+    /// Items of this batch inside the range that were added before are discarded,
+    /// items that are added afterwards are kept.
+    ///
+    /// If the batch contains range tombstones, the journal is synced to disk on commit,
+    /// regardless of the durability level.
+    pub fn remove_range<K: AsRef<[u8]>, R: RangeBounds<K>>(
+        &mut self,
+        p: &PartitionHandle,
+        range: R,
+    ) {
+        let Some((start, end)) = range_to_bounds(&range) else {
+            return;
+        };
+
+        let tombstone = RangeTombstone {
+            start,
+            end,
+            seqno: SeqNo::MAX,
+        };
+
+        // IMPORTANT: The tombstone does not cover items of its own batch,
+        // so remove previously added items inside the range
+        self.data
+            .retain(|item| item.partition != p.name || !tombstone.contains_key(&item.key));
+        self.expiries
+            .retain(|item| item.partition != p.name || !tombstone.contains_key(&item.key));
+
+        self.range_tombstones.push(RangeTombstoneItem {
+            partition: p.name.clone(),
+            start: tombstone.start,
+            end: tombstone.end,
+        });
+    }
+
     /// Commits the batch to the [`Keyspace`] atomically
     ///
     /// # Errors
     ///
-    /// Will return `Err` if an IO error occurs.
-    pub fn commit(mut self) -> crate::Result<()> {
+    /// Will return `Err` if an IO error occurs, or a key or merge operand is too large.
+    pub fn commit(self) -> crate::Result<()> {
+        self.commit_with_seqno().map(|_| ())
+    }
+
+    /// Writes the values that are too large for the journal and LSM-tree to their own files,
+    /// and replaces them by their references.
+    #[allow(clippy::expect_used)]
+    fn write_large_values(&mut self) -> crate::Result<()> {
+        for item in &mut self.data {
+            if item.value_type != ValueType::Value {
+                continue;
+            }
+
+            let Some(partition) = self
+                .keyspace
+                .partitions
+                .read()
+                .expect("lock is poisoned")
+                .get(&item.partition)
+                .cloned()
+            else {
+                continue;
+            };
+
+            let Some(large_value) = partition.write_large_value(&item.key, &item.value)? else {
+                continue;
+            };
+
+            let value = std::mem::replace(&mut item.value, large_value.reference.clone());
+
+            self.large_values
+                .push((item.partition.clone(), large_value, value));
+        }
+
+        Ok(())
+    }
+
+    /// Returns `true` if the batch writes to any journaled partition.
+    ///
+    /// Unknown partitions count as journaled, like they do when writing the journal.
+    fn touches_journal(&self, partitions: &Partitions) -> bool {
+        let is_journaled = |partition: &PartitionKey| {
+            !partitions
+                .get(partition)
+                .is_some_and(|partition| !partition.config.journaling)
+        };
+
+        self.data.iter().any(|item| is_journaled(&item.partition))
+            || self
+                .merges
+                .iter()
+                .any(|merge| is_journaled(&merge.partition))
+            || self
+                .range_tombstones
+                .iter()
+                .any(|tombstone| is_journaled(&tombstone.partition))
+            || self
+                .expiries
+                .iter()
+                .any(|expiry| is_journaled(&expiry.partition))
+    }
+
+    /// Commits the batch to the [`Keyspace`] atomically, returning
+    /// the sequence number the batch was written with.
+    #[allow(
+        clippy::too_many_lines,
+        clippy::expect_used,
+        clippy::significant_drop_tightening
+    )]
+    pub(crate) fn commit_with_seqno(mut self) -> crate::Result<SeqNo> {
         if self
             .keyspace
             .is_poisoned
@@ -83,12 +249,54 @@ impl Batch {
             return Err(crate::Error::Poisoned);
         }
 
-        log::trace!("batch: Acquiring journal writer");
-        let mut journal_writer = self.keyspace.journal.get_writer();
+        // NOTE: Replicas only accept batches of their primary, which carry their seqno
+        let is_replicated = self.keyspace.config.replica && self.seqno.is_some();
+
+        if self.keyspace.config.rejects_writes() && !is_replicated {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        check_batch_items(&self.data)?;
+
+        for merge in &self.merges {
+            check_item(&merge.key, &merge.operand)?;
+        }
+
+        for tombstone in &self.range_tombstones {
+            check_key(&tombstone.start)?;
+
+            if let Some(end) = &tombstone.end {
+                check_key(end)?;
+            }
+        }
+
+        // NOTE: Large values are written before taking any locks,
+        // so they do not block other writers
+        self.write_large_values()?;
+
+        // NOTE: Batches that only write to unlogged partitions skip the journal lock
+        let mut needs_journal =
+            self.touches_journal(&self.keyspace.partitions.read().expect("lock is poisoned"));
+
+        let (mut journal_writer, partitions) = loop {
+            log::trace!("batch: Acquiring journal writer");
+            let journal_writer = self.keyspace.journal.get_writer_for(needs_journal);
+
+            // NOTE: Fully (write) lock, so the batch can be committed atomically
+            log::trace!("batch: Acquiring partitions lock");
+            let partitions = self.keyspace.partitions.write().expect("lock is poisoned");
+
+            // NOTE: A partition may have been (re)created in the meantime
+            if journal_writer.is_some() || !self.touches_journal(&partitions) {
+                break (journal_writer, partitions);
+            }
+
+            needs_journal = true;
+        };
 
-        // NOTE: Fully (write) lock, so the batch can be committed atomically
-        log::trace!("batch: Acquiring partitions lock");
-        let partitions = self.keyspace.partitions.write().expect("lock is poisoned");
+        // NOTE: Background writes of the affected partitions need to wait for the batch,
+        // see `PartitionHandleInner::write_lock`
+        let mut write_locks = Vec::new();
 
         // IMPORTANT: Need to WRITE lock all affected partition's memtables
         // Otherwise, there may be read skew
@@ -96,12 +304,15 @@ impl Batch {
         let locked_memtables = {
             let mut lock_map = HashMap::new();
 
-            for item in &self.data {
-                if lock_map.contains_key(&item.partition) {
+            let item_partitions = self.data.iter().map(|item| &item.partition);
+            let merge_partitions = self.merges.iter().map(|merge| &merge.partition);
+
+            for name in item_partitions.chain(merge_partitions) {
+                if lock_map.contains_key(name) {
                     continue;
                 }
 
-                let Some(partition) = partitions.get(&item.partition) else {
+                let Some(partition) = partitions.get(name) else {
                     continue;
                 };
 
@@ -112,19 +323,134 @@ impl Batch {
                     return Err(crate::Error::PartitionDeleted);
                 }
 
-                lock_map.insert(
-                    item.partition.clone(),
-                    partition.tree.lock_active_memtable(),
-                );
+                write_locks.push(partition.write_lock.read().expect("lock is poisoned"));
+                lock_map.insert(name.clone(), partition.tree.lock_active_memtable());
             }
 
             lock_map
         };
 
-        let batch_seqno = self.keyspace.seqno.next();
+        for tombstone in &self.range_tombstones {
+            if partitions
+                .get(&tombstone.partition)
+                .is_some_and(|partition| {
+                    partition
+                        .is_deleted
+                        .load(std::sync::atomic::Ordering::Relaxed)
+                })
+            {
+                return Err(crate::Error::PartitionDeleted);
+            }
+        }
+
+        let batch_seqno = match self.seqno {
+            Some(seqno) => {
+                self.keyspace
+                    .seqno
+                    .fetch_max(seqno + 1, std::sync::atomic::Ordering::AcqRel);
+                seqno
+            }
+            None => self.keyspace.seqno.next(),
+        };
+
+        // IMPORTANT: Expiry timestamps need to be indexed before the items become visible
+        for expiry in &self.expiries {
+            let Some(partition) = partitions.get(&expiry.partition) else {
+                continue;
+            };
 
-        let items = self.data.iter().collect::<Vec<_>>();
-        let _ = journal_writer.write_batch(&items, batch_seqno)?;
+            partition
+                .expiry
+                .insert(&expiry.key, batch_seqno, expiry.expires_at)?;
+        }
+
+        // NOTE: Writes to unlogged partitions bypass the journal
+        let is_journaled = |partition: &PartitionKey| {
+            !partitions
+                .get(partition)
+                .is_some_and(|partition| !partition.config.journaling)
+        };
+
+        let items = self
+            .data
+            .iter()
+            .filter(|item| is_journaled(&item.partition))
+            .collect::<Vec<_>>();
+
+        let merges = self
+            .merges
+            .iter()
+            .filter(|merge| is_journaled(&merge.partition))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let range_tombstones = self
+            .range_tombstones
+            .iter()
+            .filter(|tombstone| is_journaled(&tombstone.partition))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let expiries = self
+            .expiries
+            .iter()
+            .filter(|expiry| is_journaled(&expiry.partition))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let is_logged = !(items.is_empty()
+            && merges.is_empty()
+            && range_tombstones.is_empty()
+            && expiries.is_empty());
+
+        if let Some(journal_writer) = journal_writer.as_mut().filter(|_| is_logged) {
+            let _ = journal_writer.write_batch_with_merges(
+                &items,
+                &merges,
+                &range_tombstones,
+                &expiries,
+                batch_seqno,
+            )?;
+
+            if !range_tombstones.is_empty() {
+                // IMPORTANT: The journal needs to be durable before persisting range tombstones,
+                // otherwise a range tombstone may survive a crash while its batch does not
+                journal_writer.flush(PersistMode::SyncAll)?;
+            }
+        }
+
+        let mut changes = journal_writer
+            .as_ref()
+            .is_some_and(|journal_writer| !journal_writer.subscribers.is_empty())
+            .then(Vec::new);
+
+        if !self.range_tombstones.is_empty() {
+            log::trace!(
+                "Applying {} batched range tombstones",
+                self.range_tombstones.len()
+            );
+
+            for tombstone in std::mem::take(&mut self.range_tombstones) {
+                let Some(partition) = partitions.get(&tombstone.partition) else {
+                    continue;
+                };
+
+                if let Some(changes) = &mut changes {
+                    changes.push((
+                        tombstone.partition.clone(),
+                        tombstone.start.clone(),
+                        UserValue::from(&[][..]),
+                        ChangeKind::RangeTombstone(tombstone.end.clone()),
+                    ));
+                }
+
+                partition.add_range_tombstone(RangeTombstone {
+                    start: tombstone.start,
+                    end: tombstone.end,
+                    seqno: batch_seqno,
+                })?;
+            }
+        }
 
         #[allow(clippy::mutable_key_type)]
         let mut partitions_with_possible_stall = HashSet::new();
@@ -141,6 +467,34 @@ impl Batch {
                 continue;
             };
 
+            if let Some(changes) = &mut changes {
+                // NOTE: Subscribers receive large values, not their references
+                let value = match self
+                    .large_values
+                    .iter()
+                    .find(|(partition, large_value, _)| {
+                        *partition == item.partition && large_value.reference == item.value
+                    }) {
+                    Some((_, _, value)) => value.clone(),
+
+                    // NOTE: Replicated batches refer to large values that were written before
+                    None => partition
+                        .large_values
+                        .resolve(&item.key, item.value.clone())
+                        .unwrap_or_else(|e| {
+                            log::error!("Failed to read large value to publish: {e:?}");
+                            item.value.clone()
+                        }),
+                };
+
+                changes.push((
+                    item.partition.clone(),
+                    item.key.clone(),
+                    value,
+                    item.value_type.into(),
+                ));
+            }
+
             let (item_size, _) = partition.tree.raw_insert_with_lock(
                 active_memtable,
                 item.key,
@@ -155,25 +509,91 @@ impl Batch {
             partitions_with_possible_stall.insert(partition.clone());
         }
 
-        drop(locked_memtables);
-        drop(partitions);
-
-        if let Some(mode) = self.durability {
-            if let Err(e) = journal_writer.flush(mode) {
-                self.keyspace
-                    .is_poisoned
-                    .store(true, std::sync::atomic::Ordering::Release);
+        log::trace!("Applying {} batched merge operands", self.merges.len());
+        for merge in std::mem::take(&mut self.merges) {
+            let Some(partition) = partitions.get(&merge.partition) else {
+                continue;
+            };
 
-                log::error!(
-                    "flush failed, which is a FATAL, and possibly hardware-related, failure: {e:?}"
-                );
+            let Some(active_memtable) = locked_memtables.get(&merge.partition) else {
+                continue;
+            };
 
-                return Err(crate::Error::Poisoned);
+            if let Some(changes) = &mut changes {
+                changes.push((
+                    merge.partition.clone(),
+                    merge.key.clone(),
+                    merge.operand.clone(),
+                    ChangeKind::MergeOperand,
+                ));
             }
+
+            // IMPORTANT: Register the operand before inserting it,
+            // otherwise a concurrent read may mistake it for a regular value
+            partition.merges.register(merge.key.clone(), batch_seqno);
+
+            let (item_size, _) = partition.tree.raw_insert_with_lock(
+                active_memtable,
+                merge.key,
+                merge.operand,
+                batch_seqno,
+                ValueType::Value,
+            );
+
+            batch_size += u64::from(item_size);
+
+            partitions_with_possible_stall.insert(partition.clone());
+        }
+
+        // NOTE: The references are visible now, so the files are kept from now on
+        for (_, large_value, _) in std::mem::take(&mut self.large_values) {
+            large_value.commit();
+        }
+
+        drop(locked_memtables);
+        drop(write_locks);
+        drop(partitions);
+
+        // NOTE: Subscribers are notified while holding the journal lock,
+        // so they receive batches in commit order, once they are visible
+        if let (Some(journal_writer), Some(changes)) = (&mut journal_writer, changes) {
+            journal_writer.subscribers.publish(batch_seqno, changes);
         }
 
+        // NOTE: The journal is only flushed to the OS while holding the lock,
+        // syncing happens afterwards, so concurrent writers can share a single sync
+        let flush_result = match (
+            journal_writer.as_mut().filter(|_| is_logged),
+            self.durability,
+        ) {
+            (Some(journal_writer), Some(_)) => journal_writer
+                .flush(PersistMode::Buffer)
+                .map(|()| Some(journal_writer.batch_count())),
+            _ => Ok(None),
+        };
+
         drop(journal_writer);
 
+        let sync_result =
+            flush_result
+                .map_err(Into::into)
+                .and_then(|batch| match (batch, self.durability) {
+                    (Some(batch), Some(mode)) => self.keyspace.journal.sync_batch(batch, mode),
+                    _ => Ok(()),
+                });
+
+        if let Err(e) = sync_result {
+            self.keyspace
+                .is_poisoned
+                .store(true, std::sync::atomic::Ordering::Release);
+
+            log::error!(
+                "flush failed, which is a FATAL, and possibly hardware-related, failure: {e:?}"
+            );
+
+            return Err(crate::Error::Poisoned);
+        }
+
         // IMPORTANT: Add batch size to current write buffer size
         // Otherwise write buffer growth is unbounded when using batches
         self.keyspace.write_buffer_manager.allocate(batch_size);
@@ -192,6 +612,6 @@ impl Batch {
             partition.check_write_buffer_size(write_buffer_size);
         }
 
-        Ok(())
+        Ok(batch_seqno)
     }
 }
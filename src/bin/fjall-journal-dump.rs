@@ -0,0 +1,388 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+//! Prints the batches of fjall journal files, as text or JSON.
+//!
+//! Journals are only read, never modified.
+
+use fjall::journal::{inspect, Entry, InspectedBatch};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage: fjall-journal-dump [--json] [--entries] <PATH>...
+
+Prints the batches of fjall journal files.
+
+PATH can be a journal file, a journals folder or a keyspace folder.
+
+Options:
+  --json     Prints one JSON object per journal, keys and values are written
+             as strings if they are valid UTF-8, otherwise as {\"hex\": \"...\"}
+  --entries  Prints the entries of every batch (always included in JSON)
+  -h, --help Prints this help
+
+For every journal, the highest seqno of each partition is listed. A journal
+can only be deleted once every listed partition has been flushed up to that seqno.";
+
+/// Statistics of a partition in a journal
+#[derive(Default)]
+struct PartitionStats {
+    entries: u64,
+    max_seqno: u64,
+}
+
+/// Result of reading a journal
+struct Dump {
+    path: PathBuf,
+    batches: Vec<InspectedBatch>,
+    partitions: BTreeMap<String, PartitionStats>,
+    error: Option<fjall::Error>,
+}
+
+fn dump(path: &Path) -> Dump {
+    let mut dump = Dump {
+        path: path.to_path_buf(),
+        batches: Vec::new(),
+        partitions: BTreeMap::new(),
+        error: None,
+    };
+
+    let batches = match inspect(path) {
+        Ok(batches) => batches,
+        Err(e) => {
+            dump.error = Some(e);
+            return dump;
+        }
+    };
+
+    for batch in batches {
+        match batch {
+            Ok(batch) => {
+                if batch.is_valid() {
+                    for entry in &batch.entries {
+                        let stats = dump
+                            .partitions
+                            .entry(entry.partition().to_string())
+                            .or_default();
+
+                        stats.entries += 1;
+                        stats.max_seqno = stats.max_seqno.max(batch.seqno);
+                    }
+                }
+
+                dump.batches.push(batch);
+            }
+            Err(e) => {
+                dump.error = Some(e);
+            }
+        }
+    }
+
+    dump
+}
+
+/// Returns the journal files in the given path, oldest first.
+fn journal_files(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let journals_folder = path.join("journals");
+    let folder = if journals_folder.is_dir() {
+        journals_folder
+    } else {
+        path.to_path_buf()
+    };
+
+    let mut files = Vec::new();
+
+    for dirent in std::fs::read_dir(&folder)? {
+        let dirent = dirent?;
+        let file_name = dirent.file_name();
+        let file_name = file_name.to_string_lossy();
+
+        let Ok(id) = file_name.trim_end_matches(".sealed").parse::<u64>() else {
+            continue;
+        };
+
+        files.push((id, dirent.path()));
+    }
+
+    files.sort_by_key(|(id, _)| *id);
+
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
+
+fn batch_status(batch: &InspectedBatch) -> String {
+    batch
+        .error
+        .map_or_else(|| "ok".into(), |error| format!("{error:?}"))
+}
+
+fn print_text(dump: &Dump, with_entries: bool) {
+    println!("journal {}", dump.path.display());
+
+    for batch in &dump.batches {
+        println!(
+            "  batch seqno={} offset={}..{} items={}/{} compression={:?} encrypted={} {}",
+            batch.seqno,
+            batch.offset,
+            batch.end_offset,
+            batch.entries.len(),
+            batch.item_count,
+            batch.compression,
+            batch.encrypted,
+            batch_status(batch),
+        );
+
+        if with_entries {
+            for entry in &batch.entries {
+                println!("    {}", text_entry(entry));
+            }
+        }
+    }
+
+    if let Some(error) = &dump.error {
+        println!("  error: {error:?}");
+    }
+
+    println!("  partitions:");
+
+    for (name, stats) in &dump.partitions {
+        println!(
+            "    {name}: entries={} max_seqno={}",
+            stats.entries, stats.max_seqno
+        );
+    }
+}
+
+fn text_entry(entry: &Entry) -> String {
+    let lossy = |bytes: &[u8]| format!("{:?}", String::from_utf8_lossy(bytes));
+
+    match entry {
+        Entry::Item {
+            partition,
+            key,
+            value,
+            value_type,
+        } => format!(
+            "item {partition} {} {value_type:?} ({} bytes)",
+            lossy(key),
+            value.len()
+        ),
+        Entry::RangeTombstone {
+            partition,
+            start,
+            end,
+        } => format!(
+            "range_tombstone {partition} {}..{}",
+            lossy(start),
+            end.as_deref().map(lossy).unwrap_or_default()
+        ),
+        Entry::Merge {
+            partition,
+            key,
+            operand,
+        } => format!("merge {partition} {} ({} bytes)", lossy(key), operand.len()),
+        Entry::Expiry {
+            partition,
+            key,
+            expires_at,
+        } => format!("expiry {partition} {} at={expires_at}", lossy(key)),
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", u32::from(c));
+            }
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+fn json_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) => json_string(s),
+        Err(_) => {
+            let hex = bytes.iter().fold(String::new(), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            });
+            format!("{{\"hex\":\"{hex}\"}}")
+        }
+    }
+}
+
+fn json_entry(entry: &Entry) -> String {
+    match entry {
+        Entry::Item {
+            partition,
+            key,
+            value,
+            value_type,
+        } => format!(
+            "{{\"kind\":\"item\",\"partition\":{},\"key\":{},\"value\":{},\"value_type\":\"{value_type:?}\"}}",
+            json_string(partition),
+            json_bytes(key),
+            json_bytes(value),
+        ),
+        Entry::RangeTombstone {
+            partition,
+            start,
+            end,
+        } => format!(
+            "{{\"kind\":\"range_tombstone\",\"partition\":{},\"start\":{},\"end\":{}}}",
+            json_string(partition),
+            json_bytes(start),
+            end.as_deref().map_or_else(|| "null".into(), json_bytes),
+        ),
+        Entry::Merge {
+            partition,
+            key,
+            operand,
+        } => format!(
+            "{{\"kind\":\"merge\",\"partition\":{},\"key\":{},\"operand\":{}}}",
+            json_string(partition),
+            json_bytes(key),
+            json_bytes(operand),
+        ),
+        Entry::Expiry {
+            partition,
+            key,
+            expires_at,
+        } => format!(
+            "{{\"kind\":\"expiry\",\"partition\":{},\"key\":{},\"expires_at\":{expires_at}}}",
+            json_string(partition),
+            json_bytes(key),
+        ),
+    }
+}
+
+fn print_json(dump: &Dump) {
+    let batches = dump
+        .batches
+        .iter()
+        .map(|batch| {
+            let entries = batch
+                .entries
+                .iter()
+                .map(json_entry)
+                .collect::<Vec<_>>()
+                .join(",");
+
+            format!(
+                "{{\"seqno\":{},\"offset\":{},\"end_offset\":{},\"item_count\":{},\"compression\":{},\"encrypted\":{},\"expected_checksum\":{},\"checksum\":{},\"error\":{},\"entries\":[{entries}]}}",
+                batch.seqno,
+                batch.offset,
+                batch.end_offset,
+                batch.item_count,
+                json_string(&format!("{:?}", batch.compression)),
+                batch.encrypted,
+                batch
+                    .expected_checksum
+                    .map_or_else(|| "null".into(), |checksum| checksum.to_string()),
+                batch.checksum,
+                batch
+                    .error
+                    .map_or_else(|| "null".into(), |error| json_string(&format!("{error:?}"))),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let partitions = dump
+        .partitions
+        .iter()
+        .map(|(name, stats)| {
+            format!(
+                "{{\"name\":{},\"entries\":{},\"max_seqno\":{}}}",
+                json_string(name),
+                stats.entries,
+                stats.max_seqno
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(",");
+
+    println!(
+        "{{\"path\":{},\"batches\":[{batches}],\"partitions\":[{partitions}],\"error\":{}}}",
+        json_string(&dump.path.display().to_string()),
+        dump.error
+            .as_ref()
+            .map_or_else(|| "null".into(), |error| json_string(&format!("{error:?}"))),
+    );
+}
+
+fn main() -> ExitCode {
+    let mut json = false;
+    let mut with_entries = false;
+    let mut paths = Vec::new();
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--entries" => with_entries = true,
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ => paths.push(PathBuf::from(arg)),
+        }
+    }
+
+    if paths.is_empty() {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+
+    let mut is_ok = true;
+
+    for path in paths {
+        let files = match journal_files(&path) {
+            Ok(files) => files,
+            Err(e) => {
+                eprintln!("Could not list journals in {}: {e}", path.display());
+                is_ok = false;
+                continue;
+            }
+        };
+
+        for file in files {
+            let dump = dump(&file);
+
+            is_ok &= dump.error.is_none() && dump.batches.iter().all(InspectedBatch::is_valid);
+
+            if json {
+                print_json(&dump);
+            } else {
+                print_text(&dump, with_entries);
+            }
+        }
+    }
+
+    if is_ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
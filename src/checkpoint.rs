@@ -0,0 +1,247 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    file::{
+        fsync_directory, FJALL_MARKER, JOURNALS_FOLDER, LARGE_VALUES_FOLDER, PARTITIONS_FOLDER,
+        PARTITION_CONFIG_FILE, PARTITION_DELETED_MARKER, PARTITION_RANGE_TOMBSTONES_FILE,
+        SEGMENTS_FOLDER,
+    },
+    fs::{FileSystem, OpenMode},
+    journal::writer::PersistMode,
+    large_value::is_complete,
+    range_tombstone::RangeTombstones,
+    Keyspace,
+};
+use std::{
+    ffi::OsStr,
+    fs::{create_dir, create_dir_all, File},
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+/// Copies a file and persists it.
+fn copy_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    std::fs::copy(src, dest)?;
+    File::open(dest)?.sync_all()
+}
+
+/// Copies a file into the keyspace's file system and persists it.
+fn copy_into(fs: &dyn FileSystem, mut src: impl Read, dest: &Path) -> std::io::Result<()> {
+    let mut file = fs.create(dest)?;
+    std::io::copy(&mut src, &mut file)?;
+    file.sync_all()
+}
+
+/// Hard links a file, falling back to copying it
+/// if the destination is on another file system.
+fn link_file(src: &Path, dest: &Path) -> std::io::Result<()> {
+    if let Err(e) = std::fs::hard_link(src, dest) {
+        log::debug!(
+            "Could not hard link {}, copying instead: {e:?}",
+            src.display()
+        );
+        copy_file(src, dest)?;
+    }
+
+    Ok(())
+}
+
+/// Recreates a folder.
+///
+/// Segment, blob and large value files are immutable, so they are hard linked,
+/// everything else (manifests, configs, ...) is copied.
+///
+/// Files are copied before subfolders, so the segments a copied manifest
+/// references already exist when the segments folder is copied.
+pub fn copy_folder(src: &Path, dest: &Path, link: bool) -> std::io::Result<()> {
+    copy_folder_filtered(src, dest, link, |_| true)
+}
+
+/// Recreates a folder, skipping the files of the folder that do not match the predicate,
+/// see [`copy_folder`].
+fn copy_folder_filtered<F: Fn(&OsStr) -> bool>(
+    src: &Path,
+    dest: &Path,
+    link: bool,
+    f: F,
+) -> std::io::Result<()> {
+    create_dir(dest)?;
+
+    let mut dirents = std::fs::read_dir(src)?.collect::<std::io::Result<Vec<_>>>()?;
+    dirents.retain(|dirent| dirent.file_type().is_ok_and(|x| x.is_dir()) || f(&dirent.file_name()));
+    dirents.sort_by_key(|dirent| dirent.file_type().is_ok_and(|x| x.is_dir()));
+
+    for dirent in dirents {
+        let file_name = dirent.file_name();
+
+        let src = dirent.path();
+        let dest = dest.join(&file_name);
+
+        if dirent.file_type()?.is_dir() {
+            if file_name == LARGE_VALUES_FOLDER {
+                // NOTE: Large values that are still being written are not referenced yet
+                copy_folder_filtered(&src, &dest, true, is_complete)?;
+            } else {
+                copy_folder(&src, &dest, link || file_name == SEGMENTS_FOLDER)?;
+            }
+        } else if link {
+            link_file(&src, &dest)?;
+        } else {
+            copy_file(&src, &dest)?;
+        }
+    }
+
+    fsync_directory(dest)
+}
+
+/// Creates a checkpoint of the keyspace.
+///
+/// Returns the path of the active journal and the byte offset in it
+/// the checkpoint's copy of the journal ends at.
+#[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+pub fn create(keyspace: &Keyspace, dest: &Path) -> crate::Result<(PathBuf, u64)> {
+    log::info!("Creating checkpoint at {}", dest.display());
+
+    let fs = &*keyspace.config.fs;
+    let src = &keyspace.config.path;
+
+    // IMPORTANT: Flushes, compactions and blob GC change the set of disk segments
+    // and delete sealed journals, so they need to be paused
+    let _maintenance_lock = keyspace.maintenance_lock.write().expect("lock is poisoned");
+
+    // NOTE: Writes are only paused while taking the journal position, so the partitions
+    // and their range tombstones are consistent with it
+    let (journal_position, active_journal, sealed_journals, partitions) = {
+        let mut journal_writer = keyspace.journal.get_writer();
+        journal_writer.flush(PersistMode::SyncAll)?;
+        let journal_position = (journal_writer.path.clone(), journal_writer.flushed_len()?);
+
+        // NOTE: The handle keeps reading the same journal, even if it is sealed in the meantime
+        let active_journal = fs.open(&journal_position.0, OpenMode::Read)?;
+
+        // NOTE: Sealed journals are immutable, and are not deleted while maintenance is paused
+        let sealed_journals = fs
+            .read_dir(&src.join(JOURNALS_FOLDER))?
+            .into_iter()
+            .filter(|path| path.file_name() != journal_position.0.file_name())
+            .collect::<Vec<_>>();
+
+        // NOTE: Partitions that are created later have no data in the checkpoint's journals
+        let partitions = keyspace
+            .partitions
+            .read()
+            .expect("lock is poisoned")
+            .iter()
+            .map(|(name, partition)| {
+                (
+                    name.clone(),
+                    partition.clone(),
+                    partition.range_tombstones.get(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // IMPORTANT: Record the checkpoint before journals can be archived again,
+        // so the archive keeps all journals needed to restore it
+        if let Some(archive) = &keyspace.config.journal_archive {
+            archive.record_checkpoint(fs, &journal_position.0)?;
+        }
+
+        (
+            journal_position,
+            active_journal,
+            sealed_journals,
+            partitions,
+        )
+    };
+
+    if let Some(parent) = dest.parent() {
+        create_dir_all(parent)?;
+    }
+    create_dir(dest)?;
+
+    let journals_folder = dest.join(JOURNALS_FOLDER);
+    fs.create_dir_all(&journals_folder)?;
+
+    for path in &sealed_journals {
+        let file_name = path.file_name().expect("journal should have name");
+        copy_into(
+            fs,
+            fs.open(path, OpenMode::Read)?,
+            &journals_folder.join(file_name),
+        )?;
+    }
+
+    // NOTE: Later writes are appended to the active journal, so only its prefix is copied,
+    // the rest of the preallocated journal is zeroed
+    {
+        let file_name = journal_position
+            .0
+            .file_name()
+            .expect("journal should have name");
+
+        let size = active_journal.size()?;
+
+        let mut file = fs.create(&journals_folder.join(file_name))?;
+        std::io::copy(&mut active_journal.take(journal_position.1), &mut file)?;
+        file.set_len(size)?;
+        file.sync_all()?;
+    }
+
+    fs.sync_directory(&journals_folder)?;
+
+    let partitions_folder = dest.join(PARTITIONS_FOLDER);
+    create_dir(&partitions_folder)?;
+
+    for (name, partition, range_tombstones) in partitions {
+        log::debug!("Checkpointing partition {name:?}");
+
+        let folder = partitions_folder.join(&*name);
+
+        // NOTE: The LSM-tree is stored in the operating system's file system,
+        // the partition's config and range tombstones in the keyspace's file system
+        //
+        // A partition that is deleted during the checkpoint is not deleted in the checkpoint,
+        // and its range tombstones are taken from the journal position
+        copy_folder_filtered(partition.path(), &folder, false, |file_name| {
+            file_name != PARTITION_CONFIG_FILE
+                && file_name != PARTITION_DELETED_MARKER
+                && file_name != PARTITION_RANGE_TOMBSTONES_FILE
+        })?;
+
+        fs.create_dir_all(&folder)?;
+
+        copy_into(
+            fs,
+            fs.open(
+                &partition.path().join(PARTITION_CONFIG_FILE),
+                OpenMode::Read,
+            )?,
+            &folder.join(PARTITION_CONFIG_FILE),
+        )?;
+
+        if !range_tombstones.is_empty() {
+            RangeTombstones::persist(fs, &folder, &range_tombstones)?;
+        }
+
+        fs.sync_directory(&folder)?;
+    }
+
+    fsync_directory(&partitions_folder)?;
+    fsync_directory(dest)?;
+
+    // NOTE: The version marker is written last, so an incomplete checkpoint
+    // is not mistaken for a keyspace
+    copy_into(
+        fs,
+        fs.open(&src.join(FJALL_MARKER), OpenMode::Read)?,
+        &dest.join(FJALL_MARKER),
+    )?;
+    fs.sync_directory(dest)?;
+
+    log::info!("Created checkpoint at {}", dest.display());
+
+    Ok(journal_position)
+}
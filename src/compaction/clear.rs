@@ -0,0 +1,34 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use lsm_tree::{
+    compaction::{Choice, CompactionStrategy},
+    level_manifest::LevelManifest,
+    Config, SeqNo,
+};
+
+/// Drops all segments that only contain data older than the given seqno
+///
+/// Used to reclaim disk space after a partition has been cleared.
+pub struct Strategy(pub SeqNo);
+
+impl CompactionStrategy for Strategy {
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        // NOTE: Segments that are currently being compacted are hidden,
+        // their compaction output will stay hidden by the range tombstone
+        let segment_ids = levels
+            .resolved_view()
+            .iter()
+            .flat_map(|level| level.segments.iter())
+            .filter(|segment| segment.metadata.seqnos.1 < self.0)
+            .map(|segment| segment.metadata.id)
+            .collect::<Vec<_>>();
+
+        if segment_ids.is_empty() {
+            Choice::DoNothing
+        } else {
+            Choice::Drop(segment_ids)
+        }
+    }
+}
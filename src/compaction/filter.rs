@@ -0,0 +1,326 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{large_value::LargeValues, merge::merged_item};
+use lsm_tree::{
+    blob_tree::value::MaybeInlineValue,
+    coding::Decode,
+    compaction::{Choice, CompactionStrategy},
+    segment::Segment,
+    AbstractTree, AnyTree, InternalValue, Memtable, SequenceNumberCounter, UserValue,
+};
+use std::sync::{Arc, RwLock};
+
+/// Decision of a [`CompactionFilter`] about an item
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Decision {
+    /// Keeps the item as is
+    Keep,
+
+    /// Removes the item
+    Remove,
+
+    /// Replaces the item's value
+    Change(Vec<u8>),
+}
+
+/// Decides whether items are kept, removed or changed when they are written to disk
+///
+/// This allows dropping or migrating data in the background,
+/// without having to scan the partition.
+///
+/// The filter is called for every value (not tombstones) when a memtable is flushed,
+/// and for the latest version of every key in the segments that are compacted,
+/// so items that were written before the filter was installed are filtered as well.
+///
+/// # Flushes
+///
+/// Removed items are dropped, and changed items are written to disk with their new value.
+/// If an older version of a removed key exists, a tombstone is written instead,
+/// so the older version is not revived.
+///
+/// # Compactions
+///
+/// Segments are immutable, so removed items are deleted using a tombstone, and changed items
+/// are written again. The old versions are dropped by later compactions, once no snapshot can see them anymore.
+///
+/// An item may be filtered many times, so decisions need to be idempotent:
+/// a changed value has to be kept when it is filtered again.
+///
+/// Snapshots that were taken before an item was filtered may observe the filtered value
+/// once it has been written to disk.
+///
+/// Values of 4 GiB or more are stored in their own files, so they are never filtered,
+/// and changing a value to one of that size is ignored.
+///
+/// # Examples
+///
+/// ```
+/// # use fjall::{compaction::{CompactionFilter, Decision}, Config, PartitionCreateOptions};
+/// # use std::sync::Arc;
+/// #
+/// struct DropStale;
+///
+/// impl CompactionFilter for DropStale {
+///     fn filter(&self, _key: &[u8], value: &[u8]) -> Decision {
+///         if value.starts_with(b"stale:") {
+///             Decision::Remove
+///         } else {
+///             Decision::Keep
+///         }
+///     }
+/// }
+///
+/// # let folder = tempfile::tempdir()?;
+/// # let keyspace = Config::new(folder).open()?;
+/// let partition = keyspace.open_partition(
+///     "default",
+///     PartitionCreateOptions::default().with_compaction_filter(Arc::new(DropStale)),
+/// )?;
+///
+/// partition.insert("a", "stale:abc")?;
+/// partition.insert("b", "fresh")?;
+/// assert!(partition.contains_key("a")?);
+///
+/// partition.rotate_memtable_and_wait()?;
+/// assert!(!partition.contains_key("a")?);
+/// assert!(partition.contains_key("b")?);
+/// #
+/// # Ok::<(), fjall::Error>(())
+/// ```
+pub trait CompactionFilter: Send + Sync {
+    /// Decides what happens to the item.
+    fn filter(&self, key: &[u8], value: &[u8]) -> Decision;
+}
+
+impl std::fmt::Debug for dyn CompactionFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "CompactionFilter")
+    }
+}
+
+/// Compaction filter of a partition
+///
+/// The filter is not persisted, so it is set when the partition is opened.
+#[derive(Default)]
+pub struct FilterSlot(RwLock<Option<Arc<dyn CompactionFilter>>>);
+
+impl FilterSlot {
+    pub fn new(filter: Option<Arc<dyn CompactionFilter>>) -> Self {
+        Self(RwLock::new(filter))
+    }
+
+    #[allow(clippy::expect_used)]
+    pub fn get(&self) -> Option<Arc<dyn CompactionFilter>> {
+        self.0.read().expect("lock is poisoned").clone()
+    }
+
+    #[allow(clippy::expect_used)]
+    pub fn set(&self, filter: Arc<dyn CompactionFilter>) {
+        *self.0.write().expect("lock is poisoned") = Some(filter);
+    }
+}
+
+/// Returns the user value of an item, or `None` if it is a tombstone.
+pub fn user_value(tree: &AnyTree, item: &InternalValue) -> crate::Result<Option<UserValue>> {
+    if item.is_tombstone() {
+        return Ok(None);
+    }
+
+    match tree {
+        AnyTree::Standard(_) => Ok(Some(item.value.clone())),
+        AnyTree::Blob(tree) => {
+            let mut reader = &item.value[..];
+
+            match MaybeInlineValue::decode_from(&mut reader)? {
+                MaybeInlineValue::Inline(value) => Ok(Some(value)),
+                MaybeInlineValue::Indirect { .. } => {
+                    Ok(tree.get_with_seqno(&item.key.user_key, item.key.seqno + 1)?)
+                }
+            }
+        }
+    }
+}
+
+/// Returns `true` if a version of the key that is older than the item
+/// and not deleted exists in the tree.
+fn has_older_version(tree: &AnyTree, item: &InternalValue) -> crate::Result<bool> {
+    let index = match tree {
+        AnyTree::Standard(tree) => tree,
+        AnyTree::Blob(tree) => &tree.index,
+    };
+
+    Ok(index
+        .get_internal_entry(&item.key.user_key, false, Some(item.key.seqno))?
+        .is_some_and(|older| !older.is_tombstone()))
+}
+
+/// Returns the filter's decision about an item.
+///
+/// Large values are always kept, see [`CompactionFilter`].
+fn decide(
+    filter: &dyn CompactionFilter,
+    large_values: &LargeValues,
+    key: &[u8],
+    value: &[u8],
+) -> Decision {
+    if large_values.is_reference(key, value) {
+        return Decision::Keep;
+    }
+
+    match filter.filter(key, value) {
+        Decision::Change(value) if large_values.is_large(&value) => {
+            log::warn!("Ignoring compaction filter change to a large value");
+            Decision::Keep
+        }
+        decision => decision,
+    }
+}
+
+/// Applies the compaction filter to the values of a sealed memtable.
+///
+/// Returns `None` if the filter did not change anything.
+pub fn filter_memtable(
+    tree: &AnyTree,
+    filter: &dyn CompactionFilter,
+    large_values: &LargeValues,
+    memtable: &Memtable,
+) -> crate::Result<Option<Memtable>> {
+    let Some(highest_seqno) = memtable.get_highest_seqno() else {
+        return Ok(None);
+    };
+
+    let filtered = Memtable::default();
+    let mut is_changed = false;
+
+    for item in memtable.iter() {
+        let Some(value) = user_value(tree, &item)? else {
+            filtered.insert(item);
+            continue;
+        };
+
+        match decide(filter, large_values, &item.key.user_key, &value) {
+            Decision::Keep => {
+                filtered.insert(item);
+            }
+            Decision::Remove => {
+                is_changed = true;
+
+                // IMPORTANT: Keep the item with the highest seqno (as a tombstone),
+                // otherwise the flushed segment may not cover the memtable's seqno anymore
+                // (or may not be created at all), and the journal could never be evicted
+                //
+                // The tombstone keeps the item's seqno, so it shadows
+                // older versions, but never newer ones
+                if item.key.seqno == highest_seqno || has_older_version(tree, &item)? {
+                    filtered.insert(InternalValue::new_tombstone(
+                        item.key.user_key.clone(),
+                        item.key.seqno,
+                    ));
+                }
+            }
+            Decision::Change(value) => {
+                is_changed = true;
+                filtered.insert(merged_item(tree, &item, &value.into()));
+            }
+        }
+    }
+
+    Ok(is_changed.then_some(filtered))
+}
+
+/// Returns the segments that the compaction strategy is about to merge.
+#[allow(clippy::expect_used)]
+pub fn compaction_input(tree: &AnyTree, strategy: &dyn CompactionStrategy) -> Vec<Arc<Segment>> {
+    let index = match tree {
+        AnyTree::Standard(tree) => tree,
+        AnyTree::Blob(tree) => &tree.index,
+    };
+
+    let levels = index.levels.read().expect("lock is poisoned");
+
+    // NOTE: Moved and dropped segments are not rewritten, so there is nothing to filter
+    let Choice::Merge(input) = strategy.choose(&levels, &index.config) else {
+        return vec![];
+    };
+
+    levels
+        .iter()
+        .filter(|segment| input.segment_ids.contains(&segment.metadata.id))
+        .cloned()
+        .collect()
+}
+
+/// Applies the compaction filter to the latest versions of the items in the given segments.
+///
+/// Removed items are deleted using a tombstone, and changed items are written again.
+/// Each write gets a new seqno while holding the partition's write lock,
+/// so it is only applied if the item has not been overwritten in the meantime.
+///
+/// Returns the size of the written items.
+#[allow(clippy::expect_used)]
+pub fn filter_segments(
+    tree: &AnyTree,
+    filter: &dyn CompactionFilter,
+    large_values: &LargeValues,
+    segments: &[Arc<Segment>],
+    write_lock: &RwLock<()>,
+    seqno_counter: &SequenceNumberCounter,
+) -> crate::Result<u64> {
+    let index = match tree {
+        AnyTree::Standard(tree) => tree,
+        AnyTree::Blob(tree) => &tree.index,
+    };
+
+    let mut written_bytes = 0;
+
+    for segment in segments {
+        let mut prev_key = None;
+
+        for item in segment.iter() {
+            let item = item?;
+
+            // NOTE: Versions are sorted from newest to oldest,
+            // so only the first version of a key can be its latest version
+            if prev_key.as_ref() == Some(&item.key.user_key) {
+                continue;
+            }
+            prev_key = Some(item.key.user_key.clone());
+
+            let Some(value) = user_value(tree, &item)? else {
+                continue;
+            };
+
+            let decision = decide(filter, large_values, &item.key.user_key, &value);
+
+            if decision == Decision::Keep {
+                continue;
+            }
+
+            let _lock = write_lock.write().expect("lock is poisoned");
+
+            let is_latest = index
+                .get_internal_entry(&item.key.user_key, false, None)?
+                .is_some_and(|latest| latest.key.seqno == item.key.seqno);
+
+            if !is_latest {
+                continue;
+            }
+
+            // NOTE: The writes are not journaled, because the filtered items
+            // are still on disk, so they are filtered again after a crash
+            let (size, _) = match decision {
+                Decision::Keep => continue,
+                Decision::Remove => tree.remove(&item.key.user_key, seqno_counter.next()),
+                Decision::Change(value) => {
+                    tree.insert(&item.key.user_key, value, seqno_counter.next())
+                }
+            };
+
+            written_bytes += u64::from(size);
+        }
+    }
+
+    Ok(written_bytes)
+}
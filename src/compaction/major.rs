@@ -0,0 +1,26 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use lsm_tree::{
+    compaction::{Choice, CompactionStrategy, Input},
+    level_manifest::LevelManifest,
+    Config,
+};
+
+/// Merges all segments into the last level
+///
+/// Used to apply the compaction filter to all items on disk.
+pub struct Strategy;
+
+impl CompactionStrategy for Strategy {
+    fn choose(&self, levels: &LevelManifest, _: &Config) -> Choice {
+        let segment_ids = levels.iter().map(|segment| segment.metadata.id).collect();
+
+        Choice::Merge(Input {
+            segment_ids,
+            dest_level: levels.last_level_index(),
+            target_size: u64::MAX,
+        })
+    }
+}
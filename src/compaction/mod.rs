@@ -2,11 +2,15 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+pub(crate) mod clear;
+pub(crate) mod filter;
+pub(crate) mod major;
 pub(crate) mod manager;
 pub(crate) mod worker;
 
 use std::sync::Arc;
 
+pub use filter::{CompactionFilter, Decision};
 pub use lsm_tree::compaction::{Fifo, Leveled, Levelled, SizeTiered};
 
 /// Compaction strategy
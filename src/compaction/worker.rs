@@ -19,12 +19,35 @@ pub fn run(compaction_manager: &CompactionManager, snapshot_tracker: &SnapshotTr
 
     let strategy = item.config.compaction_strategy.clone();
 
+    // NOTE: Expired items are tombstoned first, so the compaction can drop them
+    if let Err(e) = item.remove_expired() {
+        log::error!("Failed to remove expired items: {e:?}");
+    }
+
+    // NOTE: Items covered by range tombstones are deleted using point tombstones,
+    // so the compaction can drop them, and the range tombstones can be pruned
+    if let Err(e) = item.prune_range_tombstones() {
+        log::error!("Failed to prune range tombstones: {e:?}");
+    }
+
+    let strategy = strategy.inner();
+
+    // NOTE: Filtered items are deleted or rewritten first, so the compaction can drop the old versions
+    if let Err(e) = item.apply_compaction_filter(&*strategy) {
+        log::error!("Failed to apply compaction filter: {e:?}");
+    }
+
     // TODO: loop if there's more work to do
 
     if let Err(e) = item
         .tree
-        .compact(strategy.inner(), snapshot_tracker.get_seqno_safe_to_gc())
+        .compact(strategy, snapshot_tracker.get_seqno_safe_to_gc())
     {
         log::error!("Compaction failed: {e:?}");
     };
+
+    // NOTE: Large values of the dropped versions are not referenced anymore
+    if let Err(e) = item.collect_large_values() {
+        log::error!("Failed to delete unreferenced large values: {e:?}");
+    }
 }
@@ -2,8 +2,15 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{journal::error::RecoveryMode, path::absolute_path, Keyspace};
-use lsm_tree::{descriptor_table::FileDescriptorTable, BlobCache, BlockCache};
+use crate::{
+    encryption::JournalEncryptionProvider,
+    fs::{FileSystem, OsFileSystem},
+    journal::{error::RecoveryMode, JournalArchive},
+    limits::MAX_VALUE_SIZE,
+    path::absolute_path,
+    Keyspace,
+};
+use lsm_tree::{descriptor_table::FileDescriptorTable, BlobCache, BlockCache, CompressionType};
 use std::{
     path::{Path, PathBuf},
     sync::Arc,
@@ -11,6 +18,7 @@ use std::{
 
 /// Global keyspace configuration
 #[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Config {
     /// Base path of database
     pub(crate) path: PathBuf,
@@ -50,6 +58,33 @@ pub struct Config {
     pub(crate) fsync_ms: Option<u16>,
 
     pub(crate) journal_recovery_mode: RecoveryMode,
+
+    /// Compression of large journal batches
+    pub(crate) journal_compression_type: CompressionType,
+
+    /// Journal batches smaller than this are not compressed
+    pub(crate) journal_compression_threshold: usize,
+
+    /// Encryption of journals and partition configuration files
+    pub(crate) journal_encryption: Option<Arc<dyn JournalEncryptionProvider>>,
+
+    /// Archive that evicted journals are moved to
+    pub(crate) journal_archive: Option<JournalArchive>,
+
+    /// If `true`, writes are rejected, see `Error::ReadOnly`
+    pub(crate) read_only: bool,
+
+    /// If `true`, the keyspace directory is locked while the keyspace is open
+    pub(crate) use_lock_file: bool,
+
+    /// If `true`, the keyspace is only written to by replication, see `Replica`
+    pub(crate) replica: bool,
+
+    /// File system the keyspace's own files (e.g. journals) are stored in
+    pub(crate) fs: Arc<dyn FileSystem>,
+
+    /// Values larger than this are stored in their own files, see `LargeValues`
+    pub(crate) large_value_threshold: u64,
 }
 
 const DEFAULT_CPU_CORES: usize = 4;
@@ -86,12 +121,39 @@ impl Default for Config {
             flush_workers_count: cpus.min(4),
             compaction_workers_count: cpus.min(4),
             journal_recovery_mode: RecoveryMode::default(),
+
+            journal_compression_type: CompressionType::None,
+
+            journal_compression_threshold: /* 4 KiB */ 4 * 1_024,
+
+            journal_encryption: None,
+            journal_archive: None,
             manual_journal_persist: false,
+            read_only: false,
+            use_lock_file: true,
+            replica: false,
+            fs: Arc::new(OsFileSystem),
+            large_value_threshold: MAX_VALUE_SIZE,
         }
     }
 }
 
 impl Config {
+    /// Returns `true` if the keyspace directory needs to be locked.
+    ///
+    /// Only read-only keyspaces may opt out of the lock.
+    pub(crate) fn uses_lock_file(&self) -> bool {
+        self.use_lock_file || !self.read_only
+    }
+
+    /// Returns `true` if writes through the public API are rejected with `Error::ReadOnly`.
+    ///
+    /// Maintenance that does not change the data (e.g. persisting, checkpoints
+    /// or garbage collection) is only rejected if the keyspace is read-only.
+    pub(crate) fn rejects_writes(&self) -> bool {
+        self.read_only || self.replica
+    }
+
     /// Creates a new configuration
     pub fn new<P: AsRef<Path>>(path: P) -> Self {
         Self {
@@ -100,6 +162,75 @@ impl Config {
         }
     }
 
+    /// If `true`, the keyspace is opened read-only.
+    ///
+    /// Partitions are recovered, and the journals are replayed into memtables, but
+    /// nothing is written to the keyspace directory: no journal is created, deleted or
+    /// uninitialized partitions are not cleaned up, and no flush, compaction,
+    /// monitor or fsync threads are started. Writes are rejected with
+    /// [`Error::ReadOnly`](crate::Error::ReadOnly).
+    ///
+    /// Read-only keyspaces only take a shared lock, and do not create the keyspace directory,
+    /// so they can be opened concurrently, and on a read-only file system. To open a keyspace
+    /// read-only while another process has it opened for writing, also disable the
+    /// lock file with [`Config::lock_file`].
+    ///
+    /// Default = false
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # {
+    /// # let keyspace = Config::new(&folder).open()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// # partition.insert("a", "abc")?;
+    /// # }
+    /// let keyspace = Config::new(&folder).read_only(true).open()?;
+    /// let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    ///
+    /// assert!(partition.contains_key("a")?);
+    /// assert!(matches!(partition.insert("b", "abc"), Err(fjall::Error::ReadOnly)));
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    #[must_use]
+    pub fn read_only(mut self, flag: bool) -> Self {
+        self.read_only = flag;
+        self
+    }
+
+    /// If `true`, an exclusive lock is taken on the `LOCK` file in the keyspace directory,
+    /// so the keyspace cannot be opened by another process while it is open.
+    /// [Read-only](Config::read_only) keyspaces take a shared lock instead.
+    ///
+    /// Opening a locked keyspace fails with [`Error::Locked`](crate::Error::Locked).
+    ///
+    /// Default = true
+    ///
+    /// Disabling the lock only applies to [read-only](Config::read_only) keyspaces,
+    /// e.g. to open a keyspace read-only while another process has it opened.
+    /// Keyspaces that can be written to are always locked.
+    #[must_use]
+    pub fn lock_file(mut self, flag: bool) -> Self {
+        self.use_lock_file = flag;
+        self
+    }
+
+    /// Sets the file system the keyspace's own files (e.g. journals) are stored in.
+    ///
+    /// The data of partitions is always stored in the operating system's file system,
+    /// see the [`fs`](crate::fs) module.
+    ///
+    /// Default = [`OsFileSystem`]
+    #[must_use]
+    pub fn file_system(mut self, fs: Arc<dyn FileSystem>) -> Self {
+        self.fs = fs;
+        self
+    }
+
     /// If `false`, write batches or transactions automatically flush data to the operating system.
     ///
     /// Default = false
@@ -201,6 +332,81 @@ impl Config {
         self
     }
 
+    /// Sets the recovery mode to use when replaying journals.
+    ///
+    /// Default = [`RecoveryMode::TolerateCorruptTail`]
+    #[must_use]
+    pub fn journal_recovery_mode(mut self, mode: RecoveryMode) -> Self {
+        self.journal_recovery_mode = mode;
+        self
+    }
+
+    /// Sets the compression method of journal batches.
+    ///
+    /// Only batches that are larger than the [compression threshold](Config::journal_compression_threshold)
+    /// are compressed.
+    ///
+    /// Default = None
+    #[must_use]
+    pub fn journal_compression(mut self, compression: CompressionType) -> Self {
+        self.journal_compression_type = compression;
+        self
+    }
+
+    /// Sets the size (in bytes) above which journal batches are compressed.
+    ///
+    /// Compressing large batches (e.g. large JSON values) reduces journal I/O,
+    /// and makes journals fill up less quickly.
+    ///
+    /// Default = 4 KiB
+    #[must_use]
+    pub fn journal_compression_threshold(mut self, bytes: usize) -> Self {
+        self.journal_compression_threshold = bytes;
+        self
+    }
+
+    /// Sets the encryption provider, to encrypt journals and partition configuration files.
+    ///
+    /// Segment and blob files are **not** encrypted, see [`JournalEncryptionProvider`].
+    ///
+    /// Default = None
+    #[must_use]
+    pub fn journal_encryption(mut self, provider: Arc<dyn JournalEncryptionProvider>) -> Self {
+        self.journal_encryption = Some(provider);
+        self
+    }
+
+    /// Sets the journal archive, which evicted journals are moved to,
+    /// instead of being deleted.
+    ///
+    /// See [`JournalArchive`] and [`restore_to`](crate::journal::restore_to).
+    ///
+    /// Default = None
+    #[must_use]
+    pub fn journal_archive(mut self, archive: JournalArchive) -> Self {
+        self.journal_archive = Some(archive);
+        self
+    }
+
+    /// Sets the size (in bytes) above which values are stored in their own files,
+    /// instead of the journal and LSM-tree.
+    ///
+    /// Only lowered by tests, as the journal and LSM-tree cannot store larger values.
+    ///
+    /// Default = 4 GiB - 1
+    ///
+    /// # Panics
+    ///
+    /// Panics if the threshold is larger than the default.
+    #[doc(hidden)]
+    #[must_use]
+    pub fn large_value_threshold(mut self, bytes: u64) -> Self {
+        assert!(bytes <= MAX_VALUE_SIZE);
+
+        self.large_value_threshold = bytes;
+        self
+    }
+
     /// If Some, starts an fsync thread that asynchronously
     /// persists data.
     ///
@@ -223,17 +429,62 @@ impl Config {
     ///
     /// # Errors
     ///
-    /// Will return `Err` if an IO error occurs.
+    /// Will return `Err` if an IO error occurs, or the keyspace is locked by another process.
     pub fn open(self) -> crate::Result<Keyspace> {
         Keyspace::open(self)
     }
 
+    /// Opens a read-only secondary instance of the keyspace in the given directory.
+    ///
+    /// The secondary never writes to the primary's directory, and does not start
+    /// background threads, so it can be used by another process (e.g. for analytics)
+    /// while the primary is running. The configured path is used as the secondary's
+    /// working directory, in which the primary's partitions are mirrored by hard linking
+    /// their segment files. It must not be a keyspace itself.
+    ///
+    /// Use [`Keyspace::try_catch_up`] to see writes that happened on the primary
+    /// after the secondary was opened.
+    ///
+    /// Writes are rejected with [`Error::ReadOnly`](crate::Error::ReadOnly).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let secondary_folder = tempfile::tempdir()?;
+    /// let keyspace = Config::new(&folder).open()?;
+    /// let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// partition.insert("a", "abc")?;
+    ///
+    /// let secondary = Config::new(&secondary_folder).open_secondary(&folder)?;
+    /// let secondary_partition = secondary.open_partition("default", PartitionCreateOptions::default())?;
+    /// assert!(secondary_partition.contains_key("a")?);
+    ///
+    /// partition.insert("b", "abc")?;
+    /// assert!(!secondary_partition.contains_key("b")?);
+    ///
+    /// secondary.try_catch_up()?;
+    /// assert!(secondary_partition.contains_key("b")?);
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the configured path is a keyspace.
+    pub fn open_secondary<P: AsRef<Path>>(mut self, primary_path: P) -> crate::Result<Keyspace> {
+        self.read_only = true;
+        crate::secondary::open(self, absolute_path(primary_path))
+    }
+
     /// Opens a transactional keyspace using the config.
     ///
     /// # Errors
     ///
-    /// Will return `Err` if an IO error occurs.
-    #[cfg(feature = "single_writer_tx")]
+    /// Will return `Err` if an IO error occurs, or the keyspace is locked by another process.
+    #[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
     pub fn open_transactional(self) -> crate::Result<crate::TxKeyspace> {
         crate::TxKeyspace::open(self)
     }
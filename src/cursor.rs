@@ -0,0 +1,376 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use lsm_tree::{KvPair, UserKey, UserValue};
+use std::ops::Bound;
+
+type Bounds = (Bound<UserKey>, Bound<UserKey>);
+
+type BoxedIter<'a> = Box<dyn DoubleEndedIterator<Item = crate::Result<KvPair>> + 'a>;
+
+type RangeFn<'a> = Box<dyn Fn(Bounds) -> BoxedIter<'a> + 'a>;
+
+/// Maximum number of items that are skipped when seeking within the current iterator,
+/// before setting up a new iterator instead
+const MAX_SKIPPED_ITEMS: usize = 32;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Direction {
+    Forward,
+    Backward,
+}
+
+/// A seekable cursor over a consistent view of a partition
+///
+/// The cursor keeps its underlying iterator as long as it keeps moving
+/// in the same direction, which includes seeking to nearby keys ahead of the cursor,
+/// so only seeking backwards, seeking far ahead or changing direction
+/// needs to set up a new iterator.
+///
+/// # Examples
+///
+/// ```
+/// # use fjall::{Config, PartitionCreateOptions};
+/// #
+/// # let folder = tempfile::tempdir()?;
+/// # let keyspace = Config::new(folder).open()?;
+/// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+/// partition.insert("a", "1")?;
+/// partition.insert("c", "3")?;
+/// partition.insert("e", "5")?;
+///
+/// let mut cursor = partition.cursor();
+///
+/// assert!(cursor.seek("b")?);
+/// assert_eq!(b"c", &**cursor.key().unwrap());
+///
+/// assert!(cursor.next()?);
+/// assert_eq!(b"5", &**cursor.value().unwrap());
+///
+/// assert!(cursor.prev()?);
+/// assert!(cursor.prev()?);
+/// assert_eq!(b"a", &**cursor.key().unwrap());
+///
+/// assert!(!cursor.prev()?);
+/// assert!(!cursor.is_valid());
+///
+/// assert!(cursor.seek_for_prev("d")?);
+/// assert_eq!(b"c", &**cursor.key().unwrap());
+/// #
+/// # Ok::<(), fjall::Error>(())
+/// ```
+pub struct Cursor<'a> {
+    range: RangeFn<'a>,
+    iter: Option<(Direction, BoxedIter<'a>)>,
+    current: Option<KvPair>,
+}
+
+impl std::fmt::Debug for Cursor<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Cursor({:?})", self.key())
+    }
+}
+
+impl<'a> Cursor<'a> {
+    /// Creates a new cursor, using the given function to create
+    /// iterators over ranges of the underlying view.
+    pub(crate) fn new<F, I>(range: F) -> Self
+    where
+        F: Fn(Bounds) -> I + 'a,
+        I: DoubleEndedIterator<Item = crate::Result<KvPair>> + 'a,
+    {
+        Self {
+            range: Box::new(move |bounds| Box::new(range(bounds))),
+            iter: None,
+            current: None,
+        }
+    }
+
+    fn position(&mut self, direction: Direction, bounds: Bounds) -> crate::Result<bool> {
+        self.iter = Some((direction, (self.range)(bounds)));
+        self.advance()
+    }
+
+    /// Moves the current iterator on until it reaches the given key,
+    /// if the key is ahead of the cursor in the iterator's direction.
+    ///
+    /// Returns `None` if a new iterator needs to be set up instead.
+    fn skip_to(&mut self, direction: Direction, key: &UserKey) -> crate::Result<Option<bool>> {
+        let is_ahead = |current: &UserKey| match direction {
+            Direction::Forward => current < key,
+            Direction::Backward => current > key,
+        };
+
+        // NOTE: The cursor already points to the key
+        if self.key() == Some(key) {
+            return Ok(Some(true));
+        }
+
+        // NOTE: Iterators cannot go back, so they can only be used to seek ahead of the cursor
+        if !matches!(&self.iter, Some((iter_direction, _)) if *iter_direction == direction)
+            || !self.key().is_some_and(is_ahead)
+        {
+            return Ok(None);
+        }
+
+        for _ in 0..MAX_SKIPPED_ITEMS {
+            if !self.advance()? {
+                return Ok(Some(false));
+            }
+
+            if !self.key().is_some_and(is_ahead) {
+                return Ok(Some(true));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn advance(&mut self) -> crate::Result<bool> {
+        let item = match &mut self.iter {
+            Some((Direction::Forward, iter)) => iter.next(),
+            Some((Direction::Backward, iter)) => iter.next_back(),
+            None => None,
+        };
+
+        match item.transpose() {
+            Ok(item) => {
+                self.current = item;
+            }
+            Err(e) => {
+                self.current = None;
+                self.iter = None;
+                return Err(e);
+            }
+        }
+
+        if self.current.is_none() {
+            self.iter = None;
+        }
+
+        Ok(self.is_valid())
+    }
+
+    /// Moves the cursor to the first item.
+    ///
+    /// Returns `true` if the cursor points to an item.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn seek_to_first(&mut self) -> crate::Result<bool> {
+        self.position(Direction::Forward, (Bound::Unbounded, Bound::Unbounded))
+    }
+
+    /// Moves the cursor to the last item.
+    ///
+    /// Returns `true` if the cursor points to an item.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn seek_to_last(&mut self) -> crate::Result<bool> {
+        self.position(Direction::Backward, (Bound::Unbounded, Bound::Unbounded))
+    }
+
+    /// Moves the cursor to the first item with a key greater than or equal to the given key.
+    ///
+    /// Returns `true` if the cursor points to an item.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn seek<K: AsRef<[u8]>>(&mut self, key: K) -> crate::Result<bool> {
+        let key = UserKey::from(key.as_ref());
+
+        if let Some(is_valid) = self.skip_to(Direction::Forward, &key)? {
+            return Ok(is_valid);
+        }
+
+        self.position(Direction::Forward, (Bound::Included(key), Bound::Unbounded))
+    }
+
+    /// Moves the cursor to the last item with a key less than or equal to the given key.
+    ///
+    /// Returns `true` if the cursor points to an item.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn seek_for_prev<K: AsRef<[u8]>>(&mut self, key: K) -> crate::Result<bool> {
+        let key = UserKey::from(key.as_ref());
+
+        if let Some(is_valid) = self.skip_to(Direction::Backward, &key)? {
+            return Ok(is_valid);
+        }
+
+        self.position(
+            Direction::Backward,
+            (Bound::Unbounded, Bound::Included(key)),
+        )
+    }
+
+    /// Moves the cursor to the next item.
+    ///
+    /// Returns `true` if the cursor points to an item.
+    /// If the cursor does not point to an item, it is not moved.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> crate::Result<bool> {
+        let Some((key, _)) = &self.current else {
+            return Ok(false);
+        };
+
+        if matches!(self.iter, Some((Direction::Forward, _))) {
+            return self.advance();
+        }
+
+        let key = key.clone();
+        self.position(Direction::Forward, (Bound::Excluded(key), Bound::Unbounded))
+    }
+
+    /// Moves the cursor to the previous item.
+    ///
+    /// Returns `true` if the cursor points to an item.
+    /// If the cursor does not point to an item, it is not moved.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn prev(&mut self) -> crate::Result<bool> {
+        let Some((key, _)) = &self.current else {
+            return Ok(false);
+        };
+
+        if matches!(self.iter, Some((Direction::Backward, _))) {
+            return self.advance();
+        }
+
+        let key = key.clone();
+        self.position(
+            Direction::Backward,
+            (Bound::Unbounded, Bound::Excluded(key)),
+        )
+    }
+
+    /// Returns `true` if the cursor points to an item.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Returns the key of the item the cursor points to.
+    #[must_use]
+    pub fn key(&self) -> Option<&UserKey> {
+        self.current.as_ref().map(|(key, _)| key)
+    }
+
+    /// Returns the value of the item the cursor points to.
+    #[must_use]
+    pub fn value(&self) -> Option<&UserValue> {
+        self.current.as_ref().map(|(_, value)| value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, collections::BTreeMap};
+    use test_log::test;
+
+    fn cursor(map: &BTreeMap<UserKey, UserValue>) -> Cursor<'_> {
+        Cursor::new(move |bounds: Bounds| {
+            map.range(bounds)
+                .map(|(k, v)| Ok((k.clone(), v.clone())))
+                .collect::<Vec<_>>()
+                .into_iter()
+        })
+    }
+
+    #[test]
+    fn cursor_direction_change() -> crate::Result<()> {
+        let map = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|k| (UserKey::from(k), UserValue::from(k)))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut cursor = cursor(&map);
+        assert!(!cursor.is_valid());
+        assert!(!cursor.next()?);
+
+        assert!(cursor.seek("b")?);
+        assert!(cursor.next()?);
+        assert_eq!(Some(&UserKey::from("c")), cursor.key());
+        assert!(cursor.prev()?);
+        assert_eq!(Some(&UserKey::from("b")), cursor.key());
+        assert!(cursor.next()?);
+        assert!(cursor.next()?);
+        assert_eq!(Some(&UserValue::from("d")), cursor.value());
+        assert!(!cursor.next()?);
+        assert!(!cursor.prev()?);
+
+        assert!(cursor.seek_to_last()?);
+        assert_eq!(Some(&UserKey::from("d")), cursor.key());
+        assert!(cursor.seek_to_first()?);
+        assert_eq!(Some(&UserKey::from("a")), cursor.key());
+
+        assert!(!cursor.seek("e")?);
+        assert!(!cursor.seek_for_prev("0")?);
+        assert!(cursor.seek_for_prev("bb")?);
+        assert_eq!(Some(&UserKey::from("b")), cursor.key());
+
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_seek_reuses_iterator() -> crate::Result<()> {
+        let map = (0..100)
+            .map(|idx| format!("{idx:0>3}"))
+            .map(|k| (UserKey::from(&*k), UserValue::from(&*k)))
+            .collect::<BTreeMap<_, _>>();
+
+        let iterators = Cell::new(0);
+
+        let mut cursor = Cursor::new(|bounds: Bounds| {
+            iterators.set(iterators.get() + 1);
+
+            map.range(bounds)
+                .map(|(k, v)| Ok((k.clone(), v.clone())))
+                .collect::<Vec<_>>()
+                .into_iter()
+        });
+
+        assert!(cursor.seek("010")?);
+        assert!(cursor.seek("0155")?);
+        assert_eq!(Some(&UserKey::from("016")), cursor.key());
+        assert!(cursor.seek("016")?);
+        assert!(cursor.next()?);
+        assert_eq!(Some(&UserKey::from("017")), cursor.key());
+        assert_eq!(1, iterators.get());
+
+        // NOTE: Seeking backwards or far ahead needs a new iterator
+        assert!(cursor.seek("012")?);
+        assert_eq!(Some(&UserKey::from("012")), cursor.key());
+        assert_eq!(2, iterators.get());
+
+        assert!(cursor.seek("090")?);
+        assert_eq!(Some(&UserKey::from("090")), cursor.key());
+        assert_eq!(3, iterators.get());
+
+        assert!(cursor.seek_for_prev("080")?);
+        assert!(cursor.seek_for_prev("0755")?);
+        assert_eq!(Some(&UserKey::from("075")), cursor.key());
+        assert!(cursor.prev()?);
+        assert_eq!(Some(&UserKey::from("074")), cursor.key());
+        assert_eq!(4, iterators.get());
+
+        assert!(!cursor.seek("100")?);
+        assert_eq!(5, iterators.get());
+
+        Ok(())
+    }
+}
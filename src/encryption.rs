@@ -0,0 +1,223 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+/// Encrypts and decrypts journals and partition configuration files
+///
+/// This is **not** encryption at rest: the data of partitions is stored in segment and blob files,
+/// which are written by the underlying LSM-tree and are **not** encrypted, so they need to be
+/// protected by other means, e.g. file system or disk encryption.
+///
+/// Every journal batch is encrypted separately. The ciphertext needs to be self-describing
+/// (e.g. contain the nonce and the ID of the key used), so it can still be decrypted
+/// after the key for new data has been rotated.
+///
+/// A keyspace that was written without encryption cannot be opened with encryption,
+/// because unencrypted journals and configuration files are rejected.
+pub trait JournalEncryptionProvider: Send + Sync {
+    /// Encrypts the given bytes.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the data could not be encrypted.
+    fn encrypt(&self, plaintext: &[u8]) -> crate::Result<Vec<u8>>;
+
+    /// Decrypts the given bytes.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the data could not be decrypted, e.g.
+    /// because the key is not available, or the data is corrupt.
+    fn decrypt(&self, ciphertext: &[u8]) -> crate::Result<Vec<u8>>;
+}
+
+impl std::fmt::Debug for dyn JournalEncryptionProvider {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "JournalEncryptionProvider")
+    }
+}
+
+/// Header of encrypted files
+pub const ENCRYPTED_MAGIC_BYTES: &[u8] = &[b'F', b'J', b'E', 2];
+
+/// Encrypts a file's content, if encryption is enabled.
+pub fn encrypt_file(
+    encryption: Option<&dyn JournalEncryptionProvider>,
+    bytes: Vec<u8>,
+) -> crate::Result<Vec<u8>> {
+    let Some(encryption) = encryption else {
+        return Ok(bytes);
+    };
+
+    let mut encrypted = ENCRYPTED_MAGIC_BYTES.to_vec();
+    encrypted.extend(encryption.encrypt(&bytes)?);
+    Ok(encrypted)
+}
+
+/// Decrypts a file's content, if encryption is enabled.
+pub fn decrypt_file(
+    encryption: Option<&dyn JournalEncryptionProvider>,
+    bytes: Vec<u8>,
+) -> crate::Result<Vec<u8>> {
+    let Some(ciphertext) = bytes.strip_prefix(ENCRYPTED_MAGIC_BYTES) else {
+        if encryption.is_some() {
+            log::error!("File is not encrypted, but an encryption provider is configured");
+            return Err(crate::Error::Encryption);
+        }
+
+        return Ok(bytes);
+    };
+
+    let Some(encryption) = encryption else {
+        log::error!("File is encrypted, but no encryption provider is configured");
+        return Err(crate::Error::Encryption);
+    };
+
+    encryption.decrypt(ciphertext)
+}
+
+#[cfg(feature = "journal_encryption")]
+pub use aes::{Aes256GcmProvider, EncryptionKey, EncryptionKeyId};
+
+#[cfg(feature = "journal_encryption")]
+mod aes {
+    use super::JournalEncryptionProvider;
+    use aes_gcm::{
+        aead::{Aead, AeadCore, KeyInit, OsRng},
+        Aes256Gcm, Key, Nonce,
+    };
+    use std::sync::Arc;
+
+    /// ID of an encryption key
+    pub type EncryptionKeyId = u32;
+
+    /// 256-bit encryption key
+    pub type EncryptionKey = [u8; 32];
+
+    type KeyFn = dyn Fn(EncryptionKeyId) -> Option<EncryptionKey> + Send + Sync;
+
+    const KEY_ID_LEN: usize = std::mem::size_of::<EncryptionKeyId>();
+    const NONCE_LEN: usize = 12;
+
+    /// AES-256-GCM encryption, using keys provided by a callback
+    ///
+    /// New data is encrypted using the current key; the key ID and
+    /// a random nonce are stored in front of the ciphertext.
+    /// To rotate the key, reopen the keyspace with a new current key ID, while the
+    /// callback keeps returning older keys, as long as there is data encrypted with them.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Aes256GcmProvider, Config};
+    /// # use std::sync::Arc;
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// let encryption = Aes256GcmProvider::new(1, |key_id| match key_id {
+    ///     // NOTE: Load keys from a secret store instead
+    ///     1 => Some([7; 32]),
+    ///     _ => None,
+    /// });
+    ///
+    /// let keyspace = Config::new(folder)
+    ///     .journal_encryption(Arc::new(encryption))
+    ///     .open()?;
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    pub struct Aes256GcmProvider {
+        key_id: EncryptionKeyId,
+        keys: Arc<KeyFn>,
+    }
+
+    impl Aes256GcmProvider {
+        /// Creates a new provider, encrypting new data with the key of the given ID.
+        pub fn new<F>(key_id: EncryptionKeyId, keys: F) -> Self
+        where
+            F: Fn(EncryptionKeyId) -> Option<EncryptionKey> + Send + Sync + 'static,
+        {
+            Self {
+                key_id,
+                keys: Arc::new(keys),
+            }
+        }
+
+        fn cipher(&self, key_id: EncryptionKeyId) -> crate::Result<Aes256Gcm> {
+            let Some(key) = (self.keys)(key_id) else {
+                log::error!("Encryption key {key_id} is not available");
+                return Err(crate::Error::Encryption);
+            };
+
+            Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+        }
+    }
+
+    impl JournalEncryptionProvider for Aes256GcmProvider {
+        fn encrypt(&self, plaintext: &[u8]) -> crate::Result<Vec<u8>> {
+            let cipher = self.cipher(self.key_id)?;
+            let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+
+            let ciphertext = cipher
+                .encrypt(&nonce, plaintext)
+                .map_err(|_| crate::Error::Encryption)?;
+
+            let mut bytes = Vec::with_capacity(KEY_ID_LEN + NONCE_LEN + ciphertext.len());
+            bytes.extend_from_slice(&self.key_id.to_be_bytes());
+            bytes.extend_from_slice(&nonce);
+            bytes.extend(ciphertext);
+
+            Ok(bytes)
+        }
+
+        fn decrypt(&self, ciphertext: &[u8]) -> crate::Result<Vec<u8>> {
+            if ciphertext.len() < KEY_ID_LEN + NONCE_LEN {
+                return Err(crate::Error::Encryption);
+            }
+
+            let (key_id, rest) = ciphertext.split_at(KEY_ID_LEN);
+            let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+            let key_id = key_id.try_into().map_err(|_| crate::Error::Encryption)?;
+            let cipher = self.cipher(EncryptionKeyId::from_be_bytes(key_id))?;
+
+            cipher
+                .decrypt(Nonce::from_slice(nonce), ciphertext)
+                .map_err(|_| {
+                    log::error!("Could not decrypt data, the key is wrong or the data is corrupt");
+                    crate::Error::Encryption
+                })
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use test_log::test;
+
+        #[test]
+        fn aes_gcm_key_rotation() -> crate::Result<()> {
+            let keys = |key_id| match key_id {
+                1 => Some([1; 32]),
+                2 => Some([2; 32]),
+                _ => None,
+            };
+
+            let old = Aes256GcmProvider::new(1, keys);
+            let new = Aes256GcmProvider::new(2, keys);
+
+            let encrypted = old.encrypt(b"hello")?;
+            assert!(!encrypted.windows(5).any(|window| window == b"hello"));
+            assert_eq!(b"hello", &*new.decrypt(&encrypted)?);
+
+            // NOTE: Unknown keys and tampered data are rejected
+            let unknown = Aes256GcmProvider::new(3, keys);
+            assert!(unknown.encrypt(b"hello").is_err());
+
+            let mut tampered = new.encrypt(b"hello")?;
+            *tampered.last_mut().unwrap() ^= 1;
+            assert!(old.decrypt(&tampered).is_err());
+
+            Ok(())
+        }
+    }
+}
@@ -37,6 +37,61 @@ pub enum Error {
 
     /// Partition is deleted
     PartitionDeleted,
+
+    /// Partition name is empty, longer than 255 characters, or contains invalid characters
+    ///
+    /// See [`Keyspace::open_partition`](crate::Keyspace::open_partition).
+    InvalidPartitionName,
+
+    /// Key is longer than 65535 bytes
+    KeyTooLarge,
+
+    /// Merge operand is longer than 2^32 - 1 bytes
+    ValueTooLarge,
+
+    /// Partition contains merge operands, but has no merge operator
+    ///
+    /// See [`PartitionCreateOptions::with_merge_operator`](crate::PartitionCreateOptions::with_merge_operator).
+    MissingMergeOperator,
+
+    /// Data could not be encrypted or decrypted, or is not encrypted although encryption is enabled
+    ///
+    /// See [`JournalEncryptionProvider`](crate::JournalEncryptionProvider).
+    Encryption,
+
+    /// Backup does not exist
+    BackupNotFound(crate::BackupId),
+
+    /// A file of the backup does not match its checksum
+    BackupCorrupted(crate::BackupId),
+
+    /// Replica cannot catch up, because the journals it needs have been deleted on the primary
+    ///
+    /// The replica should be reopened, so it catches up from a checkpoint.
+    ///
+    /// See [`Replica`](crate::replication::Replica).
+    ReplicaTooFarBehind,
+
+    /// Keyspace was opened read-only, so it cannot be written to
+    ///
+    /// See [`Config::read_only`](crate::Config::read_only),
+    /// [`Config::open_secondary`](crate::Config::open_secondary) and
+    /// [`Replica`](crate::replication::Replica).
+    ReadOnly,
+
+    /// Keyspace is locked, because it is opened by another process (or keyspace instance)
+    ///
+    /// See [`Config::lock_file`](crate::Config::lock_file).
+    Locked,
+
+    /// Transaction conflict
+    ///
+    /// Another transaction has committed a write to a key (or range)
+    /// that was read by this transaction, so committing it would violate serializability.
+    ///
+    /// The transaction should be retried.
+    #[cfg(feature = "ssi_tx")]
+    Conflict,
 }
 
 impl std::fmt::Display for Error {
@@ -0,0 +1,320 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::file::PARTITION_EXPIRY_FOLDER;
+use lsm_tree::{AbstractTree, AnyTree, SeqNo, SequenceNumberCounter, Tree};
+use std::{
+    sync::{Arc, Mutex, OnceLock, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Returns the current unix timestamp in milliseconds.
+pub fn unix_timestamp_ms() -> u64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+
+    // NOTE: Truncation is fine for the next few million years
+    #[allow(clippy::cast_possible_truncation)]
+    {
+        now.as_millis() as u64
+    }
+}
+
+/// Returns the unix timestamp (in milliseconds) at which an item expires.
+pub fn expires_at(ttl: Duration) -> u64 {
+    let ttl = u64::try_from(ttl.as_millis()).unwrap_or(u64::MAX);
+    unix_timestamp_ms().saturating_add(ttl)
+}
+
+const ITEM_PREFIX: u8 = b'k';
+const EXPIRY_PREFIX: u8 = b'e';
+
+/// Key of the item version, pointing to its expiry timestamp
+fn item_key(key: &[u8], seqno: SeqNo) -> Vec<u8> {
+    let mut v = Vec::with_capacity(1 + key.len() + std::mem::size_of::<SeqNo>());
+    v.push(ITEM_PREFIX);
+    v.extend_from_slice(key);
+    v.extend_from_slice(&seqno.to_be_bytes());
+    v
+}
+
+/// Key of the item version, sorted by expiry timestamp
+fn expiry_key(expires_at: u64, key: &[u8], seqno: SeqNo) -> Vec<u8> {
+    let mut v = Vec::with_capacity(1 + 8 + key.len() + std::mem::size_of::<SeqNo>());
+    v.push(EXPIRY_PREFIX);
+    v.extend_from_slice(&expires_at.to_be_bytes());
+    v.extend_from_slice(key);
+    v.extend_from_slice(&seqno.to_be_bytes());
+    v
+}
+
+/// Parses an expiry key into its expiry timestamp, user key and seqno
+fn parse_expiry_key(v: &[u8]) -> Option<(u64, &[u8], SeqNo)> {
+    let v = v.strip_prefix(&[EXPIRY_PREFIX])?;
+
+    if v.len() < 16 {
+        return None;
+    }
+
+    let (expires_at, rest) = v.split_at(8);
+    let (key, seqno) = rest.split_at(rest.len() - 8);
+
+    Some((
+        u64::from_be_bytes(expires_at.try_into().ok()?),
+        key,
+        SeqNo::from_be_bytes(seqno.try_into().ok()?),
+    ))
+}
+
+/// Index of items that were written with a time-to-live
+///
+/// The LSM-tree has no notion of expiry, so the expiry timestamps are stored
+/// in a small, separate LSM-tree, keyed by the item's key and seqno.
+///
+/// Expired items are hidden from reads immediately, and are removed
+/// by writing a tombstone before the partition is compacted.
+///
+/// The index is only created once the partition receives its first item with a TTL.
+pub struct ExpiryIndex {
+    config: lsm_tree::Config,
+
+    tree: OnceLock<Tree>,
+
+    /// Guards creating the index tree
+    init_lock: Mutex<()>,
+
+    /// Serializes flushes, so all index entries of a partition's memtable
+    /// are persisted once a flush returns
+    flush_lock: Mutex<()>,
+}
+
+impl ExpiryIndex {
+    pub fn new(partition_config: &lsm_tree::Config) -> Self {
+        let config = lsm_tree::Config::new(partition_config.path.join(PARTITION_EXPIRY_FOLDER))
+            .descriptor_table(partition_config.descriptor_table.clone())
+            .block_cache(partition_config.block_cache.clone());
+
+        Self {
+            config,
+            tree: OnceLock::new(),
+            init_lock: Mutex::default(),
+            flush_lock: Mutex::default(),
+        }
+    }
+
+    /// Recovers the index, if the partition has one.
+    pub fn recover(partition_config: &lsm_tree::Config) -> crate::Result<Self> {
+        let index = Self::new(partition_config);
+
+        if index.config.path.try_exists()? {
+            index.get_or_create()?;
+        }
+
+        Ok(index)
+    }
+
+    #[allow(clippy::expect_used)]
+    fn get_or_create(&self) -> crate::Result<&Tree> {
+        if let Some(tree) = self.tree.get() {
+            return Ok(tree);
+        }
+
+        let _lock = self.init_lock.lock().expect("lock is poisoned");
+
+        if let Some(tree) = self.tree.get() {
+            return Ok(tree);
+        }
+
+        let tree = self.config.clone().open()?;
+        Ok(self.tree.get_or_init(|| tree))
+    }
+
+    /// Returns `true` if the partition has ever received an item with a TTL.
+    pub fn is_active(&self) -> bool {
+        self.tree.get().is_some()
+    }
+
+    /// Returns the index tree, if the partition has one.
+    pub fn tree(&self) -> Option<&Tree> {
+        self.tree.get()
+    }
+
+    /// Uses the given index tree, unless the index has one already.
+    ///
+    /// Returns the index tree.
+    pub fn get_or_set(&self, tree: &Tree) -> &Tree {
+        self.tree.get_or_init(|| tree.clone())
+    }
+
+    /// Stores the expiry timestamp of an item version.
+    ///
+    /// Needs to be called before the item is inserted into the memtable,
+    /// otherwise it may be read without its expiry.
+    pub fn insert(&self, key: &[u8], seqno: SeqNo, expires_at: u64) -> crate::Result<()> {
+        let tree = self.get_or_create()?;
+        tree.insert(item_key(key, seqno), expires_at.to_be_bytes(), seqno);
+        tree.insert(expiry_key(expires_at, key, seqno), [], seqno);
+        Ok(())
+    }
+
+    /// Returns `true` if the item version has expired.
+    #[allow(clippy::expect_used)]
+    pub fn is_expired(&self, key: &[u8], seqno: SeqNo) -> crate::Result<bool> {
+        let Some(tree) = self.tree.get() else {
+            return Ok(false);
+        };
+
+        let Some(expires_at) = tree.get(item_key(key, seqno))? else {
+            return Ok(false);
+        };
+
+        let expires_at = u64::from_be_bytes((*expires_at).try_into().expect("should be u64"));
+
+        Ok(expires_at <= unix_timestamp_ms())
+    }
+
+    /// Persists the index's memtable.
+    ///
+    /// Needs to be called before segments of the partition are registered,
+    /// otherwise the journal may be evicted before the expiry timestamps are persisted.
+    #[allow(clippy::expect_used)]
+    pub fn flush(&self) -> crate::Result<()> {
+        let Some(tree) = self.tree.get() else {
+            return Ok(());
+        };
+
+        let _lock = self.flush_lock.lock().expect("lock is poisoned");
+        tree.flush_active_memtable(0)?;
+
+        Ok(())
+    }
+
+    /// Writes tombstones for expired items, so they are removed by compaction.
+    ///
+    /// Index entries are removed once their item is gone (or overwritten).
+    ///
+    /// Returns the size of the written tombstones.
+    #[allow(clippy::expect_used)]
+    pub fn sweep(
+        &self,
+        partition_tree: &AnyTree,
+        write_lock: &RwLock<()>,
+        seqno_counter: &SequenceNumberCounter,
+        gc_watermark: SeqNo,
+    ) -> crate::Result<u64> {
+        let Some(tree) = self.tree.get() else {
+            return Ok(0);
+        };
+
+        let now = unix_timestamp_ms();
+        let persisted_seqno = partition_tree.get_highest_persisted_seqno();
+
+        let mut written_bytes = 0;
+
+        let mut end = vec![EXPIRY_PREFIX];
+        end.extend_from_slice(&now.saturating_add(1).to_be_bytes());
+
+        let expired = tree
+            .range([EXPIRY_PREFIX].as_slice()..end.as_slice())
+            .map(|kv| kv.map(|(k, _)| k))
+            .collect::<lsm_tree::Result<Vec<_>>>()?;
+
+        for expiry_key in expired {
+            let Some((_, key, seqno)) = parse_expiry_key(&expiry_key) else {
+                continue;
+            };
+
+            // IMPORTANT: Hold the write lock, so no write to the key can slip in
+            // between reading its latest version and writing the tombstone
+            let write_lock = write_lock.write().expect("lock is poisoned");
+
+            let latest = match partition_tree {
+                AnyTree::Standard(tree) => tree.get_internal_entry(key, false, None)?,
+                AnyTree::Blob(tree) => tree.index.get_internal_entry(key, false, None)?,
+            };
+
+            let is_obsolete = match latest {
+                // NOTE: The item is still the latest version, so delete it
+                //
+                // The tombstone gets a new seqno, so it is newer than every version
+                // of the key, no matter in which memtable or segment it is.
+                // It is not journaled, because the index entries are kept
+                // until the tombstone is persisted, so it will be rewritten after a crash
+                Some(item) if item.key.seqno == seqno && !item.is_tombstone() => {
+                    let (size, _) = partition_tree.remove(key, seqno_counter.next());
+                    written_bytes += u64::from(size);
+                    false
+                }
+
+                // NOTE: The item has been deleted, but the tombstone may not be persisted yet
+                Some(item) if item.is_tombstone() => {
+                    item.key.seqno < gc_watermark
+                        && persisted_seqno.is_some_and(|persisted| item.key.seqno <= persisted)
+                }
+
+                // NOTE: The item has been overwritten, but may still be visible to snapshots
+                Some(item) => item.key.seqno < gc_watermark,
+
+                None => true,
+            };
+
+            drop(write_lock);
+
+            if is_obsolete {
+                tree.remove(item_key(key, seqno), seqno_counter.get());
+                tree.remove(&*expiry_key, seqno_counter.get());
+            }
+        }
+
+        tree.compact(
+            Arc::new(lsm_tree::compaction::Leveled::default()),
+            SeqNo::MAX,
+        )?;
+
+        Ok(written_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn expiry_key_roundtrip() {
+        let v = expiry_key(5, b"abc", 7);
+        assert_eq!(Some((5, &b"abc"[..], 7)), parse_expiry_key(&v));
+
+        let v = expiry_key(5, b"", 7);
+        assert_eq!(Some((5, &b""[..], 7)), parse_expiry_key(&v));
+
+        assert_eq!(None, parse_expiry_key(&item_key(b"abc", 7)));
+    }
+
+    #[test]
+    fn expiry_index_is_expired() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let index = ExpiryIndex::new(&lsm_tree::Config::new(&folder));
+        assert!(!index.is_active());
+        assert!(!index.is_expired(b"a", 0)?);
+
+        index.insert(b"a", 0, 0)?;
+        index.insert(b"b", 1, u64::MAX)?;
+        assert!(index.is_active());
+
+        assert!(index.is_expired(b"a", 0)?);
+        assert!(!index.is_expired(b"a", 1)?);
+        assert!(!index.is_expired(b"b", 1)?);
+
+        index.flush()?;
+        assert!(index.is_expired(b"a", 0)?);
+
+        let index = ExpiryIndex::recover(&lsm_tree::Config::new(&folder))?;
+        assert!(index.is_active());
+        assert!(index.is_expired(b"a", 0)?);
+
+        Ok(())
+    }
+}
@@ -7,14 +7,26 @@ use std::path::Path;
 pub const MAGIC_BYTES: &[u8] = &[b'F', b'J', b'L', 2];
 
 pub const JOURNALS_FOLDER: &str = "journals";
+
+/// Folder of copies of journals that contained skipped batches, see [`crate::RecoveryMode::SkipInvalidBatches`]
+pub const SALVAGED_JOURNALS_FOLDER: &str = "salvaged_journals";
 pub const PARTITIONS_FOLDER: &str = "partitions";
 
 pub const FJALL_MARKER: &str = "version";
+pub const LOCK_FILE: &str = "LOCK";
 pub const PARTITION_DELETED_MARKER: &str = ".deleted";
 pub const PARTITION_CONFIG_FILE: &str = "config";
+pub const PARTITION_RANGE_TOMBSTONES_FILE: &str = "range_tombstones";
+pub const PARTITION_EXPIRY_FOLDER: &str = "expiry";
+
+/// Folder of values that are too large for the journal and LSM-tree, see [`crate::large_value`]
+pub const LARGE_VALUES_FOLDER: &str = "large_values";
 
 pub const LSM_MANIFEST_FILE: &str = "manifest";
 
+/// Folder of segment (and blob) files in LSM-trees and value logs
+pub const SEGMENTS_FOLDER: &str = "segments";
+
 #[cfg(not(target_os = "windows"))]
 pub fn fsync_directory<P: AsRef<Path>>(path: P) -> std::io::Result<()> {
     let file = std::fs::File::open(path)?;
@@ -4,24 +4,124 @@
 
 use super::manager::{FlushManager, Task};
 use crate::{
-    batch::PartitionKey, compaction::manager::CompactionManager, journal::manager::JournalManager,
-    snapshot_tracker::SnapshotTracker, write_buffer_manager::WriteBufferManager, HashMap,
-    PartitionHandle,
+    batch::PartitionKey,
+    compaction::{filter::filter_memtable as apply_compaction_filter, manager::CompactionManager},
+    journal::manager::JournalManager,
+    merge::merged_item,
+    range_tombstone::filter_memtable,
+    snapshot_tracker::SnapshotTracker,
+    write_buffer_manager::WriteBufferManager,
+    HashMap, PartitionHandle,
 };
-use lsm_tree::{AbstractTree, Segment, SeqNo};
+use lsm_tree::{AbstractTree, Memtable, Segment, SeqNo};
 use std::sync::{Arc, RwLock};
 
+/// Replaces the merge operands of a sealed memtable by their merged values,
+/// so merge operands never hit disk.
+///
+/// Returns `None` if the memtable does not contain any merge operands.
+fn collapse_merge_operands(
+    partition: &PartitionHandle,
+    memtable: &Memtable,
+) -> crate::Result<Option<Memtable>> {
+    let operands = {
+        let Some(registered) = partition.merges.read_operands() else {
+            return Ok(None);
+        };
+
+        memtable
+            .iter()
+            .filter(|item| {
+                registered
+                    .get(&item.key.user_key)
+                    .is_some_and(|x| x.contains_key(&item.key.seqno))
+            })
+            .collect::<Vec<_>>()
+    };
+
+    if operands.is_empty() {
+        return Ok(None);
+    }
+
+    let collapsed = Memtable::default();
+
+    for item in memtable.iter() {
+        collapsed.insert(item);
+    }
+
+    let mut resolved: HashMap<_, Vec<_>> = HashMap::default();
+    let mut large_values = Vec::new();
+
+    for item in operands {
+        // NOTE: Read the key as of the operand, so newer items are ignored
+        let mut value = partition
+            .read_filter(Some(item.key.seqno + 1))
+            .resolve(&item.key.user_key, item.value.clone())?;
+
+        // NOTE: Merged values may grow too large for the tree
+        if let Some(large_value) = partition.write_large_value(&item.key.user_key, &value)? {
+            value = large_value.reference.clone();
+            large_values.push(large_value);
+        }
+
+        collapsed.insert(merged_item(&partition.tree, &item, &value));
+
+        resolved
+            .entry(item.key.user_key)
+            .or_default()
+            .push((item.key.seqno, value));
+    }
+
+    for (key, values) in resolved {
+        partition.merges.resolve(&key, values);
+    }
+
+    // NOTE: Once resolved, the references are visible to reads
+    for large_value in large_values {
+        large_value.commit();
+    }
+
+    Ok(Some(collapsed))
+}
+
 /// Flushes a single segment.
 fn run_flush_worker(
     task: &Arc<Task>,
     eviction_threshold: SeqNo,
 ) -> crate::Result<Option<Arc<Segment>>> {
+    // NOTE: Drop items that are deleted by range tombstones, so they never hit disk
+    let filtered_memtable = filter_memtable(
+        &task.sealed_memtable,
+        &task.partition.range_tombstones.get(),
+        eviction_threshold,
+    );
+
+    let memtable = filtered_memtable.as_ref().unwrap_or(&task.sealed_memtable);
+
+    // NOTE: Merge operands are collapsed into full values,
+    // so segments (and compactions) never see merge operands
+    let collapsed_memtable = collapse_merge_operands(&task.partition, memtable)?.map(Arc::new);
+
+    let memtable = collapsed_memtable.as_ref().unwrap_or(memtable);
+
+    // NOTE: The compaction filter sees fully merged values
+    let compacted_memtable = match task.partition.compaction_filter.get() {
+        Some(filter) => apply_compaction_filter(
+            &task.partition.tree,
+            &*filter,
+            &task.partition.large_values,
+            memtable,
+        )?
+        .map(Arc::new),
+        None => None,
+    };
+
     #[rustfmt::skip]
     let segment = task.partition.tree.flush_memtable(
         // IMPORTANT: Segment has to get the task ID
         // otherwise segment ID and memtable ID will not line up
         task.id,
-        &task.sealed_memtable,
+        compacted_memtable.as_ref().unwrap_or(memtable),
         eviction_threshold,
     )?;
 
@@ -130,6 +230,13 @@ pub fn run(
                 created_segments,
                 size: memtables_size,
             }) => {
+                // IMPORTANT: Expiry timestamps need to be persisted before the segments are registered,
+                // otherwise the journal may be evicted without them
+                if let Err(e) = partition.expiry.flush() {
+                    log::error!("Failed to flush expiry index: {e:?}");
+                    continue;
+                }
+
                 // IMPORTANT: Flushed segments need to be applied *atomically* into the tree
                 // otherwise we could cover up an unwritten journal, which will result in data loss
                 if let Err(e) = partition.tree.register_segments(&created_segments) {
@@ -145,6 +252,11 @@ pub fn run(
                     );
                     flush_manager.dequeue_tasks(partition.name.clone(), created_segments.len());
 
+                    // NOTE: The flushed memtables are gone, so are their merge operands
+                    for task in partitioned_tasks.get(&partition.name).into_iter().flatten() {
+                        partition.merges.remove_memtable(&task.sealed_memtable);
+                    }
+
                     write_buffer_manager.free(memtables_size);
                     compaction_manager.notify(partition);
                 }
@@ -0,0 +1,483 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{FileHandle, FileLock, FileSystem, LockMode, OpenMode};
+use crate::HashMap;
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    time::SystemTime,
+};
+
+fn injected_fault() -> std::io::Error {
+    std::io::Error::other("injected fault")
+}
+
+/// Contents of a file, as seen by the file handles,
+/// which may not have been synced to the inner file system yet
+struct Overlay {
+    /// Path of the file in the inner file system, `None` if it was removed
+    path: Option<PathBuf>,
+
+    data: Vec<u8>,
+
+    modified: SystemTime,
+
+    /// Offset from which `data` differs from the inner file system
+    dirty_from: Option<usize>,
+
+    /// Set when the file system crashes, which invalidates all file handles
+    crashed: bool,
+}
+
+impl Overlay {
+    fn mark_dirty(&mut self, offset: usize) {
+        self.modified = SystemTime::now();
+        self.dirty_from = Some(self.dirty_from.map_or(offset, |x| x.min(offset)));
+    }
+
+    fn check_crashed(&self) -> std::io::Result<()> {
+        if self.crashed {
+            return Err(std::io::Error::other("file system crashed"));
+        }
+        Ok(())
+    }
+}
+
+type SharedOverlay = Arc<Mutex<Overlay>>;
+
+#[allow(clippy::expect_used)]
+fn lock_overlay(overlay: &SharedOverlay) -> MutexGuard<'_, Overlay> {
+    overlay.lock().expect("lock is poisoned")
+}
+
+struct Shared {
+    inner: Arc<dyn FileSystem>,
+    overlays: Mutex<HashMap<PathBuf, SharedOverlay>>,
+    fail_writes: AtomicBool,
+    fail_syncs: AtomicBool,
+}
+
+impl Shared {
+    #[allow(clippy::expect_used)]
+    fn overlays(&self) -> MutexGuard<'_, HashMap<PathBuf, SharedOverlay>> {
+        self.overlays.lock().expect("lock is poisoned")
+    }
+
+    fn check_write(&self) -> std::io::Result<()> {
+        if self.fail_writes.load(Ordering::Acquire) {
+            return Err(injected_fault());
+        }
+        Ok(())
+    }
+
+    fn check_sync(&self) -> std::io::Result<()> {
+        if self.fail_syncs.load(Ordering::Acquire) {
+            return Err(injected_fault());
+        }
+        Ok(())
+    }
+}
+
+/// A file system that wraps another file system, and can inject faults into it
+///
+/// File writes are buffered until the file is synced, so [`FaultInjectingFileSystem::crash`]
+/// can drop them, like a power loss would. Creating, renaming and removing files and folders
+/// is passed to the inner file system directly, so it is always durable.
+pub struct FaultInjectingFileSystem(Arc<Shared>);
+
+impl FaultInjectingFileSystem {
+    /// Wraps a file system.
+    #[must_use]
+    pub fn new(inner: Arc<dyn FileSystem>) -> Self {
+        Self(Arc::new(Shared {
+            inner,
+            overlays: Mutex::default(),
+            fail_writes: AtomicBool::default(),
+            fail_syncs: AtomicBool::default(),
+        }))
+    }
+
+    /// If enabled, writing to files, and creating, renaming or removing files and folders fails.
+    pub fn fail_writes(&self, flag: bool) {
+        self.0.fail_writes.store(flag, Ordering::Release);
+    }
+
+    /// If enabled, syncing files and folders fails.
+    pub fn fail_syncs(&self, flag: bool) {
+        self.0.fail_syncs.store(flag, Ordering::Release);
+    }
+
+    /// Simulates a crash by dropping all writes that were not synced.
+    ///
+    /// All file handles that are open become unusable.
+    pub fn crash(&self) {
+        for (_, overlay) in self.0.overlays().drain() {
+            lock_overlay(&overlay).crashed = true;
+        }
+
+        log::debug!("Simulated file system crash");
+    }
+
+    fn overlay(&self, path: &Path) -> std::io::Result<SharedOverlay> {
+        let mut overlays = self.0.overlays();
+
+        if let Some(overlay) = overlays.get(path) {
+            return Ok(overlay.clone());
+        }
+
+        let mut file = self.0.inner.open(path, OpenMode::Read)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        let overlay = Arc::new(Mutex::new(Overlay {
+            path: Some(path.into()),
+            data,
+            modified: file.modified()?,
+            dirty_from: None,
+            crashed: false,
+        }));
+        overlays.insert(path.into(), overlay.clone());
+        drop(overlays);
+
+        Ok(overlay)
+    }
+
+    fn detach_overlay(&self, path: &Path) -> Option<SharedOverlay> {
+        let overlay = self.0.overlays().remove(path)?;
+        lock_overlay(&overlay).path = None;
+
+        Some(overlay)
+    }
+
+    fn create_with(
+        &self,
+        path: &Path,
+        create: impl FnOnce(&dyn FileSystem) -> std::io::Result<Box<dyn FileHandle>>,
+    ) -> std::io::Result<Box<dyn FileHandle>> {
+        self.0.check_write()?;
+
+        create(&*self.0.inner)?;
+        self.detach_overlay(path);
+
+        let overlay = self.overlay(path)?;
+
+        Ok(Box::new(FaultInjectingFile {
+            fs: self.0.clone(),
+            overlay,
+            pos: 0,
+            mode: OpenMode::ReadWrite,
+        }))
+    }
+}
+
+struct FaultInjectingFile {
+    fs: Arc<Shared>,
+    overlay: SharedOverlay,
+    pos: u64,
+    mode: OpenMode,
+}
+
+impl FaultInjectingFile {
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "file is opened read-only",
+            ));
+        }
+        self.fs.check_write()
+    }
+
+    fn sync(&self) -> std::io::Result<()> {
+        self.fs.check_sync()?;
+
+        let mut overlay = lock_overlay(&self.overlay);
+        overlay.check_crashed()?;
+
+        let (Some(path), Some(dirty_from)) = (&overlay.path, overlay.dirty_from) else {
+            return Ok(());
+        };
+
+        let mut file = self.fs.inner.open(path, OpenMode::ReadWrite)?;
+        file.seek(SeekFrom::Start(dirty_from as u64))?;
+        file.write_all(overlay.data.get(dirty_from..).unwrap_or_default())?;
+        file.set_len(overlay.data.len() as u64)?;
+        file.sync_all()?;
+
+        overlay.dirty_from = None;
+        drop(overlay);
+
+        Ok(())
+    }
+}
+
+impl Read for FaultInjectingFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let overlay = lock_overlay(&self.overlay);
+        overlay.check_crashed()?;
+
+        let start = usize::try_from(self.pos).unwrap_or(usize::MAX);
+        let mut remaining = overlay.data.get(start..).unwrap_or_default();
+        let n = remaining.read(buf)?;
+        drop(overlay);
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for FaultInjectingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.check_writable()?;
+
+        let mut overlay = lock_overlay(&self.overlay);
+        overlay.check_crashed()?;
+
+        if self.mode == OpenMode::Append {
+            self.pos = overlay.data.len() as u64;
+        }
+
+        let start = usize::try_from(self.pos).map_err(|_| std::io::ErrorKind::InvalidInput)?;
+        let end = start + buf.len();
+
+        if overlay.data.len() < end {
+            overlay.data.resize(end, 0);
+        }
+
+        overlay.data.splice(start..end, buf.iter().copied());
+        overlay.mark_dirty(start);
+        drop(overlay);
+
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for FaultInjectingFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let overlay = lock_overlay(&self.overlay);
+        overlay.check_crashed()?;
+
+        let len = overlay.data.len() as u64;
+        drop(overlay);
+
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        let Some(pos) = pos else {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        };
+
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+impl FileHandle for FaultInjectingFile {
+    fn size(&self) -> std::io::Result<u64> {
+        let overlay = lock_overlay(&self.overlay);
+        overlay.check_crashed()?;
+
+        Ok(overlay.data.len() as u64)
+    }
+
+    fn modified(&self) -> std::io::Result<SystemTime> {
+        let overlay = lock_overlay(&self.overlay);
+        overlay.check_crashed()?;
+
+        Ok(overlay.modified)
+    }
+
+    fn set_len(&self, len: u64) -> std::io::Result<()> {
+        self.check_writable()?;
+
+        let mut overlay = lock_overlay(&self.overlay);
+        overlay.check_crashed()?;
+
+        let len = usize::try_from(len).map_err(|_| std::io::ErrorKind::InvalidInput)?;
+        let prev_len = overlay.data.len();
+
+        overlay.data.resize(len, 0);
+        overlay.mark_dirty(prev_len.min(len));
+        drop(overlay);
+
+        Ok(())
+    }
+
+    fn sync_all(&self) -> std::io::Result<()> {
+        self.sync()
+    }
+
+    fn sync_data(&self) -> std::io::Result<()> {
+        self.sync()
+    }
+
+    fn try_clone(&self) -> std::io::Result<Box<dyn FileHandle>> {
+        lock_overlay(&self.overlay).check_crashed()?;
+
+        Ok(Box::new(Self {
+            fs: self.fs.clone(),
+            overlay: self.overlay.clone(),
+            pos: self.pos,
+            mode: self.mode,
+        }))
+    }
+}
+
+impl FileSystem for FaultInjectingFileSystem {
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        self.0.check_write()?;
+        self.0.inner.create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        self.0.inner.read_dir(path)
+    }
+
+    fn exists(&self, path: &Path) -> std::io::Result<bool> {
+        self.0.inner.exists(path)
+    }
+
+    fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn FileHandle>> {
+        let overlay = self.overlay(path)?;
+
+        Ok(Box::new(FaultInjectingFile {
+            fs: self.0.clone(),
+            overlay,
+            pos: 0,
+            mode,
+        }))
+    }
+
+    fn create(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+        self.create_with(path, |fs| fs.create(path))
+    }
+
+    fn create_new(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+        self.create_with(path, |fs| fs.create_new(path))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        self.0.check_write()?;
+        self.0.inner.rename(from, to)?;
+
+        self.detach_overlay(to);
+
+        let mut overlays = self.0.overlays();
+
+        if let Some(overlay) = overlays.remove(from) {
+            lock_overlay(&overlay).path = Some(to.into());
+            overlays.insert(to.into(), overlay);
+        }
+        drop(overlays);
+
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        self.0.check_write()?;
+        self.0.inner.remove_file(path)?;
+        self.detach_overlay(path);
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        self.0.check_write()?;
+        self.0.inner.remove_dir_all(path)?;
+
+        let removed = self
+            .0
+            .overlays()
+            .keys()
+            .filter(|file| file.starts_with(path))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for file in removed {
+            self.detach_overlay(&file);
+        }
+
+        Ok(())
+    }
+
+    fn lock(&self, path: &Path, mode: LockMode) -> std::io::Result<Option<Box<dyn FileLock>>> {
+        self.0.inner.lock(path, mode)
+    }
+
+    fn sync_directory(&self, path: &Path) -> std::io::Result<()> {
+        self.0.check_sync()?;
+        self.0.inner.sync_directory(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::MemoryFileSystem;
+    use test_log::test;
+
+    #[test]
+    fn fault_injecting_crash() -> std::io::Result<()> {
+        let fs = FaultInjectingFileSystem::new(Arc::new(MemoryFileSystem::new()));
+        let folder = Path::new("/keyspace");
+        fs.create_dir_all(folder)?;
+
+        let mut file = fs.create_new(&folder.join("0"))?;
+        file.write_all(b"abc")?;
+        file.sync_all()?;
+        file.write_all(b"def")?;
+
+        fs.rename(&folder.join("0"), &folder.join("1"))?;
+        assert_eq!(6, fs.open(&folder.join("1"), OpenMode::Read)?.size()?);
+
+        fs.crash();
+        assert!(file.write_all(b"ghi").is_err());
+
+        let mut bytes = Vec::new();
+        fs.open(&folder.join("1"), OpenMode::Read)?
+            .read_to_end(&mut bytes)?;
+        assert_eq!(b"abc", &*bytes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn fault_injecting_failures() -> std::io::Result<()> {
+        let fs = FaultInjectingFileSystem::new(Arc::new(MemoryFileSystem::new()));
+        let folder = Path::new("/keyspace");
+        fs.create_dir_all(folder)?;
+
+        let mut file = fs.create_new(&folder.join("0"))?;
+        file.write_all(b"abc")?;
+
+        fs.fail_syncs(true);
+        assert!(file.sync_all().is_err());
+        assert!(fs.sync_directory(folder).is_err());
+
+        fs.fail_writes(true);
+        assert!(file.write_all(b"def").is_err());
+        assert!(fs.create_new(&folder.join("1")).is_err());
+
+        fs.fail_syncs(false);
+        fs.fail_writes(false);
+        file.sync_all()?;
+
+        fs.crash();
+        assert_eq!(3, fs.open(&folder.join("0"), OpenMode::Read)?.size()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,486 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{FileHandle, FileLock, FileSystem, LockMode, OpenMode};
+use crate::{HashMap, HashSet};
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::SystemTime,
+};
+
+struct Contents {
+    bytes: Vec<u8>,
+    modified: SystemTime,
+}
+
+impl Contents {
+    fn new() -> Self {
+        Self {
+            bytes: Vec::new(),
+            modified: SystemTime::now(),
+        }
+    }
+}
+
+type FileData = Arc<RwLock<Contents>>;
+
+#[allow(clippy::expect_used)]
+fn contents(data: &FileData) -> RwLockReadGuard<'_, Contents> {
+    data.read().expect("lock is poisoned")
+}
+
+#[allow(clippy::expect_used)]
+fn contents_mut(data: &FileData) -> RwLockWriteGuard<'_, Contents> {
+    data.write().expect("lock is poisoned")
+}
+
+enum HeldLock {
+    Shared(usize),
+    Exclusive,
+}
+
+type Locks = Arc<Mutex<HashMap<PathBuf, HeldLock>>>;
+
+#[allow(clippy::expect_used)]
+fn held_locks(locks: &Locks) -> MutexGuard<'_, HashMap<PathBuf, HeldLock>> {
+    locks.lock().expect("lock is poisoned")
+}
+
+/// Lock on a file of a [`MemoryFileSystem`]
+struct MemoryLock {
+    locks: Locks,
+    path: PathBuf,
+}
+
+impl FileLock for MemoryLock {}
+
+impl Drop for MemoryLock {
+    fn drop(&mut self) {
+        let mut locks = held_locks(&self.locks);
+
+        match locks.get_mut(&self.path) {
+            Some(HeldLock::Shared(count)) if *count > 1 => *count -= 1,
+            _ => {
+                locks.remove(&self.path);
+            }
+        }
+    }
+}
+
+fn not_found(path: &Path) -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("{} does not exist", path.display()),
+    )
+}
+
+#[derive(Default)]
+struct State {
+    files: HashMap<PathBuf, FileData>,
+    folders: HashSet<PathBuf>,
+}
+
+impl State {
+    fn check_parent(&self, path: &Path) -> std::io::Result<()> {
+        match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() && !self.folders.contains(parent) => {
+                Err(not_found(parent))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn create(&mut self, path: &Path) -> std::io::Result<FileData> {
+        self.check_parent(path)?;
+
+        if self.folders.contains(path) {
+            return Err(std::io::ErrorKind::AlreadyExists.into());
+        }
+
+        let data = Arc::new(RwLock::new(Contents::new()));
+        self.files.insert(path.into(), data.clone());
+        Ok(data)
+    }
+}
+
+/// A file system that keeps all files in memory
+///
+/// Only the keyspace's own files are stored in it, the data of partitions
+/// is still written to disk, see the [module documentation](super).
+///
+/// Syncing is a no-op, so all writes are durable immediately.
+/// Clone the `Arc` it is used through to reopen a keyspace in it.
+#[derive(Default)]
+pub struct MemoryFileSystem {
+    state: Mutex<State>,
+    locks: Locks,
+}
+
+impl MemoryFileSystem {
+    /// Creates an empty file system.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[allow(clippy::expect_used)]
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().expect("lock is poisoned")
+    }
+}
+
+struct MemoryFile {
+    data: FileData,
+    pos: u64,
+    mode: OpenMode,
+}
+
+impl MemoryFile {
+    fn new(data: FileData, mode: OpenMode) -> Self {
+        Self { data, pos: 0, mode }
+    }
+
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.mode == OpenMode::Read {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "file is opened read-only",
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Read for MemoryFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let contents = contents(&self.data);
+
+        let start = usize::try_from(self.pos).unwrap_or(usize::MAX);
+        let mut remaining = contents.bytes.get(start..).unwrap_or_default();
+        let n = remaining.read(buf)?;
+        drop(contents);
+
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.check_writable()?;
+
+        let mut contents = contents_mut(&self.data);
+        contents.modified = SystemTime::now();
+
+        let data = &mut contents.bytes;
+
+        if self.mode == OpenMode::Append {
+            self.pos = data.len() as u64;
+        }
+
+        let start = usize::try_from(self.pos).map_err(|_| std::io::ErrorKind::InvalidInput)?;
+        let end = start + buf.len();
+
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+
+        data.splice(start..end, buf.iter().copied());
+        drop(contents);
+
+        self.pos = end as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for MemoryFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let len = contents(&self.data).bytes.len() as u64;
+
+        let pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => len.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+
+        let Some(pos) = pos else {
+            return Err(std::io::ErrorKind::InvalidInput.into());
+        };
+
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+impl FileHandle for MemoryFile {
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(contents(&self.data).bytes.len() as u64)
+    }
+
+    fn modified(&self) -> std::io::Result<SystemTime> {
+        Ok(contents(&self.data).modified)
+    }
+
+    fn set_len(&self, len: u64) -> std::io::Result<()> {
+        self.check_writable()?;
+
+        let len = usize::try_from(len).map_err(|_| std::io::ErrorKind::InvalidInput)?;
+        let mut contents = contents_mut(&self.data);
+        contents.bytes.resize(len, 0);
+        contents.modified = SystemTime::now();
+        drop(contents);
+
+        Ok(())
+    }
+
+    fn sync_all(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn sync_data(&self) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn try_clone(&self) -> std::io::Result<Box<dyn FileHandle>> {
+        Ok(Box::new(Self {
+            data: self.data.clone(),
+            pos: self.pos,
+            mode: self.mode,
+        }))
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        let mut state = self.state();
+
+        for folder in path.ancestors() {
+            if folder.as_os_str().is_empty() {
+                continue;
+            }
+
+            if state.files.contains_key(folder) {
+                return Err(std::io::ErrorKind::AlreadyExists.into());
+            }
+
+            state.folders.insert(folder.into());
+        }
+        drop(state);
+
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let state = self.state();
+
+        if !state.folders.contains(path) {
+            return Err(not_found(path));
+        }
+
+        Ok(state
+            .files
+            .keys()
+            .chain(state.folders.iter())
+            .filter(|x| x.parent() == Some(path))
+            .cloned()
+            .collect())
+    }
+
+    fn exists(&self, path: &Path) -> std::io::Result<bool> {
+        let state = self.state();
+        Ok(state.files.contains_key(path) || state.folders.contains(path))
+    }
+
+    fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn FileHandle>> {
+        let Some(data) = self.state().files.get(path).cloned() else {
+            return Err(not_found(path));
+        };
+
+        Ok(Box::new(MemoryFile::new(data, mode)))
+    }
+
+    fn create(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+        let mut state = self.state();
+
+        // NOTE: Existing handles see the truncation, like on a real file system
+        if let Some(data) = state.files.get(path) {
+            let mut contents = contents_mut(data);
+            contents.bytes.clear();
+            contents.modified = SystemTime::now();
+            drop(contents);
+
+            return Ok(Box::new(MemoryFile::new(data.clone(), OpenMode::ReadWrite)));
+        }
+
+        let data = state.create(path)?;
+        drop(state);
+
+        Ok(Box::new(MemoryFile::new(data, OpenMode::ReadWrite)))
+    }
+
+    fn create_new(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+        let mut state = self.state();
+
+        if state.files.contains_key(path) {
+            return Err(std::io::ErrorKind::AlreadyExists.into());
+        }
+
+        let data = state.create(path)?;
+        drop(state);
+
+        Ok(Box::new(MemoryFile::new(data, OpenMode::ReadWrite)))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        let mut state = self.state();
+
+        state.check_parent(to)?;
+
+        let Some(data) = state.files.remove(from) else {
+            return Err(not_found(from));
+        };
+
+        state.files.insert(to.into(), data);
+        drop(state);
+
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        if self.state().files.remove(path).is_none() {
+            return Err(not_found(path));
+        }
+
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        let mut state = self.state();
+
+        if !state.folders.contains(path) {
+            return Err(not_found(path));
+        }
+
+        state.files.retain(|file, _| !file.starts_with(path));
+        state.folders.retain(|folder| !folder.starts_with(path));
+        drop(state);
+
+        Ok(())
+    }
+
+    fn lock(&self, path: &Path, mode: LockMode) -> std::io::Result<Option<Box<dyn FileLock>>> {
+        {
+            let mut state = self.state();
+
+            if !state.files.contains_key(path) {
+                match mode {
+                    LockMode::Shared => return Err(not_found(path)),
+                    LockMode::Exclusive => {
+                        state.create(path)?;
+                    }
+                }
+            }
+        }
+
+        let mut locks = held_locks(&self.locks);
+
+        let held = match (locks.get(path), mode) {
+            (None, LockMode::Shared) => HeldLock::Shared(1),
+            (Some(HeldLock::Shared(count)), LockMode::Shared) => HeldLock::Shared(count + 1),
+            (None, LockMode::Exclusive) => HeldLock::Exclusive,
+            _ => return Ok(None),
+        };
+        locks.insert(path.into(), held);
+        drop(locks);
+
+        Ok(Some(Box::new(MemoryLock {
+            locks: self.locks.clone(),
+            path: path.into(),
+        })))
+    }
+
+    fn sync_directory(&self, path: &Path) -> std::io::Result<()> {
+        if !self.state().folders.contains(path) {
+            return Err(not_found(path));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn memory_file_system() -> std::io::Result<()> {
+        let fs = MemoryFileSystem::new();
+        let folder = Path::new("/keyspace/journals");
+
+        assert!(fs.create(&folder.join("0")).is_err());
+
+        fs.create_dir_all(folder)?;
+        assert!(fs.exists(Path::new("/keyspace"))?);
+
+        let mut file = fs.create_new(&folder.join("0"))?;
+        file.write_all(b"abc")?;
+        file.set_len(5)?;
+        assert_eq!(5, file.size()?);
+
+        let mut appender = fs.open(&folder.join("0"), OpenMode::Append)?;
+        appender.write_all(b"def")?;
+
+        fs.rename(&folder.join("0"), &folder.join("0.sealed"))?;
+        assert_eq!(vec![folder.join("0.sealed")], fs.read_dir(folder)?);
+
+        let mut bytes = Vec::new();
+        fs.open(&folder.join("0.sealed"), OpenMode::Read)?
+            .read_to_end(&mut bytes)?;
+        assert_eq!(b"abc\0\0def", &*bytes);
+
+        let mut reader = fs.open(&folder.join("0.sealed"), OpenMode::Read)?;
+        assert!(reader.write_all(b"x").is_err());
+
+        fs.remove_file(&folder.join("0.sealed"))?;
+        assert!(fs.read_dir(folder)?.is_empty());
+
+        fs.remove_dir_all(Path::new("/keyspace"))?;
+        assert!(!fs.exists(folder)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn memory_file_system_lock() -> std::io::Result<()> {
+        let fs = MemoryFileSystem::new();
+        let path = Path::new("LOCK");
+
+        assert!(fs.lock(path, LockMode::Shared).is_err());
+
+        let lock = fs.lock(path, LockMode::Exclusive)?;
+        assert!(lock.is_some());
+        assert!(fs.lock(path, LockMode::Shared)?.is_none());
+        drop(lock);
+
+        let lock = fs.lock(path, LockMode::Shared)?;
+        let other = fs.lock(path, LockMode::Shared)?;
+        assert!(lock.is_some() && other.is_some());
+        assert!(fs.lock(path, LockMode::Exclusive)?.is_none());
+
+        drop(lock);
+        assert!(fs.lock(path, LockMode::Exclusive)?.is_none());
+
+        drop(other);
+        assert!(fs.lock(path, LockMode::Exclusive)?.is_some());
+
+        Ok(())
+    }
+}
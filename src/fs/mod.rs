@@ -0,0 +1,233 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+//! File system of the keyspace's own files
+//!
+//! The keyspace's own files are accessed through the [`FileSystem`] configured with
+//! [`Config::file_system`](crate::Config::file_system), so crash recovery of the journal
+//! can be tested deterministically:
+//!
+//! - [`OsFileSystem`] uses the operating system's file system, and is the default
+//! - [`MemoryFileSystem`] keeps the keyspace's own files in memory
+//! - [`FaultInjectingFileSystem`] wraps another file system, and can fail operations,
+//!   or simulate a crash by dropping all writes that were not synced
+//!
+//! The keyspace's own files are its journals, the version marker, the lock file,
+//! the partition configs, deletion markers and range tombstones, and the journal archive.
+//!
+//! The file system is **not** a storage backend: the data of partitions is stored by their
+//! LSM-trees, which always use the operating system's file system, so a keyspace is never
+//! fully in memory. Checkpoints store the keyspace's own files in the keyspace's file system,
+//! but other tools that work on keyspace folders (backups, journal restores and inspection,
+//! replication and secondary instances) use the operating system's file system.
+//!
+//! # Examples
+//!
+//! ```
+//! use fjall::{fs::{FaultInjectingFileSystem, MemoryFileSystem}, Config, PartitionCreateOptions, PersistMode};
+//! use std::sync::Arc;
+//! #
+//! # let folder = tempfile::tempdir()?;
+//!
+//! let fs = Arc::new(FaultInjectingFileSystem::new(Arc::new(MemoryFileSystem::new())));
+//!
+//! {
+//!     let keyspace = Config::new(&folder).file_system(fs.clone()).open()?;
+//!     let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+//!
+//!     partition.insert("a", "abc")?;
+//!     keyspace.persist(PersistMode::SyncAll)?;
+//!
+//!     partition.insert("b", "abc")?;
+//!
+//!     // Loses the write of "b", which was not synced yet
+//!     fs.crash();
+//! }
+//!
+//! let keyspace = Config::new(&folder).file_system(fs).open()?;
+//! let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+//!
+//! assert!(partition.contains_key("a")?);
+//! assert!(!partition.contains_key("b")?);
+//! #
+//! # Ok::<(), fjall::Error>(())
+//! ```
+
+mod fault;
+mod memory;
+mod os;
+
+pub use fault::FaultInjectingFileSystem;
+pub use memory::MemoryFileSystem;
+pub use os::OsFileSystem;
+
+use std::{
+    io::{Read, Seek, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// How an existing file is opened, see [`FileSystem::open`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum OpenMode {
+    /// Read access
+    Read,
+
+    /// Read and write access
+    ReadWrite,
+
+    /// Write access, every write is appended to the end of the file
+    Append,
+}
+
+/// How a file is locked, see [`FileSystem::lock`]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum LockMode {
+    /// Can be held by multiple handles, but not together with an exclusive lock
+    Shared,
+
+    /// Can only be held by a single handle
+    Exclusive,
+}
+
+/// Advisory lock on a file of a [`FileSystem`], which is released when dropped
+pub trait FileLock: Send + Sync {}
+
+/// An open file of a [`FileSystem`]
+///
+/// Reads, writes and seeks use the file handle's own cursor.
+pub trait FileHandle: Read + Write + Seek + Send {
+    /// Returns the size of the file in bytes.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn size(&self) -> std::io::Result<u64>;
+
+    /// Returns the time the file was last written to.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn modified(&self) -> std::io::Result<SystemTime>;
+
+    /// Truncates or extends (with zeroes) the file to the given size.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn set_len(&self, len: u64) -> std::io::Result<()>;
+
+    /// Makes all writes to the file (and its metadata) durable.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn sync_all(&self) -> std::io::Result<()>;
+
+    /// Makes all writes to the file durable, but not necessarily its metadata.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn sync_data(&self) -> std::io::Result<()>;
+
+    /// Opens another handle to the same file, which can be used to sync it.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn try_clone(&self) -> std::io::Result<Box<dyn FileHandle>>;
+}
+
+/// File system the keyspace's journals and metadata are stored in
+///
+/// See the [module documentation](self).
+pub trait FileSystem: Send + Sync {
+    /// Creates a folder, and all of its missing parent folders.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()>;
+
+    /// Returns the paths of all files and folders in a folder.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the folder does not exist.
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>>;
+
+    /// Returns `true` if a file or folder exists at the given path.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn exists(&self, path: &Path) -> std::io::Result<bool>;
+
+    /// Opens an existing file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the file does not exist.
+    fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn FileHandle>>;
+
+    /// Creates a file with read and write access, truncating it if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn create(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>>;
+
+    /// Creates a file with read and write access, failing if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the file exists.
+    fn create_new(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>>;
+
+    /// Renames a file, replacing the destination if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()>;
+
+    /// Removes a file.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the file does not exist.
+    fn remove_file(&self, path: &Path) -> std::io::Result<()>;
+
+    /// Removes a folder, and all of its contents.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the folder does not exist.
+    fn remove_dir_all(&self, path: &Path) -> std::io::Result<()>;
+
+    /// Takes an advisory lock on a file, returning `None` if it is held elsewhere.
+    ///
+    /// Exclusive locks create the file if it does not exist, shared locks never write to the file system.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or a file to lock shared does not exist.
+    fn lock(&self, path: &Path, mode: LockMode) -> std::io::Result<Option<Box<dyn FileLock>>>;
+
+    /// Makes the creation, renaming and removal of files in a folder durable.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    fn sync_directory(&self, path: &Path) -> std::io::Result<()>;
+}
+
+/// Reads the entire contents of a file.
+pub(crate) fn read_file(fs: &dyn FileSystem, path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    fs.open(path, OpenMode::Read)?.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
@@ -0,0 +1,170 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{FileHandle, FileLock, FileSystem, LockMode, OpenMode};
+use crate::file::fsync_directory;
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The operating system's file system
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OsFileSystem;
+
+impl FileHandle for File {
+    fn size(&self) -> std::io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn modified(&self) -> std::io::Result<SystemTime> {
+        self.metadata()?.modified()
+    }
+
+    fn set_len(&self, len: u64) -> std::io::Result<()> {
+        Self::set_len(self, len)
+    }
+
+    fn sync_all(&self) -> std::io::Result<()> {
+        Self::sync_all(self)
+    }
+
+    fn sync_data(&self) -> std::io::Result<()> {
+        Self::sync_data(self)
+    }
+
+    fn try_clone(&self) -> std::io::Result<Box<dyn FileHandle>> {
+        Ok(Box::new(Self::try_clone(self)?))
+    }
+}
+
+impl FileLock for File {}
+
+fn lock_options(mode: LockMode) -> OpenOptions {
+    let mut options = OpenOptions::new();
+
+    match mode {
+        LockMode::Shared => options.read(true),
+        LockMode::Exclusive => options.create(true).truncate(false).read(true).write(true),
+    };
+
+    options
+}
+
+/// Opens and locks the file, returning `None` if it is locked already.
+#[cfg(unix)]
+fn open_locked(path: &Path, mode: LockMode) -> std::io::Result<Option<File>> {
+    use rustix::fs::{flock, FlockOperation};
+
+    let file = lock_options(mode).open(path)?;
+
+    let operation = match mode {
+        LockMode::Shared => FlockOperation::NonBlockingLockShared,
+        LockMode::Exclusive => FlockOperation::NonBlockingLockExclusive,
+    };
+
+    match flock(&file, operation) {
+        Ok(()) => Ok(Some(file)),
+        Err(rustix::io::Errno::WOULDBLOCK) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Opens and locks the file, returning `None` if it is locked already.
+#[cfg(windows)]
+fn open_locked(path: &Path, mode: LockMode) -> std::io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+    const FILE_SHARE_READ: u32 = 1;
+
+    // NOTE: Not sharing the file with writing handles makes
+    // opening it fail while it is open elsewhere for writing, and vice versa
+    let share_mode = match mode {
+        LockMode::Shared => FILE_SHARE_READ,
+        LockMode::Exclusive => 0,
+    };
+
+    match lock_options(mode).share_mode(share_mode).open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Opens the file; without advisory locks, the lock is never contended.
+#[cfg(not(any(unix, windows)))]
+fn open_locked(path: &Path, mode: LockMode) -> std::io::Result<Option<File>> {
+    lock_options(mode).open(path).map(Some)
+}
+
+impl FileSystem for OsFileSystem {
+    fn create_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        std::fs::read_dir(path)?
+            .map(|dirent| dirent.map(|dirent| dirent.path()))
+            .collect()
+    }
+
+    fn exists(&self, path: &Path) -> std::io::Result<bool> {
+        path.try_exists()
+    }
+
+    fn open(&self, path: &Path, mode: OpenMode) -> std::io::Result<Box<dyn FileHandle>> {
+        let mut options = OpenOptions::new();
+
+        match mode {
+            OpenMode::Read => options.read(true),
+            OpenMode::ReadWrite => options.read(true).write(true),
+            OpenMode::Append => options.append(true),
+        };
+
+        Ok(Box::new(options.open(path)?))
+    }
+
+    fn create(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+
+        Ok(Box::new(file))
+    }
+
+    fn create_new(&self, path: &Path) -> std::io::Result<Box<dyn FileHandle>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+
+        Ok(Box::new(file))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> std::io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_dir_all(path)
+    }
+
+    fn lock(&self, path: &Path, mode: LockMode) -> std::io::Result<Option<Box<dyn FileLock>>> {
+        Ok(open_locked(path, mode)?.map(|file| Box::new(file) as Box<dyn FileLock>))
+    }
+
+    fn sync_directory(&self, path: &Path) -> std::io::Result<()> {
+        fsync_directory(path)
+    }
+}
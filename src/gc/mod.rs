@@ -191,7 +191,14 @@ impl GarbageCollector {
         panic!("Cannot use GC for non-KV-separated tree");
     }
 
+    #[allow(clippy::expect_used)]
     pub fn with_space_amp_target(partition: &PartitionHandle, factor: f32) -> crate::Result<u64> {
+        if partition.keyspace_config.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        let _lock = partition.maintenance_lock.read().expect("lock is poisoned");
+
         if let AnyTree::Blob(tree) = &partition.tree {
             let strategy = lsm_tree::SpaceAmpStrategy::new(factor);
 
@@ -202,10 +209,17 @@ impl GarbageCollector {
         }
     }
 
+    #[allow(clippy::expect_used)]
     pub fn with_staleness_threshold(
         partition: &PartitionHandle,
         threshold: f32,
     ) -> crate::Result<u64> {
+        if partition.keyspace_config.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        let _lock = partition.maintenance_lock.read().expect("lock is poisoned");
+
         if let AnyTree::Blob(tree) = &partition.tree {
             let strategy = lsm_tree::StaleThresholdStrategy::new(threshold);
 
@@ -216,7 +230,14 @@ impl GarbageCollector {
         panic!("Cannot use GC for non-KV-separated tree");
     }
 
+    #[allow(clippy::expect_used)]
     pub fn drop_stale_segments(partition: &PartitionHandle) -> crate::Result<u64> {
+        if partition.keyspace_config.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        let _lock = partition.maintenance_lock.read().expect("lock is poisoned");
+
         if let AnyTree::Blob(tree) = &partition.tree {
             return tree.gc_drop_stale().map_err(Into::into);
         }
@@ -0,0 +1,480 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{marker::Marker, reader::JournalReader, recovery::JournalId};
+use crate::{
+    file::JOURNALS_FOLDER,
+    fs::{read_file, FileSystem, OpenMode, OsFileSystem},
+};
+use lsm_tree::SeqNo;
+use std::{
+    io::{Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+const SEALED_SUFFIX: &str = ".sealed";
+
+/// File in the archive directory that contains the ID of the journal
+/// that was active when the newest checkpoint was created
+const CHECKPOINT_WATERMARK_FILE: &str = "checkpoint";
+
+/// Archive of evicted journals
+///
+/// Instead of deleting sealed journals once their data has been flushed,
+/// they are moved into the archive directory. Together with a checkpoint
+/// (see [`Keyspace::checkpoint`](crate::Keyspace::checkpoint)), the archive allows
+/// restoring the keyspace as of any later seqno (see [`restore_to`]).
+///
+/// An archive directory belongs to a single keyspace, and is stored in
+/// the keyspace's [file system](crate::Config::file_system).
+///
+/// Journals that are needed to restore the newest checkpoint are never deleted,
+/// even if the archive exceeds its limits. Older checkpoints may not be restorable anymore.
+///
+/// # Examples
+///
+/// ```
+/// # use fjall::{Config, journal::JournalArchive};
+/// # use std::time::Duration;
+/// #
+/// # let folder = tempfile::tempdir()?;
+/// # let archive_folder = tempfile::tempdir()?;
+/// let keyspace = Config::new(&folder)
+///     .journal_archive(
+///         JournalArchive::new(&archive_folder)
+///             .max_size(/* 1 GiB */ 1_024 * 1_024 * 1_024)
+///             .max_age(Duration::from_secs(7 * 24 * 60 * 60)),
+///     )
+///     .open()?;
+/// #
+/// # Ok::<(), fjall::Error>(())
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JournalArchive {
+    path: PathBuf,
+    max_size: Option<u64>,
+    max_age: Option<Duration>,
+}
+
+impl JournalArchive {
+    /// Creates an archive in the given directory, without any limits.
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().into(),
+            max_size: None,
+            max_age: None,
+        }
+    }
+
+    /// Sets the maximum size of the archive in bytes.
+    ///
+    /// Once the archive grows larger, the oldest journals are deleted.
+    /// The most recently archived journal, and journals needed to restore
+    /// the newest checkpoint are always kept.
+    #[must_use]
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Sets the maximum age of archived journals.
+    ///
+    /// Journals that were last written to longer ago are deleted.
+    /// The most recently archived journal, and journals needed to restore
+    /// the newest checkpoint are always kept.
+    #[must_use]
+    pub fn max_age(mut self, age: Duration) -> Self {
+        self.max_age = Some(age);
+        self
+    }
+
+    /// Moves an evicted (sealed) journal into the archive,
+    /// then deletes old journals that exceed the archive's limits.
+    #[allow(clippy::expect_used)]
+    pub(crate) fn store(&self, fs: &dyn FileSystem, journal_path: &Path) -> crate::Result<()> {
+        fs.create_dir_all(&self.path)?;
+
+        let file_name = journal_path
+            .file_name()
+            .expect("journal should have file name");
+        let archive_path = self.path.join(file_name);
+
+        // NOTE: Never overwrite an archived journal, the journal is kept
+        // in the keyspace, and archiving is retried on the next eviction
+        if fs.exists(&archive_path)? {
+            log::error!(
+                "Journal {} is already archived, is the archive shared by multiple keyspaces?",
+                archive_path.display(),
+            );
+            return Err(crate::Error::Io(std::io::ErrorKind::AlreadyExists.into()));
+        }
+
+        // NOTE: Cut off the preallocated space, which may make up most of the file
+        let valid_len = scan(fs, journal_path)?.last().map_or(0, |batch| batch.end);
+        let file = fs.open(journal_path, OpenMode::ReadWrite)?;
+        file.set_len(valid_len)?;
+        file.sync_all()?;
+
+        log::debug!(
+            "Archiving journal {} to {}",
+            journal_path.display(),
+            archive_path.display(),
+        );
+
+        if let Err(e) = fs.rename(journal_path, &archive_path) {
+            log::debug!("Could not move journal, copying instead: {e:?}");
+
+            let mut archived = fs.create(&archive_path)?;
+            std::io::copy(&mut fs.open(journal_path, OpenMode::Read)?, &mut archived)?;
+            archived.sync_all()?;
+            fs.sync_directory(&self.path)?;
+
+            fs.remove_file(journal_path)?;
+        } else {
+            fs.sync_directory(&self.path)?;
+        }
+
+        self.prune(fs)
+    }
+
+    /// Records that a checkpoint was created while the given journal was active.
+    ///
+    /// Restoring the checkpoint needs that journal and all later ones, so they are not pruned anymore.
+    pub(crate) fn record_checkpoint(
+        &self,
+        fs: &dyn FileSystem,
+        active_journal_path: &Path,
+    ) -> crate::Result<()> {
+        let Some(journal_id) = active_journal_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|id| id.parse::<JournalId>().ok())
+        else {
+            return Ok(());
+        };
+
+        fs.create_dir_all(&self.path)?;
+
+        if self
+            .checkpoint_watermark(fs)?
+            .is_some_and(|watermark| watermark >= journal_id)
+        {
+            return Ok(());
+        }
+
+        log::debug!("Recording checkpoint watermark at journal {journal_id}");
+
+        let tmp_path = self.path.join(format!("{CHECKPOINT_WATERMARK_FILE}.tmp"));
+
+        {
+            let mut file = fs.create(&tmp_path)?;
+            file.write_all(journal_id.to_string().as_bytes())?;
+            file.sync_all()?;
+        }
+
+        fs.rename(&tmp_path, &self.path.join(CHECKPOINT_WATERMARK_FILE))?;
+        fs.sync_directory(&self.path)?;
+
+        Ok(())
+    }
+
+    /// Returns the ID of the oldest journal that is needed to restore the newest checkpoint.
+    fn checkpoint_watermark(&self, fs: &dyn FileSystem) -> crate::Result<Option<JournalId>> {
+        match read_file(fs, &self.path.join(CHECKPOINT_WATERMARK_FILE)) {
+            Ok(content) => Ok(std::str::from_utf8(&content)
+                .ok()
+                .and_then(|content| content.trim().parse::<JournalId>().ok())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Deletes the oldest journals, until the archive is within its limits.
+    ///
+    /// Journals needed to restore the newest checkpoint are kept.
+    fn prune(&self, fs: &dyn FileSystem) -> crate::Result<()> {
+        if self.max_size.is_none() && self.max_age.is_none() {
+            return Ok(());
+        }
+
+        let watermark = self.checkpoint_watermark(fs)?;
+
+        let mut journals = list_archived_journals(fs, &self.path)?
+            .into_iter()
+            .map(|(id, path)| {
+                let file = fs.open(&path, OpenMode::Read)?;
+                Ok((id, path, file.size()?, file.modified()?))
+            })
+            .collect::<crate::Result<Vec<_>>>()?;
+
+        let mut size = journals.iter().map(|(_, _, len, _)| len).sum::<u64>();
+        let now = SystemTime::now();
+
+        // NOTE: Keep the newest journal
+        let _ = journals.pop();
+
+        for (id, path, len, modified) in journals {
+            if watermark.is_some_and(|watermark| id >= watermark) {
+                log::debug!(
+                    "Keeping archived journal {}, it is needed to restore the newest checkpoint",
+                    path.display(),
+                );
+                break;
+            }
+
+            let is_too_large = self.max_size.is_some_and(|max_size| size > max_size);
+
+            let is_too_old = self
+                .max_age
+                .is_some_and(|max_age| now.duration_since(modified).is_ok_and(|age| age > max_age));
+
+            if !is_too_large && !is_too_old {
+                break;
+            }
+
+            log::debug!("Deleting archived journal {}", path.display());
+            fs.remove_file(&path)?;
+            size = size.saturating_sub(len);
+        }
+
+        Ok(())
+    }
+}
+
+/// Position of a complete batch in a journal file
+struct BatchSpan {
+    seqno: SeqNo,
+    start: u64,
+    end: u64,
+}
+
+/// Lists the complete batches of a journal file.
+///
+/// Payloads of compressed or encrypted batches are not decoded,
+/// so no encryption key is needed.
+fn scan(fs: &dyn FileSystem, path: &Path) -> crate::Result<Vec<BatchSpan>> {
+    let mut reader = JournalReader::open_in(fs, path, true)?;
+    reader.skip_payloads = true;
+
+    let mut batches = Vec::new();
+    let mut current = None;
+
+    loop {
+        let marker_pos = reader.last_valid_pos;
+
+        let Some(marker) = reader.next() else {
+            return Ok(batches);
+        };
+
+        match marker? {
+            Marker::Start { seqno, .. } => {
+                current = Some((seqno, marker_pos));
+            }
+            Marker::End(_) => {
+                if let Some((seqno, start)) = current.take() {
+                    batches.push(BatchSpan {
+                        seqno,
+                        start,
+                        end: reader.last_valid_pos,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Lists the journals in a folder, ordered by their ID.
+fn list_journals(
+    fs: &dyn FileSystem,
+    folder: &Path,
+    suffix: &str,
+) -> crate::Result<Vec<(JournalId, PathBuf)>> {
+    let mut journals = Vec::new();
+
+    for path in fs.read_dir(folder)? {
+        let Some(id) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(suffix))
+            .and_then(|id| id.parse::<JournalId>().ok())
+        else {
+            continue;
+        };
+
+        journals.push((id, path));
+    }
+
+    journals.sort_by_key(|(id, _)| *id);
+
+    Ok(journals)
+}
+
+fn list_archived_journals(
+    fs: &dyn FileSystem,
+    folder: &Path,
+) -> crate::Result<Vec<(JournalId, PathBuf)>> {
+    list_journals(fs, folder, SEALED_SUFFIX)
+}
+
+/// Returns `true` if both files have the same first `len` bytes.
+fn starts_with(fs: &dyn FileSystem, a: &Path, b: &Path, len: u64) -> crate::Result<bool> {
+    let mut a_bytes = Vec::new();
+    fs.open(a, OpenMode::Read)?
+        .take(len)
+        .read_to_end(&mut a_bytes)?;
+
+    let mut b_bytes = Vec::new();
+    fs.open(b, OpenMode::Read)?
+        .take(len)
+        .read_to_end(&mut b_bytes)?;
+
+    Ok(a_bytes.len() as u64 == len && a_bytes == b_bytes)
+}
+
+fn missing_journal(path: &Path) -> crate::Error {
+    log::error!(
+        "Journal {} is missing from the archive, cannot restore",
+        path.display()
+    );
+    crate::Error::Io(std::io::ErrorKind::NotFound.into())
+}
+
+/// Rolls a checkpoint forward to the given seqno, using archived journals.
+///
+/// All writes with a seqno up to (and including) `target_seqno` that were made after
+/// the checkpoint was created are restored. Writes with a higher seqno are dropped.
+///
+/// The checkpoint is modified in place, so it should be copied first, if it is
+/// needed again. Once restored, it can be opened like any other keyspace.
+///
+/// The archive needs to contain every journal from the one that was active when the
+/// checkpoint was created, up to the one containing `target_seqno`. Journals that were not
+/// evicted yet can be copied into the archive (with a `.sealed` suffix) from the
+/// keyspace's `journals` folder.
+///
+/// # Examples
+///
+/// ```
+/// # use fjall::{Config, journal::{restore_to, JournalArchive}, PartitionCreateOptions, PersistMode};
+/// #
+/// # let folder = tempfile::tempdir()?;
+/// # let archive_folder = tempfile::tempdir()?;
+/// # let backup_folder = tempfile::tempdir()?;
+/// let keyspace = Config::new(&folder)
+///     .journal_archive(JournalArchive::new(&archive_folder))
+///     .open()?;
+/// let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+///
+/// let checkpoint_path = backup_folder.path().join("checkpoint");
+/// keyspace.checkpoint(&checkpoint_path)?;
+///
+/// partition.insert("a", "good")?;
+/// let good_seqno = keyspace.instant() - 1;
+/// partition.insert("a", "bad")?;
+///
+/// // NOTE: The active journal has not been evicted yet, so copy it into the archive
+/// keyspace.persist(PersistMode::SyncAll)?;
+/// std::fs::copy(
+///     folder.path().join("journals").join("0"),
+///     archive_folder.path().join("0.sealed"),
+/// )?;
+///
+/// restore_to(&checkpoint_path, &archive_folder, good_seqno)?;
+///
+/// let restored = Config::new(&checkpoint_path).open()?;
+/// let partition = restored.open_partition("default", PartitionCreateOptions::default())?;
+/// assert_eq!(b"good", &*partition.get("a")?.unwrap());
+/// #
+/// # Ok::<_, fjall::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if an IO error occurs, or a needed journal is missing from the archive.
+pub fn restore_to<P: AsRef<Path>, Q: AsRef<Path>>(
+    checkpoint: P,
+    archive: Q,
+    target_seqno: SeqNo,
+) -> crate::Result<()> {
+    let fs = &OsFileSystem;
+    let journals_folder = checkpoint.as_ref().join(JOURNALS_FOLDER);
+    let archive = archive.as_ref();
+
+    // NOTE: The active journal of the checkpoint is a prefix of the archived journal
+    // with the same ID, anything after that prefix was written after the checkpoint
+    let Some((active_id, active_path)) = list_journals(fs, &journals_folder, "")?.pop() else {
+        log::error!("Checkpoint has no active journal, cannot restore");
+        return Err(crate::Error::Io(std::io::ErrorKind::NotFound.into()));
+    };
+
+    let prefix_len = scan(fs, &active_path)?.last().map_or(0, |batch| batch.end);
+
+    let archived = list_archived_journals(fs, archive)?
+        .into_iter()
+        .filter(|(id, _)| *id >= active_id)
+        .collect::<Vec<_>>();
+
+    for (expected_id, (id, _)) in (active_id..).zip(&archived) {
+        if *id != expected_id {
+            return Err(missing_journal(
+                &archive.join(format!("{expected_id}{SEALED_SUFFIX}")),
+            ));
+        }
+    }
+
+    if let Some((_, path)) = archived.first() {
+        if !starts_with(fs, path, &active_path, prefix_len)? {
+            log::error!(
+                "Archived journal {} does not match the checkpoint, cannot restore",
+                path.display(),
+            );
+            return Err(crate::Error::Io(std::io::ErrorKind::InvalidData.into()));
+        }
+    }
+
+    log::info!(
+        "Restoring checkpoint {} to seqno={target_seqno} using {} archived journals",
+        checkpoint.as_ref().display(),
+        archived.len(),
+    );
+
+    let restored_path = journals_folder.join(format!("{active_id}.restore"));
+    let mut restored = fs.create(&restored_path)?;
+
+    {
+        let mut active = fs.open(&active_path, OpenMode::Read)?;
+        std::io::copy(&mut (&mut active).take(prefix_len), &mut restored)?;
+    }
+
+    for (id, path) in archived {
+        let mut journal = fs.open(&path, OpenMode::Read)?;
+
+        for batch in scan(fs, &path)? {
+            if id == active_id && batch.start < prefix_len {
+                continue;
+            }
+
+            if batch.seqno > target_seqno {
+                continue;
+            }
+
+            journal.seek(SeekFrom::Start(batch.start))?;
+            std::io::copy(
+                &mut (&mut journal).take(batch.end - batch.start),
+                &mut restored,
+            )?;
+        }
+    }
+
+    restored.flush()?;
+    restored.sync_all()?;
+    drop(restored);
+
+    fs.rename(&restored_path, &active_path)?;
+    fs.sync_directory(&journals_folder)?;
+
+    Ok(())
+}
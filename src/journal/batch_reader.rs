@@ -3,9 +3,16 @@
 // (found in the LICENSE-* files in the repository)
 
 use super::reader::JournalReader;
-use crate::{batch::item::Item as BatchItem, journal::marker::Marker, RecoveryError};
-use lsm_tree::{coding::Encode, CompressionType, SeqNo};
-use std::{fs::OpenOptions, hash::Hasher};
+use crate::{
+    batch::item::{ExpiryItem, Item as BatchItem, MergeItem, RangeTombstoneItem},
+    journal::{
+        error::{RecoveryMode, SkippedBatch},
+        marker::Marker,
+    },
+    RecoveryError,
+};
+use lsm_tree::{coding::Encode, SeqNo};
+use std::{hash::Hasher, ops::Range};
 
 macro_rules! fail_iter {
     ($e:expr) => {
@@ -20,65 +27,250 @@ macro_rules! fail_iter {
 pub struct Batch {
     pub(crate) seqno: SeqNo,
     pub(crate) items: Vec<BatchItem>,
+    pub(crate) range_tombstones: Vec<RangeTombstoneItem>,
+    pub(crate) merges: Vec<MergeItem>,
+    pub(crate) expiries: Vec<ExpiryItem>,
 }
 
 #[allow(clippy::module_name_repetitions)]
 pub struct JournalBatchReader {
     reader: JournalReader,
+    recovery_mode: RecoveryMode,
     items: Vec<BatchItem>,
+    range_tombstones: Vec<RangeTombstoneItem>,
+    merges: Vec<MergeItem>,
+    expiries: Vec<ExpiryItem>,
     is_in_batch: bool,
     batch_counter: u32,
     batch_seqno: SeqNo,
+    batch_start_pos: u64,
     last_valid_pos: u64,
     checksum_builder: xxhash_rust::xxh3::Xxh3,
+
+    /// Set if the current batch is known to be invalid,
+    /// but its end marker has not been reached yet
+    invalid_batch: Option<RecoveryError>,
+
+    /// Batches that were discarded
+    pub(crate) skipped_batches: Vec<SkippedBatch>,
+
+    /// If `true`, the journal is never truncated (see [`JournalReader::new_read_only`]),
+    /// so it can be read while it is being written to
+    read_only: bool,
 }
 
 impl JournalBatchReader {
-    pub fn new(reader: JournalReader) -> Self {
+    pub fn new(mut reader: JournalReader, recovery_mode: RecoveryMode) -> Self {
+        reader.verify_tail = recovery_mode == RecoveryMode::AbsoluteConsistency;
+        reader.resync = recovery_mode == RecoveryMode::SkipInvalidBatches;
+
         Self {
+            read_only: reader.read_only,
             reader,
+            recovery_mode,
             items: Vec::with_capacity(10),
+            range_tombstones: Vec::new(),
+            merges: Vec::new(),
+            expiries: Vec::new(),
             checksum_builder: xxhash_rust::xxh3::Xxh3::new(),
             is_in_batch: false,
             batch_seqno: 0,
+            batch_start_pos: 0,
             last_valid_pos: 0,
             batch_counter: 0,
+            invalid_batch: None,
+            skipped_batches: Vec::new(),
         }
     }
 
     // TODO: reallocate space
-    fn truncate_to(&mut self, last_valid_pos: u64) -> crate::Result<()> {
-        log::trace!("Truncating journal to {last_valid_pos}");
+    fn truncate_to(&self, last_valid_pos: u64) -> crate::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
 
-        // TODO: on windows, reading file probably needs to be closed first...?
+        log::trace!("Truncating journal to {last_valid_pos}");
 
-        let file = OpenOptions::new().write(true).open(&self.reader.path)?;
+        let file = self.reader.reader.get_ref();
         file.set_len(last_valid_pos)?;
         file.sync_all()?;
 
         Ok(())
     }
 
+    /// Records the bytes in `range` as skipped.
+    fn record_skipped_range(&mut self, range: Range<u64>, error: RecoveryError) {
+        log::warn!(
+            "Discarding batch at {}:{}..{}: {error:?}",
+            self.reader.path.display(),
+            range.start,
+            range.end,
+        );
+
+        self.skipped_batches.push(SkippedBatch {
+            path: self.reader.path.clone(),
+            offset: range.start,
+            len: range.end.saturating_sub(range.start),
+            error,
+        });
+    }
+
+    /// Records the current batch (which ends at `pos`) as skipped.
+    fn record_skipped(&mut self, error: RecoveryError, pos: u64) {
+        self.record_skipped_range(self.batch_start_pos..pos, error);
+    }
+
+    /// Discards the current batch (which ends at `pos`), and continues with the next one.
+    ///
+    /// The batch is kept in the journal file, so it can still be salvaged.
+    fn skip_batch(&mut self, error: RecoveryError, pos: u64) {
+        self.record_skipped(error, pos);
+        self.last_valid_pos = pos;
+
+        self.items.clear();
+        self.range_tombstones.clear();
+        self.merges.clear();
+        self.expiries.clear();
+
+        self.checksum_builder = xxhash_rust::xxh3::Xxh3::new();
+        self.is_in_batch = false;
+        self.batch_counter = 0;
+        self.invalid_batch = None;
+    }
+
+    /// Fails recovery because of the invalid journal entry at `pos`.
+    fn fail(&self, error: RecoveryError, pos: u64) -> crate::Error {
+        log::error!(
+            "Journal recovery failed at {}:{pos}: {error:?}",
+            self.reader.path.display(),
+        );
+        crate::Error::JournalRecovery(error)
+    }
+
     fn on_close(&mut self) -> crate::Result<()> {
         if self.is_in_batch {
+            if self.recovery_mode == RecoveryMode::AbsoluteConsistency {
+                return Err(self.fail(RecoveryError::MissingTerminator, self.batch_start_pos));
+            }
+
             log::debug!("Invalid batch: missing terminator, but last batch, so probably incomplete, discarding to keep atomicity");
 
-            // Discard batch
-            self.truncate_to(self.last_valid_pos)?;
+            self.record_skipped(
+                self.invalid_batch
+                    .unwrap_or(RecoveryError::MissingTerminator),
+                self.reader.last_valid_pos,
+            );
+
+            // NOTE: When skipping invalid batches, the journal is never truncated,
+            // so the batch can still be salvaged
+            if self.recovery_mode != RecoveryMode::SkipInvalidBatches {
+                // Discard batch
+                self.truncate_to(self.last_valid_pos)?;
+            }
         }
 
         Ok(())
     }
+
+    /// Records a marker (which ends at `pos`) that does not belong to any batch as skipped.
+    ///
+    /// Consecutive orphaned markers are recorded as a single skipped range.
+    fn skip_orphan(&mut self, marker_pos: u64, pos: u64) {
+        self.last_valid_pos = pos;
+
+        if let Some(last) = self.skipped_batches.last_mut() {
+            if last.path == self.reader.path
+                && last.error == RecoveryError::UnexpectedMarker
+                && last.offset + last.len == marker_pos
+            {
+                last.len = pos - last.offset;
+                return;
+            }
+        }
+
+        self.record_skipped_range(marker_pos..pos, RecoveryError::UnexpectedMarker);
+    }
+
+    /// Handles invalid bytes that were skipped by the reader, see [`JournalReader::take_skipped`].
+    fn on_resync(&mut self, skipped: Range<u64>) {
+        if self.is_in_batch {
+            self.skip_batch(RecoveryError::InvalidMarker, skipped.end);
+        } else {
+            self.record_skipped_range(skipped.clone(), RecoveryError::InvalidMarker);
+            self.last_valid_pos = skipped.end;
+        }
+    }
+
+    /// Checks an entry (item, range tombstone, ...) of the current batch.
+    ///
+    /// Returns `Ok(true)` if the entry should be added to the batch,
+    /// `Ok(false)` if it should be ignored, or `None` if the journal should not be read any further.
+    fn check_entry(&mut self, entry: &Marker, pos: u64) -> Option<crate::Result<bool>> {
+        let mut bytes = Vec::with_capacity(100);
+        fail_iter!(entry.encode_into(&mut bytes));
+
+        self.checksum_builder.update(&bytes);
+
+        if !self.is_in_batch {
+            log::debug!("Invalid batch: found batch entry without start marker");
+
+            match self.recovery_mode {
+                RecoveryMode::SkipInvalidBatches => {
+                    self.skip_orphan(pos, self.reader.last_valid_pos);
+                    return Some(Ok(false));
+                }
+                RecoveryMode::AbsoluteConsistency => {
+                    return Some(Err(self.fail(RecoveryError::UnexpectedMarker, pos)));
+                }
+                _ => {
+                    // Discard batch
+                    fail_iter!(self.truncate_to(self.last_valid_pos));
+
+                    return None;
+                }
+            }
+        }
+
+        if self.invalid_batch.is_some() {
+            return Some(Ok(false));
+        }
+
+        if self.batch_counter == 0 {
+            log::error!("Invalid batch: Expected end marker (too many items in batch)");
+
+            if self.recovery_mode == RecoveryMode::SkipInvalidBatches {
+                self.invalid_batch = Some(RecoveryError::TooManyItems);
+                return Some(Ok(false));
+            }
+
+            return Some(Err(
+                self.fail(RecoveryError::TooManyItems, self.batch_start_pos)
+            ));
+        }
+
+        self.batch_counter -= 1;
+
+        Some(Ok(true))
+    }
 }
 
 impl Iterator for JournalBatchReader {
     type Item = crate::Result<Batch>;
 
+    #[allow(clippy::too_many_lines)]
     fn next(&mut self) -> Option<Self::Item> {
-        use crate::Error::JournalRecovery;
+        let skip_invalid = self.recovery_mode == RecoveryMode::SkipInvalidBatches;
 
         loop {
-            let Some(item) = self.reader.next() else {
+            let marker_pos = self.reader.last_valid_pos;
+
+            let item = self.reader.next();
+
+            if let Some(skipped) = self.reader.take_skipped() {
+                self.on_resync(skipped);
+            }
+
+            let Some(item) = item else {
                 fail_iter!(self.on_close());
                 return None;
             };
@@ -86,52 +278,102 @@ impl Iterator for JournalBatchReader {
 
             let journal_file_pos = self.reader.last_valid_pos;
 
+            if !matches!(item, Marker::Start { .. } | Marker::End(_)) {
+                match self.check_entry(&item, marker_pos)? {
+                    Ok(true) => {}
+                    Ok(false) => continue,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+
             match item {
                 Marker::Start {
-                    item_count,
-                    seqno,
-                    compression,
+                    item_count, seqno, ..
                 } => {
-                    // TODO: journal compression maybe in the future
-                    assert!(
-                        compression == CompressionType::None,
-                        "journal compression not supported"
-                    );
-
                     if self.is_in_batch {
                         log::debug!("Invalid batch: found batch start inside batch");
 
-                        // Discard batch
-                        fail_iter!(self.truncate_to(self.last_valid_pos));
-
-                        return None;
+                        if skip_invalid {
+                            self.skip_batch(
+                                self.invalid_batch
+                                    .unwrap_or(RecoveryError::MissingTerminator),
+                                marker_pos,
+                            );
+                        } else if self.recovery_mode == RecoveryMode::AbsoluteConsistency {
+                            return Some(Err(
+                                self.fail(RecoveryError::MissingTerminator, self.batch_start_pos)
+                            ));
+                        } else {
+                            self.record_skipped(RecoveryError::MissingTerminator, marker_pos);
+
+                            // Discard batch
+                            fail_iter!(self.truncate_to(self.last_valid_pos));
+
+                            return None;
+                        }
                     }
 
+                    // NOTE: Entries outside of a batch are not part of its checksum
+                    self.checksum_builder = xxhash_rust::xxh3::Xxh3::new();
+
                     self.is_in_batch = true;
                     self.batch_counter = item_count;
                     self.batch_seqno = seqno;
+                    self.batch_start_pos = marker_pos;
                 }
                 Marker::End(expected_checksum) => {
-                    if self.batch_counter > 0 {
-                        log::error!("Invalid batch: insufficient length");
-                        return Some(Err(JournalRecovery(RecoveryError::InsufficientLength)));
-                    }
-
                     if !self.is_in_batch {
                         log::error!("Invalid batch: found end marker without start marker");
 
+                        if skip_invalid {
+                            self.skip_orphan(marker_pos, journal_file_pos);
+                            continue;
+                        }
+
+                        if self.recovery_mode == RecoveryMode::AbsoluteConsistency {
+                            return Some(Err(
+                                self.fail(RecoveryError::UnexpectedMarker, marker_pos)
+                            ));
+                        }
+
                         // Discard batch
                         fail_iter!(self.truncate_to(self.last_valid_pos));
 
                         return None;
                     }
 
+                    if let Some(error) = self.invalid_batch {
+                        self.skip_batch(error, journal_file_pos);
+                        continue;
+                    }
+
+                    if self.batch_counter > 0 {
+                        log::error!("Invalid batch: insufficient length");
+
+                        if skip_invalid {
+                            self.skip_batch(RecoveryError::InsufficientLength, journal_file_pos);
+                            continue;
+                        }
+
+                        return Some(Err(
+                            self.fail(RecoveryError::InsufficientLength, self.batch_start_pos)
+                        ));
+                    }
+
                     let got_checksum = self.checksum_builder.finish();
                     self.checksum_builder = xxhash_rust::xxh3::Xxh3::new();
 
                     if got_checksum != expected_checksum {
                         log::error!("Invalid batch: checksum check failed, expected: {expected_checksum}, got: {got_checksum}");
-                        return Some(Err(JournalRecovery(RecoveryError::ChecksumMismatch)));
+
+                        if skip_invalid {
+                            self.skip_batch(RecoveryError::ChecksumMismatch, journal_file_pos);
+                            continue;
+                        }
+
+                        return Some(Err(
+                            self.fail(RecoveryError::ChecksumMismatch, self.batch_start_pos)
+                        ));
                     }
 
                     // Reset all variables
@@ -141,9 +383,15 @@ impl Iterator for JournalBatchReader {
                     self.last_valid_pos = journal_file_pos;
 
                     let items = std::mem::take(&mut self.items);
+                    let range_tombstones = std::mem::take(&mut self.range_tombstones);
+                    let merges = std::mem::take(&mut self.merges);
+                    let expiries = std::mem::take(&mut self.expiries);
                     return Some(Ok(Batch {
                         seqno: self.batch_seqno,
                         items,
+                        range_tombstones,
+                        merges,
+                        expiries,
                     }));
                 }
                 Marker::Item {
@@ -152,33 +400,6 @@ impl Iterator for JournalBatchReader {
                     value,
                     value_type,
                 } => {
-                    let item = Marker::Item {
-                        partition: partition.clone(),
-                        key: key.clone(),
-                        value: value.clone(),
-                        value_type,
-                    };
-                    let mut bytes = Vec::with_capacity(100);
-                    fail_iter!(item.encode_into(&mut bytes));
-
-                    self.checksum_builder.update(&bytes);
-
-                    if !self.is_in_batch {
-                        log::debug!("Invalid batch: found end marker without start marker");
-
-                        // Discard batch
-                        fail_iter!(self.truncate_to(self.last_valid_pos));
-
-                        return None;
-                    }
-
-                    if self.batch_counter == 0 {
-                        log::error!("Invalid batch: Expected end marker (too many items in batch)");
-                        return Some(Err(JournalRecovery(RecoveryError::TooManyItems)));
-                    }
-
-                    self.batch_counter -= 1;
-
                     self.items.push(BatchItem {
                         partition,
                         key,
@@ -186,6 +407,39 @@ impl Iterator for JournalBatchReader {
                         value_type,
                     });
                 }
+                Marker::RangeTombstone {
+                    partition,
+                    start,
+                    end,
+                } => {
+                    self.range_tombstones.push(RangeTombstoneItem {
+                        partition,
+                        start,
+                        end,
+                    });
+                }
+                Marker::Merge {
+                    partition,
+                    key,
+                    operand,
+                } => {
+                    self.merges.push(MergeItem {
+                        partition,
+                        key,
+                        operand,
+                    });
+                }
+                Marker::Expiry {
+                    partition,
+                    key,
+                    expires_at,
+                } => {
+                    self.expiries.push(ExpiryItem {
+                        partition,
+                        key,
+                        expires_at,
+                    });
+                }
             }
         }
     }
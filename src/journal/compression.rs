@@ -0,0 +1,33 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use lsm_tree::CompressionType;
+
+/// Compresses the payload of a batch.
+pub fn compress(compression: CompressionType, bytes: &[u8]) -> Vec<u8> {
+    match compression {
+        CompressionType::None => bytes.into(),
+
+        #[cfg(feature = "lz4")]
+        CompressionType::Lz4 => lz4_flex::compress_prepend_size(bytes),
+
+        #[cfg(feature = "miniz")]
+        CompressionType::Miniz(level) => miniz_oxide::deflate::compress_to_vec(bytes, level),
+    }
+}
+
+/// Decompresses the payload of a batch.
+///
+/// Returns `None` if the payload is corrupt.
+pub fn decompress(compression: CompressionType, bytes: &[u8]) -> Option<Vec<u8>> {
+    match compression {
+        CompressionType::None => Some(bytes.into()),
+
+        #[cfg(feature = "lz4")]
+        CompressionType::Lz4 => lz4_flex::decompress_size_prepended(bytes).ok(),
+
+        #[cfg(feature = "miniz")]
+        CompressionType::Miniz(_) => miniz_oxide::inflate::decompress_to_vec(bytes).ok(),
+    }
+}
@@ -2,6 +2,8 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
+use std::path::PathBuf;
+
 /// Recovery mode to use
 ///
 /// Based on `RocksDB`'s WAL Recovery Modes: <https://github.com/facebook/rocksdb/wiki/WAL-Recovery-Modes>
@@ -17,25 +19,67 @@ pub enum RecoveryMode {
     /// This is the default mode.
     #[default]
     TolerateCorruptTail,
-    // TODO: in the future?
-    /*  /// Skips corrupt (invalid checksum) batches. This may violate
+
+    /// Skips corrupt (invalid checksum) batches. This may violate
     /// consistency, but will recover as much data as possible.
-    SkipInvalidBatches, */
-    // TODO: absolute consistency
+    ///
+    /// Undecodable bytes are skipped up to the next valid batch.
+    ///
+    /// Afterwards, skipped batches are removed from the active journal, so opening
+    /// the keyspace using another mode does not stop at them. The original journal is
+    /// copied into the `salvaged_journals` folder first, so skipped batches can still be salvaged.
+    ///
+    /// Skipped batches can be inspected using [`crate::Keyspace::skipped_batches`].
+    SkipInvalidBatches,
+
+    /// Any invalid or incomplete batch, including the last one, will
+    /// fail recovery with [`crate::Error::JournalRecovery`].
+    ///
+    /// Nothing is discarded, so no write that may have been acknowledged
+    /// is lost without notice, but a crash during a write requires manual intervention.
+    AbsoluteConsistency,
+}
+
+/// A batch that was discarded during journal recovery
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SkippedBatch {
+    /// Path of the journal file
+    pub path: PathBuf,
+
+    /// Byte offset of the batch in the journal file
+    pub offset: u64,
+
+    /// Number of bytes that were skipped, starting at `offset`
+    pub len: u64,
+
+    /// Reason why the batch was discarded
+    pub error: RecoveryError,
 }
 
 /// Errors that can occur during journal recovery
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 #[allow(clippy::module_name_repetitions)]
 pub enum RecoveryError {
     /// Batch had less items than expected, so it's incomplete
     InsufficientLength,
 
-    /* /// Batch was not terminated, so it's possibly incomplete
-    MissingTerminator, */
+    /// Batch was not terminated, so it's possibly incomplete
+    MissingTerminator,
+
     /// Too many items in batch
     TooManyItems,
 
     /// The checksum value does not match the expected value
     ChecksumMismatch,
+
+    /// A batch entry or end marker was found outside of a batch
+    UnexpectedMarker,
+
+    /// Bytes that are not a valid journal entry were found,
+    /// e.g. a partially written entry
+    ///
+    /// Using [`RecoveryMode::SkipInvalidBatches`], the bytes up to the next
+    /// valid batch are skipped.
+    InvalidMarker,
 }
@@ -0,0 +1,407 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{marker::Marker, reader::JournalReader};
+use crate::{batch::PartitionKey, JournalEncryptionProvider, RecoveryError};
+use lsm_tree::{coding::Encode, CompressionType, SeqNo, UserKey, UserValue, ValueType};
+use std::{hash::Hasher, path::Path, sync::Arc};
+
+/// An entry of a journal batch
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Entry {
+    /// A value or tombstone
+    Item {
+        /// Partition name
+        partition: PartitionKey,
+
+        /// User key
+        key: UserKey,
+
+        /// User value (empty for tombstones)
+        value: UserValue,
+
+        /// Value type
+        value_type: ValueType,
+    },
+
+    /// A range tombstone
+    RangeTombstone {
+        /// Partition name
+        partition: PartitionKey,
+
+        /// Start key (inclusive)
+        start: UserKey,
+
+        /// End key (exclusive), or `None` if the range is unbounded
+        end: Option<UserKey>,
+    },
+
+    /// A merge operand
+    Merge {
+        /// Partition name
+        partition: PartitionKey,
+
+        /// User key
+        key: UserKey,
+
+        /// Merge operand
+        operand: UserValue,
+    },
+
+    /// An expiry timestamp of an item
+    Expiry {
+        /// Partition name
+        partition: PartitionKey,
+
+        /// User key
+        key: UserKey,
+
+        /// Unix timestamp (in milliseconds) the item expires at
+        expires_at: u64,
+    },
+}
+
+impl Entry {
+    /// Returns the partition the entry belongs to.
+    #[must_use]
+    pub fn partition(&self) -> &PartitionKey {
+        match self {
+            Self::Item { partition, .. }
+            | Self::RangeTombstone { partition, .. }
+            | Self::Merge { partition, .. }
+            | Self::Expiry { partition, .. } => partition,
+        }
+    }
+
+    pub(crate) fn into_marker(self) -> Marker {
+        match self {
+            Self::Item {
+                partition,
+                key,
+                value,
+                value_type,
+            } => Marker::Item {
+                partition,
+                key,
+                value,
+                value_type,
+            },
+            Self::RangeTombstone {
+                partition,
+                start,
+                end,
+            } => Marker::RangeTombstone {
+                partition,
+                start,
+                end,
+            },
+            Self::Merge {
+                partition,
+                key,
+                operand,
+            } => Marker::Merge {
+                partition,
+                key,
+                operand,
+            },
+            Self::Expiry {
+                partition,
+                key,
+                expires_at,
+            } => Marker::Expiry {
+                partition,
+                key,
+                expires_at,
+            },
+        }
+    }
+
+    pub(crate) fn from_marker(marker: Marker) -> Option<Self> {
+        match marker {
+            Marker::Item {
+                partition,
+                key,
+                value,
+                value_type,
+            } => Some(Self::Item {
+                partition,
+                key,
+                value,
+                value_type,
+            }),
+            Marker::RangeTombstone {
+                partition,
+                start,
+                end,
+            } => Some(Self::RangeTombstone {
+                partition,
+                start,
+                end,
+            }),
+            Marker::Merge {
+                partition,
+                key,
+                operand,
+            } => Some(Self::Merge {
+                partition,
+                key,
+                operand,
+            }),
+            Marker::Expiry {
+                partition,
+                key,
+                expires_at,
+            } => Some(Self::Expiry {
+                partition,
+                key,
+                expires_at,
+            }),
+            Marker::Start { .. } | Marker::End(_) => None,
+        }
+    }
+}
+
+/// A batch read from a journal file
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InspectedBatch {
+    /// Seqno of the batch
+    pub seqno: SeqNo,
+
+    /// Byte offset of the batch's start marker
+    pub offset: u64,
+
+    /// Byte offset after the batch's last marker
+    pub end_offset: u64,
+
+    /// Amount of entries announced by the start marker
+    pub item_count: u32,
+
+    /// Compression of the batch's entries
+    pub compression: CompressionType,
+
+    /// `true` if the batch's entries are encrypted
+    pub encrypted: bool,
+
+    /// Entries of the batch
+    pub entries: Vec<Entry>,
+
+    /// Checksum stored in the batch's end marker,
+    /// or `None` if the batch is not terminated
+    pub expected_checksum: Option<u64>,
+
+    /// Checksum computed over the batch's entries
+    pub checksum: u64,
+
+    /// Reason why the batch is invalid, or `None` if it is valid
+    pub error: Option<RecoveryError>,
+}
+
+impl InspectedBatch {
+    /// Returns `true` if the batch is valid, so it would be recovered.
+    #[must_use]
+    pub fn is_valid(&self) -> bool {
+        self.error.is_none()
+    }
+
+    fn finish(mut self, hasher: &xxhash_rust::xxh3::Xxh3) -> Self {
+        self.checksum = hasher.finish();
+
+        let entry_count = self.entries.len() as u64;
+
+        self.error = if self.expected_checksum.is_none() {
+            Some(RecoveryError::MissingTerminator)
+        } else if entry_count < u64::from(self.item_count) {
+            Some(RecoveryError::InsufficientLength)
+        } else if entry_count > u64::from(self.item_count) {
+            Some(RecoveryError::TooManyItems)
+        } else if self.expected_checksum != Some(self.checksum) {
+            Some(RecoveryError::ChecksumMismatch)
+        } else {
+            None
+        };
+
+        self
+    }
+}
+
+/// Iterator over the batches of a journal file
+///
+/// See [`inspect`].
+pub struct Inspect {
+    reader: JournalReader,
+    current: Option<(InspectedBatch, xxhash_rust::xxh3::Xxh3)>,
+    is_done: bool,
+}
+
+impl std::fmt::Debug for Inspect {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Inspect({})", self.reader.path.display())
+    }
+}
+
+/// Reads the batches of a journal file, without modifying it.
+///
+/// In contrast to recovery, invalid batches are not discarded, but returned
+/// with the reason why they are invalid. Reading stops at the first entry that
+/// cannot be decoded (e.g. the journal's preallocated space), or at an entry
+/// that does not belong to a batch, which is returned as an error.
+///
+/// # Examples
+///
+/// ```
+/// # use fjall::{Config, PartitionCreateOptions, PersistMode};
+/// #
+/// # let folder = tempfile::tempdir()?;
+/// # let keyspace = Config::new(&folder).open()?;
+/// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+/// partition.insert("a", "abc")?;
+/// keyspace.persist(PersistMode::SyncAll)?;
+///
+/// let batches = fjall::journal::inspect(folder.path().join("journals").join("0"))?
+///     .collect::<fjall::Result<Vec<_>>>()?;
+///
+/// assert_eq!(1, batches.len());
+/// assert!(batches.iter().all(fjall::journal::InspectedBatch::is_valid));
+/// #
+/// # Ok::<(), fjall::Error>(())
+/// ```
+///
+/// # Errors
+///
+/// Will return `Err` if an IO error occurs.
+pub fn inspect<P: AsRef<Path>>(path: P) -> crate::Result<Inspect> {
+    Ok(Inspect {
+        reader: JournalReader::new_read_only(path)?,
+        current: None,
+        is_done: false,
+    })
+}
+
+/// Reads the batches of a journal file, starting at the given byte offset,
+/// which needs to be the start of a batch.
+pub fn inspect_from(
+    path: &Path,
+    offset: u64,
+    encryption: Option<Arc<dyn JournalEncryptionProvider>>,
+) -> crate::Result<Inspect> {
+    let mut reader = JournalReader::new_read_only(path)?.with_encryption(encryption);
+    reader.seek_to(offset)?;
+
+    Ok(Inspect {
+        reader,
+        current: None,
+        is_done: false,
+    })
+}
+
+impl Inspect {
+    /// Sets the encryption provider to decrypt encrypted batches with.
+    #[must_use]
+    pub fn with_encryption(mut self, encryption: Arc<dyn JournalEncryptionProvider>) -> Self {
+        self.reader.encryption = Some(encryption);
+        self
+    }
+
+    /// Stops reading, because of the given error.
+    fn fail(&mut self, error: crate::Error) -> crate::Error {
+        self.is_done = true;
+        error
+    }
+}
+
+impl Iterator for Inspect {
+    type Item = crate::Result<InspectedBatch>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.is_done {
+            return None;
+        }
+
+        loop {
+            let marker_pos = self.reader.last_valid_pos;
+
+            let Some(marker) = self.reader.next() else {
+                self.is_done = true;
+
+                return self
+                    .current
+                    .take()
+                    .map(|(batch, hasher)| Ok(batch.finish(&hasher)));
+            };
+
+            let marker = match marker {
+                Ok(marker) => marker,
+                Err(e) => return Some(Err(self.fail(e))),
+            };
+
+            let pos = self.reader.last_valid_pos;
+
+            match marker {
+                Marker::Start {
+                    item_count,
+                    seqno,
+                    compression,
+                    encrypted,
+                } => {
+                    let batch = InspectedBatch {
+                        seqno,
+                        offset: marker_pos,
+                        end_offset: pos,
+                        item_count,
+                        compression,
+                        encrypted,
+                        entries: Vec::new(),
+                        expected_checksum: None,
+                        checksum: 0,
+                        error: None,
+                    };
+
+                    let previous = self
+                        .current
+                        .replace((batch, xxhash_rust::xxh3::Xxh3::new()));
+
+                    if let Some((previous, hasher)) = previous {
+                        return Some(Ok(previous.finish(&hasher)));
+                    }
+                }
+                Marker::End(checksum) => {
+                    let Some((mut batch, hasher)) = self.current.take() else {
+                        log::error!(
+                            "Found end marker without start marker at {}:{marker_pos}",
+                            self.reader.path.display(),
+                        );
+                        return Some(Err(self.fail(crate::Error::JournalRecovery(
+                            RecoveryError::UnexpectedMarker,
+                        ))));
+                    };
+
+                    batch.expected_checksum = Some(checksum);
+                    batch.end_offset = pos;
+
+                    return Some(Ok(batch.finish(&hasher)));
+                }
+                marker => {
+                    let Some((batch, hasher)) = &mut self.current else {
+                        log::error!(
+                            "Found batch entry without start marker at {}:{marker_pos}",
+                            self.reader.path.display(),
+                        );
+                        return Some(Err(self.fail(crate::Error::JournalRecovery(
+                            RecoveryError::UnexpectedMarker,
+                        ))));
+                    };
+
+                    let bytes = match marker.encode_into_vec() {
+                        Ok(bytes) => bytes,
+                        Err(e) => return Some(Err(self.fail(e.into()))),
+                    };
+                    hasher.update(&bytes);
+
+                    batch.entries.extend(Entry::from_marker(marker));
+                    batch.end_offset = pos;
+                }
+            }
+        }
+    }
+}
@@ -2,8 +2,8 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use super::writer::Writer;
-use crate::PartitionHandle;
+use super::{writer::Writer, JournalArchive};
+use crate::{fs::FileSystem, PartitionHandle};
 use lsm_tree::{AbstractTree, Memtable, SeqNo};
 use std::{
     path::PathBuf,
@@ -17,6 +17,17 @@ pub struct EvictionWatermark {
     pub(crate) lsn: SeqNo,
 }
 
+impl EvictionWatermark {
+    /// Returns `true` if the partition was cleared after the watermark,
+    /// so its data in the journal is not needed anymore.
+    pub(crate) fn is_cleared(&self) -> bool {
+        self.partition
+            .range_tombstones
+            .cleared_seqno()
+            .is_some_and(|seqno| self.lsn < seqno)
+    }
+}
+
 impl std::fmt::Debug for EvictionWatermark {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}:{}", self.partition.name, self.lsn)
@@ -45,13 +56,29 @@ impl std::fmt::Debug for Item {
 ///
 /// Each journal may contain items of different partitions.
 #[allow(clippy::module_name_repetitions)]
-#[derive(Debug)]
 pub struct JournalManager {
     active_path: PathBuf, // TODO: remove?
     items: Vec<Item>,
 
     // TODO: should be taking into account active journal, which is preallocated...
     disk_space_in_bytes: u64,
+
+    /// If set, evicted journals are archived instead of deleted
+    archive: Option<JournalArchive>,
+
+    /// File system the journals are stored in
+    fs: Arc<dyn FileSystem>,
+}
+
+impl std::fmt::Debug for JournalManager {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("JournalManager")
+            .field("active_path", &self.active_path)
+            .field("items", &self.items)
+            .field("disk_space_in_bytes", &self.disk_space_in_bytes)
+            .field("archive", &self.archive)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Drop for JournalManager {
@@ -64,7 +91,7 @@ impl Drop for JournalManager {
 }
 
 impl JournalManager {
-    pub(crate) fn from_active<P: Into<PathBuf>>(path: P) -> Self {
+    pub(crate) fn from_active<P: Into<PathBuf>>(path: P, fs: Arc<dyn FileSystem>) -> Self {
         #[cfg(feature = "__internal_whitebox")]
         crate::drop::increment_drop_counter();
 
@@ -72,9 +99,18 @@ impl JournalManager {
             active_path: path.into(),
             items: Vec::with_capacity(10),
             disk_space_in_bytes: 0,
+            archive: None,
+            fs,
         }
     }
 
+    /// Sets the archive that evicted journals are moved to.
+    #[must_use]
+    pub(crate) fn with_archive(mut self, archive: Option<JournalArchive>) -> Self {
+        self.archive = archive;
+        self
+    }
+
     pub(crate) fn clear(&mut self) {
         self.items.clear();
     }
@@ -95,6 +131,11 @@ impl JournalManager {
         self.items.len()
     }
 
+    /// Returns the paths of the sealed journals, oldest first
+    pub(crate) fn sealed_journal_paths(&self) -> Vec<PathBuf> {
+        self.items.iter().map(|item| item.path.clone()).collect()
+    }
+
     /// Returns the amount of bytes used on disk by journals
     pub(crate) fn disk_space_used(&self) -> u64 {
         self.disk_space_in_bytes
@@ -108,6 +149,10 @@ impl JournalManager {
 
         if let Some(item) = self.items.first() {
             for item in &item.watermarks {
+                if item.is_cleared() {
+                    continue;
+                }
+
                 let highest_persisted_seqno = item.partition.tree.get_highest_persisted_seqno();
 
                 if highest_persisted_seqno.is_none()
@@ -150,6 +195,10 @@ impl JournalManager {
                     .is_deleted
                     .load(std::sync::atomic::Ordering::Acquire)
                 {
+                    if item.is_cleared() {
+                        continue;
+                    }
+
                     let Some(partition_seqno) = item.partition.tree.get_highest_persisted_seqno()
                     else {
                         return Ok(());
@@ -170,8 +219,12 @@ impl JournalManager {
             // [2] Checking the seqno is safe because the queues inside the flush manager are FIFO.
             //
             // IMPORTANT: On recovery, the journals need to be flushed from oldest to newest.
-            log::trace!("Removing fully flushed journal at {:?}", item.path);
-            std::fs::remove_file(&item.path)?;
+            if let Some(archive) = &self.archive {
+                archive.store(&*self.fs, &item.path)?;
+            } else {
+                log::trace!("Removing fully flushed journal at {}", item.path.display());
+                self.fs.remove_file(&item.path)?;
+            }
 
             self.disk_space_in_bytes = self.disk_space_in_bytes.saturating_sub(item.size_in_bytes);
             self.items.remove(0);
@@ -10,6 +10,21 @@ use lsm_tree::{
 };
 use std::io::{Read, Write};
 
+/// Reads `len` bytes.
+///
+/// Lengths are read from the input, which may be corrupt (or come from an untrusted peer),
+/// so the buffer grows with the bytes that are actually read, instead of being allocated upfront.
+pub fn read_bytes<R: Read>(reader: &mut R, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut bytes = vec![];
+    reader.take(len).read_to_end(&mut bytes)?;
+
+    if bytes.len() as u64 != len {
+        return Err(std::io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(bytes)
+}
+
 /// Journal marker. Every batch is wrapped in a Start marker, followed by N items, followed by an end marker.
 ///
 /// - The start marker contains the numbers of items. If the numbers of items following doesn't match, the batch is broken.
@@ -19,12 +34,23 @@ use std::io::{Read, Write};
 /// - The end marker terminates each batch with the magic string: [`TRAILER_MAGIC`].
 ///
 /// - If a start marker is detected, while inside a batch, the batch is broken.
+///
+/// - Range tombstones are written as their own marker kind inside a batch, and are counted as items.
+///
+/// - Merge operands are written as their own marker kind, too.
+///
+/// - Expiry timestamps are written as their own marker kind inside a batch,
+///   following the item they belong to, and are counted as items.
+///
+/// - If a batch is compressed or encrypted, its items are written as a single
+///   length-prefixed payload following the start marker.
 #[derive(Debug, Eq, PartialEq)]
 pub enum Marker {
     Start {
         item_count: u32,
         seqno: SeqNo,
         compression: CompressionType,
+        encrypted: bool,
     },
     Item {
         partition: PartitionKey,
@@ -33,6 +59,34 @@ pub enum Marker {
         value_type: ValueType,
     },
     End(u64),
+    RangeTombstone {
+        partition: PartitionKey,
+        start: UserKey,
+        end: Option<UserKey>,
+    },
+    Merge {
+        partition: PartitionKey,
+        key: UserKey,
+        operand: UserValue,
+    },
+    Expiry {
+        partition: PartitionKey,
+        key: UserKey,
+        expires_at: u64,
+    },
+}
+
+/// Converts a length into the integer type the journal format stores it as.
+///
+/// Limits are validated before writing, so this should never fail,
+/// but a length must never be truncated, because that would corrupt the journal.
+fn checked_len<T: TryFrom<usize>>(len: usize) -> Result<T, EncodeError> {
+    T::try_from(len).map_err(|_| {
+        EncodeError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "length exceeds journal format limit",
+        ))
+    })
 }
 
 pub fn serialize_marker_item<W: Write>(
@@ -46,40 +100,136 @@ pub fn serialize_marker_item<W: Write>(
 
     writer.write_u8(u8::from(value_type))?;
 
-    // NOTE: Truncation is okay and actually needed
-    #[allow(clippy::cast_possible_truncation)]
-    writer.write_u8(partition.as_bytes().len() as u8)?;
+    writer.write_u8(checked_len(partition.len())?)?;
     writer.write_all(partition.as_bytes())?;
 
-    // NOTE: Truncation is okay and actually needed
-    #[allow(clippy::cast_possible_truncation)]
-    writer.write_u16::<BigEndian>(key.len() as u16)?;
+    writer.write_u16::<BigEndian>(checked_len(key.len())?)?;
     writer.write_all(key)?;
 
-    // NOTE: Truncation is okay and actually needed
-    #[allow(clippy::cast_possible_truncation)]
-    writer.write_u32::<BigEndian>(value.len() as u32)?;
+    writer.write_u32::<BigEndian>(checked_len(value.len())?)?;
     writer.write_all(value)?;
 
     Ok(())
 }
 
+pub fn serialize_marker_range_tombstone<W: Write>(
+    writer: &mut W,
+    partition: &str,
+    start: &[u8],
+    end: Option<&[u8]>,
+) -> Result<(), EncodeError> {
+    writer.write_u8(Tag::RangeTombstone.into())?;
+
+    writer.write_u8(checked_len(partition.len())?)?;
+    writer.write_all(partition.as_bytes())?;
+
+    writer.write_u16::<BigEndian>(checked_len(start.len())?)?;
+    writer.write_all(start)?;
+
+    if let Some(end) = end {
+        writer.write_u8(1)?;
+
+        writer.write_u16::<BigEndian>(checked_len(end.len())?)?;
+        writer.write_all(end)?;
+    } else {
+        writer.write_u8(0)?;
+    }
+
+    Ok(())
+}
+
+pub fn serialize_marker_merge<W: Write>(
+    writer: &mut W,
+    partition: &str,
+    key: &[u8],
+    operand: &[u8],
+) -> Result<(), EncodeError> {
+    writer.write_u8(Tag::Merge.into())?;
+
+    writer.write_u8(checked_len(partition.len())?)?;
+    writer.write_all(partition.as_bytes())?;
+
+    writer.write_u16::<BigEndian>(checked_len(key.len())?)?;
+    writer.write_all(key)?;
+
+    writer.write_u32::<BigEndian>(checked_len(operand.len())?)?;
+    writer.write_all(operand)?;
+
+    Ok(())
+}
+
+pub fn serialize_marker_expiry<W: Write>(
+    writer: &mut W,
+    partition: &str,
+    key: &[u8],
+    expires_at: u64,
+) -> Result<(), EncodeError> {
+    writer.write_u8(Tag::Expiry.into())?;
+
+    writer.write_u8(checked_len(partition.len())?)?;
+    writer.write_all(partition.as_bytes())?;
+
+    writer.write_u16::<BigEndian>(checked_len(key.len())?)?;
+    writer.write_all(key)?;
+
+    writer.write_u64::<BigEndian>(expires_at)?;
+
+    Ok(())
+}
+
+/// Decodes the compression type of a batch.
+///
+/// Unlike [`CompressionType::decode_from`], invalid bytes are an error instead of a panic,
+/// because the journal may be corrupt.
+fn decode_compression<R: Read>(reader: &mut R) -> Result<CompressionType, DecodeError> {
+    let tag = reader.read_u8()?;
+    let param = reader.read_u8()?;
+
+    match (tag, param) {
+        (0, 0) => Ok(CompressionType::None),
+
+        #[cfg(feature = "lz4")]
+        (1, 0) => Ok(CompressionType::Lz4),
+
+        #[cfg(feature = "miniz")]
+        (2, level @ 0..=10) => Ok(CompressionType::Miniz(level)),
+
+        (0, _) => Err(DecodeError::InvalidHeader("CompressionType")),
+
+        #[cfg(feature = "lz4")]
+        (1, _) => Err(DecodeError::InvalidHeader("CompressionType")),
+
+        #[cfg(feature = "miniz")]
+        (2, _) => Err(DecodeError::InvalidHeader("CompressionType")),
+
+        (tag, _) => Err(DecodeError::InvalidTag(("CompressionType", tag))),
+    }
+}
+
 pub enum Tag {
     Start = 1,
     Item = 2,
     End = 3,
+    RangeTombstone = 4,
+    Merge = 5,
+    Expiry = 6,
+    EncryptedStart = 7,
 }
 
 impl TryFrom<u8> for Tag {
     type Error = DecodeError;
 
     fn try_from(value: u8) -> Result<Self, Self::Error> {
-        use Tag::{End, Item, Start};
+        use Tag::{EncryptedStart, End, Expiry, Item, Merge, RangeTombstone, Start};
 
         match value {
             1 => Ok(Start),
             2 => Ok(Item),
             3 => Ok(End),
+            4 => Ok(RangeTombstone),
+            5 => Ok(Merge),
+            6 => Ok(Expiry),
+            7 => Ok(EncryptedStart),
             _ => Err(DecodeError::InvalidTag(("JournalMarkerTag", value))),
         }
     }
@@ -93,15 +243,20 @@ impl From<Tag> for u8 {
 
 impl Encode for Marker {
     fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
-        use Marker::{End, Item, Start};
+        use Marker::{End, Expiry, Item, Merge, RangeTombstone, Start};
 
         match self {
             Start {
                 item_count,
                 seqno,
                 compression,
+                encrypted,
             } => {
-                writer.write_u8(Tag::Start.into())?;
+                writer.write_u8(if *encrypted {
+                    Tag::EncryptedStart.into()
+                } else {
+                    Tag::Start.into()
+                })?;
                 writer.write_u32::<BigEndian>(*item_count)?;
                 writer.write_u64::<BigEndian>(*seqno)?;
                 compression.encode_into(writer)?;
@@ -123,29 +278,46 @@ impl Encode for Marker {
                 // (only partially written, with the rest being padding zeroes)
                 writer.write_all(MAGIC_BYTES)?;
             }
+            RangeTombstone {
+                partition,
+                start,
+                end,
+            } => {
+                serialize_marker_range_tombstone(writer, partition, start, end.as_deref())?;
+            }
+            Merge {
+                partition,
+                key,
+                operand,
+            } => {
+                serialize_marker_merge(writer, partition, key, operand)?;
+            }
+            Expiry {
+                partition,
+                key,
+                expires_at,
+            } => {
+                serialize_marker_expiry(writer, partition, key, *expires_at)?;
+            }
         }
         Ok(())
     }
 }
 
 impl Decode for Marker {
+    #[allow(clippy::too_many_lines)]
     fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
         match reader.read_u8()?.try_into()? {
-            Tag::Start => {
+            tag @ (Tag::Start | Tag::EncryptedStart) => {
                 let item_count = reader.read_u32::<BigEndian>()?;
                 let seqno = reader.read_u64::<BigEndian>()?;
-                let compression = CompressionType::decode_from(reader)?;
-
-                assert_eq!(
-                    compression,
-                    CompressionType::None,
-                    "invalid compression type"
-                );
+                let compression = decode_compression(reader)?;
 
                 Ok(Self::Start {
                     item_count,
                     seqno,
                     compression,
+                    encrypted: matches!(tag, Tag::EncryptedStart),
                 })
             }
             Tag::Item => {
@@ -167,8 +339,7 @@ impl Decode for Marker {
 
                 // Read value
                 let value_len = reader.read_u32::<BigEndian>()?;
-                let mut value = vec![0; value_len as usize];
-                reader.read_exact(&mut value)?;
+                let value = read_bytes(reader, value_len.into())?;
 
                 Ok(Self::Item {
                     partition: partition.into(),
@@ -190,6 +361,78 @@ impl Decode for Marker {
 
                 Ok(Self::End(checksum))
             }
+            Tag::RangeTombstone => {
+                // Read partition key
+                let partition_len = reader.read_u8()?;
+                let mut partition = vec![0; partition_len.into()];
+                reader.read_exact(&mut partition)?;
+                let partition = std::str::from_utf8(&partition)?;
+
+                // Read start key
+                let start_len = reader.read_u16::<BigEndian>()?;
+                let mut start = vec![0; start_len.into()];
+                reader.read_exact(&mut start)?;
+
+                // Read end key, if bounded
+                let end = match reader.read_u8()? {
+                    0 => None,
+                    1 => {
+                        let end_len = reader.read_u16::<BigEndian>()?;
+                        let mut end = vec![0; end_len.into()];
+                        reader.read_exact(&mut end)?;
+                        Some(end.into())
+                    }
+                    tag => return Err(DecodeError::InvalidTag(("RangeTombstoneEnd", tag))),
+                };
+
+                Ok(Self::RangeTombstone {
+                    partition: partition.into(),
+                    start: start.into(),
+                    end,
+                })
+            }
+            Tag::Merge => {
+                // Read partition key
+                let partition_len = reader.read_u8()?;
+                let mut partition = vec![0; partition_len.into()];
+                reader.read_exact(&mut partition)?;
+                let partition = std::str::from_utf8(&partition)?;
+
+                // Read key
+                let key_len = reader.read_u16::<BigEndian>()?;
+                let mut key = vec![0; key_len.into()];
+                reader.read_exact(&mut key)?;
+
+                // Read operand
+                let operand_len = reader.read_u32::<BigEndian>()?;
+                let operand = read_bytes(reader, operand_len.into())?;
+
+                Ok(Self::Merge {
+                    partition: partition.into(),
+                    key: key.into(),
+                    operand: operand.into(),
+                })
+            }
+            Tag::Expiry => {
+                // Read partition key
+                let partition_len = reader.read_u8()?;
+                let mut partition = vec![0; partition_len.into()];
+                reader.read_exact(&mut partition)?;
+                let partition = std::str::from_utf8(&partition)?;
+
+                // Read key
+                let key_len = reader.read_u16::<BigEndian>()?;
+                let mut key = vec![0; key_len.into()];
+                reader.read_exact(&mut key)?;
+
+                let expires_at = reader.read_u64::<BigEndian>()?;
+
+                Ok(Self::Expiry {
+                    partition: partition.into(),
+                    key: key.into(),
+                    expires_at,
+                })
+            }
         }
     }
 }
@@ -217,6 +460,79 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_serialize_and_deserialize_range_tombstone() -> crate::Result<()> {
+        for end in [Some(vec![4, 5].into()), None] {
+            let item = Marker::RangeTombstone {
+                partition: "default".into(),
+                start: vec![1, 2, 3].into(),
+                end,
+            };
+
+            let serialized_data = item.encode_into_vec()?;
+            let mut reader = &serialized_data[..];
+            let deserialized_item = Marker::decode_from(&mut reader)?;
+
+            assert_eq!(item, deserialized_item);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_merge() -> crate::Result<()> {
+        let item = Marker::Merge {
+            partition: "default".into(),
+            key: vec![1, 2, 3].into(),
+            operand: vec![4, 5].into(),
+        };
+
+        let serialized_data = item.encode_into_vec()?;
+        let mut reader = &serialized_data[..];
+        let deserialized_item = Marker::decode_from(&mut reader)?;
+
+        assert_eq!(item, deserialized_item);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_expiry() -> crate::Result<()> {
+        let item = Marker::Expiry {
+            partition: "default".into(),
+            key: vec![1, 2, 3].into(),
+            expires_at: 1_000,
+        };
+
+        let serialized_data = item.encode_into_vec()?;
+        let mut reader = &serialized_data[..];
+        let deserialized_item = Marker::decode_from(&mut reader)?;
+
+        assert_eq!(item, deserialized_item);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_serialize_and_deserialize_start() -> crate::Result<()> {
+        for encrypted in [false, true] {
+            let item = Marker::Start {
+                item_count: 3,
+                seqno: 5,
+                compression: CompressionType::None,
+                encrypted,
+            };
+
+            let serialized_data = item.encode_into_vec()?;
+            let mut reader = &serialized_data[..];
+            let deserialized_item = Marker::decode_from(&mut reader)?;
+
+            assert_eq!(item, deserialized_item);
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_invalid_deserialize() {
         let invalid_data = [Tag::Start as u8; 1]; // Should be followed by a u32
@@ -239,7 +555,7 @@ mod tests {
 
     #[test]
     fn test_invalid_tag() {
-        let invalid_data = [4u8; 1]; // Invalid tag
+        let invalid_data = [8u8; 1]; // Invalid tag
 
         // Try to deserialize with invalid data
         let mut reader = &invalid_data[..];
@@ -248,7 +564,7 @@ mod tests {
         match result {
             Ok(_) => panic!("should error"),
             Err(error) => match error {
-                DecodeError::InvalidTag(("JournalMarkerTag", 4)) => {}
+                DecodeError::InvalidTag(("JournalMarkerTag", 8)) => {}
                 _ => panic!("should throw InvalidTag"),
             },
         }
@@ -2,27 +2,44 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-pub mod batch_reader;
-pub mod error;
-pub mod manager;
-pub mod marker;
-pub mod reader;
+mod archive;
+pub(crate) mod batch_reader;
+mod compression;
+pub(crate) mod error;
+mod inspect;
+pub(crate) mod manager;
+pub(crate) mod marker;
+pub(crate) mod reader;
 mod recovery;
-pub mod writer;
+pub(crate) mod writer;
+
+pub use archive::{restore_to, JournalArchive};
+pub(crate) use inspect::inspect_from;
+pub use inspect::{inspect, Entry, Inspect, InspectedBatch};
+pub(crate) use recovery::JournalId;
 
 use self::writer::PersistMode;
-use crate::file::fsync_directory;
+use crate::fs::{FileSystem, OpenMode};
 use batch_reader::JournalBatchReader;
+use error::{RecoveryMode, SkippedBatch};
 use reader::JournalReader;
 use recovery::{recover_journals, RecoveryResult};
 use std::{
+    io::{Read, Write},
     path::{Path, PathBuf},
-    sync::{Mutex, MutexGuard},
+    sync::{atomic::AtomicBool, Arc, Mutex, MutexGuard},
 };
-use writer::Writer;
+use writer::{GroupCommit, Writer};
 
-pub struct Journal {
+pub(crate) struct Journal {
     writer: Mutex<Writer>,
+    group_commit: GroupCommit,
+
+    /// If `true`, the journal file is opened without write access, and never flushed
+    read_only: bool,
+
+    /// Set once a subscriber is added, see [`Journal::get_writer_for`]
+    pub(crate) has_subscribers: AtomicBool,
 }
 
 impl std::fmt::Debug for Journal {
@@ -50,29 +67,47 @@ impl Drop for Journal {
 }
 
 impl Journal {
-    fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+    fn from_file<P: AsRef<Path>>(fs: Arc<dyn FileSystem>, path: P) -> crate::Result<Self> {
+        Ok(Self {
+            writer: Mutex::new(Writer::from_file(fs, path)?),
+            group_commit: GroupCommit::default(),
+            read_only: false,
+            has_subscribers: AtomicBool::default(),
+        })
+    }
+
+    /// Opens an existing journal without write access.
+    ///
+    /// Used by read-only keyspaces, which never write to the journal.
+    pub fn open_read_only<P: AsRef<Path>>(fs: Arc<dyn FileSystem>, path: P) -> crate::Result<Self> {
         Ok(Self {
-            writer: Mutex::new(Writer::from_file(path)?),
+            writer: Mutex::new(Writer::open_read_only(fs, path)?),
+            group_commit: GroupCommit::default(),
+            read_only: true,
+            has_subscribers: AtomicBool::default(),
         })
     }
 
-    pub fn create_new<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+    pub fn create_new<P: AsRef<Path>>(fs: Arc<dyn FileSystem>, path: P) -> crate::Result<Self> {
         let path = path.as_ref();
         log::trace!("Creating new journal at {path:?}");
 
         let folder = path.parent().expect("parent should exist");
-        std::fs::create_dir_all(folder)?;
+        fs.create_dir_all(folder)?;
 
-        let writer = Writer::create_new(path)?;
+        let writer = Writer::create_new(fs, path)?;
 
         // IMPORTANT: fsync folder on Unix
-        fsync_directory(folder)?;
+        writer.fs.sync_directory(folder)?;
 
         #[cfg(feature = "__internal_whitebox")]
         crate::drop::increment_drop_counter();
 
         Ok(Self {
             writer: Mutex::new(writer),
+            group_commit: GroupCommit::default(),
+            read_only: false,
+            has_subscribers: AtomicBool::default(),
         })
     }
 
@@ -81,23 +116,174 @@ impl Journal {
         self.writer.lock().expect("lock is poisoned")
     }
 
+    /// Hands out write access for the journal, if a write needs it.
+    ///
+    /// Writes that are not journaled only need the journal lock
+    /// to notify subscribers in commit order, so they skip it if there are none.
+    pub(crate) fn get_writer_for(&self, is_journaled: bool) -> Option<MutexGuard<'_, Writer>> {
+        (is_journaled
+            || self
+                .has_subscribers
+                .load(std::sync::atomic::Ordering::Acquire))
+        .then(|| self.get_writer())
+    }
+
     pub fn path(&self) -> PathBuf {
         self.get_writer().path.clone()
     }
 
-    pub fn get_reader(&self) -> crate::Result<JournalBatchReader> {
-        let raw_reader = JournalReader::new(self.path())?;
-        Ok(JournalBatchReader::new(raw_reader))
+    pub fn get_reader(&self, recovery_mode: RecoveryMode) -> crate::Result<JournalBatchReader> {
+        let (fs, path, encryption) = {
+            let writer = self.get_writer();
+            (
+                writer.fs.clone(),
+                writer.path.clone(),
+                writer.encryption.clone(),
+            )
+        };
+        let raw_reader = JournalReader::open_in(&*fs, path, self.read_only)?;
+
+        Ok(JournalBatchReader::new(
+            raw_reader.with_encryption(encryption),
+            recovery_mode,
+        ))
     }
 
     /// Flushes the journal.
     pub fn flush(&self, mode: PersistMode) -> crate::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
         let mut lock = self.get_writer();
         lock.flush(mode).map_err(Into::into)
     }
 
-    pub fn recover<P: AsRef<Path>>(path: P) -> crate::Result<RecoveryResult> {
-        recover_journals(path)
+    /// Blocks until the given batch is durable, sharing the sync with concurrent writers.
+    ///
+    /// The journal writer lock must not be held.
+    pub(crate) fn sync_batch(&self, batch: u64, mode: PersistMode) -> crate::Result<()> {
+        if mode == PersistMode::Buffer {
+            return Ok(());
+        }
+
+        self.group_commit
+            .wait(&self.writer, batch, mode)
+            .map_err(Into::into)
+    }
+
+    /// Rewrites the journal without the bytes of the given skipped batches.
+    ///
+    /// After recovering with [`RecoveryMode::SkipInvalidBatches`], the journal still contains
+    /// the invalid bytes, so a later recovery using another mode would stop at them,
+    /// and discard all valid batches behind them.
+    ///
+    /// The original journal is copied into `salvage_folder` first, and the skipped
+    /// batches are changed to refer to the copy, so they can still be salvaged.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub(crate) fn rewrite_without(
+        &self,
+        skipped_batches: &mut [SkippedBatch],
+        salvage_folder: &Path,
+    ) -> crate::Result<()> {
+        let mut writer = self.get_writer();
+        writer.flush(PersistMode::SyncAll)?;
+
+        let fs = writer.fs.clone();
+        let path = writer.path.clone();
+        let folder = path.parent().expect("should have parent");
+        let file_name = path
+            .file_name()
+            .expect("should have file name")
+            .to_str()
+            .expect("should be utf-8");
+
+        let mut bytes = vec![];
+        fs.open(&path, OpenMode::Read)?.read_to_end(&mut bytes)?;
+
+        fs.create_dir_all(salvage_folder)?;
+
+        // NOTE: The same journal may need to be salvaged more than once
+        let mut salvage_path = salvage_folder.join(file_name);
+        let mut idx = 0;
+
+        while fs.exists(&salvage_path)? {
+            idx += 1;
+            salvage_path = salvage_folder.join(format!("{file_name}.{idx}"));
+        }
+
+        log::warn!(
+            "Copying journal {} with skipped batches to {}",
+            path.display(),
+            salvage_path.display(),
+        );
+
+        {
+            let mut file = fs.create(&salvage_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+
+        let mut skipped_ranges = skipped_batches
+            .iter()
+            .filter(|batch| batch.path == path)
+            .map(|batch| batch.offset..(batch.offset + batch.len))
+            .collect::<Vec<_>>();
+        skipped_ranges.sort_by_key(|range| range.start);
+
+        // NOTE: The new journal is written next to the salvaged copy, so a crash
+        // never leaves a second active journal in the journals folder
+        let tmp_path = salvage_folder.join(format!("{file_name}.tmp"));
+
+        {
+            let mut file = fs.create(&tmp_path)?;
+            let mut pos = 0;
+
+            #[allow(clippy::cast_possible_truncation)]
+            for range in skipped_ranges {
+                if let Some(kept) = bytes.get(pos..range.start as usize) {
+                    file.write_all(kept)?;
+                }
+
+                pos = pos.max(range.end as usize);
+            }
+
+            if let Some(kept) = bytes.get(pos..) {
+                file.write_all(kept)?;
+            }
+            file.sync_all()?;
+        }
+
+        fs.sync_directory(salvage_folder)?;
+
+        log::info!(
+            "Rewriting journal {} without skipped batches",
+            path.display()
+        );
+
+        fs.rename(&tmp_path, &path)?;
+
+        // IMPORTANT: fsync folder on Unix
+        fs.sync_directory(folder)?;
+
+        writer.reopen()?;
+
+        for batch in skipped_batches
+            .iter_mut()
+            .filter(|batch| batch.path == path)
+        {
+            batch.path.clone_from(&salvage_path);
+        }
+
+        Ok(())
+    }
+
+    pub fn recover<P: AsRef<Path>>(
+        fs: Arc<dyn FileSystem>,
+        path: P,
+        read_only: bool,
+    ) -> crate::Result<RecoveryResult> {
+        recover_journals(fs, path, read_only)
     }
 }
 
@@ -105,7 +291,10 @@ impl Journal {
 #[allow(clippy::expect_used, clippy::unwrap_used)]
 mod tests {
     use super::*;
-    use crate::batch::item::Item as BatchItem;
+    use crate::{
+        batch::item::Item as BatchItem,
+        fs::{FaultInjectingFileSystem, MemoryFileSystem, OsFileSystem},
+    };
     use lsm_tree::{coding::Encode, ValueType};
     use marker::Marker;
     use std::io::Write;
@@ -120,7 +309,7 @@ mod tests {
         let next_path = dir.path().join("1");
 
         {
-            let journal = Journal::create_new(&path)?;
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
             let mut writer = journal.get_writer();
 
             writer.write_batch(
@@ -128,6 +317,8 @@ mod tests {
                     &BatchItem::new("default", *b"a", *b"a", ValueType::Value),
                     &BatchItem::new("default", *b"b", *b"b", ValueType::Value),
                 ],
+                &[],
+                &[],
                 0,
             )?;
             writer.rotate()?;
@@ -140,6 +331,49 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn journal_crash_drops_unsynced_batches() -> crate::Result<()> {
+        let fs = Arc::new(FaultInjectingFileSystem::new(Arc::new(
+            MemoryFileSystem::new(),
+        )));
+        let dir = Path::new("/journals");
+
+        {
+            let journal = Journal::create_new(fs.clone(), dir.join("0"))?;
+            let mut writer = journal.get_writer();
+
+            writer.write_batch(
+                &[&BatchItem::new("default", *b"a", *b"a", ValueType::Value)],
+                &[],
+                &[],
+                0,
+            )?;
+            writer.flush(PersistMode::SyncAll)?;
+
+            writer.write_batch(
+                &[&BatchItem::new("default", *b"b", *b"b", ValueType::Value)],
+                &[],
+                &[],
+                1,
+            )?;
+            writer.flush(PersistMode::Buffer)?;
+
+            fs.crash();
+        }
+
+        let journal_recovered = Journal::recover(fs, dir, false)?;
+        assert!(!journal_recovered.was_active_created);
+
+        let reader = journal_recovered
+            .active
+            .get_reader(RecoveryMode::default())?;
+        let collected = reader.flatten().collect::<Vec<_>>();
+        assert_eq!(1, collected.len());
+        assert_eq!(0, collected.first().unwrap().seqno);
+
+        Ok(())
+    }
+
     #[test]
     fn journal_recovery_active() -> crate::Result<()> {
         let dir = tempdir()?;
@@ -151,7 +385,7 @@ mod tests {
         let next_next_path = dir.path().join("2");
 
         {
-            let journal = Journal::create_new(&path)?;
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
             let mut writer = journal.get_writer();
 
             writer.write_batch(
@@ -159,6 +393,8 @@ mod tests {
                     &BatchItem::new("default", *b"a", *b"a", ValueType::Value),
                     &BatchItem::new("default", *b"b", *b"b", ValueType::Value),
                 ],
+                &[],
+                &[],
                 0,
             )?;
             writer.rotate()?;
@@ -168,6 +404,8 @@ mod tests {
                     &BatchItem::new("default2", *b"c", *b"c", ValueType::Value),
                     &BatchItem::new("default2", *b"d", *b"d", ValueType::Value),
                 ],
+                &[],
+                &[],
                 1,
             )?;
             writer.rotate()?;
@@ -177,6 +415,8 @@ mod tests {
                     &BatchItem::new("default3", *b"c", *b"c", ValueType::Value),
                     &BatchItem::new("default3", *b"d", *b"d", ValueType::Value),
                 ],
+                &[],
+                &[],
                 1,
             )?;
         }
@@ -186,7 +426,7 @@ mod tests {
         assert!(next_path_rotated.try_exists()?);
         assert!(next_next_path.try_exists()?);
 
-        let journal_recovered = Journal::recover(dir)?;
+        let journal_recovered = Journal::recover(Arc::new(OsFileSystem), dir, false)?;
         assert_eq!(journal_recovered.active.path(), next_next_path);
         assert_eq!(
             journal_recovered.sealed,
@@ -205,7 +445,7 @@ mod tests {
         let next_path = dir.path().join("1");
 
         {
-            let journal = Journal::create_new(&path)?;
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
 
             {
                 let mut writer = journal.get_writer();
@@ -215,6 +455,8 @@ mod tests {
                         &BatchItem::new("default", *b"a", *b"a", ValueType::Value),
                         &BatchItem::new("default", *b"b", *b"b", ValueType::Value),
                     ],
+                    &[],
+                    &[],
                     0,
                 )?;
                 writer.rotate()?;
@@ -227,7 +469,7 @@ mod tests {
         assert!(path_rotated.try_exists()?);
         assert!(!next_path.try_exists()?);
 
-        let journal_recovered = Journal::recover(dir)?;
+        let journal_recovered = Journal::recover(Arc::new(OsFileSystem), dir, false)?;
         assert_eq!(journal_recovered.active.path(), next_path);
         assert_eq!(journal_recovered.sealed, &[(0, path_rotated)]);
 
@@ -245,13 +487,13 @@ mod tests {
         ];
 
         {
-            let journal = Journal::create_new(&path)?;
-            journal.get_writer().write_batch(&values, 0)?;
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            journal.get_writer().write_batch(&values, &[], &[], 0)?;
         }
 
         {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -267,8 +509,8 @@ mod tests {
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -284,8 +526,8 @@ mod tests {
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -307,13 +549,13 @@ mod tests {
         ];
 
         {
-            let journal = Journal::create_new(&path)?;
-            journal.get_writer().write_batch(&values, 0)?;
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            journal.get_writer().write_batch(&values, &[], &[], 0)?;
         }
 
         {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -328,14 +570,15 @@ mod tests {
                 item_count: 2,
                 seqno: 64,
                 compression: lsm_tree::CompressionType::None,
+                encrypted: false,
             }
             .encode_into(&mut file)?;
             file.sync_all()?;
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -350,14 +593,15 @@ mod tests {
                 item_count: 2,
                 seqno: 64,
                 compression: lsm_tree::CompressionType::None,
+                encrypted: false,
             }
             .encode_into(&mut file)?;
             file.sync_all()?;
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -379,13 +623,13 @@ mod tests {
         ];
 
         {
-            let journal = Journal::create_new(&path)?;
-            journal.get_writer().write_batch(&values, 0)?;
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            journal.get_writer().write_batch(&values, &[], &[], 0)?;
         }
 
         {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -401,8 +645,8 @@ mod tests {
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -418,8 +662,8 @@ mod tests {
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -441,13 +685,13 @@ mod tests {
         ];
 
         {
-            let journal = Journal::create_new(&path)?;
-            journal.get_writer().write_batch(&values, 0)?;
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            journal.get_writer().write_batch(&values, &[], &[], 0)?;
         }
 
         {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -470,8 +714,8 @@ mod tests {
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -494,8 +738,8 @@ mod tests {
         }
 
         for _ in 0..10 {
-            let journal = Journal::from_file(&path)?;
-            let reader = journal.get_reader()?;
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::default())?;
             let collected = reader.flatten().collect::<Vec<_>>();
             assert_eq!(
                 values.into_iter().cloned().collect::<Vec<_>>(),
@@ -505,4 +749,434 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn journal_skip_invalid_batches() -> crate::Result<()> {
+        use crate::journal::error::RecoveryError;
+        use lsm_tree::coding::Decode;
+
+        let dir = tempdir()?;
+        let path = dir.path().join("0");
+
+        let batches = [
+            [&BatchItem::new(
+                "default",
+                *b"a",
+                *b"aaaaaaaa",
+                ValueType::Value,
+            )],
+            [&BatchItem::new(
+                "default",
+                *b"b",
+                *b"bbbbbbbb",
+                ValueType::Value,
+            )],
+            [&BatchItem::new(
+                "default",
+                *b"c",
+                *b"cccccccc",
+                ValueType::Value,
+            )],
+        ];
+
+        {
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            let mut writer = journal.get_writer();
+
+            for (seqno, items) in batches.iter().enumerate() {
+                writer.write_batch(items, &[], &[], seqno as u64)?;
+            }
+            writer.flush(PersistMode::SyncAll)?;
+        }
+
+        // Mangle the value of the second batch
+        let mut bytes = std::fs::read(&path)?;
+        let pos = bytes
+            .windows(8)
+            .position(|window| window == b"bbbbbbbb")
+            .unwrap();
+        *bytes.get_mut(pos).unwrap() = b'x';
+        std::fs::write(&path, &bytes)?;
+
+        {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let mut reader = journal.get_reader(RecoveryMode::default())?;
+            assert!(reader.next().unwrap().is_ok());
+            assert!(matches!(
+                reader.next(),
+                Some(Err(crate::Error::JournalRecovery(
+                    RecoveryError::ChecksumMismatch
+                )))
+            ));
+        }
+
+        {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let mut reader = journal.get_reader(RecoveryMode::SkipInvalidBatches)?;
+            let collected = (&mut reader).collect::<crate::Result<Vec<_>>>()?;
+
+            assert_eq!(2, collected.len());
+            assert_eq!(0, collected.first().unwrap().seqno);
+            assert_eq!(2, collected.get(1).unwrap().seqno);
+
+            assert_eq!(1, reader.skipped_batches.len());
+            let skipped = reader.skipped_batches.first().unwrap();
+            assert_eq!(path, skipped.path);
+            assert_eq!(RecoveryError::ChecksumMismatch, skipped.error);
+
+            // NOTE: The offset points to the start of the skipped batch
+            let mut bytes = bytes.get(skipped.offset as usize..).unwrap();
+            assert!(matches!(
+                Marker::decode_from(&mut bytes)?,
+                Marker::Start { seqno: 1, .. }
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn journal_skip_invalid_batches_resync() -> crate::Result<()> {
+        use crate::journal::{error::RecoveryError, marker::Tag};
+        use lsm_tree::coding::Decode;
+
+        let dir = tempdir()?;
+        let path = dir.path().join("0");
+
+        let batches = [
+            [&BatchItem::new(
+                "default",
+                *b"a",
+                *b"aaaaaaaa",
+                ValueType::Value,
+            )],
+            [&BatchItem::new(
+                "default",
+                *b"b",
+                *b"bbbbbbbb",
+                ValueType::Value,
+            )],
+            [&BatchItem::new(
+                "default",
+                *b"c",
+                *b"cccccccc",
+                ValueType::Value,
+            )],
+            [&BatchItem::new(
+                "default",
+                *b"d",
+                *b"dddddddd",
+                ValueType::Value,
+            )],
+        ];
+
+        {
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            let mut writer = journal.get_writer();
+
+            for (seqno, items) in batches.iter().enumerate() {
+                writer.write_batch(items, &[], &[], seqno as u64)?;
+            }
+            writer.flush(PersistMode::SyncAll)?;
+        }
+
+        // Mangle the item tag of the second batch, so it cannot be decoded anymore
+        let mut bytes = std::fs::read(&path)?;
+        let value_pos = bytes
+            .windows(8)
+            .position(|window| window == b"bbbbbbbb")
+            .unwrap();
+        let tag = bytes.get_mut(value_pos - 17).unwrap();
+        assert_eq!(u8::from(Tag::Item), *tag);
+        *tag = 0xFF;
+        std::fs::write(&path, &bytes)?;
+
+        // NOTE: The journal is not truncated, so recovering again has the same result
+        for _ in 0..2 {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let mut reader = journal.get_reader(RecoveryMode::SkipInvalidBatches)?;
+            let collected = (&mut reader).collect::<crate::Result<Vec<_>>>()?;
+
+            assert_eq!(
+                vec![0, 2, 3],
+                collected
+                    .iter()
+                    .map(|batch| batch.seqno)
+                    .collect::<Vec<_>>(),
+            );
+
+            assert_eq!(1, reader.skipped_batches.len());
+            let skipped = reader.skipped_batches.first().unwrap();
+            assert_eq!(RecoveryError::InvalidMarker, skipped.error);
+
+            // NOTE: The skipped range spans the second batch
+            let start = bytes.get(skipped.offset as usize..).unwrap();
+            assert!(matches!(
+                Marker::decode_from(&mut &start[..])?,
+                Marker::Start { seqno: 1, .. }
+            ));
+
+            let end = bytes
+                .get((skipped.offset + skipped.len) as usize..)
+                .unwrap();
+            assert!(matches!(
+                Marker::decode_from(&mut &end[..])?,
+                Marker::Start { seqno: 2, .. }
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn journal_skip_invalid_batches_orphan_markers() -> crate::Result<()> {
+        use crate::journal::error::RecoveryError;
+        use lsm_tree::coding::Decode;
+
+        let dir = tempdir()?;
+        let path = dir.path().join("0");
+
+        {
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            let mut writer = journal.get_writer();
+
+            for seqno in 0..2 {
+                writer.write_batch(
+                    &[&BatchItem::new("default", *b"a", *b"a", ValueType::Value)],
+                    &[],
+                    &[],
+                    seqno,
+                )?;
+            }
+            writer.flush(PersistMode::SyncAll)?;
+        }
+
+        // Insert an item and an end marker that do not belong to any batch
+        // between the two batches
+        let mut bytes = std::fs::read(&path)?;
+
+        let mut reader = &bytes[..];
+        let pos = loop {
+            let pos = bytes.len() - reader.len();
+
+            if let Marker::Start { seqno: 1, .. } = Marker::decode_from(&mut reader)? {
+                break pos;
+            }
+        };
+
+        let mut orphans = vec![];
+        Marker::Item {
+            partition: "default".into(),
+            key: (*b"b").into(),
+            value: (*b"b").into(),
+            value_type: ValueType::Value,
+        }
+        .encode_into(&mut orphans)?;
+        Marker::End(0).encode_into(&mut orphans)?;
+
+        bytes.splice(pos..pos, orphans.iter().copied());
+        std::fs::write(&path, &bytes)?;
+
+        let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+        let mut reader = journal.get_reader(RecoveryMode::SkipInvalidBatches)?;
+        let collected = (&mut reader).collect::<crate::Result<Vec<_>>>()?;
+
+        assert_eq!(
+            vec![0, 1],
+            collected
+                .iter()
+                .map(|batch| batch.seqno)
+                .collect::<Vec<_>>(),
+        );
+
+        // NOTE: Both markers are recorded as a single skipped range
+        assert_eq!(
+            vec![SkippedBatch {
+                path: path.clone(),
+                offset: pos as u64,
+                len: orphans.len() as u64,
+                error: RecoveryError::UnexpectedMarker,
+            }],
+            reader.skipped_batches,
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn journal_absolute_consistency() -> crate::Result<()> {
+        use crate::journal::error::RecoveryError;
+
+        let dir = tempdir()?;
+        let path = dir.path().join("0");
+
+        let values = [&BatchItem::new(
+            "default",
+            *b"abc",
+            *b"def",
+            ValueType::Value,
+        )];
+
+        {
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            journal.get_writer().write_batch(&values, &[], &[], 0)?;
+        }
+
+        // NOTE: Preallocated space is not an error
+        for _ in 0..2 {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::AbsoluteConsistency)?;
+            let collected = reader.collect::<crate::Result<Vec<_>>>()?;
+            assert_eq!(1, collected.len());
+        }
+
+        // Unterminated batch
+        {
+            let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+            Marker::Start {
+                item_count: 1,
+                seqno: 1,
+                compression: lsm_tree::CompressionType::None,
+                encrypted: false,
+            }
+            .encode_into(&mut file)?;
+            file.sync_all()?;
+        }
+
+        for _ in 0..2 {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::AbsoluteConsistency)?;
+            assert!(matches!(
+                reader.collect::<crate::Result<Vec<_>>>(),
+                Err(crate::Error::JournalRecovery(
+                    RecoveryError::MissingTerminator
+                ))
+            ));
+        }
+
+        {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let mut reader = journal.get_reader(RecoveryMode::default())?;
+            assert_eq!(1, (&mut reader).flatten().count());
+            assert_eq!(
+                RecoveryError::MissingTerminator,
+                reader.skipped_batches.first().unwrap().error
+            );
+        }
+
+        // Corrupt bytes
+        {
+            let mut file = std::fs::OpenOptions::new().append(true).open(&path)?;
+            file.write_all(b"09pmu35w3a9mp53bao9upw3ab5up")?;
+            file.sync_all()?;
+        }
+
+        for _ in 0..2 {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::AbsoluteConsistency)?;
+            assert!(matches!(
+                reader.collect::<crate::Result<Vec<_>>>(),
+                Err(crate::Error::JournalRecovery(RecoveryError::InvalidMarker))
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "lz4")]
+    fn journal_compression() -> crate::Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("0");
+
+        let large_value = "a".repeat(10_000);
+
+        let small = [&BatchItem::new("default", *b"a", *b"a", ValueType::Value)];
+        let large = [
+            &BatchItem::new("default", *b"b", large_value.as_bytes(), ValueType::Value),
+            &BatchItem::new("default", *b"c", large_value.as_bytes(), ValueType::Value),
+        ];
+
+        {
+            let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+            let mut writer = journal.get_writer();
+            writer.set_compression(lsm_tree::CompressionType::Lz4, 1_024);
+
+            let small_size = writer.write_batch(&small, &[], &[], 0)?;
+            let large_size = writer.write_batch(&large, &[], &[], 1)?;
+            assert!(small_size < 1_024);
+            assert!(large_size < large_value.len());
+
+            writer.flush(PersistMode::SyncAll)?;
+        }
+
+        for _ in 0..2 {
+            let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+            let reader = journal.get_reader(RecoveryMode::AbsoluteConsistency)?;
+            let collected = reader.collect::<crate::Result<Vec<_>>>()?;
+
+            assert_eq!(2, collected.len());
+            assert_eq!(
+                small.into_iter().cloned().collect::<Vec<_>>(),
+                collected.first().unwrap().items
+            );
+            assert_eq!(
+                large.into_iter().cloned().collect::<Vec<_>>(),
+                collected.get(1).unwrap().items
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn journal_group_commit() -> crate::Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("0");
+
+        let journal = Journal::create_new(Arc::new(OsFileSystem), &path)?;
+
+        std::thread::scope(|scope| {
+            let threads = (0..8u8)
+                .map(|thread| {
+                    let journal = &journal;
+
+                    scope.spawn(move || -> crate::Result<()> {
+                        for i in 0..10u8 {
+                            let key = [thread, i];
+                            let item = BatchItem::new("default", key, key, ValueType::Value);
+
+                            let batch = {
+                                let mut writer = journal.get_writer();
+                                writer.write_batch(&[&item], &[], &[], u64::from(i))?;
+                                writer.batch_count()
+                            };
+
+                            let mode = if i % 2 == 0 {
+                                PersistMode::SyncData
+                            } else {
+                                PersistMode::SyncAll
+                            };
+                            journal.sync_batch(batch, mode)?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            threads
+                .into_iter()
+                .try_for_each(|thread| thread.join().expect("thread should not panic"))
+        })?;
+
+        assert_eq!(80, journal.get_writer().batch_count());
+        drop(journal);
+
+        let journal = Journal::from_file(Arc::new(OsFileSystem), &path)?;
+        let reader = journal.get_reader(RecoveryMode::AbsoluteConsistency)?;
+        assert_eq!(80, reader.count());
+
+        Ok(())
+    }
 }
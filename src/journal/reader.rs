@@ -2,12 +2,23 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use super::marker::Marker;
-use lsm_tree::{coding::Decode, DecodeError};
+use super::{
+    compression::decompress,
+    marker::{Marker, Tag},
+};
+use crate::{
+    encryption::JournalEncryptionProvider,
+    fs::{FileHandle, FileSystem, OpenMode, OsFileSystem},
+    RecoveryError,
+};
+use byteorder::{BigEndian, ReadBytesExt};
+use lsm_tree::{coding::Decode, CompressionType, DecodeError};
 use std::{
-    fs::{File, OpenOptions},
-    io::{BufReader, Seek},
+    collections::VecDeque,
+    io::{BufRead, BufReader, Read, Seek, SeekFrom},
+    ops::Range,
     path::{Path, PathBuf},
+    sync::Arc,
 };
 
 macro_rules! fail_iter {
@@ -24,24 +35,101 @@ macro_rules! fail_iter {
 ///
 /// Will truncate the file to the last valid position to prevent corrupt
 /// bytes at the end of the file, which would jeopardize future writes into the file.
-#[allow(clippy::module_name_repetitions)]
+///
+/// If `verify_tail` is set, only preallocated (zeroed) space is truncated,
+/// and any other bytes fail the read instead.
+///
+/// If `resync` is set, invalid bytes are skipped by scanning forward to the next batch,
+/// and only preallocated (zeroed) space is truncated.
+#[allow(clippy::module_name_repetitions, clippy::struct_excessive_bools)]
 pub struct JournalReader {
     pub(crate) path: PathBuf,
-    pub(crate) reader: BufReader<File>,
+    pub(crate) reader: BufReader<Box<dyn FileHandle>>,
     pub(crate) last_valid_pos: u64,
+
+    /// If `true`, only zeroes (preallocated space) may follow
+    /// the last valid entry, otherwise reading fails
+    pub(crate) verify_tail: bool,
+
+    /// Entries of a compressed or encrypted batch that have not been emitted yet
+    pending: VecDeque<Marker>,
+
+    /// Decrypts encrypted batches
+    pub(crate) encryption: Option<Arc<dyn JournalEncryptionProvider>>,
+
+    /// If `true`, the file is never truncated
+    pub(crate) read_only: bool,
+
+    /// If `true`, payloads of compressed or encrypted batches are skipped
+    /// instead of being unpacked, so only the batch markers are emitted
+    pub(crate) skip_payloads: bool,
+
+    /// If `true`, invalid bytes are skipped, and reading continues at the next batch
+    pub(crate) resync: bool,
+
+    /// Bytes that were skipped by the last resync, see [`JournalReader::take_skipped`]
+    skipped: Option<Range<u64>>,
 }
 
 impl JournalReader {
-    pub fn new<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
-        let file = OpenOptions::new().read(true).write(true).open(&path)?;
+    /// Opens a journal file without write access, so it is never truncated.
+    pub fn new_read_only<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+        Self::open_in(&OsFileSystem, path, true)
+    }
+
+    /// Opens a journal file in the given file system.
+    ///
+    /// If `read_only` is `true`, the file is opened without write access, so it is never truncated.
+    pub fn open_in<P: AsRef<Path>>(
+        fs: &dyn FileSystem,
+        path: P,
+        read_only: bool,
+    ) -> crate::Result<Self> {
+        let mode = if read_only {
+            OpenMode::Read
+        } else {
+            OpenMode::ReadWrite
+        };
+        let file = fs.open(path.as_ref(), mode)?;
 
         Ok(Self {
             path: path.as_ref().into(),
             reader: BufReader::new(file),
             last_valid_pos: 0,
+            verify_tail: false,
+            pending: VecDeque::new(),
+            encryption: None,
+            read_only,
+            skip_payloads: false,
+            resync: false,
+            skipped: None,
         })
     }
 
+    /// Sets the encryption provider to decrypt encrypted batches with.
+    pub fn with_encryption(
+        mut self,
+        encryption: Option<Arc<dyn JournalEncryptionProvider>>,
+    ) -> Self {
+        self.encryption = encryption;
+        self
+    }
+
+    /// Continues reading at the given byte offset, which needs to be the start of a marker.
+    pub(crate) fn seek_to(&mut self, pos: u64) -> crate::Result<()> {
+        self.reader.seek(SeekFrom::Start(pos))?;
+        self.last_valid_pos = pos;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Returns the bytes that were skipped since the last call, because they could not be decoded.
+    ///
+    /// Only set if `resync` is enabled.
+    pub(crate) fn take_skipped(&mut self) -> Option<Range<u64>> {
+        self.skipped.take()
+    }
+
     fn truncate_file(&mut self, pos: u64) -> crate::Result<()> {
         log::debug!("truncating journal to {pos}");
         self.reader.get_mut().set_len(pos)?;
@@ -49,7 +137,207 @@ impl JournalReader {
         Ok(())
     }
 
+    /// Reads the next marker.
+    ///
+    /// If it starts a compressed or encrypted batch, the batch's payload is read as well.
+    fn read_marker(&mut self) -> Result<(Marker, Option<Vec<u8>>), DecodeError> {
+        let marker = Marker::decode_from(&mut self.reader)?;
+
+        if let Marker::Start {
+            compression,
+            encrypted,
+            ..
+        } = marker
+        {
+            if compression != CompressionType::None || encrypted {
+                let len = self.reader.read_u32::<BigEndian>()?;
+
+                let mut bytes = vec![];
+                (&mut self.reader)
+                    .take(len.into())
+                    .read_to_end(&mut bytes)?;
+
+                if bytes.len() as u64 != u64::from(len) {
+                    return Err(DecodeError::Io(std::io::ErrorKind::UnexpectedEof.into()));
+                }
+
+                return Ok((marker, Some(bytes)));
+            }
+        }
+
+        Ok((marker, None))
+    }
+
+    /// Unpacks the payload of a compressed or encrypted batch,
+    /// so its entries are emitted after the start marker.
+    fn unpack_payload(&mut self, marker: &Marker, payload: Vec<u8>) -> crate::Result<()> {
+        let Marker::Start {
+            compression,
+            encrypted,
+            ..
+        } = *marker
+        else {
+            return Ok(());
+        };
+
+        let payload = if encrypted {
+            let Some(encryption) = &self.encryption else {
+                log::error!(
+                    "Journal contains an encrypted batch, but no encryption provider is configured"
+                );
+                return Err(crate::Error::Encryption);
+            };
+
+            // NOTE: Batches that cannot be decrypted are not treated as corrupt,
+            // because the key may just not be available
+            encryption.decrypt(&payload)?
+        } else {
+            payload
+        };
+
+        self.pending = decode_payload_entries(compression, &payload);
+
+        Ok(())
+    }
+
+    /// Returns `true` if a structurally valid batch (start marker, entries and end marker) starts at `pos`.
+    ///
+    /// The checksum is not checked, so the batch may still be invalid.
+    fn is_batch_at(&mut self, pos: u64) -> crate::Result<bool> {
+        self.reader.seek(SeekFrom::Start(pos))?;
+
+        let Ok((Marker::Start { item_count, .. }, payload)) = self.read_marker() else {
+            return Ok(false);
+        };
+
+        // NOTE: Entries of compressed or encrypted batches are part of the payload
+        if payload.is_none() {
+            for _ in 0..item_count {
+                match Marker::decode_from(&mut self.reader) {
+                    Ok(Marker::Start { .. } | Marker::End(_)) | Err(_) => return Ok(false),
+                    Ok(_) => {}
+                }
+            }
+        }
+
+        Ok(matches!(
+            Marker::decode_from(&mut self.reader),
+            Ok(Marker::End(_))
+        ))
+    }
+
+    /// Scans forward from `pos` to the start of the next structurally valid batch.
+    fn find_next_batch(&mut self, mut pos: u64) -> crate::Result<Option<u64>> {
+        let start_tags = [Tag::Start.into(), Tag::EncryptedStart.into()];
+
+        loop {
+            self.reader.seek(SeekFrom::Start(pos))?;
+
+            let buf = self.reader.fill_buf()?;
+
+            if buf.is_empty() {
+                return Ok(None);
+            }
+
+            let Some(idx) = buf.iter().position(|byte| start_tags.contains(byte)) else {
+                pos += buf.len() as u64;
+                continue;
+            };
+
+            let candidate = pos + idx as u64;
+
+            if self.is_batch_at(candidate)? {
+                return Ok(Some(candidate));
+            }
+
+            pos = candidate + 1;
+        }
+    }
+
+    /// Skips the invalid bytes at the last valid position.
+    ///
+    /// Returns `false` if there is no batch after them.
+    fn resync(&mut self) -> crate::Result<bool> {
+        let invalid_pos = self.last_valid_pos;
+
+        if let Some(pos) = self.find_next_batch(invalid_pos + 1)? {
+            log::warn!(
+                "Skipping invalid bytes at {}:{invalid_pos}..{pos}",
+                self.path.display(),
+            );
+
+            self.seek_to(pos)?;
+            self.add_skipped(invalid_pos..pos);
+
+            return Ok(true);
+        }
+
+        if self.is_tail_zeroed()? {
+            if !self.read_only {
+                self.truncate_file(invalid_pos)?;
+            }
+            return Ok(false);
+        }
+
+        // NOTE: The bytes are kept, so they can still be salvaged
+        let len = self.reader.get_ref().size()?;
+
+        log::warn!(
+            "Skipping invalid bytes at {}:{invalid_pos}..{len}",
+            self.path.display(),
+        );
+
+        self.add_skipped(invalid_pos..len);
+
+        Ok(false)
+    }
+
+    /// Marks the given bytes as skipped, merging them with bytes
+    /// that were skipped directly before (and not taken yet).
+    fn add_skipped(&mut self, range: Range<u64>) {
+        let start = self
+            .skipped
+            .take()
+            .map_or(range.start, |skipped| skipped.start);
+
+        self.skipped = Some(start..range.end);
+    }
+
+    /// Returns `true` if there are only zeroes after the last valid position.
+    fn is_tail_zeroed(&mut self) -> crate::Result<bool> {
+        self.reader.seek(SeekFrom::Start(self.last_valid_pos))?;
+
+        loop {
+            let buf = self.reader.fill_buf()?;
+
+            if buf.is_empty() {
+                return Ok(true);
+            }
+
+            if buf.iter().any(|&byte| byte != 0) {
+                return Ok(false);
+            }
+
+            let len = buf.len();
+            self.reader.consume(len);
+        }
+    }
+
     fn maybe_truncate_file_to_last_valid_pos(&mut self) -> crate::Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+
+        if self.verify_tail && !self.is_tail_zeroed()? {
+            log::error!(
+                "Journal recovery failed at {}:{}: {:?}",
+                self.path.display(),
+                self.last_valid_pos,
+                RecoveryError::InvalidMarker,
+            );
+            return Err(crate::Error::JournalRecovery(RecoveryError::InvalidMarker));
+        }
+
         let stream_pos = self.reader.stream_position()?;
 
         if stream_pos > self.last_valid_pos {
@@ -60,29 +348,102 @@ impl JournalReader {
     }
 }
 
+/// Decodes the entries of a (decrypted) batch payload.
+///
+/// If the batch is corrupt, the entries that could be decoded are returned,
+/// so the batch reader will notice the batch is incomplete.
+fn decode_payload_entries(compression: CompressionType, bytes: &[u8]) -> VecDeque<Marker> {
+    let mut entries = VecDeque::new();
+
+    let Some(bytes) = decompress(compression, bytes) else {
+        log::error!("Invalid batch: could not decompress batch entries");
+        return entries;
+    };
+
+    let mut reader = &bytes[..];
+
+    while !reader.is_empty() {
+        match Marker::decode_from(&mut reader) {
+            Ok(marker) => entries.push_back(marker),
+            Err(e) => {
+                log::error!("Invalid batch: could not decode batch entry: {e:?}");
+                break;
+            }
+        }
+    }
+
+    entries
+}
+
 impl Iterator for JournalReader {
     type Item = crate::Result<Marker>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        match Marker::decode_from(&mut self.reader) {
-            Ok(item) => {
-                self.last_valid_pos = fail_iter!(self.reader.stream_position());
-                Some(Ok(item))
-            }
-            Err(e) => {
-                if let DecodeError::Io(e) = e {
-                    match e.kind() {
-                        std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::Other => {
-                            fail_iter!(self.maybe_truncate_file_to_last_valid_pos());
-                            None
+        if let Some(marker) = self.pending.pop_front() {
+            return Some(Ok(marker));
+        }
+
+        loop {
+            return match self.read_marker() {
+                Ok((item, payload)) => {
+                    self.last_valid_pos = fail_iter!(self.reader.stream_position());
+
+                    let is_plaintext = matches!(
+                        item,
+                        Marker::Start {
+                            encrypted: false,
+                            ..
                         }
-                        _ => Some(Err(crate::Error::Io(e))),
+                    );
+
+                    // NOTE: With encryption, every batch is encrypted
+                    if is_plaintext && self.encryption.is_some() {
+                        log::error!(
+                            "Journal contains an unencrypted batch, but an encryption provider is configured"
+                        );
+                        return Some(Err(crate::Error::Encryption));
                     }
-                } else {
-                    fail_iter!(self.maybe_truncate_file_to_last_valid_pos());
-                    None
+
+                    if let Some(payload) = payload.filter(|_| !self.skip_payloads) {
+                        fail_iter!(self.unpack_payload(&item, payload));
+                    }
+
+                    Some(Ok(item))
                 }
-            }
+                Err(e) => {
+                    let is_io_error = matches!(&e, DecodeError::Io(e) if !matches!(
+                        e.kind(),
+                        std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::Other
+                    ));
+                    let is_unsupported_compression =
+                        matches!(e, DecodeError::InvalidTag(("CompressionType", 1 | 2)));
+
+                    if self.resync && !is_io_error && !is_unsupported_compression {
+                        if fail_iter!(self.resync()) {
+                            continue;
+                        }
+                        return None;
+                    }
+
+                    if let DecodeError::Io(e) = e {
+                        match e.kind() {
+                            std::io::ErrorKind::UnexpectedEof | std::io::ErrorKind::Other => {
+                                fail_iter!(self.maybe_truncate_file_to_last_valid_pos());
+                                None
+                            }
+                            _ => Some(Err(crate::Error::Io(e))),
+                        }
+                    } else if let DecodeError::InvalidTag(("CompressionType", 1 | 2)) = e {
+                        // NOTE: Don't truncate the journal, the batch is most likely
+                        // valid, but its compression is not enabled in the crate features
+                        log::error!("Journal contains a batch with unsupported compression: {e:?}");
+                        Some(Err(crate::Error::Decode(e)))
+                    } else {
+                        fail_iter!(self.maybe_truncate_file_to_last_valid_pos());
+                        None
+                    }
+                }
+            };
         }
     }
 }
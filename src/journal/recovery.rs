@@ -1,5 +1,9 @@
 use super::Journal;
-use std::path::{Path, PathBuf};
+use crate::fs::FileSystem;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 pub type JournalId = u64;
 
@@ -11,7 +15,17 @@ pub struct RecoveryResult {
     pub(crate) was_active_created: bool,
 }
 
-pub fn recover_journals<P: AsRef<Path>>(path: P) -> crate::Result<RecoveryResult> {
+/// Recovers the journals in the given folder.
+///
+/// If `read_only` is `true`, the active journal is opened without write access.
+/// If there is no active journal, the newest sealed journal is used instead of
+/// creating a new one.
+#[allow(clippy::expect_used)]
+pub fn recover_journals<P: AsRef<Path>>(
+    fs: Arc<dyn FileSystem>,
+    path: P,
+    read_only: bool,
+) -> crate::Result<RecoveryResult> {
     let path = path.as_ref();
 
     let mut sealed = vec![];
@@ -19,13 +33,8 @@ pub fn recover_journals<P: AsRef<Path>>(path: P) -> crate::Result<RecoveryResult
     let mut max_journal_id: JournalId = 0;
     let mut was_active_created = false;
 
-    for dirent in std::fs::read_dir(path)? {
-        let dirent = dirent?;
-        let path = dirent.path();
-
-        assert!(dirent.file_type()?.is_file());
-
-        let filename = dirent.file_name();
+    for path in fs.read_dir(path)? {
+        let filename = path.file_name().expect("should have file name");
         let filename = filename.to_str().expect("should be utf-8");
         let is_sealed = filename.ends_with(".sealed");
 
@@ -49,14 +58,30 @@ pub fn recover_journals<P: AsRef<Path>>(path: P) -> crate::Result<RecoveryResult
 
     sealed.sort_by(|(a, _), (b, _)| a.cmp(b));
 
-    let active = active.map_or_else(
-        || {
+    let active = if read_only {
+        if let Some(active) = active {
+            Journal::open_read_only(fs, active)?
+        } else {
+            // NOTE: The sealed journal is only used as a placeholder, it is never written to
+            // and its batches are recovered as sealed memtables
+            let Some((_, newest_sealed)) = sealed.last() else {
+                log::error!(
+                    "Cannot open journal at {} read-only, because there is none",
+                    path.display()
+                );
+                return Err(crate::Error::Io(std::io::ErrorKind::NotFound.into()));
+            };
+
             was_active_created = true;
-            let id: JournalId = max_journal_id + 1;
-            Journal::create_new(path.join(id.to_string()))
-        },
-        Journal::from_file,
-    )?;
+            Journal::open_read_only(fs, newest_sealed)?
+        }
+    } else if let Some(active) = active {
+        Journal::from_file(fs, active)?
+    } else {
+        was_active_created = true;
+        let id: JournalId = max_journal_id + 1;
+        Journal::create_new(fs, path.join(id.to_string()))?
+    };
 
     Ok(RecoveryResult {
         active,
@@ -2,14 +2,30 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use super::marker::{serialize_marker_item, Marker};
-use crate::{batch::item::Item as BatchItem, file::fsync_directory, journal::recovery::JournalId};
-use lsm_tree::{coding::Encode, EncodeError, SeqNo, ValueType};
+use super::{
+    compression::compress,
+    marker::{
+        serialize_marker_expiry, serialize_marker_item, serialize_marker_merge,
+        serialize_marker_range_tombstone, Marker,
+    },
+};
+use crate::{
+    batch::{
+        item::{ExpiryItem, Item as BatchItem, MergeItem, RangeTombstoneItem},
+        PartitionKey,
+    },
+    encryption::JournalEncryptionProvider,
+    fs::{FileHandle, FileSystem, OpenMode},
+    journal::recovery::JournalId,
+    subscription::{ChangeKind, Subscribers},
+};
+use byteorder::{BigEndian, WriteBytesExt};
+use lsm_tree::{coding::Encode, CompressionType, EncodeError, SeqNo, ValueType};
 use std::{
-    fs::{rename, File, OpenOptions},
     hash::Hasher,
-    io::{BufWriter, Write},
+    io::{BufWriter, Seek, Write},
     path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
 };
 
 pub const PRE_ALLOCATED_BYTES: u64 = 16 * 1_024 * 1_024;
@@ -17,8 +33,26 @@ pub const JOURNAL_BUFFER_BYTES: usize = 8 * 1_024;
 
 pub struct Writer {
     pub(crate) path: PathBuf,
-    file: BufWriter<File>,
+    pub(crate) fs: Arc<dyn FileSystem>,
+    file: BufWriter<Box<dyn FileHandle>>,
     buf: Vec<u8>,
+
+    /// Compression of batches
+    compression: CompressionType,
+
+    /// Batches smaller than this are not compressed
+    compression_threshold: usize,
+
+    /// Encryption of batches
+    pub(crate) encryption: Option<Arc<dyn JournalEncryptionProvider>>,
+
+    /// Number of batches written, used to track which batches are durable
+    batch_count: u64,
+
+    /// Subscribers of committed writes
+    ///
+    /// Kept here, so batches are published in the order they are written to the journal.
+    pub(crate) subscribers: Subscribers,
 }
 
 /// The persist mode allows setting the durability guarantee of previous writes
@@ -42,7 +76,7 @@ pub enum PersistMode {
 
 impl Writer {
     pub fn len(&self) -> crate::Result<u64> {
-        Ok(self.file.get_ref().metadata()?.len())
+        Ok(self.file.get_ref().size()?)
     }
 
     pub fn rotate(&mut self) -> crate::Result<(PathBuf, PathBuf)> {
@@ -66,54 +100,147 @@ impl Writer {
             .expect("should be valid journal ID");
 
         let sealed_path = folder.join(format!("{journal_id}.sealed"));
-        rename(&self.path, &sealed_path)?;
+        self.fs.rename(&self.path, &sealed_path)?;
 
         let new_path = folder.join((journal_id + 1).to_string());
         log::debug!("Rotating active journal to {new_path:?}");
-        *self = Self::create_new(&new_path)?;
+        let (compression, compression_threshold) = (self.compression, self.compression_threshold);
+        let encryption = self.encryption.clone();
+        let batch_count = self.batch_count;
+        let subscribers = std::mem::take(&mut self.subscribers);
+        *self = Self::create_new(self.fs.clone(), &new_path)?;
+        self.set_compression(compression, compression_threshold);
+        self.encryption = encryption;
+        self.batch_count = batch_count;
+        self.subscribers = subscribers;
 
         // IMPORTANT: fsync folder on Unix
-        fsync_directory(&folder)?;
+        self.fs.sync_directory(&folder)?;
 
         Ok((sealed_path, new_path))
     }
 
-    pub fn create_new<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+    /// Reopens the journal file, e.g. after it has been replaced.
+    pub fn reopen(&mut self) -> crate::Result<()> {
+        let file = self.fs.open(&self.path, OpenMode::Append)?;
+        self.file = BufWriter::with_capacity(JOURNAL_BUFFER_BYTES, file);
+        Ok(())
+    }
+
+    pub fn create_new<P: AsRef<Path>>(fs: Arc<dyn FileSystem>, path: P) -> crate::Result<Self> {
         let path = path.as_ref();
-        let file = File::create(path)?;
+        let file = fs.create(path)?;
         file.set_len(PRE_ALLOCATED_BYTES)?;
         file.sync_all()?;
 
         Ok(Self {
             path: path.into(),
+            fs,
             file: BufWriter::new(file),
             buf: Vec::new(),
+            compression: CompressionType::None,
+            compression_threshold: 0,
+            encryption: None,
+            batch_count: 0,
+            subscribers: Subscribers::default(),
         })
     }
 
-    pub fn from_file<P: AsRef<Path>>(path: P) -> crate::Result<Self> {
+    pub fn from_file<P: AsRef<Path>>(fs: Arc<dyn FileSystem>, path: P) -> crate::Result<Self> {
         let path = path.as_ref();
 
-        if !path.try_exists()? {
-            let file = OpenOptions::new().create_new(true).write(true).open(path)?;
+        if !fs.exists(path)? {
+            let file = fs.create_new(path)?;
             file.set_len(PRE_ALLOCATED_BYTES)?;
 
             return Ok(Self {
                 path: path.into(),
+                fs,
                 file: BufWriter::with_capacity(JOURNAL_BUFFER_BYTES, file),
                 buf: Vec::new(),
+                compression: CompressionType::None,
+                compression_threshold: 0,
+                encryption: None,
+                batch_count: 0,
+                subscribers: Subscribers::default(),
             });
         }
 
-        let file = OpenOptions::new().append(true).open(path)?;
+        let file = fs.open(path, OpenMode::Append)?;
 
         Ok(Self {
             path: path.into(),
+            fs,
             file: BufWriter::with_capacity(JOURNAL_BUFFER_BYTES, file),
             buf: Vec::new(),
+            compression: CompressionType::None,
+            compression_threshold: 0,
+            encryption: None,
+            batch_count: 0,
+            subscribers: Subscribers::default(),
         })
     }
 
+    /// Opens an existing journal file without write access.
+    pub fn open_read_only<P: AsRef<Path>>(fs: Arc<dyn FileSystem>, path: P) -> crate::Result<Self> {
+        let path = path.as_ref();
+        let file = fs.open(path, OpenMode::Read)?;
+
+        Ok(Self {
+            path: path.into(),
+            fs,
+            file: BufWriter::new(file),
+            buf: Vec::new(),
+            compression: CompressionType::None,
+            compression_threshold: 0,
+            encryption: None,
+            batch_count: 0,
+            subscribers: Subscribers::default(),
+        })
+    }
+
+    /// Sets the compression of batches whose payload is at least `threshold` bytes.
+    pub fn set_compression(&mut self, compression: CompressionType, threshold: usize) {
+        self.compression = compression;
+        self.compression_threshold = threshold;
+    }
+
+    /// Returns the number of batches written so far.
+    pub(crate) fn batch_count(&self) -> u64 {
+        self.batch_count
+    }
+
+    /// Flushes buffered batches to the OS, and returns a handle to sync them,
+    /// as well as the number of batches written so far.
+    fn prepare_sync(&mut self) -> std::io::Result<(u64, Box<dyn FileHandle>)> {
+        self.file.flush()?;
+        Ok((self.batch_count, self.file.get_ref().try_clone()?))
+    }
+
+    /// Publishes a committed single item write to subscribers.
+    pub(crate) fn publish_item(
+        &mut self,
+        partition: &PartitionKey,
+        key: &[u8],
+        value: &[u8],
+        kind: ChangeKind,
+        seqno: SeqNo,
+    ) {
+        if !self.subscribers.is_empty() {
+            self.subscribers.publish(
+                seqno,
+                vec![(partition.clone(), key.into(), value.into(), kind)],
+            );
+        }
+    }
+
+    /// Flushes buffered batches to the OS, and returns the byte offset
+    /// after the last batch written so far.
+    pub(crate) fn flushed_len(&mut self) -> std::io::Result<u64> {
+        self.file.flush()?;
+        self.file.get_mut().stream_position()
+    }
+
     /// Flushes the journal file.
     pub(crate) fn flush(&mut self, mode: PersistMode) -> std::io::Result<()> {
         log::trace!("Flush journal {:?} with mode={mode:?}", self.path);
@@ -128,30 +255,93 @@ impl Writer {
     }
 
     /// Writes a batch start marker to the journal
-    fn write_start(&mut self, item_count: u32, seqno: SeqNo) -> Result<usize, EncodeError> {
-        debug_assert!(self.buf.is_empty());
-
-        Marker::Start {
+    fn write_start(
+        &mut self,
+        item_count: u32,
+        seqno: SeqNo,
+        compression: CompressionType,
+        encrypted: bool,
+    ) -> Result<usize, EncodeError> {
+        let bytes = Marker::Start {
             item_count,
             seqno,
-            compression: lsm_tree::CompressionType::None,
+            compression,
+            encrypted,
         }
-        .encode_into(&mut self.buf)?;
+        .encode_into_vec()?;
 
-        self.file.write_all(&self.buf)?;
+        self.file.write_all(&bytes)?;
 
-        Ok(self.buf.len())
+        Ok(bytes.len())
     }
 
     /// Writes a batch end marker to the journal
     fn write_end(&mut self, checksum: u64) -> Result<usize, EncodeError> {
-        debug_assert!(self.buf.is_empty());
+        let bytes = Marker::End(checksum).encode_into_vec()?;
+
+        self.file.write_all(&bytes)?;
+
+        Ok(bytes.len())
+    }
+
+    /// Writes the serialized entries in the buffer as a batch to the journal.
+    ///
+    /// If the entries are large enough, they are compressed. Compressed or encrypted
+    /// entries are written after the start marker, prefixed by their length in bytes.
+    ///
+    /// The checksum is always computed over the plain entries.
+    fn write_buffered_batch(&mut self, item_count: u32, seqno: SeqNo) -> crate::Result<usize> {
+        let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+        hasher.update(&self.buf);
+        let checksum = hasher.finish();
+
+        let mut compression = CompressionType::None;
+        let mut payload = None;
+
+        if self.compression != CompressionType::None && self.buf.len() >= self.compression_threshold
+        {
+            let compressed = compress(self.compression, &self.buf);
+
+            // NOTE: Only keep compressed payload if it's actually smaller
+            if compressed.len() < self.buf.len() {
+                compression = self.compression;
+                payload = Some(compressed);
+            }
+        }
+
+        if let Some(encryption) = &self.encryption {
+            payload = Some(encryption.encrypt(payload.as_deref().unwrap_or(&self.buf))?);
+        }
+
+        let mut byte_count = 0;
+
+        if let Some(payload) = payload {
+            let Ok(len) = u32::try_from(payload.len()) else {
+                return Err(crate::Error::Encode(EncodeError::Io(
+                    std::io::ErrorKind::InvalidInput.into(),
+                )));
+            };
+
+            byte_count +=
+                self.write_start(item_count, seqno, compression, self.encryption.is_some())?;
+
+            self.file.write_u32::<BigEndian>(len)?;
+            self.file.write_all(&payload)?;
+            byte_count += std::mem::size_of::<u32>() + payload.len();
+        } else {
+            byte_count += self.write_start(item_count, seqno, CompressionType::None, false)?;
+
+            self.file.write_all(&self.buf)?;
+            byte_count += self.buf.len();
+        }
 
-        Marker::End(checksum).encode_into(&mut self.buf)?;
+        self.buf.clear();
+
+        byte_count += self.write_end(checksum)?;
 
-        self.file.write_all(&self.buf)?;
+        self.batch_count += 1;
 
-        Ok(self.buf.len())
+        Ok(byte_count)
     }
 
     pub(crate) fn write_raw(
@@ -162,29 +352,47 @@ impl Writer {
         value_type: ValueType,
         seqno: u64,
     ) -> crate::Result<usize> {
-        let mut hasher = xxhash_rust::xxh3::Xxh3::new();
-        let mut byte_count = 0;
-
-        self.buf.clear();
-        byte_count += self.write_start(1, seqno)?;
         self.buf.clear();
 
         serialize_marker_item(&mut self.buf, partition, key, value, value_type)?;
 
-        self.file.write_all(&self.buf)?;
-
-        hasher.update(&self.buf);
-        byte_count += self.buf.len();
+        self.write_buffered_batch(1, seqno)
+    }
 
+    pub(crate) fn write_merge(
+        &mut self,
+        partition: &str,
+        key: &[u8],
+        operand: &[u8],
+        seqno: SeqNo,
+    ) -> crate::Result<usize> {
         self.buf.clear();
-        let checksum = hasher.finish();
-        byte_count += self.write_end(checksum)?;
 
-        Ok(byte_count)
+        serialize_marker_merge(&mut self.buf, partition, key, operand)?;
+
+        self.write_buffered_batch(1, seqno)
+    }
+
+    pub fn write_batch(
+        &mut self,
+        items: &[&BatchItem],
+        range_tombstones: &[RangeTombstoneItem],
+        expiries: &[ExpiryItem],
+        seqno: SeqNo,
+    ) -> crate::Result<usize> {
+        self.write_batch_with_merges(items, &[], range_tombstones, expiries, seqno)
     }
 
-    pub fn write_batch(&mut self, items: &[&BatchItem], seqno: SeqNo) -> crate::Result<usize> {
-        if items.is_empty() {
+    /// Writes a batch that may contain merge operands as well.
+    pub(crate) fn write_batch_with_merges(
+        &mut self,
+        items: &[&BatchItem],
+        merges: &[MergeItem],
+        range_tombstones: &[RangeTombstoneItem],
+        expiries: &[ExpiryItem],
+        seqno: SeqNo,
+    ) -> crate::Result<usize> {
+        if items.is_empty() && merges.is_empty() && range_tombstones.is_empty() {
             return Ok(0);
         }
 
@@ -192,17 +400,10 @@ impl Writer {
 
         // NOTE: entries.len() is surely never > u32::MAX
         #[allow(clippy::cast_possible_truncation)]
-        let item_count = items.len() as u32;
-
-        let mut hasher = xxhash_rust::xxh3::Xxh3::new();
-        let mut byte_count = 0;
-
-        byte_count += self.write_start(item_count, seqno)?;
-        self.buf.clear();
+        let item_count =
+            (items.len() + merges.len() + range_tombstones.len() + expiries.len()) as u32;
 
         for item in items {
-            debug_assert!(self.buf.is_empty());
-
             serialize_marker_item(
                 &mut self.buf,
                 &item.partition,
@@ -210,18 +411,146 @@ impl Writer {
                 &item.value,
                 item.value_type,
             )?;
+        }
 
-            self.file.write_all(&self.buf)?;
+        for merge in merges {
+            serialize_marker_merge(&mut self.buf, &merge.partition, &merge.key, &merge.operand)?;
+        }
 
-            hasher.update(&self.buf);
-            byte_count += self.buf.len();
+        for tombstone in range_tombstones {
+            serialize_marker_range_tombstone(
+                &mut self.buf,
+                &tombstone.partition,
+                &tombstone.start,
+                tombstone.end.as_deref(),
+            )?;
+        }
 
-            self.buf.clear();
+        for expiry in expiries {
+            serialize_marker_expiry(
+                &mut self.buf,
+                &expiry.partition,
+                &expiry.key,
+                expiry.expires_at,
+            )?;
         }
 
-        let checksum = hasher.finish();
-        byte_count += self.write_end(checksum)?;
+        self.write_buffered_batch(item_count, seqno)
+    }
+}
 
-        Ok(byte_count)
+#[derive(Default)]
+struct GroupCommitState {
+    /// Number of batches synced using `fdatasync` (or `fsync`)
+    synced_data: u64,
+
+    /// Number of batches synced using `fsync`
+    synced_all: u64,
+
+    /// If `true`, a leader is currently syncing the journal
+    is_syncing: bool,
+
+    /// If `true`, a waiting writer needs the next sync to use `fsync`
+    wants_sync_all: bool,
+
+    /// If `true`, a sync failed, so no further batches can become durable
+    failed: bool,
+}
+
+impl GroupCommitState {
+    fn is_synced(&self, batch: u64, mode: PersistMode) -> bool {
+        match mode {
+            PersistMode::Buffer => true,
+            PersistMode::SyncData => self.synced_data >= batch,
+            PersistMode::SyncAll => self.synced_all >= batch,
+        }
+    }
+}
+
+/// Group commit of concurrent writers
+///
+/// Writers append their batches while holding the journal writer lock,
+/// but wait for them to become durable after releasing it.
+///
+/// The first writer to wait becomes the leader, which syncs the journal once
+/// for all batches written up to that point. Writers that arrive in the meantime
+/// wait for the leader, and return once their batch is durable, or become the
+/// leader of the next sync if their batch was written too late.
+#[derive(Default)]
+pub struct GroupCommit {
+    state: Mutex<GroupCommitState>,
+    synced: Condvar,
+}
+
+impl GroupCommit {
+    /// Blocks until the given batch (see [`Writer::batch_count`]) is durable.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub(crate) fn wait(
+        &self,
+        writer: &Mutex<Writer>,
+        batch: u64,
+        mode: PersistMode,
+    ) -> std::io::Result<()> {
+        let mut state = self.state.lock().expect("lock is poisoned");
+
+        loop {
+            if state.failed {
+                return Err(std::io::Error::other("journal sync of group commit failed"));
+            }
+
+            if state.is_synced(batch, mode) {
+                return Ok(());
+            }
+
+            if state.is_syncing {
+                if mode == PersistMode::SyncAll {
+                    state.wants_sync_all = true;
+                }
+
+                state = self.synced.wait(state).expect("lock is poisoned");
+                continue;
+            }
+
+            // NOTE: Become the leader
+            state.is_syncing = true;
+            let sync_all =
+                mode == PersistMode::SyncAll || std::mem::take(&mut state.wants_sync_all);
+            drop(state);
+
+            log::trace!("Syncing journal for group commit, sync_all={sync_all}");
+
+            // NOTE: Only hold the writer lock while flushing to the OS,
+            // so other writers can append batches while the leader syncs
+            let prepared = writer.lock().expect("lock is poisoned").prepare_sync();
+
+            let result = prepared.and_then(|(batch_count, file)| {
+                if sync_all {
+                    file.sync_all()?;
+                } else {
+                    file.sync_data()?;
+                }
+                Ok(batch_count)
+            });
+
+            state = self.state.lock().expect("lock is poisoned");
+            state.is_syncing = false;
+
+            match result {
+                Ok(batch_count) => {
+                    state.synced_data = state.synced_data.max(batch_count);
+
+                    if sync_all {
+                        state.synced_all = state.synced_all.max(batch_count);
+                    }
+                }
+                Err(e) => {
+                    state.failed = true;
+                    self.synced.notify_all();
+                    return Err(e);
+                }
+            }
+
+            self.synced.notify_all();
+        }
     }
 }
@@ -7,25 +7,31 @@ use crate::{
     compaction::manager::CompactionManager,
     config::Config,
     file::{
-        fsync_directory, FJALL_MARKER, JOURNALS_FOLDER, PARTITIONS_FOLDER, PARTITION_DELETED_MARKER,
+        FJALL_MARKER, JOURNALS_FOLDER, PARTITIONS_FOLDER, PARTITION_DELETED_MARKER,
+        SALVAGED_JOURNALS_FOLDER,
     },
     flush::manager::FlushManager,
-    journal::{manager::JournalManager, writer::PersistMode, Journal},
+    fs::{read_file, FileSystem},
+    journal::{error::SkippedBatch, manager::JournalManager, writer::PersistMode, Journal},
+    lock_file::LockFile,
     monitor::Monitor,
     partition::name::is_valid_partition_name,
     recovery::{recover_partitions, recover_sealed_memtables},
+    secondary::Secondary,
     snapshot_tracker::SnapshotTracker,
+    subscription::{self, ChangeBatch},
     version::Version,
     write_buffer_manager::WriteBufferManager,
     HashMap, PartitionCreateOptions, PartitionHandle,
 };
-use lsm_tree::{AbstractTree, SequenceNumberCounter};
+use lsm_tree::{AbstractTree, SeqNo, SequenceNumberCounter};
 use std::{
-    fs::{remove_dir_all, File},
+    fs::remove_dir_all,
     path::Path,
     sync::{
         atomic::{AtomicBool, AtomicUsize},
-        Arc, RwLock,
+        mpsc::{channel, Receiver},
+        Arc, Mutex, RwLock,
     },
 };
 use std_semaphore::Semaphore;
@@ -76,6 +82,20 @@ pub struct KeyspaceInner {
     pub(crate) is_poisoned: Arc<AtomicBool>,
 
     pub(crate) snapshot_tracker: SnapshotTracker,
+
+    /// Held (shared) by flushes, compactions and blob GC,
+    /// so checkpoints can pause all changes to disk segments
+    pub(crate) maintenance_lock: Arc<RwLock<()>>,
+
+    /// Journal batches that were discarded during recovery
+    pub(crate) skipped_batches: Mutex<Vec<SkippedBatch>>,
+
+    /// State of a secondary instance, see `Config::open_secondary`
+    pub(crate) secondary: Option<Secondary>,
+
+    /// Exclusive lock on the keyspace directory, released when the keyspace is dropped
+    #[allow(dead_code)]
+    pub(crate) lock_file: Option<LockFile>,
 }
 
 impl Drop for KeyspaceInner {
@@ -247,6 +267,120 @@ impl Keyspace {
         self.journal_disk_space() + partitions_size
     }
 
+    /// Subscribes to committed writes.
+    ///
+    /// Every committed batch (or single write) is sent to the receiver in commit order,
+    /// once it is visible to reads, including writes of transactions.
+    ///
+    /// Range tombstones (e.g. [`PartitionHandle::remove_range`] or [`PartitionHandle::clear`])
+    /// are sent as [`ChangeKind::RangeTombstone`](crate::ChangeKind::RangeTombstone).
+    ///
+    /// The channel is unbounded, so the receiver needs to keep up with the writes.
+    /// Dropping the receiver ends the subscription.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{ChangeKind, Config, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(folder).open()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// let changes = keyspace.subscribe();
+    ///
+    /// partition.insert("a", "abc")?;
+    ///
+    /// let (_seqno, batch) = changes.recv().expect("should receive batch");
+    /// let (partition_name, key, value, kind) = batch.first().expect("should have item");
+    /// assert_eq!("default", &**partition_name);
+    /// assert_eq!(b"a", &**key);
+    /// assert_eq!(b"abc", &**value);
+    /// assert_eq!(ChangeKind::Value, *kind);
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<ChangeBatch> {
+        let (sender, receiver) = channel();
+        self.journal
+            .has_subscribers
+            .store(true, std::sync::atomic::Ordering::Release);
+        self.journal.get_writer().subscribers.add(sender);
+        receiver
+    }
+
+    /// Subscribes to committed writes, starting at the given seqno.
+    ///
+    /// Batches that are still in the journals are read back first, so a subscriber
+    /// can resume after the last seqno it has seen. Journals are deleted once their data
+    /// has been flushed, so older batches may not be available anymore.
+    ///
+    /// See [`Keyspace::subscribe`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or a large value that is read back
+    /// has been overwritten and deleted already.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn subscribe_from(&self, seqno: SeqNo) -> crate::Result<Receiver<ChangeBatch>> {
+        let (sender, receiver) = channel();
+
+        // NOTE: Writes are only paused while the journals are listed and the subscriber
+        // is added, so every batch is either in the journals, or buffered until replaying has finished
+        self.journal
+            .has_subscribers
+            .store(true, std::sync::atomic::Ordering::Release);
+
+        let (journals, until, backlog) = {
+            let mut journal_writer = self.journal.get_writer();
+            journal_writer.flush(PersistMode::Buffer)?;
+
+            let mut journals = self
+                .journal_manager
+                .read()
+                .expect("lock is poisoned")
+                .sealed_journal_paths();
+            journals.push(journal_writer.path.clone());
+
+            // NOTE: Seqnos of batches are given out while holding the journal lock,
+            // so all later batches are buffered
+            let until = self.seqno.get();
+
+            let backlog = journal_writer.subscribers.add_replaying(sender.clone());
+
+            (journals, until, backlog)
+        };
+
+        let replayed = subscription::replay(
+            &journals,
+            seqno,
+            until,
+            self.config.journal_encryption.as_ref(),
+            &sender,
+            |partition, key, value| {
+                let handle = self
+                    .partitions
+                    .read()
+                    .expect("lock is poisoned")
+                    .get(partition)
+                    .cloned();
+
+                match handle {
+                    Some(handle) => handle.large_values.resolve(key, value),
+                    None => Ok(value),
+                }
+            },
+        );
+        subscription::finish_replay(&backlog, &sender);
+        replayed?;
+
+        Ok(receiver)
+    }
+
     /// Flushes the active journal. The durability depends on the [`PersistMode`]
     /// used.
     ///
@@ -276,6 +410,10 @@ impl Keyspace {
             return Err(crate::Error::Poisoned);
         }
 
+        if self.config.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
         if let Err(e) = self.journal.flush(mode) {
             self.is_poisoned
                 .store(true, std::sync::atomic::Ordering::Release);
@@ -290,11 +428,53 @@ impl Keyspace {
         Ok(())
     }
 
+    /// Creates a consistent copy of the keyspace in the given directory,
+    /// which can be opened like any other keyspace.
+    ///
+    /// Segment and blob files are hard linked (if possible), so checkpoints are cheap.
+    /// Flushes and compactions are paused, and writes are blocked while the checkpoint is created.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let backup_folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(&folder).open()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// partition.insert("a", "abc")?;
+    ///
+    /// let checkpoint_path = backup_folder.path().join("checkpoint");
+    /// keyspace.checkpoint(&checkpoint_path)?;
+    ///
+    /// let checkpoint = Config::new(&checkpoint_path).open()?;
+    /// let partition = checkpoint.open_partition("default", PartitionCreateOptions::default())?;
+    /// assert_eq!(b"abc", &*partition.get("a")?.unwrap());
+    /// #
+    /// # Ok::<_, fjall::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if the directory already exists, or an IO error occurred.
+    pub fn checkpoint<P: AsRef<Path>>(&self, path: P) -> crate::Result<()> {
+        if self.is_poisoned.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::Poisoned);
+        }
+
+        if self.config.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        crate::checkpoint::create(self, path.as_ref()).map(|_| ())
+    }
+
     /// Opens a keyspace in the given directory.
     ///
     /// # Errors
     ///
-    /// Returns error, if an IO error occurred.
+    /// Returns error, if an IO error occurred, or the keyspace is locked by another process.
     pub fn open(config: Config) -> crate::Result<Self> {
         log::debug!(
             "block cache capacity={}MiB",
@@ -323,18 +503,46 @@ impl Keyspace {
     pub fn create_or_recover(config: Config) -> crate::Result<Self> {
         log::info!("Opening keyspace at {:?}", config.path);
 
-        if config.path.join(FJALL_MARKER).try_exists()? {
+        let exists = config.fs.exists(&config.path.join(FJALL_MARKER))?;
+
+        if config.read_only && !exists {
+            log::error!(
+                "Cannot open keyspace at {} read-only, because it does not exist",
+                config.path.display()
+            );
+            return Err(crate::Error::Io(std::io::ErrorKind::NotFound.into()));
+        }
+
+        if exists {
             Self::recover(config)
         } else {
             Self::create_new(config)
         }
     }
 
+    /// Locks the keyspace directory, unless the (read-only) keyspace opts out of the lock.
+    ///
+    /// Read-only keyspaces only take a shared lock.
+    fn lock(config: &Config) -> crate::Result<Option<LockFile>> {
+        if !config.uses_lock_file() {
+            Ok(None)
+        } else if config.read_only {
+            LockFile::acquire_shared(&*config.fs, &config.path)
+        } else {
+            LockFile::acquire(&*config.fs, &config.path).map(Some)
+        }
+    }
+
     /// Starts background threads that maintain the keyspace.
     ///
     /// Should not be called, unless in [`Keyspace::open`]
     /// and should definitely not be user-facing.
     pub(crate) fn start_background_threads(&self) {
+        // NOTE: Read-only keyspaces never flush, compact or fsync
+        if self.config.read_only {
+            return;
+        }
+
         if self.config.flush_workers_count > 0 {
             self.spawn_flush_worker();
 
@@ -370,13 +578,20 @@ impl Keyspace {
     ///
     /// Will return `Err` if an IO error occurs.
     pub fn delete_partition(&self, handle: PartitionHandle) -> crate::Result<()> {
+        if self.config.rejects_writes() {
+            return Err(crate::Error::ReadOnly);
+        }
+
         let partition_path = handle.path();
 
-        let file = File::create(partition_path.join(PARTITION_DELETED_MARKER))?;
+        let file = self
+            .config
+            .fs
+            .create(&partition_path.join(PARTITION_DELETED_MARKER))?;
         file.sync_all()?;
 
         // IMPORTANT: fsync folder on Unix
-        fsync_directory(partition_path)?;
+        self.config.fs.sync_directory(partition_path)?;
 
         handle
             .is_deleted
@@ -401,27 +616,48 @@ impl Keyspace {
     /// Creates or opens a keyspace partition.
     ///
     /// Partition names can be up to 255 characters long, can not be empty and
-    /// can only contain alphanumerics, underscore (`_`) and dash (`-`).
+    /// can only contain alphanumerics, underscore (`_`), dash (`-`), dot (`.`) and hash tag (`#`).
     ///
     /// # Errors
     ///
-    /// Returns error, if an IO error occurred.
+    /// Returns error, if an IO error occurred, or the partition name is invalid.
     ///
     /// # Panics
     ///
-    /// Panics if the partition name is invalid.
+    /// Panics if a lock is poisoned.
     pub fn open_partition(
         &self,
         name: &str,
         create_options: PartitionCreateOptions,
     ) -> crate::Result<PartitionHandle> {
-        assert!(is_valid_partition_name(name));
+        if !is_valid_partition_name(name) {
+            return Err(crate::Error::InvalidPartitionName);
+        }
 
         let mut partitions = self.partitions.write().expect("lock is poisoned");
 
         Ok(if let Some(partition) = partitions.get(name) {
+            // NOTE: The merge operator is not persisted, so it needs to be set
+            // when a recovered partition is opened
+            if let Some(merge_operator) = create_options.merge_operator {
+                partition.merges.set_operator(merge_operator);
+
+                // NOTE: Flushes may have failed because of the missing merge operator
+                self.flush_semaphore.release();
+            }
+
+            if let Some(compaction_filter) = create_options.compaction_filter {
+                partition.compaction_filter.set(compaction_filter);
+            }
+
             partition.clone()
         } else {
+            // NOTE: Replicas may create partitions before the writes of their primary arrive,
+            // e.g. to set a merge operator
+            if self.config.rejects_writes() && !self.config.replica {
+                return Err(crate::Error::ReadOnly);
+            }
+
             let name: PartitionKey = name.into();
 
             let handle = PartitionHandle::create_new(self, name.clone(), create_options)?;
@@ -434,6 +670,47 @@ impl Keyspace {
         })
     }
 
+    /// Catches up with the primary, if the keyspace is a secondary instance.
+    ///
+    /// The primary's partitions are rescanned and its journals are replayed,
+    /// so writes of the primary become visible. Partitions created by the primary
+    /// can be opened afterwards; partitions it deleted are removed.
+    ///
+    /// Does nothing if the keyspace is not a secondary instance,
+    /// see [`Config::open_secondary`].
+    ///
+    /// # Errors
+    ///
+    /// Returns error, if an IO error occurred.
+    pub fn try_catch_up(&self) -> crate::Result<()> {
+        if let Some(secondary) = &self.secondary {
+            secondary.catch_up(self)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the journal batches that were discarded when the keyspace was recovered.
+    ///
+    /// Using [`RecoveryMode::TolerateCorruptTail`](crate::RecoveryMode::TolerateCorruptTail),
+    /// this is at most an incomplete batch at the end of the journal.
+    ///
+    /// Using [`RecoveryMode::SkipInvalidBatches`](crate::RecoveryMode::SkipInvalidBatches),
+    /// this also contains corrupt batches in the middle of journals, so they can be salvaged.
+    /// Batches skipped in the active journal refer to its copy in the `salvaged_journals` folder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[must_use]
+    #[allow(clippy::expect_used)]
+    pub fn skipped_batches(&self) -> Vec<SkippedBatch> {
+        self.skipped_batches
+            .lock()
+            .expect("lock is poisoned")
+            .clone()
+    }
+
     /// Returns the amount of partitions
     #[must_use]
     pub fn partition_count(&self) -> usize {
@@ -517,8 +794,8 @@ impl Keyspace {
         self.seqno.get()
     }
 
-    fn check_version<P: AsRef<Path>>(path: P) -> crate::Result<()> {
-        let bytes = std::fs::read(path.as_ref().join(FJALL_MARKER))?;
+    pub(crate) fn check_version<P: AsRef<Path>>(fs: &dyn FileSystem, path: P) -> crate::Result<()> {
+        let bytes = read_file(fs, &path.as_ref().join(FJALL_MARKER))?;
 
         if let Some(version) = Version::parse_file_header(&bytes) {
             if version != Version::V2 {
@@ -532,26 +809,36 @@ impl Keyspace {
     }
 
     /// Recovers existing keyspace from directory.
-    #[allow(clippy::too_many_lines)]
+    #[allow(clippy::too_many_lines, clippy::expect_used)]
     #[doc(hidden)]
     pub fn recover(config: Config) -> crate::Result<Self> {
         log::info!("Recovering keyspace at {:?}", config.path);
 
-        // TODO:
-        // let recovery_mode = config.journal_recovery_mode;
+        let lock_file = Self::lock(&config)?;
 
         // Check version
-        Self::check_version(&config.path)?;
+        Self::check_version(&*config.fs, &config.path)?;
 
         // Reload active journal
         let journals_folder = config.path.join(JOURNALS_FOLDER);
-        let journal_recovery = Journal::recover(journals_folder)?;
+        let journal_recovery =
+            Journal::recover(config.fs.clone(), journals_folder, config.read_only)?;
         log::debug!("journal recovery result: {journal_recovery:#?}");
 
+        {
+            let mut writer = journal_recovery.active.get_writer();
+            writer.set_compression(
+                config.journal_compression_type,
+                config.journal_compression_threshold,
+            );
+            writer.encryption.clone_from(&config.journal_encryption);
+        }
+
         let active_journal = Arc::new(journal_recovery.active);
         let sealed_journals = journal_recovery.sealed;
 
-        let journal_manager = JournalManager::from_active(active_journal.path());
+        let journal_manager = JournalManager::from_active(active_journal.path(), config.fs.clone())
+            .with_archive(config.journal_archive.clone());
 
         // Construct (empty) keyspace, then fill back with partition data
         let inner = KeyspaceInner {
@@ -571,6 +858,10 @@ impl Keyspace {
             write_buffer_manager: WriteBufferManager::default(),
             is_poisoned: Arc::default(),
             snapshot_tracker: SnapshotTracker::default(),
+            maintenance_lock: Arc::default(),
+            skipped_batches: Mutex::default(),
+            secondary: None,
+            lock_file,
         };
 
         let keyspace = Self(Arc::new(inner));
@@ -605,11 +896,21 @@ impl Keyspace {
             if !journal_recovery.was_active_created {
                 log::trace!("Recovering active memtables from active journal");
 
-                let reader = keyspace.journal.get_reader()?;
+                let mut reader = keyspace
+                    .journal
+                    .get_reader(keyspace.config.journal_recovery_mode)?;
 
-                for batch in reader {
+                for batch in &mut reader {
                     let batch = batch?;
 
+                    for expiry in batch.expiries {
+                        if let Some(partition) = partitions.get(&expiry.partition) {
+                            partition
+                                .expiry
+                                .insert(&expiry.key, batch.seqno, expiry.expires_at)?;
+                        }
+                    }
+
                     for item in batch.items {
                         if let Some(partition) = partitions.get(&item.partition) {
                             let tree = &partition.tree;
@@ -627,8 +928,53 @@ impl Keyspace {
                             }
                         }
                     }
+
+                    for merge in batch.merges {
+                        if let Some(partition) = partitions.get(&merge.partition) {
+                            partition.merges.register(merge.key.clone(), batch.seqno);
+                            partition.tree.insert(merge.key, merge.operand, batch.seqno);
+                        }
+                    }
+
+                    for tombstone in batch.range_tombstones {
+                        if let Some(partition) = partitions.get(&tombstone.partition) {
+                            partition.add_range_tombstone(
+                                crate::range_tombstone::RangeTombstone {
+                                    start: tombstone.start,
+                                    end: tombstone.end,
+                                    seqno: batch.seqno,
+                                },
+                            )?;
+
+                            keyspace
+                                .seqno
+                                .fetch_max(batch.seqno + 1, std::sync::atomic::Ordering::AcqRel);
+                        }
+                    }
+                }
+
+                let mut skipped_batches = std::mem::take(&mut reader.skipped_batches);
+                drop(reader);
+
+                // IMPORTANT: Remove the skipped batches from the active journal, otherwise
+                // opening the keyspace with another recovery mode would discard the
+                // valid batches behind them
+                if keyspace.config.journal_recovery_mode == crate::RecoveryMode::SkipInvalidBatches
+                    && !skipped_batches.is_empty()
+                    && !keyspace.config.rejects_writes()
+                {
+                    keyspace.journal.rewrite_without(
+                        &mut skipped_batches,
+                        &keyspace.config.path.join(SALVAGED_JOURNALS_FOLDER),
+                    )?;
                 }
 
+                keyspace
+                    .skipped_batches
+                    .lock()
+                    .expect("lock is poisoned")
+                    .append(&mut skipped_batches);
+
                 for partition in partitions.values() {
                     let size = partition.tree.active_memtable_size().into();
 
@@ -659,26 +1005,41 @@ impl Keyspace {
         Ok(keyspace)
     }
 
+    /// Creates a new keyspace in the directory.
     #[doc(hidden)]
     pub fn create_new(config: Config) -> crate::Result<Self> {
         let path = config.path.clone();
+        let fs = config.fs.clone();
         log::info!("Creating keyspace at {path:?}");
 
-        std::fs::create_dir_all(&path)?;
+        fs.create_dir_all(&path)?;
+
+        let lock_file = Self::lock(&config)?;
 
         let marker_path = path.join(FJALL_MARKER);
-        assert!(!marker_path.try_exists()?);
+        assert!(!fs.exists(&marker_path)?);
 
         let journal_folder_path = path.join(JOURNALS_FOLDER);
         let partition_folder_path = path.join(PARTITIONS_FOLDER);
 
-        std::fs::create_dir_all(&journal_folder_path)?;
-        std::fs::create_dir_all(&partition_folder_path)?;
+        fs.create_dir_all(&journal_folder_path)?;
+        fs.create_dir_all(&partition_folder_path)?;
 
         let active_journal_path = journal_folder_path.join("0");
-        let journal = Journal::create_new(&active_journal_path)?;
+        let journal = Journal::create_new(fs.clone(), &active_journal_path)?;
+        {
+            let mut writer = journal.get_writer();
+            writer.set_compression(
+                config.journal_compression_type,
+                config.journal_compression_threshold,
+            );
+            writer.encryption.clone_from(&config.journal_encryption);
+        }
         let journal = Arc::new(journal);
 
+        let journal_manager = JournalManager::from_active(active_journal_path, fs.clone())
+            .with_archive(config.journal_archive.clone());
+
         let inner = KeyspaceInner {
             config,
             journal,
@@ -688,9 +1049,7 @@ impl Keyspace {
             ))),
             seqno: SequenceNumberCounter::default(),
             flush_manager: Arc::new(RwLock::new(FlushManager::new())),
-            journal_manager: Arc::new(RwLock::new(JournalManager::from_active(
-                active_journal_path,
-            ))),
+            journal_manager: Arc::new(RwLock::new(journal_manager)),
             flush_semaphore: Arc::new(Semaphore::new(0)),
             compaction_manager: CompactionManager::default(),
             stop_signal: lsm_tree::stop_signal::StopSignal::default(),
@@ -698,18 +1057,22 @@ impl Keyspace {
             write_buffer_manager: WriteBufferManager::default(),
             is_poisoned: Arc::default(),
             snapshot_tracker: SnapshotTracker::default(),
+            maintenance_lock: Arc::default(),
+            skipped_batches: Mutex::default(),
+            secondary: None,
+            lock_file,
         };
 
         // NOTE: Lastly, fsync .fjall marker, which contains the version
         // -> the keyspace is fully initialized
-        let mut file = std::fs::File::create(marker_path)?;
+        let mut file = fs.create(&marker_path)?;
         Version::V2.write_file_header(&mut file)?;
         file.sync_all()?;
 
         // IMPORTANT: fsync folders on Unix
-        fsync_directory(&journal_folder_path)?;
-        fsync_directory(&partition_folder_path)?;
-        fsync_directory(&path)?;
+        fs.sync_directory(&journal_folder_path)?;
+        fs.sync_directory(&partition_folder_path)?;
+        fs.sync_directory(&path)?;
 
         Ok(Self(Arc::new(inner)))
     }
@@ -764,11 +1127,13 @@ impl Keyspace {
         });
     }
 
+    #[allow(clippy::expect_used)]
     fn spawn_compaction_worker(&self) {
         let compaction_manager = self.compaction_manager.clone();
         let stop_signal = self.stop_signal.clone();
         let thread_counter = self.active_background_threads.clone();
         let snapshot_tracker = self.snapshot_tracker.clone();
+        let maintenance_lock = self.maintenance_lock.clone();
 
         thread_counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
@@ -777,6 +1142,7 @@ impl Keyspace {
                 log::trace!("compaction: waiting for work");
                 compaction_manager.wait_for();
 
+                let _lock = maintenance_lock.read().expect("lock is poisoned");
                 crate::compaction::worker::run(&compaction_manager, &snapshot_tracker);
             }
 
@@ -789,9 +1155,12 @@ impl Keyspace {
     ///
     /// Should NOT be called when there is a flush worker active already!!!
     #[doc(hidden)]
+    #[allow(clippy::expect_used)]
     pub fn force_flush(&self) {
         let parallelism = self.config.flush_workers_count;
 
+        let _lock = self.maintenance_lock.read().expect("lock is poisoned");
+
         crate::flush::worker::run(
             &self.flush_manager,
             &self.journal_manager,
@@ -802,6 +1171,7 @@ impl Keyspace {
         );
     }
 
+    #[allow(clippy::expect_used)]
     fn spawn_flush_worker(&self) {
         let flush_manager = self.flush_manager.clone();
         let journal_manager = self.journal_manager.clone();
@@ -809,6 +1179,7 @@ impl Keyspace {
         let flush_semaphore = self.flush_semaphore.clone();
         let write_buffer_manager = self.write_buffer_manager.clone();
         let snapshot_tracker = self.snapshot_tracker.clone();
+        let maintenance_lock = self.maintenance_lock.clone();
 
         let thread_counter = self.active_background_threads.clone();
         let stop_signal = self.stop_signal.clone();
@@ -822,6 +1193,8 @@ impl Keyspace {
                 log::trace!("flush worker: acquiring flush semaphore");
                 flush_semaphore.acquire();
 
+                let _lock = maintenance_lock.read().expect("lock is poisoned");
+
                 crate::flush::worker::run(
                     &flush_manager,
                     &journal_manager,
@@ -0,0 +1,651 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    compaction::filter::user_value,
+    file::{fsync_directory, LARGE_VALUES_FOLDER, MAGIC_BYTES},
+    merge::MergeState,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use lsm_tree::{
+    blob_tree::value::MaybeInlineValue, coding::Decode, AnyTree, InternalValue, SeqNo, UserKey,
+    UserValue,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsStr,
+    fs::{create_dir_all, remove_file, rename, File},
+    io::{BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+};
+
+/// Prefix of the values that are stored in the tree instead of large values
+const REFERENCE_PREFIX: &[u8] = b"\0fjall:large_value\0";
+
+/// Length of the values that are stored in the tree instead of large values
+const REFERENCE_LEN: usize = REFERENCE_PREFIX.len() + std::mem::size_of::<u64>();
+
+/// Suffix of large value files that are still being written
+const TEMP_SUFFIX: &str = ".tmp";
+
+/// Returns `false` if the file is a large value file that is still being written.
+pub fn is_complete(file_name: &OsStr) -> bool {
+    !file_name.to_string_lossy().ends_with(TEMP_SUFFIX)
+}
+
+/// Returns the value that is stored in the tree instead of the large value with the given ID.
+fn reference(id: u64) -> UserValue {
+    [REFERENCE_PREFIX, &id.to_be_bytes()].concat().into()
+}
+
+/// Returns the ID of the large value the value refers to, if it looks like a reference.
+pub fn parse_reference(value: &[u8]) -> Option<u64> {
+    let id = value.strip_prefix(REFERENCE_PREFIX)?;
+    Some(u64::from_be_bytes(id.try_into().ok()?))
+}
+
+/// Returns the ID of the large value the item refers to, if it looks like a reference.
+fn stored_reference(tree: &AnyTree, item: &InternalValue) -> crate::Result<Option<u64>> {
+    // NOTE: Separated values are only read if they may be a reference
+    if let AnyTree::Blob(_) = tree {
+        if let (false, MaybeInlineValue::Indirect { size, .. }) = (
+            item.is_tombstone(),
+            MaybeInlineValue::decode_from(&mut &item.value[..])?,
+        ) {
+            if size as usize != REFERENCE_LEN {
+                return Ok(None);
+            }
+        }
+    }
+
+    Ok(user_value(tree, item)?.and_then(|value| parse_reference(&value)))
+}
+
+/// Reads the header of a large value file, returning its key and the size of the header.
+fn read_header<R: Read>(reader: &mut R) -> crate::Result<(UserKey, u64)> {
+    let mut magic = [0; MAGIC_BYTES.len()];
+    reader.read_exact(&mut magic)?;
+
+    if magic != MAGIC_BYTES {
+        log::error!("Large value file has invalid header");
+        return Err(crate::Error::Io(std::io::ErrorKind::InvalidData.into()));
+    }
+
+    let key_len = reader.read_u16::<BigEndian>()?;
+    let key = UserKey::from_reader(reader, key_len.into())?;
+
+    let header_size = MAGIC_BYTES.len() + 2 + key.len();
+
+    Ok((key, header_size as u64))
+}
+
+struct Entry {
+    key: UserKey,
+
+    /// File size in bytes
+    size: u64,
+
+    /// If `true`, the value is not committed yet, so it is not garbage collected
+    is_pending: bool,
+}
+
+#[derive(Default)]
+struct State {
+    folder: PathBuf,
+    entries: BTreeMap<u64, Entry>,
+}
+
+/// Values that are too large to be stored in the journal and LSM-tree of a partition
+///
+/// Both the journal and the LSM-tree (including its blob files) store value lengths
+/// as 32-bit integers, so larger values are written to their own file, named by an ID.
+/// The file starts with the item's key, followed by the value.
+/// The journal and the tree store a small reference to the file instead of the value.
+///
+/// A value that looks like a reference is only treated as one if a file with that ID exists,
+/// and belongs to the same key.
+///
+/// Files that are not referenced by any version of their key that a snapshot may read
+/// are deleted after compactions.
+pub struct LargeValues {
+    state: RwLock<State>,
+
+    /// Values larger than this are stored in their own file
+    threshold: u64,
+
+    next_id: AtomicU64,
+
+    /// Cheap check to skip locking for partitions that do not have large values
+    has_values: AtomicBool,
+}
+
+impl LargeValues {
+    pub fn new(partition_folder: &Path, threshold: u64) -> Self {
+        Self {
+            state: RwLock::new(State {
+                folder: partition_folder.join(LARGE_VALUES_FOLDER),
+                entries: BTreeMap::new(),
+            }),
+            threshold,
+            next_id: AtomicU64::default(),
+            has_values: AtomicBool::default(),
+        }
+    }
+
+    /// Recovers the large values of a partition.
+    ///
+    /// Files that were not completely written are deleted, unless the keyspace is read-only.
+    pub fn recover(
+        partition_folder: &Path,
+        threshold: u64,
+        read_only: bool,
+    ) -> crate::Result<Self> {
+        let mut values = Self::new(partition_folder, threshold);
+
+        let Ok(state) = values.state.get_mut() else {
+            unreachable!("lock should not be poisoned");
+        };
+
+        if !state.folder.try_exists()? {
+            return Ok(values);
+        }
+
+        for dirent in std::fs::read_dir(&state.folder)? {
+            let dirent = dirent?;
+            let path = dirent.path();
+
+            let file_name = dirent.file_name();
+            let file_name = file_name.to_string_lossy();
+
+            if !is_complete(&dirent.file_name()) {
+                if !read_only {
+                    log::debug!("Deleting incomplete large value file {}", path.display());
+                    remove_file(&path)?;
+                }
+                continue;
+            }
+
+            let Ok(id) = file_name.parse::<u64>() else {
+                log::warn!("Ignoring unexpected file {}", path.display());
+                continue;
+            };
+
+            let file = File::open(&path)?;
+            let size = file.metadata()?.len();
+            let (key, _) = read_header(&mut BufReader::new(file))?;
+
+            state.entries.insert(
+                id,
+                Entry {
+                    key,
+                    size,
+                    is_pending: false,
+                },
+            );
+        }
+
+        log::debug!("Recovered {} large values", state.entries.len());
+
+        let next_id = state.entries.keys().next_back().map_or(0, |id| id + 1);
+        let has_values = !state.entries.is_empty();
+
+        values.next_id.store(next_id, Ordering::Release);
+        values.has_values.store(has_values, Ordering::Release);
+
+        Ok(values)
+    }
+
+    /// Returns `true` if there are no large values.
+    pub fn is_empty(&self) -> bool {
+        !self.has_values.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if the value is too large to be stored in the journal and LSM-tree.
+    pub fn is_large(&self, value: &[u8]) -> bool {
+        value.len() as u64 > self.threshold
+    }
+
+    #[allow(clippy::expect_used)]
+    fn folder(&self) -> PathBuf {
+        self.state.read().expect("lock is poisoned").folder.clone()
+    }
+
+    /// Returns the ID of the large value the value refers to.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn reference_id(&self, key: &[u8], value: &[u8]) -> Option<u64> {
+        if !self.has_values.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let id = parse_reference(value)?;
+
+        let state = self.state.read().expect("lock is poisoned");
+        let entry = state.entries.get(&id)?;
+
+        (&*entry.key == key).then_some(id)
+    }
+
+    /// Returns `true` if the value refers to a large value.
+    pub fn is_reference(&self, key: &[u8], value: &[u8]) -> bool {
+        self.reference_id(key, value).is_some()
+    }
+
+    /// Returns the path of the large value file with the given ID.
+    pub fn path_of(&self, id: u64) -> PathBuf {
+        self.folder().join(id.to_string())
+    }
+
+    /// Returns the path a large value file with the given ID is written to,
+    /// before it is complete.
+    ///
+    /// The folder is created if it does not exist yet.
+    fn temp_path(&self, id: u64) -> crate::Result<PathBuf> {
+        let folder = self.folder();
+        create_dir_all(&folder)?;
+        Ok(folder.join(format!("{id}{TEMP_SUFFIX}")))
+    }
+
+    /// Creates a file for a large value that is written elsewhere, e.g. received by replication,
+    /// and returns its ID.
+    ///
+    /// Once written, the file needs to be added using [`LargeValues::import`].
+    pub fn create_temp(&self) -> crate::Result<(u64, File)> {
+        let id = self.next_id.fetch_add(1, Ordering::AcqRel);
+        let file = File::create(self.temp_path(id)?)?;
+        Ok((id, file))
+    }
+
+    /// Returns the large value the value refers to,
+    /// or the value itself if it does not refer to a large value.
+    #[allow(clippy::expect_used)]
+    pub fn resolve(&self, key: &[u8], value: UserValue) -> crate::Result<UserValue> {
+        let Some(id) = self.reference_id(key, &value) else {
+            return Ok(value);
+        };
+
+        // NOTE: The file is opened while holding the lock,
+        // so it cannot be deleted before it is opened
+        let file = {
+            let state = self.state.read().expect("lock is poisoned");
+
+            if !state.entries.contains_key(&id) {
+                log::error!("Large value {id} was deleted while reading it");
+                return Err(crate::Error::Io(std::io::ErrorKind::NotFound.into()));
+            }
+
+            File::open(state.folder.join(id.to_string()))?
+        };
+
+        let size = file.metadata()?.len();
+
+        let mut reader = BufReader::new(file);
+        let (_, header_size) = read_header(&mut reader)?;
+
+        let len = usize::try_from(size - header_size)
+            .map_err(|_| crate::Error::Io(std::io::ErrorKind::OutOfMemory.into()))?;
+
+        Ok(UserValue::from_reader(&mut reader, len)?)
+    }
+
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    fn add_entry(self: &Arc<Self>, id: u64, key: UserKey, size: u64) -> PendingValue {
+        let mut state = self.state.write().expect("lock is poisoned");
+
+        state.entries.insert(
+            id,
+            Entry {
+                key,
+                size,
+                is_pending: true,
+            },
+        );
+        self.has_values.store(true, Ordering::Release);
+
+        PendingValue {
+            values: self.clone(),
+            id,
+            reference: reference(id),
+            is_committed: false,
+        }
+    }
+
+    /// Writes a large value to its own file.
+    ///
+    /// The returned reference needs to be written to the tree instead of the value,
+    /// and committed afterwards, otherwise the file is deleted again.
+    pub fn write(self: &Arc<Self>, key: &[u8], value: &[u8]) -> crate::Result<PendingValue> {
+        let id = self.next_id.fetch_add(1, Ordering::AcqRel);
+
+        let temp_path = self.temp_path(id)?;
+        let path = self.path_of(id);
+
+        log::debug!(
+            "Writing large value of {} bytes to {}",
+            value.len(),
+            path.display()
+        );
+
+        let result = (|| -> crate::Result<u64> {
+            let mut writer = BufWriter::new(File::create(&temp_path)?);
+
+            writer.write_all(MAGIC_BYTES)?;
+            // NOTE: Keys are checked against the key size limit before
+            #[allow(clippy::cast_possible_truncation)]
+            writer.write_u16::<BigEndian>(key.len() as u16)?;
+            writer.write_all(key)?;
+            writer.write_all(value)?;
+
+            let file = writer
+                .into_inner()
+                .map_err(std::io::IntoInnerError::into_error)?;
+            file.sync_all()?;
+            let size = file.metadata()?.len();
+
+            // NOTE: The file is only renamed once it is complete,
+            // so an incomplete file is never mistaken for a large value
+            rename(&temp_path, &path)?;
+
+            if let Some(folder) = path.parent() {
+                fsync_directory(folder)?;
+            }
+
+            Ok(size)
+        })();
+
+        match result {
+            Ok(size) => Ok(self.add_entry(id, key.into(), size)),
+            Err(e) => {
+                if let Err(e) = remove_file(&temp_path) {
+                    log::warn!("Failed to clean up {}: {e:?}", temp_path.display());
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Adds a large value file that has been created using [`LargeValues::create_temp`].
+    ///
+    /// Like [`LargeValues::write`], the file is deleted again if it is not committed.
+    pub fn import(self: &Arc<Self>, id: u64) -> crate::Result<PendingValue> {
+        let temp_path = self.temp_path(id)?;
+        let path = self.path_of(id);
+
+        let file = File::open(&temp_path)?;
+        file.sync_all()?;
+        let size = file.metadata()?.len();
+        let (key, _) = read_header(&mut BufReader::new(file))?;
+
+        rename(&temp_path, &path)?;
+
+        if let Some(folder) = path.parent() {
+            fsync_directory(folder)?;
+        }
+
+        Ok(self.add_entry(id, key, size))
+    }
+
+    /// Links the large value file the value refers to from another folder,
+    /// if it is not known yet, e.g. because it was written after the partition was mirrored.
+    ///
+    /// Does nothing, if the value does not refer to a large value file in the given folder.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn link_missing(&self, partition_folder: &Path, value: &[u8]) -> crate::Result<()> {
+        let Some(id) = parse_reference(value) else {
+            return Ok(());
+        };
+
+        let mut state = self.state.write().expect("lock is poisoned");
+
+        if state.entries.contains_key(&id) {
+            return Ok(());
+        }
+
+        let src = partition_folder
+            .join(LARGE_VALUES_FOLDER)
+            .join(id.to_string());
+
+        if !src.try_exists()? {
+            return Ok(());
+        }
+
+        create_dir_all(&state.folder)?;
+
+        let dest = state.folder.join(id.to_string());
+
+        if let Err(e) = std::fs::hard_link(&src, &dest) {
+            log::debug!(
+                "Could not hard link {}, copying instead: {e:?}",
+                src.display()
+            );
+            std::fs::copy(&src, &dest)?;
+        }
+
+        let file = File::open(&dest)?;
+        let size = file.metadata()?.len();
+        let (key, _) = read_header(&mut BufReader::new(file))?;
+
+        state.entries.insert(
+            id,
+            Entry {
+                key,
+                size,
+                is_pending: false,
+            },
+        );
+        self.has_values.store(true, Ordering::Release);
+        self.next_id.fetch_max(id + 1, Ordering::AcqRel);
+
+        Ok(())
+    }
+
+    /// Returns the IDs of the committed large values, by key.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn committed(&self) -> BTreeMap<UserKey, Vec<u64>> {
+        let mut committed: BTreeMap<UserKey, Vec<u64>> = BTreeMap::new();
+
+        if !self.has_values.load(Ordering::Acquire) {
+            return committed;
+        }
+
+        let state = self.state.read().expect("lock is poisoned");
+
+        for (id, entry) in &state.entries {
+            if !entry.is_pending {
+                committed.entry(entry.key.clone()).or_default().push(*id);
+            }
+        }
+
+        committed
+    }
+
+    /// Deletes the files of large values that are not referenced by any version of their key
+    /// that is visible to reads at (or after) the given seqno.
+    ///
+    /// The partition's write lock needs to be held, so no reference is written in the meantime.
+    pub fn collect_garbage(
+        &self,
+        tree: &AnyTree,
+        merges: &MergeState,
+        gc_seqno: SeqNo,
+    ) -> crate::Result<()> {
+        let index = match tree {
+            AnyTree::Standard(tree) => tree,
+            AnyTree::Blob(tree) => &tree.index,
+        };
+
+        for (key, ids) in self.committed() {
+            let mut referenced = HashSet::new();
+
+            // IMPORTANT: Merged values of memtables that are being flushed are not in the tree yet,
+            // and the merge state is only cleaned up after the flushed segment is registered,
+            // so it needs to be read before the tree
+            if let Some(operands) = merges.read_operands() {
+                if let Some(operands) = operands.get(&key) {
+                    referenced.extend(
+                        operands
+                            .values()
+                            .flatten()
+                            .filter_map(|value| parse_reference(value)),
+                    );
+                }
+            }
+
+            // NOTE: Walk through the versions of the key, from newest to oldest,
+            // until there is a version that all reads see (or shadow)
+            let mut seqno = None;
+
+            while let Some(item) = index.get_internal_entry(&key, false, seqno)? {
+                referenced.extend(stored_reference(tree, &item)?);
+
+                if item.key.seqno < gc_seqno {
+                    break;
+                }
+
+                seqno = Some(item.key.seqno);
+            }
+
+            for id in ids {
+                if !referenced.contains(&id) {
+                    self.remove(id)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the file of a large value.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn remove(&self, id: u64) -> crate::Result<()> {
+        let mut state = self.state.write().expect("lock is poisoned");
+
+        let path = state.folder.join(id.to_string());
+        log::debug!("Deleting large value file {}", path.display());
+
+        match remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e.into()),
+        }
+
+        state.entries.remove(&id);
+
+        if state.entries.is_empty() {
+            self.has_values.store(false, Ordering::Release);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the disk space used by large value files.
+    #[allow(clippy::expect_used)]
+    pub fn disk_space(&self) -> u64 {
+        if !self.has_values.load(Ordering::Acquire) {
+            return 0;
+        }
+
+        let state = self.state.read().expect("lock is poisoned");
+        state.entries.values().map(|entry| entry.size).sum()
+    }
+
+    /// Replaces the large values by the ones of `other`.
+    #[allow(clippy::expect_used)]
+    pub fn replace(&self, other: &Self) {
+        let mut state = self.state.write().expect("lock is poisoned");
+        *state = std::mem::take(&mut *other.state.write().expect("lock is poisoned"));
+
+        self.next_id
+            .store(other.next_id.load(Ordering::Acquire), Ordering::Release);
+        self.has_values
+            .store(!state.entries.is_empty(), Ordering::Release);
+    }
+}
+
+/// A large value file that is not committed yet
+///
+/// Dropping it without committing deletes the file.
+pub struct PendingValue {
+    values: Arc<LargeValues>,
+    id: u64,
+
+    /// Value that is stored in the tree instead of the large value
+    pub reference: UserValue,
+
+    is_committed: bool,
+}
+
+impl PendingValue {
+    /// Marks the large value as committed, once its reference has been written to the tree.
+    ///
+    /// From then on, the file is deleted once the reference is not visible anymore.
+    #[allow(clippy::expect_used)]
+    pub fn commit(mut self) {
+        let mut state = self.values.state.write().expect("lock is poisoned");
+
+        if let Some(entry) = state.entries.get_mut(&self.id) {
+            entry.is_pending = false;
+        }
+
+        drop(state);
+        self.is_committed = true;
+    }
+}
+
+impl Drop for PendingValue {
+    fn drop(&mut self) {
+        if self.is_committed {
+            return;
+        }
+
+        if let Err(e) = self.values.remove(self.id) {
+            log::warn!(
+                "Failed to delete uncommitted large value {}: {e:?}",
+                self.id
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn large_values_commit_and_recover() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let values = Arc::new(LargeValues::new(folder.path(), 4));
+
+        assert!(!values.is_large(b"abcd"));
+        assert!(values.is_large(b"abcde"));
+
+        let committed = values.write(b"a", b"abcde")?;
+        let reference = committed.reference.clone();
+        committed.commit();
+
+        let discarded = values.write(b"b", b"fghij")?;
+        let discarded_reference = discarded.reference.clone();
+        assert!(values.is_reference(b"b", &discarded_reference));
+        drop(discarded);
+        assert!(!values.is_reference(b"b", &discarded_reference));
+
+        assert_eq!(&*values.resolve(b"a", reference.clone())?, b"abcde");
+
+        // NOTE: References only count for the key they were written for
+        assert!(!values.is_reference(b"b", &reference));
+        assert_eq!(values.resolve(b"b", reference.clone())?, reference);
+
+        let recovered = LargeValues::recover(folder.path(), 4, false)?;
+        assert_eq!(&*recovered.resolve(b"a", reference)?, b"abcde");
+        assert_eq!(1, recovered.committed().len());
+        assert_eq!(values.disk_space(), recovered.disk_space());
+        assert_eq!(1, recovered.next_id.load(Ordering::Acquire));
+
+        Ok(())
+    }
+}
@@ -10,6 +10,7 @@
 //! - Cross-partition snapshots (MVCC)
 //! - Automatic background maintenance
 //! - Single-writer transactions (optional)
+//! - Optimistic, serializable snapshot isolation transactions (optional)
 //! - Key-value separation for large blob use cases (optional)
 //!
 //! Each `Keyspace` is a single logical database and is split into `partitions` (a.k.a. column families) - you should probably only use a single keyspace for your application.
@@ -22,7 +23,7 @@
 //! - a relational database
 //! - a wide-column database: it has no notion of columns
 //!
-//! Keys are limited to 65536 bytes, values are limited to 2^32 bytes. As is normal with any kind of storage engine, larger keys and values have a bigger performance impact.
+//! Keys are limited to 65535 bytes, values are limited to 2^32 - 1 bytes. As is normal with any kind of storage engine, larger keys and values have a bigger performance impact.
 //!
 //! For the underlying LSM-tree implementation, see: <https://crates.io/crates/lsm-tree>.
 //!
@@ -78,32 +79,52 @@
 #![allow(clippy::missing_const_for_fn)]
 #![warn(clippy::multiple_crate_versions)]
 
+#[cfg(all(feature = "single_writer_tx", feature = "ssi_tx"))]
+compile_error!("Either single_writer_tx or ssi_tx can be enabled at once");
+
+mod backup;
 mod batch;
+mod checkpoint;
 
 /// Contains compaction strategies
 pub mod compaction;
 
 mod config;
+mod cursor;
+mod encryption;
 
 #[cfg(feature = "__internal_whitebox")]
 #[doc(hidden)]
 pub mod drop;
 
 mod error;
+mod expiry;
 mod file;
 mod flush;
+pub mod fs;
 mod gc;
-mod journal;
+
+/// Contains utilities to inspect journal files
+pub mod journal;
 mod keyspace;
+mod large_value;
+mod limits;
+mod lock_file;
+mod merge;
 mod monitor;
 mod partition;
 mod path;
+mod range_tombstone;
+mod read_filter;
 mod recovery;
+pub mod replication;
+mod secondary;
 mod snapshot_nonce;
 mod snapshot_tracker;
+mod subscription;
 mod tracked_snapshot;
 
-#[cfg(feature = "single_writer_tx")]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
 mod tx;
 
 mod version;
@@ -113,26 +134,37 @@ pub(crate) type HashMap<K, V> = std::collections::HashMap<K, V, xxhash_rust::xxh
 pub(crate) type HashSet<K> = std::collections::HashSet<K, xxhash_rust::xxh3::Xxh3Builder>;
 
 pub use {
+    backup::{BackupEngine, BackupId, BackupInfo},
     batch::Batch,
     config::Config,
+    cursor::Cursor,
+    encryption::JournalEncryptionProvider,
     error::{Error, Result},
     gc::GarbageCollection,
-    journal::{error::RecoveryError, writer::PersistMode},
+    journal::{
+        error::{RecoveryError, RecoveryMode, SkippedBatch},
+        writer::PersistMode,
+    },
     keyspace::Keyspace,
+    merge::MergeOperator,
     partition::{
         options::CreateOptions as PartitionCreateOptions, options::KvSeparationOptions,
         PartitionHandle,
     },
+    subscription::{Change, ChangeBatch, ChangeKind},
     tracked_snapshot::TrackedSnapshot as Snapshot,
     version::Version,
 };
 
-#[cfg(feature = "single_writer_tx")]
+#[cfg(feature = "journal_encryption")]
+pub use encryption::{Aes256GcmProvider, EncryptionKey, EncryptionKeyId};
+
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
 pub use tx::{
     keyspace::{TransactionalKeyspace, TxKeyspace},
     partition::TransactionalPartitionHandle,
     read_tx::ReadTransaction,
-    write_tx::WriteTransaction,
+    write_tx::{Savepoint, WriteTransaction},
 };
 
 /// Alias for [`Batch`]
@@ -142,15 +174,15 @@ pub type WriteBatch = Batch;
 pub type Partition = PartitionHandle;
 
 /// Alias for [`TransactionalPartitionHandle`]
-#[cfg(feature = "single_writer_tx")]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
 pub type TxPartition = TransactionalPartitionHandle;
 
 /// Alias for [`TransactionalPartitionHandle`]
-#[cfg(feature = "single_writer_tx")]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
 pub type TxPartitionHandle = TransactionalPartitionHandle;
 
 /// Alias for [`TransactionalPartitionHandle`]
-#[cfg(feature = "single_writer_tx")]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
 pub type TransactionalPartition = TransactionalPartitionHandle;
 
 /// A snapshot moment
@@ -166,4 +198,5 @@ pub use lsm_tree::AbstractTree;
 
 pub use lsm_tree::{
     AnyTree, BlobCache, BlockCache, CompressionType, KvPair, Slice, TreeType, UserKey, UserValue,
+    ValueType,
 };
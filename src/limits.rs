@@ -0,0 +1,49 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::batch::item::Item as BatchItem;
+
+/// Maximum key size in bytes
+pub const MAX_KEY_SIZE: usize = u16::MAX as usize;
+
+/// Maximum size in bytes of values that are stored in the journal and LSM-tree
+///
+/// Both the journal and blob files store value lengths as 32-bit integers,
+/// so key-value separation does not raise the limit.
+/// Larger values are stored in their own files instead, see [`crate::large_value::LargeValues`],
+/// only merge operands are limited to this size.
+pub const MAX_VALUE_SIZE: u64 = u32::MAX as u64;
+
+/// Returns an error if the key cannot be stored.
+pub fn check_key(key: &[u8]) -> crate::Result<()> {
+    if key.len() > MAX_KEY_SIZE {
+        log::debug!("Rejecting key of {} bytes", key.len());
+        return Err(crate::Error::KeyTooLarge);
+    }
+
+    Ok(())
+}
+
+/// Returns an error if the value cannot be stored.
+pub fn check_value(value: &[u8]) -> crate::Result<()> {
+    if value.len() as u64 > MAX_VALUE_SIZE {
+        log::debug!("Rejecting value of {} bytes", value.len());
+        return Err(crate::Error::ValueTooLarge);
+    }
+
+    Ok(())
+}
+
+/// Returns an error if the key or value cannot be stored.
+pub fn check_item(key: &[u8], value: &[u8]) -> crate::Result<()> {
+    check_key(key)?;
+    check_value(value)
+}
+
+/// Returns an error if any key of the batch cannot be stored.
+///
+/// Values of any size can be stored, because large values are written to their own files.
+pub fn check_batch_items(items: &[BatchItem]) -> crate::Result<()> {
+    items.iter().try_for_each(|item| check_key(&item.key))
+}
@@ -0,0 +1,121 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    file::LOCK_FILE,
+    fs::{FileLock, FileSystem, LockMode},
+};
+use std::path::Path;
+
+/// Advisory lock on the `LOCK` file in a keyspace's directory,
+/// which prevents the keyspace from being opened twice at the same time
+///
+/// Read-only keyspaces take a shared lock, so they can be opened
+/// concurrently, just not while the keyspace is opened for writing.
+///
+/// The lock is released when the file is closed, so it never outlives its process.
+pub struct LockFile(#[allow(dead_code)] Box<dyn FileLock>);
+
+impl LockFile {
+    /// Takes the exclusive lock of the keyspace in the given directory.
+    ///
+    /// Returns `Error::Locked` if another process (or keyspace instance) holds the lock.
+    pub fn acquire(fs: &dyn FileSystem, folder: &Path) -> crate::Result<Self> {
+        let path = folder.join(LOCK_FILE);
+
+        let Some(lock) = fs.lock(&path, LockMode::Exclusive)? else {
+            log::error!(
+                "Keyspace at {} is locked by another process",
+                folder.display()
+            );
+            return Err(crate::Error::Locked);
+        };
+
+        log::trace!("Acquired lock file at {}", path.display());
+
+        Ok(Self(lock))
+    }
+
+    /// Takes a shared lock of the keyspace in the given directory, without writing to it.
+    ///
+    /// Returns `Error::Locked` if another process (or keyspace instance) holds the exclusive lock.
+    ///
+    /// Returns `None` if there is no `LOCK` file, because then the keyspace is not
+    /// opened for writing, and the file cannot be created without writing to the directory.
+    pub fn acquire_shared(fs: &dyn FileSystem, folder: &Path) -> crate::Result<Option<Self>> {
+        let path = folder.join(LOCK_FILE);
+
+        match fs.lock(&path, LockMode::Shared) {
+            Ok(Some(lock)) => {
+                log::trace!("Acquired shared lock file at {}", path.display());
+                Ok(Some(Self(lock)))
+            }
+            Ok(None) => {
+                log::error!(
+                    "Keyspace at {} is locked by another process",
+                    folder.display()
+                );
+                Err(crate::Error::Locked)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::debug!(
+                    "No lock file at {}, opening keyspace without lock",
+                    path.display()
+                );
+                Ok(None)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::OsFileSystem;
+    use test_log::test;
+
+    #[test]
+    fn lock_file_exclusive() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+
+        let lock = LockFile::acquire(&OsFileSystem, folder.path())?;
+        assert!(matches!(
+            LockFile::acquire(&OsFileSystem, folder.path()),
+            Err(crate::Error::Locked)
+        ));
+
+        drop(lock);
+        LockFile::acquire(&OsFileSystem, folder.path())?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn lock_file_shared() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+
+        // NOTE: The lock file is only created by exclusive locks
+        assert!(LockFile::acquire_shared(&OsFileSystem, folder.path())?.is_none());
+        drop(LockFile::acquire(&OsFileSystem, folder.path())?);
+
+        let lock = LockFile::acquire_shared(&OsFileSystem, folder.path())?;
+        assert!(lock.is_some());
+        assert!(LockFile::acquire_shared(&OsFileSystem, folder.path())?.is_some());
+        assert!(matches!(
+            LockFile::acquire(&OsFileSystem, folder.path()),
+            Err(crate::Error::Locked)
+        ));
+
+        drop(lock);
+        let lock = LockFile::acquire(&OsFileSystem, folder.path())?;
+        assert!(matches!(
+            LockFile::acquire_shared(&OsFileSystem, folder.path()),
+            Err(crate::Error::Locked)
+        ));
+        drop(lock);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,297 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::HashMap;
+use lsm_tree::{coding::Encode, AnyTree, InternalValue, Memtable, SeqNo, UserKey, UserValue};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock, RwLockReadGuard,
+    },
+};
+
+/// Combines merge operands into a value
+///
+/// Merge operands are written using [`PartitionHandle::merge`](crate::PartitionHandle::merge),
+/// and are combined lazily when the key is read, and eagerly when the memtable is flushed.
+///
+/// The merge operator needs to be deterministic, because the same operands may be merged
+/// multiple times.
+///
+/// # Examples
+///
+/// ```
+/// # use fjall::{Config, MergeOperator, PartitionCreateOptions};
+/// # use std::sync::Arc;
+/// #
+/// struct Counter;
+///
+/// impl MergeOperator for Counter {
+///     fn merge(&self, _key: &[u8], existing: Option<&[u8]>, operands: &[&[u8]]) -> Vec<u8> {
+///         let parse = |bytes: &[u8]| u64::from_be_bytes(bytes.try_into().expect("should be u64"));
+///
+///         let sum = existing.map(parse).unwrap_or_default()
+///             + operands.iter().copied().map(parse).sum::<u64>();
+///
+///         sum.to_be_bytes().to_vec()
+///     }
+/// }
+///
+/// # let folder = tempfile::tempdir()?;
+/// # let keyspace = Config::new(folder).open()?;
+/// let partition = keyspace.open_partition(
+///     "counters",
+///     PartitionCreateOptions::default().with_merge_operator(Arc::new(Counter)),
+/// )?;
+///
+/// partition.merge("visits", 1u64.to_be_bytes())?;
+/// partition.merge("visits", 2u64.to_be_bytes())?;
+///
+/// let value = partition.get("visits")?.expect("should exist");
+/// assert_eq!(&*value, 3u64.to_be_bytes());
+/// #
+/// # Ok::<(), fjall::Error>(())
+/// ```
+pub trait MergeOperator: Send + Sync {
+    /// Merges the operands (oldest first) into the existing value.
+    ///
+    /// `existing` is `None` if the key does not exist, or was deleted.
+    fn merge(&self, key: &[u8], existing: Option<&[u8]>, operands: &[&[u8]]) -> Vec<u8>;
+}
+
+impl std::fmt::Debug for dyn MergeOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "MergeOperator")
+    }
+}
+
+/// Merge operands of a key, by seqno
+///
+/// An operand becomes resolved (`Some`) once its memtable is being flushed,
+/// because the flushed segment will contain the merged value instead of the operand.
+pub type Operands = BTreeMap<SeqNo, Option<UserValue>>;
+
+/// Merge operator and merge operands of a partition
+///
+/// The LSM-tree has no notion of merge operands, so they are stored as regular values,
+/// and this keeps track of which items in the memtables are operands.
+/// Segments never contain merge operands.
+#[derive(Default)]
+pub struct MergeState {
+    operator: RwLock<Option<Arc<dyn MergeOperator>>>,
+
+    operands: RwLock<HashMap<UserKey, Operands>>,
+
+    /// Cheap check to skip locking for partitions that do not use merge operands
+    has_operands: AtomicBool,
+}
+
+impl MergeState {
+    pub fn new(operator: Option<Arc<dyn MergeOperator>>) -> Self {
+        Self {
+            operator: RwLock::new(operator),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the merge operator.
+    #[allow(clippy::expect_used)]
+    pub fn operator(&self) -> Option<Arc<dyn MergeOperator>> {
+        self.operator.read().expect("lock is poisoned").clone()
+    }
+
+    /// Sets the merge operator.
+    #[allow(clippy::expect_used)]
+    pub fn set_operator(&self, operator: Arc<dyn MergeOperator>) {
+        *self.operator.write().expect("lock is poisoned") = Some(operator);
+    }
+
+    /// Marks the item with the given key and seqno as a merge operand.
+    ///
+    /// Needs to be called before the operand is inserted into the memtable,
+    /// otherwise it may be read as a regular value.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn register(&self, key: UserKey, seqno: SeqNo) {
+        let mut lock = self.operands.write().expect("lock is poisoned");
+        lock.entry(key).or_default().insert(seqno, None);
+        self.has_operands.store(true, Ordering::Release);
+    }
+
+    /// Returns the merge operands, if there are any.
+    ///
+    /// The lock needs to be held while reading from the tree,
+    /// so a concurrent flush cannot replace an operand by its merged value unnoticed.
+    #[allow(clippy::expect_used)]
+    pub fn read_operands(&self) -> Option<RwLockReadGuard<'_, HashMap<UserKey, Operands>>> {
+        if !self.has_operands.load(Ordering::Acquire) {
+            return None;
+        }
+        Some(self.operands.read().expect("lock is poisoned"))
+    }
+
+    /// Stores the merged values of operands that are about to be flushed.
+    #[allow(clippy::expect_used)]
+    pub fn resolve(&self, key: &UserKey, resolved: Vec<(SeqNo, UserValue)>) {
+        let mut lock = self.operands.write().expect("lock is poisoned");
+
+        if let Some(operands) = lock.get_mut(key) {
+            for (seqno, value) in resolved {
+                operands.insert(seqno, Some(value));
+            }
+        }
+    }
+
+    /// Replaces the merge operands by the ones of `other`.
+    ///
+    /// `swap_memtable` is called while no reads are in progress, to replace
+    /// the memtable that holds the operands.
+    #[allow(clippy::expect_used)]
+    pub fn replace_operands<F: FnOnce()>(&self, other: &Self, swap_memtable: F) {
+        let mut lock = self.operands.write().expect("lock is poisoned");
+
+        let operands = std::mem::take(&mut *other.operands.write().expect("lock is poisoned"));
+        self.has_operands.store(true, Ordering::Release);
+
+        swap_memtable();
+
+        *lock = operands;
+        self.has_operands.store(!lock.is_empty(), Ordering::Release);
+    }
+
+    /// Forgets the operands of a memtable that has been flushed (or discarded).
+    #[allow(clippy::expect_used)]
+    pub fn remove_memtable(&self, memtable: &Memtable) {
+        if !self.has_operands.load(Ordering::Acquire) {
+            return;
+        }
+
+        let mut lock = self.operands.write().expect("lock is poisoned");
+
+        for item in memtable.iter() {
+            if let Some(operands) = lock.get_mut(&item.key.user_key) {
+                operands.remove(&item.key.seqno);
+
+                if operands.is_empty() {
+                    lock.remove(&item.key.user_key);
+                }
+            }
+        }
+
+        if lock.is_empty() {
+            self.has_operands.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Merges the operands of a key into a single value.
+pub fn merge_operands(
+    operator: Option<&Arc<dyn MergeOperator>>,
+    key: &[u8],
+    existing: Option<&[u8]>,
+    operands: &[UserValue],
+) -> crate::Result<UserValue> {
+    let Some(operator) = operator else {
+        return Err(crate::Error::MissingMergeOperator);
+    };
+
+    // NOTE: Operands are collected from newest to oldest
+    let operands = operands.iter().rev().map(|x| &**x).collect::<Vec<_>>();
+
+    Ok(operator.merge(key, existing, &operands).into())
+}
+
+/// Creates a memtable item with a merged value, replacing a merge operand.
+#[allow(clippy::expect_used)]
+pub fn merged_item(tree: &AnyTree, item: &InternalValue, value: &UserValue) -> InternalValue {
+    let value: UserValue = match tree {
+        AnyTree::Standard(_) => value.clone(),
+        AnyTree::Blob(_) => {
+            // NOTE: The blob tree's memtable stores inline values,
+            // they are separated into blob files when flushing
+            lsm_tree::blob_tree::value::MaybeInlineValue::Inline(value.clone())
+                .encode_into_vec()
+                .expect("should serialize")
+                .into()
+        }
+    };
+
+    InternalValue::from_components(
+        item.key.user_key.clone(),
+        value,
+        item.key.seqno,
+        lsm_tree::ValueType::Value,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    struct Concat;
+
+    impl MergeOperator for Concat {
+        fn merge(&self, _: &[u8], existing: Option<&[u8]>, operands: &[&[u8]]) -> Vec<u8> {
+            let mut value = existing.unwrap_or_default().to_vec();
+
+            for operand in operands {
+                value.extend_from_slice(operand);
+            }
+
+            value
+        }
+    }
+
+    #[test]
+    fn merge_state_register_remove() {
+        let state = MergeState::new(Some(Arc::new(Concat)));
+        assert!(state.read_operands().is_none());
+
+        let memtable = Memtable::default();
+
+        for seqno in [1, 2] {
+            state.register((*b"a").into(), seqno);
+            memtable.insert(InternalValue::from_components(
+                *b"a",
+                *b"x",
+                seqno,
+                lsm_tree::ValueType::Value,
+            ));
+        }
+
+        assert_eq!(
+            2,
+            state
+                .read_operands()
+                .expect("should have operands")
+                .get(&b"a"[..])
+                .expect("should exist")
+                .len()
+        );
+
+        state.remove_memtable(&memtable);
+        assert!(state.read_operands().is_none());
+    }
+
+    #[test]
+    fn merge_operands_order() -> crate::Result<()> {
+        let operator: Arc<dyn MergeOperator> = Arc::new(Concat);
+
+        let value = merge_operands(
+            Some(&operator),
+            b"a",
+            Some(b"1"),
+            &[(*b"3").into(), (*b"2").into()],
+        )?;
+        assert_eq!(&*value, b"123");
+
+        assert!(matches!(
+            merge_operands(None, b"a", None, &[]),
+            Err(crate::Error::MissingMergeOperator)
+        ));
+
+        Ok(())
+    }
+}
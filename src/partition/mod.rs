@@ -7,28 +7,40 @@ pub mod options;
 mod write_delay;
 
 use crate::{
-    batch::PartitionKey,
-    compaction::manager::CompactionManager,
+    batch::{
+        item::{ExpiryItem, Item as BatchItem, RangeTombstoneItem},
+        PartitionKey,
+    },
+    compaction::{filter::FilterSlot, manager::CompactionManager},
     config::Config as KeyspaceConfig,
+    cursor::Cursor,
+    encryption::encrypt_file,
+    expiry::{expires_at, ExpiryIndex},
     file::{LSM_MANIFEST_FILE, PARTITIONS_FOLDER, PARTITION_CONFIG_FILE, PARTITION_DELETED_MARKER},
     flush::manager::{FlushManager, Task as FlushTask},
-    gc::GarbageCollection,
+    fs::FileSystem,
+    gc::{GarbageCollection, GarbageCollector},
     journal::{
         manager::{EvictionWatermark, JournalManager},
         Journal,
     },
     keyspace::Partitions,
+    large_value::{LargeValues, PendingValue},
+    limits::{check_item, check_key},
+    merge::MergeState,
+    range_tombstone::{range_to_bounds, RangeTombstone, RangeTombstones},
+    read_filter::ReadFilter,
     snapshot_nonce::SnapshotNonce,
     snapshot_tracker::SnapshotTracker,
+    subscription::ChangeKind,
     write_buffer_manager::WriteBufferManager,
     Error, Keyspace,
 };
 use lsm_tree::{
-    AbstractTree, AnyTree, GcReport, KvPair, SequenceNumberCounter, UserKey, UserValue,
+    AbstractTree, AnyTree, GcReport, KvPair, SeqNo, SequenceNumberCounter, UserKey, UserValue,
 };
 use options::CreateOptions;
 use std::{
-    fs::File,
     ops::RangeBounds,
     path::Path,
     sync::{atomic::AtomicBool, Arc, RwLock},
@@ -58,6 +70,21 @@ pub struct PartitionHandleInner {
     #[doc(hidden)]
     pub tree: AnyTree,
 
+    /// Range tombstones of the partition
+    pub(crate) range_tombstones: Arc<RangeTombstones>,
+
+    /// Merge operator and merge operands of the partition
+    pub(crate) merges: Arc<MergeState>,
+
+    /// Expiry timestamps of items of the partition
+    pub(crate) expiry: Arc<ExpiryIndex>,
+
+    /// Values of the partition that are too large for the journal and LSM-tree
+    pub(crate) large_values: Arc<LargeValues>,
+
+    /// Compaction filter of the partition
+    pub(crate) compaction_filter: FilterSlot,
+
     // Keyspace stuff
     //
     /// Config of keyspace
@@ -91,6 +118,16 @@ pub struct PartitionHandleInner {
 
     /// Snapshot tracker
     pub(crate) snapshot_tracker: SnapshotTracker,
+
+    /// Pauses changes to disk segments while a checkpoint is created
+    pub(crate) maintenance_lock: Arc<RwLock<()>>,
+
+    /// Writers hold the read lock while assigning a seqno and inserting into the memtable
+    ///
+    /// Background writes that depend on the latest version of a key
+    /// (e.g. tombstones of expired items) hold the write lock,
+    /// so no write can slip in between reading and writing the key.
+    pub(crate) write_lock: RwLock<()>,
 }
 
 impl Drop for PartitionHandleInner {
@@ -117,7 +154,8 @@ impl Drop for PartitionHandleInner {
                         if let Err(e) = std::fs::remove_file(manifest_file) {
                             log::error!("Failed to cleanup partition manifest at {path:?}: {e}");
                         } else {
-                            if let Err(e) = std::fs::remove_dir_all(path) {
+                            if let Err(e) = remove_partition_folder(&*self.keyspace_config.fs, path)
+                            {
                                 log::error!(
                                     "Failed to cleanup deleted partition's folder at {path:?}: {e}"
                                 );
@@ -136,6 +174,22 @@ impl Drop for PartitionHandleInner {
     }
 }
 
+/// Removes the folder of a partition.
+///
+/// The LSM-tree is stored in the operating system's file system,
+/// the partition's config and deletion marker in the keyspace's file system.
+pub fn remove_partition_folder(fs: &dyn FileSystem, path: &Path) -> std::io::Result<()> {
+    if path.try_exists()? {
+        std::fs::remove_dir_all(path)?;
+    }
+
+    if fs.exists(path)? {
+        fs.remove_dir_all(path)?;
+    }
+
+    Ok(())
+}
+
 /// Access to a keyspace partition
 ///
 /// Each partition is backed by an LSM-tree to provide a
@@ -196,10 +250,18 @@ impl PartitionHandle {
         tree: AnyTree,
         name: PartitionKey,
         config: CreateOptions,
+        range_tombstones: RangeTombstones,
+        expiry: ExpiryIndex,
+        large_values: LargeValues,
     ) -> Self {
         Self(Arc::new(PartitionHandleInner {
             name,
             tree,
+            range_tombstones: Arc::new(range_tombstones),
+            merges: Arc::default(),
+            expiry: Arc::new(expiry),
+            large_values: Arc::new(large_values),
+            compaction_filter: FilterSlot::default(),
             partitions: keyspace.partitions.clone(),
             keyspace_config: keyspace.config.clone(),
             flush_manager: keyspace.flush_manager.clone(),
@@ -212,6 +274,8 @@ impl PartitionHandle {
             is_deleted: AtomicBool::default(),
             is_poisoned: keyspace.is_poisoned.clone(),
             snapshot_tracker: keyspace.snapshot_tracker.clone(),
+            maintenance_lock: keyspace.maintenance_lock.clone(),
+            write_lock: RwLock::default(),
             config,
         }))
     }
@@ -223,21 +287,28 @@ impl PartitionHandle {
         config: CreateOptions,
     ) -> crate::Result<Self> {
         use lsm_tree::coding::Encode;
+        use std::io::Write;
 
         log::debug!("Creating partition {name:?}");
 
         let base_folder = keyspace.config.path.join(PARTITIONS_FOLDER).join(&*name);
 
-        if base_folder.join(PARTITION_DELETED_MARKER).try_exists()? {
+        let fs = &*keyspace.config.fs;
+
+        if fs.exists(&base_folder.join(PARTITION_DELETED_MARKER))? {
             log::error!("Failed to open partition, partition is deleted.");
             return Err(Error::PartitionDeleted);
         }
 
-        std::fs::create_dir_all(&base_folder)?;
+        fs.create_dir_all(&base_folder)?;
 
         // Write config
-        let mut file = File::create(base_folder.join(PARTITION_CONFIG_FILE))?;
-        config.encode_into(&mut file)?;
+        let config_bytes = encrypt_file(
+            keyspace.config.journal_encryption.as_deref(),
+            config.encode_into_vec()?,
+        )?;
+        let mut file = fs.create(&base_folder.join(PARTITION_CONFIG_FILE))?;
+        file.write_all(&config_bytes)?;
         file.sync_all()?;
 
         let mut base_config = lsm_tree::Config::new(base_folder)
@@ -268,6 +339,13 @@ impl PartitionHandle {
 
         Ok(Self(Arc::new(PartitionHandleInner {
             name,
+            merges: Arc::new(MergeState::new(config.merge_operator.clone())),
+            expiry: Arc::new(ExpiryIndex::new(tree.tree_config())),
+            large_values: Arc::new(LargeValues::new(
+                &tree.tree_config().path,
+                keyspace.config.large_value_threshold,
+            )),
+            compaction_filter: FilterSlot::new(config.compaction_filter.clone()),
             config,
             partitions: keyspace.partitions.clone(),
             keyspace_config: keyspace.config.clone(),
@@ -278,10 +356,13 @@ impl PartitionHandle {
             compaction_manager: keyspace.compaction_manager.clone(),
             seqno: keyspace.seqno.clone(),
             tree,
+            range_tombstones: Arc::default(),
             write_buffer_manager: keyspace.write_buffer_manager.clone(),
             is_deleted: AtomicBool::default(),
             is_poisoned: keyspace.is_poisoned.clone(),
             snapshot_tracker: keyspace.snapshot_tracker.clone(),
+            maintenance_lock: keyspace.maintenance_lock.clone(),
+            write_lock: RwLock::default(),
         })))
     }
 
@@ -307,7 +388,7 @@ impl PartitionHandle {
     /// ```
     #[must_use]
     pub fn disk_space(&self) -> u64 {
-        self.tree.disk_space()
+        self.tree.disk_space() + self.large_values.disk_space()
     }
 
     /// Returns an iterator that scans through the entire partition.
@@ -331,7 +412,8 @@ impl PartitionHandle {
     /// ```
     #[must_use]
     pub fn iter(&self) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
-        self.tree.iter().map(|item| item.map_err(Into::into))
+        self.read_filter(None)
+            .apply_kv(self.tree.iter().map(|item| item.map_err(Into::into)))
     }
 
     /// Returns an iterator that scans through the entire partition, returning only keys.
@@ -339,7 +421,10 @@ impl PartitionHandle {
     /// Avoid using this function, or limit it as otherwise it may scan a lot of items.
     #[must_use]
     pub fn keys(&self) -> impl DoubleEndedIterator<Item = crate::Result<UserKey>> + 'static {
-        self.tree.keys().map(|item| item.map_err(Into::into))
+        self.read_filter(None).apply(
+            self.tree.keys().map(|item| item.map_err(Into::into)),
+            |key| key,
+        )
     }
 
     /// Returns an iterator that scans through the entire partition, returning only values.
@@ -347,7 +432,7 @@ impl PartitionHandle {
     /// Avoid using this function, or limit it as otherwise it may scan a lot of items.
     #[must_use]
     pub fn values(&self) -> impl DoubleEndedIterator<Item = crate::Result<UserValue>> + 'static {
-        self.tree.values().map(|item| item.map_err(Into::into))
+        self.iter().map(|item| item.map(|(_, value)| value))
     }
 
     /// Returns an iterator over a range of items.
@@ -373,7 +458,8 @@ impl PartitionHandle {
         &'a self,
         range: R,
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
-        self.tree.range(range).map(|item| item.map_err(Into::into))
+        self.read_filter(None)
+            .apply_kv(self.tree.range(range).map(|item| item.map_err(Into::into)))
     }
 
     /// Returns an iterator over a prefixed set of items.
@@ -399,9 +485,20 @@ impl PartitionHandle {
         &'a self,
         prefix: K,
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
-        self.tree
-            .prefix(prefix)
-            .map(|item| item.map_err(Into::into))
+        self.read_filter(None).apply_kv(
+            self.tree
+                .prefix(prefix)
+                .map(|item| item.map_err(Into::into)),
+        )
+    }
+
+    /// Opens a seekable cursor over a snapshot of the partition.
+    ///
+    /// See [`Cursor`](crate::Cursor) for more.
+    #[must_use]
+    pub fn cursor(&self) -> Cursor<'static> {
+        let snapshot = self.snapshot();
+        Cursor::new(move |bounds| snapshot.range(bounds))
     }
 
     /// Approximates the amount of items in the partition.
@@ -528,7 +625,11 @@ impl PartitionHandle {
     ///
     /// Will return `Err` if an IO error occurs.
     pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> crate::Result<bool> {
-        self.tree.contains_key(key).map_err(Into::into)
+        if !self.tree.contains_key(&key)? {
+            return Ok(false);
+        }
+
+        Ok(!self.read_filter(None).is_deleted(key.as_ref())?)
     }
 
     /// Retrieves an item from the partition.
@@ -553,7 +654,17 @@ impl PartitionHandle {
     ///
     /// Will return `Err` if an IO error occurs.
     pub fn get<K: AsRef<[u8]>>(&self, key: K) -> crate::Result<Option<lsm_tree::UserValue>> {
-        Ok(self.tree.get(key)?)
+        let Some(value) = self.tree.get(&key)? else {
+            return Ok(None);
+        };
+
+        let filter = self.read_filter(None);
+
+        if filter.is_deleted(key.as_ref())? {
+            return Ok(None);
+        }
+
+        Ok(Some(filter.resolve(key.as_ref(), value)?))
     }
 
     /// Returns the first key-value pair in the partition.
@@ -581,7 +692,7 @@ impl PartitionHandle {
     ///
     /// Will return `Err` if an IO error occurs.
     pub fn first_key_value(&self) -> crate::Result<Option<KvPair>> {
-        Ok(self.tree.first_key_value()?)
+        self.iter().next().transpose()
     }
 
     /// Returns the last key-value pair in the partition.
@@ -609,7 +720,7 @@ impl PartitionHandle {
     ///
     /// Will return `Err` if an IO error occurs.
     pub fn last_key_value(&self) -> crate::Result<Option<KvPair>> {
-        Ok(self.tree.last_key_value()?)
+        self.iter().next_back().transpose()
     }
 
     // NOTE: Used in tests
@@ -628,9 +739,33 @@ impl PartitionHandle {
         Ok(())
     }
 
+    // NOTE: Used in tests
+    //
+    // Evicts all old versions, so no snapshots may be open
+    #[doc(hidden)]
+    #[allow(clippy::expect_used)]
+    pub fn major_compact(&self) -> crate::Result<()> {
+        let _lock = self.maintenance_lock.read().expect("lock is poisoned");
+
+        let strategy = Arc::new(crate::compaction::major::Strategy);
+
+        self.apply_compaction_filter(&*strategy)?;
+
+        // NOTE: Tombstones are dropped in the last level, so the versions
+        // they cover need to be dropped as well
+        self.tree.compact(strategy, SeqNo::MAX)?;
+
+        self.collect_large_values()
+    }
+
     /// Returns `true` if the memtable was indeed rotated.
     #[doc(hidden)]
     pub fn rotate_memtable(&self) -> crate::Result<bool> {
+        // NOTE: Rotating the memtable would seal the primary's journal
+        if self.keyspace_config.read_only {
+            return Err(crate::Error::ReadOnly);
+        }
+
         log::debug!("Rotating memtable {:?}", self.name);
 
         log::trace!("partition: acquiring journal lock");
@@ -645,24 +780,29 @@ impl PartitionHandle {
         log::trace!("partition: acquiring journal manager lock");
         let mut journal_manager = self.journal_manager.write().expect("lock is poisoned");
 
-        let seqno_map = {
-            let partitions = self.partitions.write().expect("lock is poisoned");
-
-            let mut seqnos = Vec::with_capacity(partitions.len());
-
-            for partition in partitions.values() {
-                if let Some(lsn) = partition.tree.get_highest_memtable_seqno() {
-                    seqnos.push(EvictionWatermark {
-                        lsn,
-                        partition: partition.clone(),
-                    });
+        // NOTE: Unlogged partitions have no data in the journal,
+        // so there is no need to seal it
+        if self.config.journaling {
+            let seqno_map = {
+                let partitions = self.partitions.write().expect("lock is poisoned");
+
+                let mut seqnos = Vec::with_capacity(partitions.len());
+
+                // NOTE: Unlogged partitions never hold back journal eviction
+                for partition in partitions.values().filter(|p| p.config.journaling) {
+                    if let Some(lsn) = partition.tree.get_highest_memtable_seqno() {
+                        seqnos.push(EvictionWatermark {
+                            lsn,
+                            partition: partition.clone(),
+                        });
+                    }
                 }
-            }
 
-            seqnos
-        };
+                seqnos
+            };
 
-        journal_manager.rotate_journal(&mut journal, seqno_map)?;
+            journal_manager.rotate_journal(&mut journal, seqno_map)?;
+        }
 
         log::trace!("partition: acquiring flush manager lock");
         let mut flush_manager = self.flush_manager.write().expect("lock is poisoned");
@@ -797,14 +937,51 @@ impl PartitionHandle {
         crate::Snapshot::new(
             self.tree.snapshot(seqno),
             SnapshotNonce::new(seqno, self.snapshot_tracker.clone()),
+            self.read_filter(Some(seqno)),
         )
     }
 
+    /// Returns a filter that hides items deleted by range tombstones or expired items,
+    /// and merges merge operands for reads at the given seqno.
+    pub(crate) fn read_filter(&self, seqno: Option<SeqNo>) -> ReadFilter {
+        ReadFilter::new(self, seqno, None)
+    }
+
+    /// Adds a range tombstone to the partition.
+    ///
+    /// The range tombstone needs to be durably journaled before calling this.
+    pub(crate) fn add_range_tombstone(&self, tombstone: RangeTombstone) -> crate::Result<()> {
+        // NOTE: Read-only keyspaces only add range tombstones when replaying the journal
+        if self.keyspace_config.read_only {
+            self.range_tombstones.insert_in_memory(tombstone);
+            return Ok(());
+        }
+
+        if let Err(e) =
+            self.range_tombstones
+                .insert(&*self.keyspace_config.fs, self.path(), tombstone)
+        {
+            self.is_poisoned
+                .store(true, std::sync::atomic::Ordering::Release);
+
+            log::error!(
+                "persisting range tombstones failed, which is a FATAL, and possibly hardware-related, failure: {e:?}"
+            );
+
+            return Err(crate::Error::Poisoned);
+        }
+
+        Ok(())
+    }
+
     /// Inserts a key-value pair into the partition.
     ///
-    /// Keys may be up to 65536 bytes long, values up to 2^32 bytes.
+    /// Keys may be up to 65535 bytes long.
     /// Shorter keys and values result in better performance.
     ///
+    /// Values of 4 GiB or more cannot be stored in the journal or the LSM-tree
+    /// (not even using key-value separation), so they are written to their own files instead.
+    ///
     /// If the key already exists, the item will be overwritten.
     ///
     /// # Examples
@@ -824,7 +1001,12 @@ impl PartitionHandle {
     ///
     /// # Errors
     ///
-    /// Will return `Err` if an IO error occurs.
+    /// Will return `Err` if an IO error occurs, or the key is too large.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used)]
     pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> crate::Result<()> {
         if self.is_deleted.load(std::sync::atomic::Ordering::Relaxed) {
             return Err(crate::Error::PartitionDeleted);
@@ -834,22 +1016,52 @@ impl PartitionHandle {
             return Err(crate::Error::Poisoned);
         }
 
+        if self.keyspace_config.rejects_writes() {
+            return Err(crate::Error::ReadOnly);
+        }
+
         let key = key.as_ref();
         let value = value.as_ref();
+        check_key(key)?;
+
+        let large_value = self.write_large_value(key, value)?;
+        let stored_value = large_value.as_ref().map_or(value, |x| &x.reference);
+
+        let mut journal_writer = self.journal.get_writer_for(self.config.journaling);
+
+        // IMPORTANT: The seqno needs to be assigned while holding the write lock,
+        // otherwise a background write may not see this write, see `write_lock`
+        let write_lock = self.write_lock.read().expect("lock is poisoned");
         let seqno = self.seqno.next();
 
-        let mut journal_writer = self.journal.get_writer();
+        if let Some(journal_writer) = journal_writer.as_mut().filter(|_| self.config.journaling) {
+            journal_writer.write_raw(
+                &self.name,
+                key,
+                stored_value,
+                lsm_tree::ValueType::Value,
+                seqno,
+            )?;
+
+            if !self.config.manual_journal_persist {
+                journal_writer.flush(crate::PersistMode::Buffer)?;
+            }
+        }
 
-        journal_writer.write_raw(&self.name, key, value, lsm_tree::ValueType::Value, seqno)?;
+        let (item_size, memtable_size) = self.tree.insert(key, stored_value, seqno);
 
-        if !self.config.manual_journal_persist {
-            journal_writer.flush(crate::PersistMode::Buffer)?;
+        if let Some(large_value) = large_value {
+            large_value.commit();
         }
+        drop(write_lock);
 
+        // NOTE: Subscribers are notified while holding the journal lock,
+        // so they receive writes in commit order, once they are visible
+        if let Some(journal_writer) = &mut journal_writer {
+            journal_writer.publish_item(&self.name, key, value, ChangeKind::Value, seqno);
+        }
         drop(journal_writer);
 
-        let (item_size, memtable_size) = self.tree.insert(key, value, seqno);
-
         let write_buffer_size = self.write_buffer_manager.allocate(u64::from(item_size));
 
         self.check_memtable_overflow(memtable_size)?;
@@ -859,9 +1071,258 @@ impl PartitionHandle {
         Ok(())
     }
 
+    /// Inserts a key-value pair into the partition, which expires after the given duration.
+    ///
+    /// Expired items are invisible to reads immediately,
+    /// and are physically removed by compaction.
+    ///
+    /// Overwriting or removing the key discards the expiry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, Keyspace, PartitionCreateOptions};
+    /// # use std::time::Duration;
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(folder).open()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// partition.insert_with_ttl("session", "abc", Duration::from_secs(60))?;
+    /// assert!(partition.contains_key("session")?);
+    ///
+    /// partition.insert_with_ttl("session", "abc", Duration::ZERO)?;
+    /// assert!(!partition.contains_key("session")?);
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the key is too large.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used)]
+    pub fn insert_with_ttl<K: AsRef<[u8]>, V: AsRef<[u8]>>(
+        &self,
+        key: K,
+        value: V,
+        ttl: Duration,
+    ) -> crate::Result<()> {
+        if self.is_deleted.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::PartitionDeleted);
+        }
+
+        if self.is_poisoned.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::Poisoned);
+        }
+
+        if self.keyspace_config.rejects_writes() {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        let key = key.as_ref();
+        let value = value.as_ref();
+        check_key(key)?;
+
+        let large_value = self.write_large_value(key, value)?;
+        let stored_value = large_value.as_ref().map_or(value, |x| &x.reference);
+
+        let expires_at = expires_at(ttl);
+        let mut journal_writer = self.journal.get_writer_for(self.config.journaling);
+
+        let write_lock = self.write_lock.read().expect("lock is poisoned");
+        let seqno = self.seqno.next();
+
+        // IMPORTANT: Index the expiry before the item becomes visible
+        self.expiry.insert(key, seqno, expires_at)?;
+
+        if let Some(journal_writer) = journal_writer.as_mut().filter(|_| self.config.journaling) {
+            journal_writer.write_batch(
+                &[&BatchItem::new(
+                    self.name.clone(),
+                    key,
+                    stored_value,
+                    lsm_tree::ValueType::Value,
+                )],
+                &[],
+                &[ExpiryItem {
+                    partition: self.name.clone(),
+                    key: key.into(),
+                    expires_at,
+                }],
+                seqno,
+            )?;
+
+            if !self.config.manual_journal_persist {
+                journal_writer.flush(crate::PersistMode::Buffer)?;
+            }
+        }
+
+        let (item_size, memtable_size) = self.tree.insert(key, stored_value, seqno);
+
+        if let Some(large_value) = large_value {
+            large_value.commit();
+        }
+        drop(write_lock);
+
+        if let Some(journal_writer) = &mut journal_writer {
+            journal_writer.publish_item(&self.name, key, value, ChangeKind::Value, seqno);
+        }
+        drop(journal_writer);
+
+        let write_buffer_size = self.write_buffer_manager.allocate(u64::from(item_size));
+
+        self.check_memtable_overflow(memtable_size)?;
+        self.check_write_buffer_size(write_buffer_size);
+
+        Ok(())
+    }
+
+    /// Writes the value to its own file, if it is too large to be stored in the tree.
+    ///
+    /// The returned reference is stored in the journal and tree instead of the value.
+    pub(crate) fn write_large_value(
+        &self,
+        key: &[u8],
+        value: &[u8],
+    ) -> crate::Result<Option<PendingValue>> {
+        if !self.large_values.is_large(value) {
+            return Ok(None);
+        }
+
+        self.large_values.write(key, value).map(Some)
+    }
+
+    /// Deletes the files of large values that are no longer referenced.
+    ///
+    /// Called after compacting the partition, which drops old versions of the keys.
+    #[allow(clippy::expect_used)]
+    pub(crate) fn collect_large_values(&self) -> crate::Result<()> {
+        if self.large_values.is_empty() {
+            return Ok(());
+        }
+
+        let _lock = self.write_lock.write().expect("lock is poisoned");
+
+        self.large_values.collect_garbage(
+            &self.tree,
+            &self.merges,
+            self.snapshot_tracker.get_seqno_safe_to_gc(),
+        )
+    }
+
+    /// Removes items that have expired, by writing tombstones for them.
+    ///
+    /// Called before compacting the partition, so the compaction can drop them.
+    pub(crate) fn remove_expired(&self) -> crate::Result<()> {
+        let written_bytes = self.expiry.sweep(
+            &self.tree,
+            &self.write_lock,
+            &self.seqno,
+            self.snapshot_tracker.get_seqno_safe_to_gc(),
+        )?;
+
+        if written_bytes > 0 {
+            let write_buffer_size = self.write_buffer_manager.allocate(written_bytes);
+            self.check_memtable_overflow(self.tree.active_memtable_size())?;
+            self.check_write_buffer_size(write_buffer_size);
+        }
+
+        Ok(())
+    }
+
+    /// Applies the compaction filter to the segments the compaction strategy is about to merge.
+    ///
+    /// Called before compacting the partition, so the compaction can drop the filtered items.
+    pub(crate) fn apply_compaction_filter(
+        &self,
+        strategy: &dyn lsm_tree::compaction::CompactionStrategy,
+    ) -> crate::Result<()> {
+        // NOTE: Replicas may only write seqnos given out by their primary
+        if self.keyspace_config.replica {
+            return Ok(());
+        }
+
+        let Some(filter) = self.compaction_filter.get() else {
+            return Ok(());
+        };
+
+        let segments = crate::compaction::filter::compaction_input(&self.tree, strategy);
+
+        let written_bytes = crate::compaction::filter::filter_segments(
+            &self.tree,
+            &*filter,
+            &self.large_values,
+            &segments,
+            &self.write_lock,
+            &self.seqno,
+        )?;
+
+        if written_bytes > 0 {
+            let write_buffer_size = self.write_buffer_manager.allocate(written_bytes);
+            self.check_memtable_overflow(self.tree.active_memtable_size())?;
+            self.check_write_buffer_size(write_buffer_size);
+        }
+
+        Ok(())
+    }
+
+    /// Deletes items that are covered by range tombstones using point tombstones,
+    /// and removes range tombstones that are not needed anymore.
+    ///
+    /// Called before compacting the partition, so the compaction can drop the covered items.
+    pub(crate) fn prune_range_tombstones(&self) -> crate::Result<()> {
+        // NOTE: Replicas may only write seqnos given out by their primary
+        if self.keyspace_config.replica {
+            return Ok(());
+        }
+
+        let mut written_bytes = 0;
+
+        // NOTE: Snapshots that were opened before a range tombstone don't see the point tombstones,
+        // so the covered items can be deleted right away
+        for tombstone in self.range_tombstones.get().iter() {
+            if self.range_tombstones.is_materialized(tombstone) {
+                continue;
+            }
+
+            let (size, highest_seqno) = crate::range_tombstone::materialize(
+                &self.tree,
+                &self.write_lock,
+                &self.seqno,
+                tombstone,
+            )?;
+
+            written_bytes += size;
+
+            self.range_tombstones.set_materialized(
+                tombstone.clone(),
+                self.seqno.get().saturating_sub(1),
+                highest_seqno,
+            );
+        }
+
+        self.range_tombstones.prune(
+            &*self.keyspace_config.fs,
+            self.path(),
+            self.tree.get_highest_persisted_seqno(),
+            |seqno| self.snapshot_tracker.has_open_snapshot_before(seqno),
+        )?;
+
+        if written_bytes > 0 {
+            let write_buffer_size = self.write_buffer_manager.allocate(written_bytes);
+            self.check_memtable_overflow(self.tree.active_memtable_size())?;
+            self.check_write_buffer_size(write_buffer_size);
+        }
+
+        Ok(())
+    }
+
     /// Removes an item from the partition.
     ///
-    /// The key may be up to 65536 bytes long.
+    /// The key may be up to 65535 bytes long.
     /// Shorter keys result in better performance.
     ///
     /// # Examples
@@ -887,7 +1348,12 @@ impl PartitionHandle {
     ///
     /// # Errors
     ///
-    /// Will return `Err` if an IO error occurs.
+    /// Will return `Err` if an IO error occurs, or the key is too large.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used)]
     pub fn remove<K: AsRef<[u8]>>(&self, key: K) -> crate::Result<()> {
         if self.is_deleted.load(std::sync::atomic::Ordering::Relaxed) {
             return Err(crate::Error::PartitionDeleted);
@@ -897,20 +1363,144 @@ impl PartitionHandle {
             return Err(crate::Error::Poisoned);
         }
 
+        if self.keyspace_config.rejects_writes() {
+            return Err(crate::Error::ReadOnly);
+        }
+
         let key = key.as_ref();
-        let seqno = self.seqno.next();
+        check_key(key)?;
 
-        let mut journal_writer = self.journal.get_writer();
+        let mut journal_writer = self.journal.get_writer_for(self.config.journaling);
 
-        journal_writer.write_raw(&self.name, key, &[], lsm_tree::ValueType::Tombstone, seqno)?;
+        let write_lock = self.write_lock.read().expect("lock is poisoned");
+        let seqno = self.seqno.next();
 
-        if !self.config.manual_journal_persist {
-            journal_writer.flush(crate::PersistMode::Buffer)?;
+        if let Some(journal_writer) = journal_writer.as_mut().filter(|_| self.config.journaling) {
+            journal_writer.write_raw(
+                &self.name,
+                key,
+                &[],
+                lsm_tree::ValueType::Tombstone,
+                seqno,
+            )?;
+
+            if !self.config.manual_journal_persist {
+                journal_writer.flush(crate::PersistMode::Buffer)?;
+            }
         }
 
+        let (item_size, memtable_size) = self.tree.remove(key, seqno);
+        drop(write_lock);
+
+        if let Some(journal_writer) = &mut journal_writer {
+            journal_writer.publish_item(&self.name, key, &[], ChangeKind::Tombstone, seqno);
+        }
         drop(journal_writer);
 
-        let (item_size, memtable_size) = self.tree.remove(key, seqno);
+        let write_buffer_size = self.write_buffer_manager.allocate(u64::from(item_size));
+
+        self.check_memtable_overflow(memtable_size)?;
+        self.check_write_buffer_size(write_buffer_size);
+
+        Ok(())
+    }
+
+    /// Writes a merge operand for a key.
+    ///
+    /// Instead of reading the current value, modifying and writing it back,
+    /// the operand is written as is, and combined with the existing value
+    /// using the partition's [`MergeOperator`](crate::MergeOperator) when the key is read,
+    /// or when the memtable is flushed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, MergeOperator, PartitionCreateOptions};
+    /// # use std::sync::Arc;
+    /// #
+    /// struct Append;
+    ///
+    /// impl MergeOperator for Append {
+    ///     fn merge(&self, _key: &[u8], existing: Option<&[u8]>, operands: &[&[u8]]) -> Vec<u8> {
+    ///         let mut value = existing.unwrap_or_default().to_vec();
+    ///
+    ///         for operand in operands {
+    ///             value.extend_from_slice(operand);
+    ///         }
+    ///
+    ///         value
+    ///     }
+    /// }
+    ///
+    /// # let folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(folder).open()?;
+    /// let partition = keyspace.open_partition(
+    ///     "default",
+    ///     PartitionCreateOptions::default().with_merge_operator(Arc::new(Append)),
+    /// )?;
+    ///
+    /// partition.insert("a", "abc")?;
+    /// partition.merge("a", "def")?;
+    ///
+    /// assert_eq!(b"abcdef", &*partition.get("a")?.expect("should exist"));
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, the key or operand is too large,
+    /// or the partition has no merge operator.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used)]
+    pub fn merge<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, operand: V) -> crate::Result<()> {
+        if self.is_deleted.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::PartitionDeleted);
+        }
+
+        if self.is_poisoned.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::Poisoned);
+        }
+
+        if self.keyspace_config.rejects_writes() {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        if self.merges.operator().is_none() {
+            return Err(crate::Error::MissingMergeOperator);
+        }
+
+        let key = key.as_ref();
+        let operand = operand.as_ref();
+        check_item(key, operand)?;
+
+        let mut journal_writer = self.journal.get_writer_for(self.config.journaling);
+
+        let write_lock = self.write_lock.read().expect("lock is poisoned");
+        let seqno = self.seqno.next();
+
+        if let Some(journal_writer) = journal_writer.as_mut().filter(|_| self.config.journaling) {
+            journal_writer.write_merge(&self.name, key, operand, seqno)?;
+
+            if !self.config.manual_journal_persist {
+                journal_writer.flush(crate::PersistMode::Buffer)?;
+            }
+        }
+
+        // IMPORTANT: Register the operand before inserting it,
+        // otherwise a concurrent read may mistake it for a regular value
+        self.merges.register(key.into(), seqno);
+
+        let (item_size, memtable_size) = self.tree.insert(key, operand, seqno);
+        drop(write_lock);
+
+        if let Some(journal_writer) = &mut journal_writer {
+            journal_writer.publish_item(&self.name, key, operand, ChangeKind::MergeOperand, seqno);
+        }
+        drop(journal_writer);
 
         let write_buffer_size = self.write_buffer_manager.allocate(u64::from(item_size));
 
@@ -919,4 +1509,210 @@ impl PartitionHandle {
 
         Ok(())
     }
+
+    /// Removes all items in the given key range from the partition.
+    ///
+    /// The items are deleted using a single range tombstone, so this operation
+    /// has O(1) write cost, regardless of how many items are deleted.
+    ///
+    /// Deleted items are hidden from reads immediately. Covered items that are
+    /// still in memory are dropped when their memtable is flushed; items that already
+    /// have been flushed to disk are deleted by compaction, once no snapshot can see them anymore.
+    ///
+    /// Because range tombstones are rare and affect a lot of data,
+    /// the journal is always synced to disk.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, Keyspace, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(folder).open()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// partition.insert("a", "abc")?;
+    /// partition.insert("b", "abc")?;
+    /// partition.insert("c", "abc")?;
+    ///
+    /// partition.remove_range("a".."c")?;
+    ///
+    /// assert_eq!(1, partition.len()?);
+    /// assert!(partition.contains_key("c")?);
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or a bound of the range is too large.
+    pub fn remove_range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, range: R) -> crate::Result<()> {
+        if self.is_deleted.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::PartitionDeleted);
+        }
+
+        if self.is_poisoned.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::Poisoned);
+        }
+
+        if self.keyspace_config.rejects_writes() {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        let Some((start, end)) = range_to_bounds(&range) else {
+            return Ok(());
+        };
+
+        check_key(&start)?;
+        if let Some(end) = &end {
+            check_key(end)?;
+        }
+
+        self.write_range_tombstone(start, end)?;
+
+        Ok(())
+    }
+
+    /// Removes all items from the partition, keeping its handle and configuration.
+    ///
+    /// The partition is emptied atomically by writing a single range tombstone over
+    /// the whole key space, so neither reads nor journal replay can observe the old data.
+    /// Writes that happen after the clear are not affected.
+    ///
+    /// Afterwards, the memtables are flushed and all segments (and blob files, if the
+    /// partition uses key-value separation) that only contain cleared data are dropped.
+    ///
+    /// If a snapshot that was opened before the clear is still open, disk space
+    /// is not reclaimed, because the snapshot may still read the old data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, Keyspace, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(folder).open()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// partition.insert("a", "abc")?;
+    /// partition.insert("b", "abc")?;
+    ///
+    /// partition.clear()?;
+    /// assert!(partition.is_empty()?);
+    ///
+    /// partition.insert("c", "abc")?;
+    /// assert_eq!(1, partition.len()?);
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used)]
+    pub fn clear(&self) -> crate::Result<()> {
+        if self.is_deleted.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::PartitionDeleted);
+        }
+
+        if self.is_poisoned.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(crate::Error::Poisoned);
+        }
+
+        if self.keyspace_config.rejects_writes() {
+            return Err(crate::Error::ReadOnly);
+        }
+
+        let seqno = self.write_range_tombstone(UserKey::from(&[][..]), None)?;
+
+        if self.snapshot_tracker.has_open_snapshot_before(seqno) {
+            log::debug!(
+                "Partition {:?} has snapshots older than the clear, not dropping data",
+                self.name
+            );
+            return Ok(());
+        }
+
+        // NOTE: Older range tombstones are covered by the new one,
+        // and there is no snapshot anymore that could see them on their own
+        self.range_tombstones
+            .retain(&*self.keyspace_config.fs, self.path(), |x| x.seqno >= seqno)?;
+
+        // NOTE: Flush memtables, so all cleared data ends up in segments that can be dropped
+        if self.keyspace_config.flush_workers_count > 0 {
+            self.rotate_memtable()?;
+
+            while self
+                .flush_manager
+                .read()
+                .expect("lock is poisoned")
+                .get_partitions_with_tasks()
+                .contains(&self.name)
+            {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        }
+
+        {
+            let _lock = self.maintenance_lock.read().expect("lock is poisoned");
+
+            self.tree.compact(
+                Arc::new(crate::compaction::clear::Strategy(seqno)),
+                self.snapshot_tracker.get_seqno_safe_to_gc(),
+            )?;
+        }
+
+        if let AnyTree::Blob(_) = &self.tree {
+            GarbageCollector::scan(self)?;
+            GarbageCollector::drop_stale_segments(self)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a range tombstone to the journal and adds it to the partition.
+    ///
+    /// Returns the seqno of the range tombstone.
+    fn write_range_tombstone(&self, start: UserKey, end: Option<UserKey>) -> crate::Result<SeqNo> {
+        let mut journal_writer = self.journal.get_writer_for(self.config.journaling);
+
+        let seqno = self.seqno.next();
+
+        if let Some(journal_writer) = journal_writer.as_mut().filter(|_| self.config.journaling) {
+            journal_writer.write_batch(
+                &[],
+                &[RangeTombstoneItem {
+                    partition: self.name.clone(),
+                    start: start.clone(),
+                    end: end.clone(),
+                }],
+                &[],
+                seqno,
+            )?;
+
+            // IMPORTANT: The journal needs to be durable before persisting the range tombstone,
+            // otherwise the range tombstone may survive a crash while its batch does not
+            journal_writer.flush(crate::PersistMode::SyncAll)?;
+        }
+
+        let tombstone = RangeTombstone { start, end, seqno };
+        self.add_range_tombstone(tombstone.clone())?;
+
+        // NOTE: Subscribers are notified while holding the journal lock,
+        // so they receive writes in commit order, once they are visible
+        if let Some(journal_writer) = &mut journal_writer {
+            journal_writer.publish_item(
+                &self.name,
+                &tombstone.start,
+                &[],
+                ChangeKind::RangeTombstone(tombstone.end),
+                seqno,
+            );
+        }
+        drop(journal_writer);
+
+        Ok(seqno)
+    }
 }
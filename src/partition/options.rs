@@ -2,9 +2,14 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{compaction::Strategy as CompactionStrategy, file::MAGIC_BYTES};
+use crate::{
+    compaction::{CompactionFilter, Strategy as CompactionStrategy},
+    file::MAGIC_BYTES,
+    MergeOperator,
+};
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use lsm_tree::{CompressionType, TreeType};
+use std::sync::Arc;
 
 /// Configuration options for key-value-separated partitions.
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -126,10 +131,17 @@ pub struct CreateOptions {
 
     pub(crate) manual_journal_persist: bool,
 
+    /// If `false`, writes bypass the journal
+    pub(crate) journaling: bool,
+
     #[doc(hidden)]
     pub compaction_strategy: CompactionStrategy,
 
     pub(crate) kv_separation: Option<KvSeparationOptions>,
+
+    pub(crate) merge_operator: Option<Arc<dyn MergeOperator>>,
+
+    pub(crate) compaction_filter: Option<Arc<dyn CompactionFilter>>,
 }
 
 impl lsm_tree::coding::Encode for CreateOptions {
@@ -186,6 +198,8 @@ impl lsm_tree::coding::Encode for CreateOptions {
             }
         }
 
+        writer.write_u8(u8::from(self.journaling))?;
+
         Ok(())
     }
 }
@@ -276,6 +290,13 @@ impl lsm_tree::coding::Decode for CreateOptions {
             }
         };
 
+        // NOTE: Partitions created before journaling was configurable are always journaled
+        let journaling = match reader.read_u8() {
+            Ok(flag) => flag != 0,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => true,
+            Err(e) => return Err(e.into()),
+        };
+
         Ok(Self {
             max_memtable_size,
             data_block_size,
@@ -285,8 +306,11 @@ impl lsm_tree::coding::Decode for CreateOptions {
             tree_type,
             compression,
             manual_journal_persist,
+            journaling,
             compaction_strategy,
             kv_separation,
+            merge_operator: None,
+            compaction_filter: None,
         })
     }
 }
@@ -297,6 +321,7 @@ impl Default for CreateOptions {
 
         Self {
             manual_journal_persist: false,
+            journaling: true,
 
             max_memtable_size: /* 16 MiB */ 16 * 1_024 * 1_024,
 
@@ -318,6 +343,9 @@ impl Default for CreateOptions {
 
             kv_separation: None,
 
+            merge_operator: None,
+            compaction_filter: None,
+
             compaction_strategy: CompactionStrategy::default(),
         }
     }
@@ -360,6 +388,25 @@ impl CreateOptions {
         self
     }
 
+    /// If `false`, writes to the partition bypass the journal and go straight to the memtable.
+    ///
+    /// This is useful for partitions holding data that can be rebuilt, such as caches
+    /// or materialized indexes, because they do not cause journal I/O and do not keep
+    /// journals from being evicted.
+    ///
+    /// Data that has not been flushed to disk yet is lost when the keyspace is closed or crashes.
+    /// Writes are still sent to [subscribers](crate::Keyspace::subscribe), but cannot be
+    /// replayed using [`Keyspace::subscribe_from`](crate::Keyspace::subscribe_from).
+    ///
+    /// Once set for a partition, this property is not considered in the future.
+    ///
+    /// Default = true
+    #[must_use]
+    pub fn journaling(mut self, flag: bool) -> Self {
+        self.journaling = flag;
+        self
+    }
+
     /// Sets the maximum memtable size.
     ///
     /// Default = 16 MiB
@@ -443,6 +490,36 @@ impl CreateOptions {
 
         self
     }
+
+    /// Sets the merge operator, which allows writing merge operands
+    /// using [`PartitionHandle::merge`](crate::PartitionHandle::merge).
+    ///
+    /// The merge operator is not persisted, so it needs to be set every time
+    /// the partition is opened (even if it already exists).
+    /// Until then, merge operands cannot be read or flushed.
+    ///
+    /// Default = none
+    #[must_use]
+    pub fn with_merge_operator(mut self, merge_operator: Arc<dyn MergeOperator>) -> Self {
+        self.merge_operator = Some(merge_operator);
+        self
+    }
+
+    /// Sets the compaction filter, which decides whether items are kept,
+    /// removed or changed when they are written to disk.
+    ///
+    /// The filter applies to items that are flushed or compacted after it has been set,
+    /// see [`CompactionFilter`] for details.
+    ///
+    /// The compaction filter is not persisted, so it needs to be set every time
+    /// the partition is opened (even if it already exists).
+    ///
+    /// Default = none
+    #[must_use]
+    pub fn with_compaction_filter(mut self, compaction_filter: Arc<dyn CompactionFilter>) -> Self {
+        self.compaction_filter = Some(compaction_filter);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,765 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    file::{MAGIC_BYTES, PARTITION_RANGE_TOMBSTONES_FILE},
+    fs::{read_file, FileSystem},
+    limits::MAX_KEY_SIZE,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use lsm_tree::{
+    coding::{Decode, DecodeError, Encode, EncodeError},
+    AbstractTree, AnyTree, InternalValue, Memtable, SeqNo, SequenceNumberCounter, UserKey,
+};
+use std::{
+    io::{Read, Write},
+    ops::{Bound, RangeBounds},
+    path::Path,
+    sync::{Arc, Mutex, RwLock},
+};
+
+/// Converts a range into the half-open `[start, end)` form used by range tombstones.
+///
+/// Returns `None` if the range is empty.
+pub fn range_to_bounds<K: AsRef<[u8]>, R: RangeBounds<K>>(
+    range: &R,
+) -> Option<(UserKey, Option<UserKey>)> {
+    let start: UserKey = match range.start_bound() {
+        Bound::Included(key) => key.as_ref().into(),
+        Bound::Excluded(key) => successor(key.as_ref())?,
+        Bound::Unbounded => UserKey::from(&[][..]),
+    };
+
+    let end: Option<UserKey> = match range.end_bound() {
+        Bound::Included(key) => successor(key.as_ref()),
+        Bound::Excluded(key) => Some(key.as_ref().into()),
+        Bound::Unbounded => None,
+    };
+
+    if end.as_ref().is_some_and(|end| *end <= start) {
+        return None;
+    }
+
+    Some((start, end))
+}
+
+/// Returns the smallest key that is greater than the given key.
+///
+/// Returns `None` if no storable key is greater than the given key.
+fn successor(key: &[u8]) -> Option<UserKey> {
+    // NOTE: Appending a byte would make a key of maximum size too large,
+    // but as no key can extend it, the next key is found by incrementing its last byte
+    // that can be incremented, and dropping the bytes after it
+    if key.len() != MAX_KEY_SIZE {
+        return Some([key, &[0]].concat().into());
+    }
+
+    let mut successor = key.to_vec();
+
+    while let Some(byte) = successor.pop() {
+        if byte < u8::MAX {
+            successor.push(byte + 1);
+            return Some(successor.into());
+        }
+    }
+
+    None
+}
+
+/// Marks all keys in `[start, end)` that were written before `seqno` as deleted
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RangeTombstone {
+    /// Inclusive lower bound
+    pub start: UserKey,
+
+    /// Exclusive upper bound, or unbounded if `None`
+    pub end: Option<UserKey>,
+
+    /// Items with a lower seqno are deleted
+    pub seqno: SeqNo,
+}
+
+impl RangeTombstone {
+    /// Returns `true` if the key is inside the tombstone's key range.
+    pub fn contains_key(&self, key: &[u8]) -> bool {
+        &*self.start <= key && self.end.as_ref().map_or(true, |end| key < &**end)
+    }
+
+    /// Returns `true` if this tombstone deletes everything the other tombstone deletes.
+    pub fn subsumes(&self, other: &Self) -> bool {
+        let contains_end = match (&self.end, &other.end) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(end), Some(other_end)) => other_end <= end,
+        };
+
+        self.seqno >= other.seqno && self.start <= other.start && contains_end
+    }
+
+    /// Returns `true` if the item is deleted by this tombstone.
+    pub fn covers(&self, item: &InternalValue) -> bool {
+        item.key.seqno < self.seqno && self.contains_key(&item.key.user_key)
+    }
+}
+
+impl Encode for RangeTombstone {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        writer.write_u64::<BigEndian>(self.seqno)?;
+
+        // NOTE: Truncation is okay, keys are limited to 16 bits
+        #[allow(clippy::cast_possible_truncation)]
+        writer.write_u16::<BigEndian>(self.start.len() as u16)?;
+        writer.write_all(&self.start)?;
+
+        match &self.end {
+            Some(end) => {
+                writer.write_u8(1)?;
+
+                // NOTE: Truncation is okay, keys are limited to 16 bits
+                #[allow(clippy::cast_possible_truncation)]
+                writer.write_u16::<BigEndian>(end.len() as u16)?;
+                writer.write_all(end)?;
+            }
+            None => {
+                writer.write_u8(0)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for RangeTombstone {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        let seqno = reader.read_u64::<BigEndian>()?;
+
+        let start_len = reader.read_u16::<BigEndian>()?;
+        let mut start = vec![0; start_len.into()];
+        reader.read_exact(&mut start)?;
+
+        let end = match reader.read_u8()? {
+            0 => None,
+            1 => {
+                let end_len = reader.read_u16::<BigEndian>()?;
+                let mut end = vec![0; end_len.into()];
+                reader.read_exact(&mut end)?;
+                Some(end.into())
+            }
+            tag => return Err(DecodeError::InvalidTag(("RangeTombstoneEnd", tag))),
+        };
+
+        Ok(Self {
+            start: start.into(),
+            end,
+            seqno,
+        })
+    }
+}
+
+/// Range tombstone whose covered items have been deleted using point tombstones
+struct Materialized {
+    tombstone: RangeTombstone,
+
+    /// Highest seqno that was given out when the point tombstones were written
+    ///
+    /// Reads above this seqno see all point tombstones.
+    seqno: SeqNo,
+
+    /// Highest seqno of the written point tombstones
+    highest_tombstone_seqno: Option<SeqNo>,
+}
+
+/// Range tombstones of a partition
+///
+/// Range tombstones are kept in memory and persisted in the partition folder,
+/// because the LSM-tree has no notion of them.
+///
+/// Items that are covered by a range tombstone are eventually deleted using point tombstones,
+/// so compaction can drop them. Once no snapshot needs the range tombstone anymore,
+/// it is pruned.
+#[derive(Default)]
+pub struct RangeTombstones {
+    tombstones: RwLock<Arc<Vec<RangeTombstone>>>,
+
+    /// Range tombstones that wait for their point tombstones to be persisted,
+    /// see [`RangeTombstones::prune`]
+    ///
+    /// Not persisted, so range tombstones are materialized again after a restart.
+    materialized: Mutex<Vec<Materialized>>,
+}
+
+impl RangeTombstones {
+    /// Loads the range tombstones of the partition in the given folder.
+    pub fn recover<P: AsRef<Path>>(fs: &dyn FileSystem, folder: P) -> crate::Result<Self> {
+        let path = folder.as_ref().join(PARTITION_RANGE_TOMBSTONES_FILE);
+
+        if !fs.exists(&path)? {
+            return Ok(Self::default());
+        }
+
+        let bytes = read_file(fs, &path)?;
+        let mut reader = &bytes[..];
+
+        let mut header = [0; MAGIC_BYTES.len()];
+        reader.read_exact(&mut header)?;
+
+        if header != MAGIC_BYTES {
+            return Err(DecodeError::InvalidHeader("RangeTombstones").into());
+        }
+
+        let count = reader.read_u32::<BigEndian>()?;
+
+        let tombstones = (0..count)
+            .map(|_| RangeTombstone::decode_from(&mut reader))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        log::debug!("Recovered {} range tombstones", tombstones.len());
+
+        Ok(Self {
+            tombstones: RwLock::new(Arc::new(tombstones)),
+            materialized: Mutex::default(),
+        })
+    }
+
+    /// Returns all range tombstones.
+    #[allow(clippy::expect_used)]
+    pub fn get(&self) -> Arc<Vec<RangeTombstone>> {
+        self.tombstones.read().expect("lock is poisoned").clone()
+    }
+
+    /// Returns the highest seqno of all range tombstones.
+    #[allow(clippy::expect_used)]
+    pub fn highest_seqno(&self) -> Option<SeqNo> {
+        self.tombstones
+            .read()
+            .expect("lock is poisoned")
+            .iter()
+            .map(|x| x.seqno)
+            .max()
+    }
+
+    /// Returns the highest seqno of all range tombstones that cover the entire key space.
+    ///
+    /// All data below that seqno is deleted.
+    #[allow(clippy::expect_used)]
+    pub fn cleared_seqno(&self) -> Option<SeqNo> {
+        self.tombstones
+            .read()
+            .expect("lock is poisoned")
+            .iter()
+            .filter(|x| x.start.is_empty() && x.end.is_none())
+            .map(|x| x.seqno)
+            .max()
+    }
+
+    /// Adds a range tombstone and persists the list to the partition folder.
+    ///
+    /// Adding a tombstone that is already covered by another tombstone
+    /// (e.g. during journal replay) is a no-op.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn insert<P: AsRef<Path>>(
+        &self,
+        fs: &dyn FileSystem,
+        folder: P,
+        tombstone: RangeTombstone,
+    ) -> crate::Result<()> {
+        // NOTE: Hold the lock while persisting, so concurrent inserts don't overwrite each other
+        let mut lock = self.tombstones.write().expect("lock is poisoned");
+
+        if lock.iter().any(|x| x.subsumes(&tombstone)) {
+            return Ok(());
+        }
+
+        let mut tombstones = Vec::clone(&lock);
+        tombstones.push(tombstone);
+
+        // NOTE: Persist first, so memory and disk state don't diverge if persisting fails
+        Self::persist(fs, folder.as_ref(), &tombstones)?;
+
+        *lock = Arc::new(tombstones);
+
+        Ok(())
+    }
+
+    /// Adds a range tombstone without persisting it, see [`RangeTombstones::insert`].
+    ///
+    /// Used by read-only keyspaces, which replay range tombstones from the journal.
+    #[allow(clippy::expect_used)]
+    pub fn insert_in_memory(&self, tombstone: RangeTombstone) {
+        let mut lock = self.tombstones.write().expect("lock is poisoned");
+
+        if lock.iter().any(|x| x.subsumes(&tombstone)) {
+            return;
+        }
+
+        let mut tombstones = Vec::clone(&lock);
+        tombstones.push(tombstone);
+
+        *lock = Arc::new(tombstones);
+    }
+
+    /// Replaces the range tombstones by the ones of `other`, without persisting them.
+    #[allow(clippy::expect_used)]
+    pub fn replace(&self, other: &Self) {
+        *self.tombstones.write().expect("lock is poisoned") = other.get();
+        self.materialized.lock().expect("lock is poisoned").clear();
+    }
+
+    /// Removes all range tombstones that do not match the predicate
+    /// and persists the list to the partition folder.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn retain<P: AsRef<Path>, F: Fn(&RangeTombstone) -> bool>(
+        &self,
+        fs: &dyn FileSystem,
+        folder: P,
+        f: F,
+    ) -> crate::Result<()> {
+        let mut lock = self.tombstones.write().expect("lock is poisoned");
+
+        let tombstones = lock.iter().filter(|x| f(x)).cloned().collect::<Vec<_>>();
+
+        if tombstones.len() == lock.len() {
+            return Ok(());
+        }
+
+        Self::persist(fs, folder.as_ref(), &tombstones)?;
+
+        *lock = Arc::new(tombstones);
+
+        Ok(())
+    }
+
+    /// Returns `true` if the covered items of the range tombstone have been deleted already.
+    #[allow(clippy::expect_used)]
+    pub fn is_materialized(&self, tombstone: &RangeTombstone) -> bool {
+        self.materialized
+            .lock()
+            .expect("lock is poisoned")
+            .iter()
+            .any(|x| x.tombstone == *tombstone)
+    }
+
+    /// Records that the covered items of the range tombstone have been deleted,
+    /// see [`materialize`].
+    #[allow(clippy::expect_used)]
+    pub fn set_materialized(
+        &self,
+        tombstone: RangeTombstone,
+        seqno: SeqNo,
+        highest_tombstone_seqno: Option<SeqNo>,
+    ) {
+        let mut materialized = self.materialized.lock().expect("lock is poisoned");
+
+        if materialized.iter().any(|x| x.tombstone == tombstone) {
+            return;
+        }
+
+        materialized.push(Materialized {
+            tombstone,
+            seqno,
+            highest_tombstone_seqno,
+        });
+    }
+
+    /// Removes all materialized range tombstones that are not needed anymore
+    /// and persists the list to the partition folder.
+    ///
+    /// A range tombstone is not needed anymore once all reads see its point tombstones
+    /// (there is no open snapshot at or below the given seqno),
+    /// and the point tombstones are persisted in segments.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn prune<P: AsRef<Path>, F: Fn(SeqNo) -> bool>(
+        &self,
+        fs: &dyn FileSystem,
+        folder: P,
+        persisted_seqno: Option<SeqNo>,
+        has_open_snapshot_before: F,
+    ) -> crate::Result<()> {
+        let mut materialized = self.materialized.lock().expect("lock is poisoned");
+
+        let (prunable, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut *materialized)
+            .into_iter()
+            .partition(|x| {
+                !has_open_snapshot_before(x.seqno)
+                    && x.highest_tombstone_seqno.map_or(true, |seqno| {
+                        persisted_seqno.is_some_and(|persisted| seqno <= persisted)
+                    })
+            });
+
+        *materialized = pending;
+
+        if prunable.is_empty() {
+            return Ok(());
+        }
+
+        self.retain(fs, folder, |x| !prunable.iter().any(|y| y.tombstone == *x))?;
+
+        log::debug!("Pruned {} range tombstones", prunable.len());
+
+        Ok(())
+    }
+
+    /// Writes the list of range tombstones to the given partition folder.
+    pub fn persist(
+        fs: &dyn FileSystem,
+        folder: &Path,
+        tombstones: &[RangeTombstone],
+    ) -> crate::Result<()> {
+        let mut bytes = Vec::new();
+        bytes.write_all(MAGIC_BYTES)?;
+
+        // NOTE: Nobody is going to write 4 billion range tombstones
+        #[allow(clippy::cast_possible_truncation)]
+        bytes.write_u32::<BigEndian>(tombstones.len() as u32)?;
+
+        for tombstone in tombstones {
+            tombstone.encode_into(&mut bytes)?;
+        }
+
+        let tmp_path = folder.join(format!("{PARTITION_RANGE_TOMBSTONES_FILE}.tmp"));
+
+        {
+            let mut file = fs.create(&tmp_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+        }
+
+        fs.rename(&tmp_path, &folder.join(PARTITION_RANGE_TOMBSTONES_FILE))?;
+        fs.sync_directory(folder)?;
+
+        Ok(())
+    }
+}
+
+/// Deletes the items that are covered by the range tombstone using point tombstones,
+/// so compaction can drop them.
+///
+/// Each point tombstone gets a new seqno while holding the partition's write lock,
+/// so it is newer than every version of its key, and no write can slip in
+/// between reading and deleting the key.
+///
+/// Returns the size of the written point tombstones, and the highest seqno of them.
+#[allow(clippy::expect_used)]
+pub fn materialize(
+    tree: &AnyTree,
+    write_lock: &RwLock<()>,
+    seqno_counter: &SequenceNumberCounter,
+    tombstone: &RangeTombstone,
+) -> crate::Result<(u64, Option<SeqNo>)> {
+    const CHUNK_SIZE: usize = 1_000;
+
+    let index = match tree {
+        AnyTree::Standard(tree) => tree,
+        AnyTree::Blob(tree) => &tree.index,
+    };
+
+    let end = tombstone
+        .end
+        .clone()
+        .map_or(Bound::Unbounded, Bound::Excluded);
+
+    let mut start = Bound::Included(tombstone.start.clone());
+    let mut written_bytes = 0;
+    let mut highest_seqno = None;
+
+    loop {
+        let range = (start, end.clone());
+
+        // NOTE: Collect the keys first, because the iterator holds the memtable lock,
+        // which would deadlock with writers that hold the write lock
+        let keys = index
+            .create_range(&range, Some(tombstone.seqno), None)
+            .take(CHUNK_SIZE)
+            .map(|kv| kv.map(|(key, _)| key))
+            .collect::<lsm_tree::Result<Vec<_>>>()?;
+
+        for key in &keys {
+            let _lock = write_lock.write().expect("lock is poisoned");
+
+            // NOTE: Only delete the key if it has not been written after the range tombstone
+            let is_covered = index
+                .get_internal_entry(key, false, None)?
+                .is_some_and(|item| !item.is_tombstone() && tombstone.covers(&item));
+
+            if is_covered {
+                let seqno = seqno_counter.next();
+                let (size, _) = tree.remove(key, seqno);
+
+                written_bytes += u64::from(size);
+                highest_seqno = Some(seqno);
+            }
+        }
+
+        let Some(last_key) = keys.last() else {
+            break;
+        };
+
+        if keys.len() < CHUNK_SIZE {
+            break;
+        }
+
+        start = Bound::Excluded(last_key.clone());
+    }
+
+    Ok((written_bytes, highest_seqno))
+}
+
+/// Removes items that are deleted by range tombstones from a sealed memtable before it is flushed.
+///
+/// Only range tombstones below the eviction threshold are applied, so items that are
+/// still visible to an open snapshot are kept.
+///
+/// Returns `None` if no range tombstone can be applied.
+pub fn filter_memtable(
+    memtable: &Memtable,
+    tombstones: &[RangeTombstone],
+    eviction_threshold: SeqNo,
+) -> Option<Arc<Memtable>> {
+    let tombstones = tombstones
+        .iter()
+        .filter(|x| x.seqno < eviction_threshold)
+        .collect::<Vec<_>>();
+
+    if tombstones.is_empty() {
+        return None;
+    }
+
+    let highest_seqno = memtable.get_highest_seqno()?;
+
+    let filtered = Memtable::default();
+
+    for item in memtable.iter() {
+        // IMPORTANT: Always keep the items with the highest seqno,
+        // otherwise the flushed segment may not cover the memtable's seqno anymore
+        // (or may not be created at all), and the journal could never be evicted
+        if item.key.seqno != highest_seqno && tombstones.iter().any(|x| x.covers(&item)) {
+            continue;
+        }
+
+        filtered.insert(item);
+    }
+
+    log::trace!(
+        "Dropped {} range deleted items from sealed memtable",
+        memtable.len() - filtered.len()
+    );
+
+    Some(Arc::new(filtered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fs::OsFileSystem;
+    use test_log::test;
+
+    #[test]
+    fn range_tombstone_bounds() {
+        let (start, end) = range_to_bounds(&("b".."d")).unwrap();
+        assert_eq!(&*start, b"b");
+        assert_eq!(end.as_deref(), Some(&b"d"[..]));
+
+        let (start, end) = range_to_bounds(&("b"..="d")).unwrap();
+        assert_eq!(&*start, b"b");
+        assert_eq!(end.as_deref(), Some(&b"d\0"[..]));
+
+        let (start, end) = range_to_bounds::<&str, _>(&(..)).unwrap();
+        assert!(start.is_empty());
+        assert!(end.is_none());
+
+        assert!(range_to_bounds(&("d".."b")).is_none());
+        assert!(range_to_bounds(&("b".."b")).is_none());
+    }
+
+    #[test]
+    fn range_tombstone_bounds_max_key() {
+        let mut key = vec![b'a'; MAX_KEY_SIZE];
+        key[MAX_KEY_SIZE - 1] = u8::MAX;
+
+        let mut next = vec![b'a'; MAX_KEY_SIZE - 1];
+        next[MAX_KEY_SIZE - 2] = b'b';
+
+        let (start, end) = range_to_bounds(&(..=&key[..])).unwrap();
+        assert!(start.is_empty());
+        assert_eq!(end.as_deref(), Some(&next[..]));
+
+        let (start, end) =
+            range_to_bounds::<&[u8], _>(&(Bound::Excluded(&key[..]), Bound::Unbounded)).unwrap();
+        assert_eq!(&*start, &next[..]);
+        assert!(end.is_none());
+
+        let key = vec![u8::MAX; MAX_KEY_SIZE];
+
+        let (start, end) = range_to_bounds(&(..=&key[..])).unwrap();
+        assert!(start.is_empty());
+        assert!(end.is_none());
+
+        assert!(
+            range_to_bounds::<&[u8], _>(&(Bound::Excluded(&key[..]), Bound::Unbounded)).is_none()
+        );
+    }
+
+    #[test]
+    fn range_tombstone_contains() {
+        let tombstone = RangeTombstone {
+            start: (*b"b").into(),
+            end: Some((*b"d").into()),
+            seqno: 5,
+        };
+
+        assert!(!tombstone.contains_key(b"a"));
+        assert!(tombstone.contains_key(b"b"));
+        assert!(tombstone.contains_key(b"cccc"));
+        assert!(!tombstone.contains_key(b"d"));
+
+        assert!(tombstone.covers(&InternalValue::from_components(
+            *b"c",
+            *b"",
+            4,
+            lsm_tree::ValueType::Value,
+        )));
+        assert!(!tombstone.covers(&InternalValue::from_components(
+            *b"c",
+            *b"",
+            5,
+            lsm_tree::ValueType::Value,
+        )));
+    }
+
+    #[test]
+    fn range_tombstone_subsumes() {
+        let tombstone = RangeTombstone {
+            start: (*b"b").into(),
+            end: Some((*b"d").into()),
+            seqno: 5,
+        };
+
+        assert!(tombstone.subsumes(&tombstone));
+        assert!(tombstone.subsumes(&RangeTombstone {
+            start: (*b"c").into(),
+            end: Some((*b"d").into()),
+            seqno: 3,
+        }));
+        assert!(!tombstone.subsumes(&RangeTombstone {
+            start: (*b"c").into(),
+            end: Some((*b"d").into()),
+            seqno: 6,
+        }));
+        assert!(!tombstone.subsumes(&RangeTombstone {
+            start: (*b"c").into(),
+            end: None,
+            seqno: 3,
+        }));
+        assert!(RangeTombstone {
+            start: (*b"").into(),
+            end: None,
+            seqno: 5,
+        }
+        .subsumes(&tombstone));
+    }
+
+    #[test]
+    fn range_tombstone_roundtrip() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+
+        let tombstones = RangeTombstones::default();
+        tombstones.insert(
+            &OsFileSystem,
+            &folder,
+            RangeTombstone {
+                start: (*b"a").into(),
+                end: Some((*b"c").into()),
+                seqno: 5,
+            },
+        )?;
+        tombstones.insert(
+            &OsFileSystem,
+            &folder,
+            RangeTombstone {
+                start: (*b"x").into(),
+                end: None,
+                seqno: 7,
+            },
+        )?;
+
+        let recovered = RangeTombstones::recover(&OsFileSystem, &folder)?;
+        assert_eq!(tombstones.get(), recovered.get());
+        assert_eq!(Some(7), recovered.highest_seqno());
+
+        Ok(())
+    }
+
+    #[test]
+    fn range_tombstone_prune() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+
+        let a = RangeTombstone {
+            start: (*b"a").into(),
+            end: Some((*b"c").into()),
+            seqno: 5,
+        };
+        let b = RangeTombstone {
+            start: (*b"x").into(),
+            end: None,
+            seqno: 7,
+        };
+
+        let tombstones = RangeTombstones::default();
+        tombstones.insert(&OsFileSystem, &folder, a.clone())?;
+        tombstones.insert(&OsFileSystem, &folder, b.clone())?;
+
+        tombstones.set_materialized(a.clone(), 10, Some(9));
+        tombstones.set_materialized(b.clone(), 10, None);
+        assert!(tombstones.is_materialized(&a));
+
+        // NOTE: Snapshot may still read below the point tombstones
+        tombstones.prune(&OsFileSystem, &folder, Some(20), |seqno| seqno >= 10)?;
+        assert_eq!(2, tombstones.get().len());
+
+        // NOTE: Point tombstones of `a` are not persisted yet
+        tombstones.prune(&OsFileSystem, &folder, Some(8), |_| false)?;
+        assert_eq!(&[a.clone()], &**tombstones.get());
+
+        tombstones.prune(&OsFileSystem, &folder, Some(9), |_| false)?;
+        assert!(tombstones.get().is_empty());
+        assert!(!tombstones.is_materialized(&a));
+
+        let recovered = RangeTombstones::recover(&OsFileSystem, &folder)?;
+        assert!(recovered.get().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn range_tombstone_filter_memtable() {
+        let memtable = Memtable::default();
+
+        for (key, seqno) in [(*b"a", 0), (*b"b", 1), (*b"c", 2), (*b"b", 4), (*b"d", 5)] {
+            memtable.insert(InternalValue::from_components(
+                key,
+                *b"",
+                seqno,
+                lsm_tree::ValueType::Value,
+            ));
+        }
+
+        let tombstones = [RangeTombstone {
+            start: (*b"a").into(),
+            end: None,
+            seqno: 3,
+        }];
+
+        // NOTE: Range tombstone may still be needed by snapshots
+        assert!(filter_memtable(&memtable, &tombstones, 3).is_none());
+
+        let filtered = filter_memtable(&memtable, &tombstones, 4).unwrap();
+
+        let items = filtered
+            .iter()
+            .map(|x| (x.key.user_key, x.key.seqno))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![((*b"b").into(), 4), ((*b"d").into(), 5)], items,);
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    expiry::ExpiryIndex,
+    large_value::LargeValues,
+    merge::{merge_operands, MergeState},
+    partition::PartitionHandleInner,
+    range_tombstone::RangeTombstone,
+};
+use lsm_tree::{AbstractTree, AnyTree, InternalValue, KvPair, Memtable, SeqNo, UserValue};
+use std::sync::Arc;
+
+/// Applies the partition state that the LSM-tree does not know about to reads
+///
+/// Hides items that are deleted by range tombstones or have expired,
+/// merges merge operands, and reads large values.
+#[derive(Clone)]
+pub struct ReadFilter {
+    tree: AnyTree,
+
+    /// Range tombstones that are visible to the read
+    tombstones: Vec<RangeTombstone>,
+
+    /// Merge operator and operands of the partition
+    merges: Arc<MergeState>,
+
+    /// Expiry timestamps of the partition
+    expiry: Arc<ExpiryIndex>,
+
+    /// Values of the partition that are too large for the tree
+    large_values: Arc<LargeValues>,
+
+    /// Read seqno
+    seqno: Option<SeqNo>,
+
+    /// Write transaction state, which is never covered by range tombstones
+    /// and never contains merge operands
+    ephemeral: Option<Arc<Memtable>>,
+}
+
+impl ReadFilter {
+    pub fn new(
+        partition: &PartitionHandleInner,
+        seqno: Option<SeqNo>,
+        ephemeral: Option<Arc<Memtable>>,
+    ) -> Self {
+        // NOTE: Range tombstones newer than the read seqno are not visible
+        let tombstones = partition
+            .range_tombstones
+            .get()
+            .iter()
+            .filter(|x| seqno.map_or(true, |seqno| x.seqno < seqno))
+            .cloned()
+            .collect();
+
+        Self {
+            tree: partition.tree.clone(),
+            tombstones,
+            merges: partition.merges.clone(),
+            expiry: partition.expiry.clone(),
+            large_values: partition.large_values.clone(),
+            seqno,
+            ephemeral,
+        }
+    }
+
+    /// Adds tombstones that are not bound by the read seqno
+    /// (range deletes of a write transaction that is not committed yet).
+    pub fn with_pending(mut self, tombstones: &[RangeTombstone]) -> Self {
+        self.tombstones.extend(tombstones.iter().cloned());
+        self
+    }
+
+    fn is_ephemeral(&self, key: &[u8]) -> bool {
+        self.ephemeral
+            .as_ref()
+            .is_some_and(|ephemeral| ephemeral.get(key, None).is_some())
+    }
+
+    /// Returns the highest seqno of all range tombstones that contain the key.
+    fn tombstone_seqno(&self, key: &[u8]) -> Option<SeqNo> {
+        self.tombstones
+            .iter()
+            .filter(|x| x.contains_key(key))
+            .map(|x| x.seqno)
+            .max()
+    }
+
+    fn get_internal_entry(
+        &self,
+        key: &[u8],
+        seqno: Option<SeqNo>,
+    ) -> crate::Result<Option<InternalValue>> {
+        Ok(match &self.tree {
+            AnyTree::Standard(tree) => tree.get_internal_entry(key, false, seqno)?,
+            AnyTree::Blob(tree) => tree.index.get_internal_entry(key, false, seqno)?,
+        })
+    }
+
+    /// Returns the user value of an item.
+    #[allow(clippy::expect_used)]
+    fn get_value(&self, item: &InternalValue) -> crate::Result<UserValue> {
+        match &self.tree {
+            AnyTree::Standard(_) => Ok(item.value.clone()),
+            AnyTree::Blob(tree) => Ok(tree
+                .get_with_seqno(&item.key.user_key, item.key.seqno + 1)?
+                .expect("item should exist")),
+        }
+    }
+
+    /// Returns `true` if the key is deleted by a range tombstone, or has expired.
+    pub fn is_deleted(&self, key: &[u8]) -> crate::Result<bool> {
+        let tombstone_seqno = self.tombstone_seqno(key);
+
+        if tombstone_seqno.is_none() && !self.expiry.is_active() {
+            return Ok(false);
+        }
+
+        if self.is_ephemeral(key) {
+            return Ok(false);
+        }
+
+        let Some(item) = self.get_internal_entry(key, self.seqno)? else {
+            return Ok(true);
+        };
+
+        if item.is_tombstone() || tombstone_seqno.is_some_and(|x| item.key.seqno < x) {
+            return Ok(true);
+        }
+
+        self.expiry.is_expired(key, item.key.seqno)
+    }
+
+    /// Merges the key's merge operands, if the visible value is a merge operand,
+    /// and reads the value from its own file, if it is a large value.
+    ///
+    /// Otherwise, the value is returned as is.
+    pub fn resolve(&self, key: &[u8], value: UserValue) -> crate::Result<UserValue> {
+        match self.merge(key, value)? {
+            (value, true) => Ok(value),
+            (value, false) => self.large_values.resolve(key, value),
+        }
+    }
+
+    /// Merges the key's merge operands, if the visible value is a merge operand.
+    ///
+    /// Returns the value as stored in the tree (which may refer to a large value),
+    /// and `false`, if nothing was merged.
+    fn merge(&self, key: &[u8], value: UserValue) -> crate::Result<(UserValue, bool)> {
+        let Some(operands) = self.merges.read_operands() else {
+            return Ok((value, false));
+        };
+
+        let Some(operands) = operands.get(key) else {
+            return Ok((value, false));
+        };
+
+        if self.is_ephemeral(key) {
+            return Ok((value, false));
+        }
+
+        let tombstone_seqno = self.tombstone_seqno(key);
+
+        let mut collected = Vec::new();
+        let mut seqno = self.seqno;
+
+        // NOTE: Walk through the versions of the key, from newest to oldest,
+        // until there is a value that is not a merge operand
+        let existing = loop {
+            let Some(item) = self.get_internal_entry(key, seqno)? else {
+                break None;
+            };
+
+            if item.is_tombstone() || tombstone_seqno.is_some_and(|x| item.key.seqno < x) {
+                break None;
+            }
+
+            match operands.get(&item.key.seqno) {
+                Some(None) => {
+                    collected.push(self.get_value(&item)?);
+                    seqno = Some(item.key.seqno);
+                }
+                Some(Some(merged)) => break Some(merged.clone()),
+                None if self.expiry.is_expired(key, item.key.seqno)? => break None,
+                None => break Some(self.get_value(&item)?),
+            }
+        };
+
+        if collected.is_empty() {
+            return Ok((existing.unwrap_or(value), false));
+        }
+
+        // NOTE: The merge operator sees the existing value, not its reference
+        let existing = existing
+            .map(|existing| self.large_values.resolve(key, existing))
+            .transpose()?;
+
+        let merged = merge_operands(
+            self.merges.operator().as_ref(),
+            key,
+            existing.as_deref(),
+            &collected,
+        )?;
+
+        Ok((merged, true))
+    }
+
+    /// Removes items that are deleted by range tombstones, or have expired, from the iterator.
+    pub fn apply<T, I: DoubleEndedIterator<Item = crate::Result<T>>, F: Fn(&T) -> &[u8]>(
+        self,
+        iter: I,
+        get_key: F,
+    ) -> impl DoubleEndedIterator<Item = crate::Result<T>> {
+        iter.filter_map(move |item| {
+            if self.tombstones.is_empty() && !self.expiry.is_active() {
+                return Some(item);
+            }
+
+            match item {
+                Ok(item) => match self.is_deleted(get_key(&item)) {
+                    Ok(true) => None,
+                    Ok(false) => Some(Ok(item)),
+                    Err(e) => Some(Err(e)),
+                },
+                Err(e) => Some(Err(e)),
+            }
+        })
+    }
+
+    /// Removes items that are deleted by range tombstones, or have expired, from the iterator,
+    /// and merges merge operands.
+    pub fn apply_kv<I: DoubleEndedIterator<Item = crate::Result<KvPair>>>(
+        self,
+        iter: I,
+    ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> {
+        let filter = self.clone();
+
+        self.apply(iter, |(key, _)| key).map(move |item| {
+            let (key, value) = item?;
+            let value = filter.resolve(&key, value)?;
+            Ok((key, value))
+        })
+    }
+}
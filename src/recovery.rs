@@ -4,36 +4,58 @@
 
 use crate::{
     batch::PartitionKey,
+    encryption::decrypt_file,
+    expiry::ExpiryIndex,
     file::{LSM_MANIFEST_FILE, PARTITIONS_FOLDER, PARTITION_CONFIG_FILE, PARTITION_DELETED_MARKER},
+    fs::read_file,
     journal::{
         batch_reader::JournalBatchReader, manager::EvictionWatermark, reader::JournalReader,
     },
-    partition::options::CreateOptions as PartitionCreateOptions,
+    keyspace::Partitions,
+    large_value::LargeValues,
+    partition::{options::CreateOptions as PartitionCreateOptions, remove_partition_folder},
+    range_tombstone::{RangeTombstone, RangeTombstones},
     HashMap, Keyspace, PartitionHandle,
 };
 use lsm_tree::{AbstractTree, AnyTree};
-use std::{fs::File, path::PathBuf};
+use std::path::{Path, PathBuf};
 
 /// Recovers partitions
 pub fn recover_partitions(keyspace: &Keyspace) -> crate::Result<()> {
-    use lsm_tree::coding::Decode;
-
     let partitions_folder = keyspace.config.path.join(PARTITIONS_FOLDER);
 
     #[allow(clippy::significant_drop_tightening)]
     let mut partitions_lock = keyspace.partitions.write().expect("lock is poisoned");
 
-    for dirent in std::fs::read_dir(&partitions_folder)? {
-        let dirent = dirent?;
-        let partition_name = dirent.file_name();
-        let partition_path = dirent.path();
+    recover_partitions_in(keyspace, &partitions_folder, &mut partitions_lock)
+}
+
+/// Recovers the partitions in the given folder into the partition map
+#[allow(clippy::expect_used)]
+pub fn recover_partitions_in(
+    keyspace: &Keyspace,
+    partitions_folder: &Path,
+    partitions_lock: &mut Partitions,
+) -> crate::Result<()> {
+    use lsm_tree::coding::Decode;
+
+    let fs = &*keyspace.config.fs;
 
-        assert!(dirent.file_type()?.is_dir());
+    for partition_path in fs.read_dir(partitions_folder)? {
+        let partition_name = partition_path
+            .file_name()
+            .expect("partition folder should have name")
+            .to_owned();
 
         log::trace!("Recovering partition {:?}", partition_name);
 
         // NOTE: Check deletion marker
-        if partition_path.join(PARTITION_DELETED_MARKER).try_exists()? {
+        if fs.exists(&partition_path.join(PARTITION_DELETED_MARKER))? {
+            if keyspace.config.read_only {
+                log::debug!("Skipping deleted partition {partition_name:?}");
+                continue;
+            }
+
             log::debug!("Deleting deleted partition {:?}", partition_name);
 
             // IMPORTANT: First, delete the manifest,
@@ -48,14 +70,19 @@ pub fn recover_partitions(keyspace: &Keyspace) -> crate::Result<()> {
                 std::fs::remove_file(manifest_file)?;
             }
 
-            std::fs::remove_dir_all(partition_path)?;
+            remove_partition_folder(fs, &partition_path)?;
             continue;
         }
 
         // NOTE: Check for marker, maybe the partition is not fully initialized
         if !partition_path.join(LSM_MANIFEST_FILE).try_exists()? {
+            if keyspace.config.read_only {
+                log::debug!("Skipping uninitialized partition {partition_name:?}");
+                continue;
+            }
+
             log::debug!("Deleting uninitialized partition {:?}", partition_name);
-            std::fs::remove_dir_all(partition_path)?;
+            remove_partition_folder(fs, &partition_path)?;
             continue;
         }
 
@@ -65,8 +92,11 @@ pub fn recover_partitions(keyspace: &Keyspace) -> crate::Result<()> {
 
         let path = partitions_folder.join(partition_name);
 
-        let mut config_file = File::open(partition_path.join(PARTITION_CONFIG_FILE))?;
-        let recovered_config = PartitionCreateOptions::decode_from(&mut config_file)?;
+        let config_bytes = decrypt_file(
+            keyspace.config.journal_encryption.as_deref(),
+            read_file(fs, &partition_path.join(PARTITION_CONFIG_FILE))?,
+        )?;
+        let recovered_config = PartitionCreateOptions::decode_from(&mut &config_bytes[..])?;
 
         let mut base_config = lsm_tree::Config::new(path)
             .descriptor_table(keyspace.config.descriptor_table.clone())
@@ -96,8 +126,33 @@ pub fn recover_partitions(keyspace: &Keyspace) -> crate::Result<()> {
             AnyTree::Standard(base_config.open()?)
         };
 
-        let partition =
-            PartitionHandle::from_keyspace(keyspace, tree, partition_name.into(), recovered_config);
+        let range_tombstones = RangeTombstones::recover(fs, &partition_path)?;
+
+        // NOTE: Range tombstones are not stored in the tree, so the tree's seqno
+        // may be lower than the highest seqno that was given out
+        if let Some(seqno) = range_tombstones.highest_seqno() {
+            keyspace
+                .seqno
+                .fetch_max(seqno + 1, std::sync::atomic::Ordering::AcqRel);
+        }
+
+        let expiry = ExpiryIndex::recover(tree.tree_config())?;
+
+        let large_values = LargeValues::recover(
+            &partition_path,
+            keyspace.config.large_value_threshold,
+            keyspace.config.read_only,
+        )?;
+
+        let partition = PartitionHandle::from_keyspace(
+            keyspace,
+            tree,
+            partition_name.into(),
+            recovered_config,
+            range_tombstones,
+            expiry,
+            large_values,
+        );
 
         // Add partition to dictionary
         partitions_lock.insert(partition_name.into(), partition.clone());
@@ -108,7 +163,7 @@ pub fn recover_partitions(keyspace: &Keyspace) -> crate::Result<()> {
     Ok(())
 }
 
-#[allow(clippy::too_many_lines)]
+#[allow(clippy::too_many_lines, clippy::expect_used)]
 pub fn recover_sealed_memtables(
     keyspace: &Keyspace,
     sealed_journal_paths: &[PathBuf],
@@ -125,18 +180,30 @@ pub fn recover_sealed_memtables(
     for journal_path in sealed_journal_paths {
         log::debug!("Recovering sealed journal: {journal_path:?}");
 
-        let journal_size = journal_path.metadata()?.len();
-
         log::debug!("Reading sealed journal at {journal_path:?}");
 
-        let raw_reader = JournalReader::new(journal_path)?;
-        let reader = JournalBatchReader::new(raw_reader);
+        let raw_reader = JournalReader::open_in(
+            &*keyspace.config.fs,
+            journal_path,
+            keyspace.config.read_only,
+        )?;
+        let journal_size = raw_reader.reader.get_ref().size()?;
+        let raw_reader = raw_reader.with_encryption(keyspace.config.journal_encryption.clone());
+        let mut reader = JournalBatchReader::new(raw_reader, keyspace.config.journal_recovery_mode);
 
         let mut watermarks: HashMap<PartitionKey, EvictionWatermark> = HashMap::default();
 
-        for batch in reader {
+        for batch in &mut reader {
             let batch = batch?;
 
+            for expiry in batch.expiries {
+                if let Some(handle) = partitions_lock.get(&expiry.partition) {
+                    handle
+                        .expiry
+                        .insert(&expiry.key, batch.seqno, expiry.expires_at)?;
+                }
+            }
+
             for item in batch.items {
                 if let Some(handle) = partitions_lock.get(&item.partition) {
                     let tree = &handle.tree;
@@ -164,8 +231,45 @@ pub fn recover_sealed_memtables(
                     }
                 }
             }
+
+            for merge in batch.merges {
+                if let Some(handle) = partitions_lock.get(&merge.partition) {
+                    watermarks
+                        .entry(merge.partition)
+                        .and_modify(|prev| {
+                            prev.lsn = prev.lsn.max(batch.seqno);
+                        })
+                        .or_insert_with(|| EvictionWatermark {
+                            partition: handle.clone(),
+                            lsn: batch.seqno,
+                        });
+
+                    handle.merges.register(merge.key.clone(), batch.seqno);
+                    handle.tree.insert(merge.key, merge.operand, batch.seqno);
+                }
+            }
+
+            for tombstone in batch.range_tombstones {
+                if let Some(handle) = partitions_lock.get(&tombstone.partition) {
+                    handle.add_range_tombstone(RangeTombstone {
+                        start: tombstone.start,
+                        end: tombstone.end,
+                        seqno: batch.seqno,
+                    })?;
+
+                    keyspace
+                        .seqno
+                        .fetch_max(batch.seqno + 1, std::sync::atomic::Ordering::AcqRel);
+                }
+            }
         }
 
+        keyspace
+            .skipped_batches
+            .lock()
+            .expect("lock is poisoned")
+            .append(&mut reader.skipped_batches);
+
         log::debug!("Sealing recovered memtables");
         let mut recovered_count = 0;
 
@@ -176,11 +280,17 @@ pub fn recover_sealed_memtables(
 
             // IMPORTANT: Only apply sealed memtables to partitions
             // that have a lower seqno to avoid double flushing
-            let should_skip_sealed_memtable =
-                partition_lsn.map_or(false, |partition_lsn| partition_lsn >= handle.lsn);
+            //
+            // Also skip if the partition was cleared afterwards, as the data is deleted anyway
+            let should_skip_sealed_memtable = partition_lsn
+                .is_some_and(|partition_lsn| partition_lsn >= handle.lsn)
+                || handle.is_cleared();
 
             if should_skip_sealed_memtable {
-                handle.partition.tree.lock_active_memtable().clear();
+                let mut memtable = handle.partition.tree.lock_active_memtable();
+                handle.partition.merges.remove_memtable(&memtable);
+                memtable.clear();
+                drop(memtable);
 
                 log::trace!(
                     "Partition {} has higher seqno ({partition_lsn:?}), skipping",
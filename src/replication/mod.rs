@@ -0,0 +1,420 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+//! Primary/replica replication
+//!
+//! A [`Primary`] ships the batches of its keyspace's journal to a [`Replica`],
+//! which applies them to its own keyspace, using the same seqnos. Messages are
+//! exchanged through a [`ReplicationTransport`], so replication can run over any
+//! kind of connection; [`TcpTransport`] and [`ChannelTransport`] are provided.
+//!
+//! When connecting, the replica announces the seqno it has applied up to.
+//! If the journals on the primary do not reach back far enough to catch up from
+//! there, the primary sends a checkpoint of its keyspace instead, which replaces
+//! the replica's keyspace.
+//!
+//! # Examples
+//!
+//! ```
+//! use fjall::{replication::{ChannelTransport, Primary, Replica}, Config, PartitionCreateOptions};
+//! #
+//! # let primary_folder = tempfile::tempdir()?;
+//! # let replica_folder = tempfile::tempdir()?;
+//!
+//! let keyspace = Config::new(&primary_folder).open()?;
+//! let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+//! partition.insert("a", "abc")?;
+//!
+//! let (primary_transport, replica_transport) = ChannelTransport::pair();
+//!
+//! let primary = Primary::new(keyspace.clone());
+//! std::thread::spawn(move || primary.serve(primary_transport));
+//!
+//! let mut replica = Replica::open(Config::new(&replica_folder), replica_transport)?;
+//!
+//! // Applies the next batch the primary sends
+//! assert!(replica.apply_next()?);
+//!
+//! let replicated = replica.keyspace().open_partition("default", PartitionCreateOptions::default())?;
+//! assert!(replicated.contains_key("a")?);
+//! #
+//! # Ok::<(), fjall::Error>(())
+//! ```
+
+mod primary;
+mod replica;
+mod transport;
+
+pub use primary::Primary;
+pub use replica::Replica;
+pub use transport::{ChannelTransport, ReplicationTransport, TcpTransport};
+
+use crate::{
+    journal::{
+        marker::{read_bytes, Marker},
+        Entry,
+    },
+    RecoveryError,
+};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use lsm_tree::{
+    coding::{Decode, Encode},
+    CompressionType, DecodeError, EncodeError, SeqNo,
+};
+use std::{
+    hash::Hasher,
+    io::{Read, Write},
+};
+
+/// A batch of the primary's journal
+///
+/// The batch is shipped with the checksum of its entries,
+/// computed the same way as in the journal, so corruption in transit is detected.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReplicationBatch {
+    /// Seqno the batch was committed with
+    pub seqno: SeqNo,
+
+    /// Entries of the batch
+    pub entries: Vec<Entry>,
+
+    /// Checksum of the batch's entries
+    pub checksum: u64,
+}
+
+impl ReplicationBatch {
+    /// Computes the checksum of the given entries.
+    fn compute_checksum(entries: &[Entry]) -> Result<u64, EncodeError> {
+        let mut hasher = xxhash_rust::xxh3::Xxh3::new();
+
+        for entry in entries {
+            hasher.update(&entry.clone().into_marker().encode_into_vec()?);
+        }
+
+        Ok(hasher.finish())
+    }
+
+    /// Returns an error if the batch's entries do not match its checksum.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the checksum does not match.
+    pub fn verify(&self) -> crate::Result<()> {
+        if Self::compute_checksum(&self.entries)? != self.checksum {
+            log::error!("Replicated batch {} has invalid checksum", self.seqno);
+            return Err(crate::Error::JournalRecovery(
+                RecoveryError::ChecksumMismatch,
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// A message exchanged between primary and replica
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReplicationMessage {
+    /// Sent by the replica when connecting, with the seqno to continue at
+    Hello {
+        /// Seqno of the first batch the replica has not applied yet
+        seqno: SeqNo,
+    },
+
+    /// Sent by the primary in response to [`ReplicationMessage::Hello`],
+    /// if the replica can continue at the seqno it sent
+    Resume,
+
+    /// A batch of the primary's journal
+    Batch(ReplicationBatch),
+
+    /// A chunk of a checkpoint file, which is appended to the file
+    ///
+    /// Sent by the primary in response to [`ReplicationMessage::Hello`],
+    /// if the replica needs to catch up from a checkpoint.
+    CheckpointFile {
+        /// Path of the file, relative to the keyspace folder, using `/` as separator
+        path: String,
+
+        /// File contents
+        data: Vec<u8>,
+    },
+
+    /// Sent after the last checkpoint file
+    CheckpointEnd,
+
+    /// A chunk of a large value file, which is appended to the file
+    ///
+    /// Sent by the primary before the batch that refers to the large value.
+    LargeValue {
+        /// Partition of the large value
+        partition: String,
+
+        /// ID of the large value in the partition
+        id: u64,
+
+        /// File contents
+        data: Vec<u8>,
+    },
+
+    /// Sent by the primary, if the journals the replica needs have been deleted
+    TooFarBehind,
+}
+
+const TAG_HELLO: u8 = 0;
+const TAG_RESUME: u8 = 1;
+const TAG_BATCH: u8 = 2;
+const TAG_CHECKPOINT_FILE: u8 = 3;
+const TAG_CHECKPOINT_END: u8 = 4;
+const TAG_TOO_FAR_BEHIND: u8 = 5;
+const TAG_LARGE_VALUE: u8 = 6;
+
+/// Converts a length into the integer type the message format stores it as.
+fn checked_len<T: TryFrom<usize>>(len: usize) -> Result<T, EncodeError> {
+    T::try_from(len).map_err(|_| EncodeError::Io(std::io::ErrorKind::InvalidInput.into()))
+}
+
+impl Encode for ReplicationMessage {
+    fn encode_into<W: Write>(&self, writer: &mut W) -> Result<(), EncodeError> {
+        match self {
+            Self::Hello { seqno } => {
+                writer.write_u8(TAG_HELLO)?;
+                writer.write_u64::<BigEndian>(*seqno)?;
+            }
+            Self::Resume => {
+                writer.write_u8(TAG_RESUME)?;
+            }
+            Self::Batch(batch) => {
+                writer.write_u8(TAG_BATCH)?;
+
+                // NOTE: The batch is encoded like in the journal, but never compressed
+                Marker::Start {
+                    item_count: checked_len(batch.entries.len())?,
+                    seqno: batch.seqno,
+                    compression: CompressionType::None,
+                    encrypted: false,
+                }
+                .encode_into(writer)?;
+
+                for entry in &batch.entries {
+                    entry.clone().into_marker().encode_into(writer)?;
+                }
+
+                Marker::End(batch.checksum).encode_into(writer)?;
+            }
+            Self::CheckpointFile { path, data } => {
+                writer.write_u8(TAG_CHECKPOINT_FILE)?;
+
+                writer.write_u16::<BigEndian>(checked_len(path.len())?)?;
+                writer.write_all(path.as_bytes())?;
+
+                writer.write_u32::<BigEndian>(checked_len(data.len())?)?;
+                writer.write_all(data)?;
+            }
+            Self::CheckpointEnd => {
+                writer.write_u8(TAG_CHECKPOINT_END)?;
+            }
+            Self::TooFarBehind => {
+                writer.write_u8(TAG_TOO_FAR_BEHIND)?;
+            }
+            Self::LargeValue {
+                partition,
+                id,
+                data,
+            } => {
+                writer.write_u8(TAG_LARGE_VALUE)?;
+
+                writer.write_u16::<BigEndian>(checked_len(partition.len())?)?;
+                writer.write_all(partition.as_bytes())?;
+
+                writer.write_u64::<BigEndian>(*id)?;
+
+                writer.write_u32::<BigEndian>(checked_len(data.len())?)?;
+                writer.write_all(data)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Decode for ReplicationMessage {
+    fn decode_from<R: Read>(reader: &mut R) -> Result<Self, DecodeError> {
+        match reader.read_u8()? {
+            TAG_HELLO => {
+                let seqno = reader.read_u64::<BigEndian>()?;
+                Ok(Self::Hello { seqno })
+            }
+            TAG_RESUME => Ok(Self::Resume),
+            TAG_BATCH => {
+                let Marker::Start {
+                    item_count, seqno, ..
+                } = Marker::decode_from(reader)?
+                else {
+                    return Err(DecodeError::InvalidHeader("ReplicationBatch"));
+                };
+
+                // NOTE: The item count is sent by the peer, so it is not used to pre-allocate
+                let mut entries = Vec::new();
+
+                for _ in 0..item_count {
+                    let Some(entry) = Entry::from_marker(Marker::decode_from(reader)?) else {
+                        return Err(DecodeError::InvalidHeader("ReplicationBatch"));
+                    };
+                    entries.push(entry);
+                }
+
+                let Marker::End(checksum) = Marker::decode_from(reader)? else {
+                    return Err(DecodeError::InvalidTrailer);
+                };
+
+                Ok(Self::Batch(ReplicationBatch {
+                    seqno,
+                    entries,
+                    checksum,
+                }))
+            }
+            TAG_CHECKPOINT_FILE => {
+                let path_len = reader.read_u16::<BigEndian>()?;
+                let mut path = vec![0; path_len.into()];
+                reader.read_exact(&mut path)?;
+                let path =
+                    String::from_utf8(path).map_err(|e| DecodeError::Utf8(e.utf8_error()))?;
+
+                let data_len = reader.read_u32::<BigEndian>()?;
+                let data = read_bytes(reader, data_len.into())?;
+
+                Ok(Self::CheckpointFile { path, data })
+            }
+            TAG_CHECKPOINT_END => Ok(Self::CheckpointEnd),
+            TAG_TOO_FAR_BEHIND => Ok(Self::TooFarBehind),
+            TAG_LARGE_VALUE => {
+                let partition_len = reader.read_u16::<BigEndian>()?;
+                let mut partition = vec![0; partition_len.into()];
+                reader.read_exact(&mut partition)?;
+                let partition =
+                    String::from_utf8(partition).map_err(|e| DecodeError::Utf8(e.utf8_error()))?;
+
+                let id = reader.read_u64::<BigEndian>()?;
+
+                let data_len = reader.read_u32::<BigEndian>()?;
+                let data = read_bytes(reader, data_len.into())?;
+
+                Ok(Self::LargeValue {
+                    partition,
+                    id,
+                    data,
+                })
+            }
+            tag => Err(DecodeError::InvalidTag(("ReplicationMessage", tag))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsm_tree::ValueType;
+    use test_log::test;
+
+    #[test]
+    fn replication_message_roundtrip() -> crate::Result<()> {
+        let entries = vec![
+            Entry::Item {
+                partition: "default".into(),
+                key: "a".into(),
+                value: "abc".into(),
+                value_type: ValueType::Value,
+            },
+            Entry::RangeTombstone {
+                partition: "default".into(),
+                start: "b".into(),
+                end: None,
+            },
+        ];
+
+        let messages = [
+            ReplicationMessage::Hello { seqno: 5 },
+            ReplicationMessage::Resume,
+            ReplicationMessage::Batch(ReplicationBatch {
+                seqno: 7,
+                checksum: ReplicationBatch::compute_checksum(&entries)?,
+                entries,
+            }),
+            ReplicationMessage::CheckpointFile {
+                path: "journals/0".into(),
+                data: vec![1, 2, 3],
+            },
+            ReplicationMessage::CheckpointEnd,
+            ReplicationMessage::TooFarBehind,
+            ReplicationMessage::LargeValue {
+                partition: "default".into(),
+                id: 3,
+                data: vec![4, 5, 6],
+            },
+        ];
+
+        for message in messages {
+            let bytes = message.encode_into_vec()?;
+            let decoded = ReplicationMessage::decode_from(&mut &bytes[..])?;
+            assert_eq!(message, decoded);
+
+            if let ReplicationMessage::Batch(batch) = decoded {
+                batch.verify()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn replication_batch_checksum_mismatch() -> crate::Result<()> {
+        let mut batch = ReplicationBatch {
+            seqno: 0,
+            entries: vec![Entry::Item {
+                partition: "default".into(),
+                key: "a".into(),
+                value: "abc".into(),
+                value_type: ValueType::Value,
+            }],
+            checksum: 0,
+        };
+        batch.checksum = ReplicationBatch::compute_checksum(&batch.entries)?;
+        batch.verify()?;
+
+        batch.entries.push(batch.entries[0].clone());
+
+        assert!(matches!(
+            batch.verify(),
+            Err(crate::Error::JournalRecovery(
+                RecoveryError::ChecksumMismatch
+            )),
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn replication_message_untrusted_lengths() -> crate::Result<()> {
+        // NOTE: Announces 4 GiB of data, but the message ends early
+        let mut bytes = vec![TAG_CHECKPOINT_FILE, 0, 1, b'a'];
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+
+        assert!(ReplicationMessage::decode_from(&mut &bytes[..]).is_err());
+
+        // NOTE: Announces u32::MAX entries, but contains none
+        let mut bytes = vec![TAG_BATCH];
+        Marker::Start {
+            item_count: u32::MAX,
+            seqno: 0,
+            compression: CompressionType::None,
+            encrypted: false,
+        }
+        .encode_into(&mut bytes)?;
+
+        assert!(ReplicationMessage::decode_from(&mut &bytes[..]).is_err());
+
+        Ok(())
+    }
+}
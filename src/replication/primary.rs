@@ -0,0 +1,366 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{ReplicationBatch, ReplicationMessage, ReplicationTransport};
+use crate::{
+    backup::collect_files,
+    file::JOURNALS_FOLDER,
+    journal::{inspect, inspect_from, Entry, JournalId},
+    JournalEncryptionProvider, Keyspace,
+};
+use lsm_tree::{SeqNo, ValueType};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+/// How long to wait for a commit, before checking the journal anyway
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Size of the chunks checkpoint and large value files are sent in
+const CHUNK_BYTES: u64 = 1_024 * 1_024;
+
+/// Sends a file in chunks, using `f` to create the message of each chunk.
+fn send_file<T: ReplicationTransport, F: Fn(Vec<u8>) -> ReplicationMessage>(
+    transport: &mut T,
+    mut file: std::fs::File,
+    f: F,
+) -> crate::Result<()> {
+    // NOTE: Send at least one chunk, so empty files are created as well
+    loop {
+        let mut data = Vec::new();
+        (&mut file).take(CHUNK_BYTES).read_to_end(&mut data)?;
+
+        let is_last = (data.len() as u64) < CHUNK_BYTES;
+
+        transport.send(f(data))?;
+
+        if is_last {
+            return Ok(());
+        }
+    }
+}
+
+#[allow(clippy::expect_used)]
+fn journal_id(path: &Path) -> JournalId {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.split('.').next())
+        .and_then(|id| id.parse::<JournalId>().ok())
+        .expect("should be valid journal file name")
+}
+
+pub(super) fn unexpected_message(message: &ReplicationMessage) -> crate::Error {
+    log::error!("Received unexpected replication message: {message:?}");
+    crate::Error::Io(std::io::ErrorKind::InvalidData.into())
+}
+
+/// Read position in the journals of the primary
+struct JournalTail {
+    folder: PathBuf,
+    journal_id: JournalId,
+
+    /// Byte offset of the next batch in the journal
+    offset: u64,
+
+    /// Batches with a lower seqno have already been applied by the replica
+    min_seqno: SeqNo,
+
+    encryption: Option<Arc<dyn JournalEncryptionProvider>>,
+}
+
+impl JournalTail {
+    /// Returns the current path of the journal, which is renamed when it is sealed.
+    fn journal_path(&self) -> crate::Result<Option<PathBuf>> {
+        for path in [
+            self.folder.join(self.journal_id.to_string()),
+            self.folder.join(format!("{}.sealed", self.journal_id)),
+        ] {
+            if path.try_exists()? {
+                return Ok(Some(path));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the batches that have been written since the last poll.
+    fn poll<F: FnMut(ReplicationBatch) -> crate::Result<()>>(
+        &mut self,
+        keyspace: &Keyspace,
+        mut f: F,
+    ) -> crate::Result<()> {
+        // NOTE: Only complete batches are flushed while holding the lock,
+        // so everything up to the flushed length can be read
+        let (active_journal_id, flushed_len) = {
+            let mut journal_writer = keyspace.journal.get_writer();
+            (
+                journal_id(&journal_writer.path),
+                journal_writer.flushed_len()?,
+            )
+        };
+
+        loop {
+            let is_active = self.journal_id == active_journal_id;
+
+            let Some(path) = self.journal_path()? else {
+                log::warn!(
+                    "Journal {} was deleted before it could be replicated",
+                    self.journal_id,
+                );
+                return Err(crate::Error::ReplicaTooFarBehind);
+            };
+
+            for batch in inspect_from(&path, self.offset, self.encryption.clone())? {
+                let batch = batch?;
+
+                if is_active && batch.end_offset > flushed_len {
+                    break;
+                }
+
+                if let Some(error) = batch.error {
+                    log::error!(
+                        "Cannot replicate invalid batch at {}:{}: {error:?}",
+                        path.display(),
+                        batch.offset,
+                    );
+                    return Err(crate::Error::JournalRecovery(error));
+                }
+
+                self.offset = batch.end_offset;
+
+                if batch.seqno >= self.min_seqno {
+                    f(ReplicationBatch {
+                        seqno: batch.seqno,
+                        entries: batch.entries,
+                        checksum: batch.checksum,
+                    })?;
+                }
+            }
+
+            if is_active {
+                return Ok(());
+            }
+
+            // NOTE: The journal is sealed, so it is complete
+            self.journal_id += 1;
+            self.offset = 0;
+        }
+    }
+}
+
+/// Ships the journal of a keyspace to replicas
+///
+/// See [`Replica`](super::Replica).
+pub struct Primary {
+    keyspace: Keyspace,
+}
+
+impl Primary {
+    /// Creates a primary for the given keyspace.
+    #[must_use]
+    pub fn new(keyspace: Keyspace) -> Self {
+        Self { keyspace }
+    }
+
+    /// Serves a replica connected through the given transport.
+    ///
+    /// Blocks while the replica is connected; run it in its own thread.
+    ///
+    /// Writes to unlogged partitions are not replicated,
+    /// neither is the creation or deletion of partitions.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the replica disconnects,
+    /// which is noticed when sending the next batch.
+    ///
+    /// Returns [`Error::ReplicaTooFarBehind`](crate::Error::ReplicaTooFarBehind)
+    /// if a journal was deleted before it was shipped to the replica.
+    pub fn serve<T: ReplicationTransport>(&self, mut transport: T) -> crate::Result<()> {
+        // NOTE: Subscribe before reading the journal, so no commit is missed
+        let commits = self.keyspace.subscribe();
+
+        let seqno = match transport.recv()? {
+            Some(ReplicationMessage::Hello { seqno }) => seqno,
+            Some(message) => return Err(unexpected_message(&message)),
+            None => return Ok(()),
+        };
+
+        let mut tail = match self.locate(seqno)? {
+            Some(tail) => {
+                transport.send(ReplicationMessage::Resume)?;
+                tail
+            }
+            None => self.send_checkpoint(&mut transport)?,
+        };
+
+        loop {
+            let result = tail.poll(&self.keyspace, |batch| {
+                self.send_large_values(&mut transport, &batch)?;
+                transport.send(ReplicationMessage::Batch(batch))
+            });
+
+            if matches!(result, Err(crate::Error::ReplicaTooFarBehind)) {
+                transport.send(ReplicationMessage::TooFarBehind)?;
+            }
+            result?;
+
+            let _ = commits.recv_timeout(POLL_INTERVAL);
+
+            // NOTE: One poll catches up with all commits so far
+            while commits.try_recv().is_ok() {}
+        }
+    }
+
+    /// Finds the journal to continue at for a replica that has applied
+    /// all batches before the given seqno.
+    ///
+    /// Returns `None` if the journal has been deleted already.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    fn locate(&self, seqno: SeqNo) -> crate::Result<Option<JournalTail>> {
+        let (journals, instant) = {
+            let journal_writer = self.keyspace.journal.get_writer();
+
+            let mut journals = self
+                .keyspace
+                .journal_manager
+                .read()
+                .expect("lock is poisoned")
+                .sealed_journal_paths();
+            journals.push(journal_writer.path.clone());
+
+            (journals, self.keyspace.instant())
+        };
+
+        // NOTE: The replica has applied writes the primary does not know about
+        if seqno > instant {
+            log::warn!("Replica is ahead of primary ({seqno} > {instant}), resyncing");
+            return Ok(None);
+        }
+
+        let oldest_journal = journals.first().expect("active journal should exist");
+        let oldest_journal_id = journal_id(oldest_journal);
+
+        // NOTE: If no journal has been deleted yet, all batches are still available
+        if oldest_journal_id > 0 {
+            let Ok(mut batches) = inspect(oldest_journal) else {
+                return Ok(None);
+            };
+
+            let first_seqno = match batches.next() {
+                Some(batch) => batch?.seqno,
+                None => instant,
+            };
+
+            if seqno < first_seqno {
+                log::debug!(
+                    "Replica is too far behind ({seqno} < {first_seqno}), sending checkpoint",
+                );
+                return Ok(None);
+            }
+        }
+
+        Ok(Some(JournalTail {
+            folder: self.keyspace.config.path.join(JOURNALS_FOLDER),
+            journal_id: oldest_journal_id,
+            offset: 0,
+            min_seqno: seqno,
+            encryption: self.keyspace.config.journal_encryption.clone(),
+        }))
+    }
+
+    /// Sends the files of the large values the batch refers to.
+    #[allow(clippy::expect_used)]
+    fn send_large_values<T: ReplicationTransport>(
+        &self,
+        transport: &mut T,
+        batch: &ReplicationBatch,
+    ) -> crate::Result<()> {
+        for entry in &batch.entries {
+            let Entry::Item {
+                partition,
+                key,
+                value,
+                value_type: ValueType::Value,
+            } = entry
+            else {
+                continue;
+            };
+
+            let Some(handle) = self
+                .keyspace
+                .partitions
+                .read()
+                .expect("lock is poisoned")
+                .get(partition)
+                .cloned()
+            else {
+                continue;
+            };
+
+            let Some(id) = handle.large_values.reference_id(key, value) else {
+                continue;
+            };
+
+            // NOTE: The value may have been overwritten and deleted in the meantime,
+            // then the replica receives the newer value with a later batch
+            let file = match std::fs::File::open(handle.large_values.path_of(id)) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                    log::debug!("Large value {id} of {partition:?} was deleted, skipping");
+                    continue;
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            send_file(transport, file, |data| ReplicationMessage::LargeValue {
+                partition: partition.to_string(),
+                id,
+                data,
+            })?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a checkpoint of the keyspace, and returns the journal position it ends at.
+    fn send_checkpoint<T: ReplicationTransport>(
+        &self,
+        transport: &mut T,
+    ) -> crate::Result<JournalTail> {
+        let folder = tempfile::Builder::new()
+            .prefix(".replication")
+            .tempdir_in(&self.keyspace.config.path)?;
+        let checkpoint_path = folder.path().join("checkpoint");
+
+        let (journal_path, offset) = crate::checkpoint::create(&self.keyspace, &checkpoint_path)?;
+
+        let mut files = Vec::new();
+        collect_files(&checkpoint_path, &checkpoint_path, &mut files)?;
+
+        log::debug!("Sending checkpoint of {} files to replica", files.len());
+
+        for (relative_path, path) in files {
+            send_file(transport, std::fs::File::open(&path)?, |data| {
+                ReplicationMessage::CheckpointFile {
+                    path: relative_path.clone(),
+                    data,
+                }
+            })?;
+        }
+
+        transport.send(ReplicationMessage::CheckpointEnd)?;
+
+        Ok(JournalTail {
+            folder: self.keyspace.config.path.join(JOURNALS_FOLDER),
+            journal_id: journal_id(&journal_path),
+            offset,
+            min_seqno: 0,
+            encryption: self.keyspace.config.journal_encryption.clone(),
+        })
+    }
+}
@@ -0,0 +1,518 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::{
+    primary::unexpected_message, ReplicationBatch, ReplicationMessage, ReplicationTransport,
+};
+use crate::{
+    batch::{
+        item::{ExpiryItem, Item, MergeItem, RangeTombstoneItem},
+        PartitionKey,
+    },
+    file::fsync_directory,
+    journal::Entry,
+    large_value::parse_reference,
+    Config, Keyspace, PartitionCreateOptions,
+};
+use std::{
+    ffi::OsString,
+    fs::{create_dir_all, remove_dir_all, rename, File, OpenOptions},
+    io::Write,
+    path::{Component, Path, PathBuf},
+};
+
+/// Resolves the path of a checkpoint file sent by the primary inside the given folder.
+///
+/// The path is sent by the peer, so it may only consist of plain file and folder names,
+/// otherwise files outside of the folder could be overwritten.
+fn checkpoint_file_path(folder: &Path, path: &str) -> crate::Result<PathBuf> {
+    let mut file_path = folder.to_path_buf();
+
+    for part in path.split('/') {
+        let mut components = Path::new(part).components();
+
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(name)), None) if name == part => file_path.push(name),
+            _ => {
+                log::error!("Received checkpoint file with invalid path {path:?}");
+                return Err(crate::Error::Io(std::io::ErrorKind::InvalidData.into()));
+            }
+        }
+    }
+
+    // NOTE: Only normal components were pushed, but make sure anyway
+    if !file_path.starts_with(folder) {
+        log::error!("Received checkpoint file with invalid path {path:?}");
+        return Err(crate::Error::Io(std::io::ErrorKind::InvalidData.into()));
+    }
+
+    Ok(file_path)
+}
+
+/// Writes the checkpoint files sent by the primary into the given folder.
+fn receive_checkpoint<T: ReplicationTransport>(
+    transport: &mut T,
+    first_message: ReplicationMessage,
+    folder: &Path,
+) -> crate::Result<()> {
+    create_dir_all(folder)?;
+
+    let mut current: Option<(String, File)> = None;
+    let mut message = first_message;
+
+    loop {
+        match message {
+            ReplicationMessage::CheckpointFile { path, data } => {
+                if current.as_ref().map(|(current_path, _)| current_path) != Some(&path) {
+                    if let Some((_, file)) = current.take() {
+                        file.sync_all()?;
+                    }
+
+                    let file_path = checkpoint_file_path(folder, &path)?;
+
+                    if let Some(parent) = file_path.parent() {
+                        create_dir_all(parent)?;
+                    }
+
+                    let file = OpenOptions::new()
+                        .create_new(true)
+                        .write(true)
+                        .open(&file_path)?;
+
+                    current = Some((path, file));
+                }
+
+                if let Some((_, file)) = &mut current {
+                    file.write_all(&data)?;
+                }
+            }
+            ReplicationMessage::CheckpointEnd => {
+                if let Some((_, file)) = current.take() {
+                    file.sync_all()?;
+                }
+
+                fsync_directory(folder)?;
+
+                return Ok(());
+            }
+            message => return Err(unexpected_message(&message)),
+        }
+
+        message = transport
+            .recv()?
+            .ok_or_else(|| crate::Error::Io(std::io::ErrorKind::UnexpectedEof.into()))?;
+    }
+}
+
+/// Returns the path next to the keyspace folder with the given suffix.
+fn sibling_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut sibling = OsString::from(path.as_os_str());
+    sibling.push(suffix);
+    PathBuf::from(sibling)
+}
+
+/// Finishes or rolls back a checkpoint install that was interrupted by a crash.
+fn recover_checkpoint_install(path: &Path) -> crate::Result<()> {
+    let staging_path = sibling_path(path, ".checkpoint");
+    let old_path = sibling_path(path, ".old");
+
+    if !old_path.try_exists()? {
+        return Ok(());
+    }
+
+    if path.try_exists()? {
+        log::debug!("Removing replaced keyspace folder {}", old_path.display());
+        remove_dir_all(&old_path)?;
+        return Ok(());
+    }
+
+    // NOTE: The keyspace is only moved aside after the checkpoint was fully received
+    if staging_path.try_exists()? {
+        log::info!("Finishing interrupted checkpoint install");
+        rename(&staging_path, path)?;
+    } else {
+        log::info!("Rolling back interrupted checkpoint install");
+        rename(&old_path, path)?;
+    }
+
+    if let Some(parent) = path.parent() {
+        fsync_directory(parent)?;
+    }
+
+    if old_path.try_exists()? {
+        remove_dir_all(&old_path)?;
+    }
+
+    Ok(())
+}
+
+/// Large value file of the primary that is being received
+struct IncomingLargeValue {
+    partition: PartitionKey,
+
+    /// ID of the large value on the primary, which the batch refers to
+    primary_id: u64,
+
+    /// ID of the large value on the replica
+    local_id: u64,
+
+    file: File,
+}
+
+/// Keeps a keyspace in sync with a replication primary
+///
+/// Batches are applied in the order they were committed on the primary,
+/// with the seqnos they were committed with.
+///
+/// Writes through the replica's keyspace are rejected with
+/// [`Error::ReadOnly`](crate::Error::ReadOnly), so it cannot diverge from the primary.
+/// The keyspace needs to use the same encryption provider as the primary. Partitions are created on their first
+/// write with default options; to use a merge operator, open the partition
+/// with it before applying batches.
+///
+/// Large values are received before the batches that write them,
+/// and stored in the replica's own large value files.
+///
+/// See [`Primary`](super::Primary).
+pub struct Replica<T: ReplicationTransport> {
+    keyspace: Keyspace,
+    transport: T,
+}
+
+impl<T: ReplicationTransport> Replica<T> {
+    /// Opens the replica's keyspace, and connects to the primary.
+    ///
+    /// If the primary cannot provide all batches the replica is missing,
+    /// it sends a checkpoint, which replaces the keyspace in the configured folder.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn open(mut config: Config, mut transport: T) -> crate::Result<Self> {
+        config.replica = true;
+
+        recover_checkpoint_install(&config.path)?;
+
+        let keyspace = Keyspace::open(config.clone())?;
+
+        transport.send(ReplicationMessage::Hello {
+            seqno: keyspace.instant(),
+        })?;
+
+        let keyspace = match transport.recv()? {
+            Some(ReplicationMessage::Resume) => keyspace,
+            Some(message @ ReplicationMessage::CheckpointFile { .. }) => {
+                drop(keyspace);
+                Self::install_checkpoint(&config.path, &mut transport, message)?;
+                Keyspace::open(config)?
+            }
+            Some(message) => return Err(unexpected_message(&message)),
+            None => return Err(crate::Error::Io(std::io::ErrorKind::UnexpectedEof.into())),
+        };
+
+        Ok(Self {
+            keyspace,
+            transport,
+        })
+    }
+
+    /// Replaces the keyspace folder with the checkpoint sent by the primary.
+    fn install_checkpoint(
+        path: &Path,
+        transport: &mut T,
+        first_message: ReplicationMessage,
+    ) -> crate::Result<()> {
+        log::info!("Replica is too far behind, installing checkpoint of primary");
+
+        // NOTE: The checkpoint is received next to the keyspace, so an interrupted
+        // transfer does not leave a partial keyspace behind
+        let staging_path = sibling_path(path, ".checkpoint");
+        let old_path = sibling_path(path, ".old");
+
+        if staging_path.try_exists()? {
+            remove_dir_all(&staging_path)?;
+        }
+
+        receive_checkpoint(transport, first_message, &staging_path)?;
+
+        // NOTE: The old keyspace is moved aside instead of deleted, so there is always
+        // a complete keyspace to recover to, see `recover_checkpoint_install`
+        if old_path.try_exists()? {
+            remove_dir_all(&old_path)?;
+        }
+
+        rename(path, &old_path)?;
+        rename(&staging_path, path)?;
+
+        if let Some(parent) = path.parent() {
+            fsync_directory(parent)?;
+        }
+
+        remove_dir_all(&old_path)?;
+
+        Ok(())
+    }
+
+    /// Returns the replica's keyspace.
+    ///
+    /// The keyspace can be read from, but writes are rejected with
+    /// [`Error::ReadOnly`](crate::Error::ReadOnly).
+    #[must_use]
+    pub fn keyspace(&self) -> &Keyspace {
+        &self.keyspace
+    }
+
+    /// Receives and applies the next batch of the primary.
+    ///
+    /// Returns `false` if the primary disconnected.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs, or the batch is corrupt.
+    ///
+    /// Returns [`Error::ReplicaTooFarBehind`](crate::Error::ReplicaTooFarBehind)
+    /// if the primary deleted journals before they were shipped; the replica
+    /// needs to be reopened to catch up from a checkpoint.
+    pub fn apply_next(&mut self) -> crate::Result<bool> {
+        let mut large_values = Vec::new();
+
+        loop {
+            match self.transport.recv()? {
+                Some(ReplicationMessage::LargeValue {
+                    partition,
+                    id,
+                    data,
+                }) => {
+                    self.receive_large_value(&mut large_values, partition, id, &data)?;
+                }
+                Some(ReplicationMessage::Batch(batch)) => {
+                    self.apply(batch, large_values)?;
+                    return Ok(true);
+                }
+                Some(ReplicationMessage::TooFarBehind) => {
+                    return Err(crate::Error::ReplicaTooFarBehind)
+                }
+                Some(message) => return Err(unexpected_message(&message)),
+                None => return Ok(false),
+            }
+        }
+    }
+
+    /// Appends a chunk of a large value file of the primary to the replica's file.
+    fn receive_large_value(
+        &self,
+        large_values: &mut Vec<IncomingLargeValue>,
+        partition: String,
+        id: u64,
+        data: &[u8],
+    ) -> crate::Result<()> {
+        let is_current = large_values
+            .last()
+            .is_some_and(|x| *x.partition == *partition && x.primary_id == id);
+
+        if !is_current {
+            let handle = self
+                .keyspace
+                .open_partition(&partition, PartitionCreateOptions::default())?;
+
+            let (local_id, file) = handle.large_values.create_temp()?;
+
+            large_values.push(IncomingLargeValue {
+                partition: partition.into(),
+                primary_id: id,
+                local_id,
+                file,
+            });
+        }
+
+        if let Some(large_value) = large_values.last_mut() {
+            large_value.file.write_all(data)?;
+        }
+
+        Ok(())
+    }
+
+    /// Applies batches of the primary, until it disconnects.
+    ///
+    /// # Errors
+    ///
+    /// See [`Replica::apply_next`].
+    pub fn run(&mut self) -> crate::Result<()> {
+        while self.apply_next()? {}
+        Ok(())
+    }
+
+    fn apply(
+        &self,
+        batch: ReplicationBatch,
+        large_values: Vec<IncomingLargeValue>,
+    ) -> crate::Result<()> {
+        batch.verify()?;
+
+        let mut pending_values = Vec::with_capacity(large_values.len());
+
+        for large_value in large_values {
+            drop(large_value.file);
+
+            let handle = self
+                .keyspace
+                .open_partition(&large_value.partition, PartitionCreateOptions::default())?;
+
+            pending_values.push((
+                large_value.partition,
+                large_value.primary_id,
+                handle.large_values.import(large_value.local_id)?,
+            ));
+        }
+
+        for entry in &batch.entries {
+            if !self.keyspace.partition_exists(entry.partition()) {
+                self.keyspace
+                    .open_partition(entry.partition(), PartitionCreateOptions::default())?;
+            }
+        }
+
+        let mut write_batch = self.keyspace.batch();
+        write_batch.seqno = Some(batch.seqno);
+
+        for entry in batch.entries {
+            match entry {
+                Entry::Item {
+                    partition,
+                    key,
+                    mut value,
+                    value_type,
+                } => {
+                    // NOTE: The replica's large value files have their own IDs
+                    if let Some(primary_id) = parse_reference(&value) {
+                        if let Some((_, _, large_value)) = pending_values
+                            .iter()
+                            .find(|(x, id, _)| *x == partition && *id == primary_id)
+                        {
+                            value = large_value.reference.clone();
+                        }
+                    }
+
+                    write_batch
+                        .data
+                        .push(Item::new(partition, key, value, value_type));
+                }
+                Entry::RangeTombstone {
+                    partition,
+                    start,
+                    end,
+                } => {
+                    write_batch.range_tombstones.push(RangeTombstoneItem {
+                        partition,
+                        start,
+                        end,
+                    });
+                }
+                Entry::Merge {
+                    partition,
+                    key,
+                    operand,
+                } => {
+                    write_batch.merges.push(MergeItem {
+                        partition,
+                        key,
+                        operand,
+                    });
+                }
+                Entry::Expiry {
+                    partition,
+                    key,
+                    expires_at,
+                } => {
+                    write_batch.expiries.push(ExpiryItem {
+                        partition,
+                        key,
+                        expires_at,
+                    });
+                }
+            }
+        }
+
+        write_batch.commit()?;
+
+        // NOTE: The references are written, so the large values are kept
+        for (_, _, large_value) in pending_values {
+            large_value.commit();
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn replica_checkpoint_file_path() -> crate::Result<()> {
+        let folder = Path::new("/replica.checkpoint");
+
+        assert_eq!(
+            folder.join("journals").join("0"),
+            checkpoint_file_path(folder, "journals/0")?,
+        );
+
+        for path in [
+            "",
+            "/",
+            "..",
+            "../x",
+            "journals/../../x",
+            "./journals",
+            "journals//0",
+            "/etc/passwd",
+            "journals/",
+        ] {
+            assert!(
+                checkpoint_file_path(folder, path).is_err(),
+                "{path:?} should be rejected"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn replica_recover_checkpoint_install() -> crate::Result<()> {
+        let folder = tempfile::tempdir()?;
+        let path = folder.path().join("replica");
+        let staging_path = sibling_path(&path, ".checkpoint");
+        let old_path = sibling_path(&path, ".old");
+
+        // Crashed after moving the old keyspace aside
+        create_dir_all(&old_path)?;
+        File::create(old_path.join("old"))?;
+        create_dir_all(&staging_path)?;
+        File::create(staging_path.join("new"))?;
+
+        recover_checkpoint_install(&path)?;
+        assert!(path.join("new").try_exists()?);
+        assert!(!staging_path.try_exists()?);
+        assert!(!old_path.try_exists()?);
+
+        // Crashed before deleting the old keyspace
+        create_dir_all(&old_path)?;
+
+        recover_checkpoint_install(&path)?;
+        assert!(path.join("new").try_exists()?);
+        assert!(!old_path.try_exists()?);
+
+        // Crashed without a complete checkpoint
+        remove_dir_all(&path)?;
+        create_dir_all(&old_path)?;
+        File::create(old_path.join("old"))?;
+
+        recover_checkpoint_install(&path)?;
+        assert!(path.join("old").try_exists()?);
+        assert!(!old_path.try_exists()?);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::ReplicationMessage;
+use crate::journal::marker::read_bytes;
+use byteorder::{BigEndian, ReadBytesExt};
+use lsm_tree::coding::{Decode, Encode};
+use std::{
+    io::Write,
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc::{channel, Receiver, Sender},
+};
+
+/// Connection between a replication primary and a replica
+///
+/// Messages need to be delivered in order, and without loss.
+pub trait ReplicationTransport: Send {
+    /// Sends a message to the peer.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the message could not be sent,
+    /// e.g. because the peer disconnected.
+    fn send(&mut self, message: ReplicationMessage) -> crate::Result<()>;
+
+    /// Receives the next message from the peer, blocking until it arrives.
+    ///
+    /// Returns `None` if the peer closed the connection.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the message could not be received.
+    fn recv(&mut self) -> crate::Result<Option<ReplicationMessage>>;
+}
+
+fn disconnected() -> crate::Error {
+    crate::Error::Io(std::io::ErrorKind::BrokenPipe.into())
+}
+
+/// In-process transport, passing messages through channels
+///
+/// Mostly useful for testing.
+pub struct ChannelTransport {
+    sender: Sender<ReplicationMessage>,
+    receiver: Receiver<ReplicationMessage>,
+}
+
+impl ChannelTransport {
+    /// Creates two connected transports, one for each peer.
+    #[must_use]
+    pub fn pair() -> (Self, Self) {
+        let (a_sender, b_receiver) = channel();
+        let (b_sender, a_receiver) = channel();
+
+        (
+            Self {
+                sender: a_sender,
+                receiver: a_receiver,
+            },
+            Self {
+                sender: b_sender,
+                receiver: b_receiver,
+            },
+        )
+    }
+}
+
+impl ReplicationTransport for ChannelTransport {
+    fn send(&mut self, message: ReplicationMessage) -> crate::Result<()> {
+        self.sender.send(message).map_err(|_| disconnected())
+    }
+
+    fn recv(&mut self) -> crate::Result<Option<ReplicationMessage>> {
+        Ok(self.receiver.recv().ok())
+    }
+}
+
+/// Default maximum size of a frame
+const DEFAULT_MAX_FRAME_BYTES: u32 = /* 64 MiB */ 64 * 1_024 * 1_024;
+
+/// Transport over a TCP connection
+///
+/// Each message is sent as a frame, prefixed by its length in bytes.
+///
+/// The connection is not authenticated or encrypted,
+/// so it should only be used in trusted networks.
+pub struct TcpTransport {
+    stream: TcpStream,
+
+    /// Frames larger than this are rejected
+    max_frame_bytes: u32,
+}
+
+impl TcpTransport {
+    /// Uses an established connection.
+    #[must_use]
+    pub fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            max_frame_bytes: DEFAULT_MAX_FRAME_BYTES,
+        }
+    }
+
+    /// Sets the maximum size of a frame, which limits how much memory
+    /// a single message of the peer can make this side allocate.
+    ///
+    /// Batches are sent as a single frame, so this needs to be larger than the
+    /// largest batch written to the primary, and needs to be set on both sides.
+    ///
+    /// Default = 64 MiB
+    #[must_use]
+    pub fn with_max_frame_size(mut self, bytes: u32) -> Self {
+        self.max_frame_bytes = bytes;
+        self
+    }
+
+    fn frame_too_large(&self, len: u64) -> crate::Error {
+        log::error!(
+            "Replication frame of {len} bytes exceeds maximum of {} bytes",
+            self.max_frame_bytes,
+        );
+        crate::Error::Io(std::io::ErrorKind::InvalidData.into())
+    }
+
+    /// Connects to the given address.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn connect<A: ToSocketAddrs>(addr: A) -> crate::Result<Self> {
+        let stream = TcpStream::connect(addr)?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl ReplicationTransport for TcpTransport {
+    fn send(&mut self, message: ReplicationMessage) -> crate::Result<()> {
+        let bytes = message.encode_into_vec()?;
+
+        let len = match u32::try_from(bytes.len()) {
+            Ok(len) if len <= self.max_frame_bytes => len,
+            _ => return Err(self.frame_too_large(bytes.len() as u64)),
+        };
+
+        let mut frame = Vec::with_capacity(std::mem::size_of::<u32>() + bytes.len());
+        frame.extend_from_slice(&len.to_be_bytes());
+        frame.extend_from_slice(&bytes);
+
+        self.stream.write_all(&frame)?;
+
+        Ok(())
+    }
+
+    fn recv(&mut self) -> crate::Result<Option<ReplicationMessage>> {
+        let len = match self.stream.read_u32::<BigEndian>() {
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if len > self.max_frame_bytes {
+            return Err(self.frame_too_large(len.into()));
+        }
+
+        let frame = read_bytes(&mut self.stream, len.into())?;
+
+        Ok(Some(ReplicationMessage::decode_from(&mut &frame[..])?))
+    }
+}
@@ -0,0 +1,551 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    batch::PartitionKey,
+    checkpoint::copy_folder,
+    compaction::manager::CompactionManager,
+    config::Config,
+    file::{FJALL_MARKER, JOURNALS_FOLDER, PARTITIONS_FOLDER, PARTITION_DELETED_MARKER},
+    flush::manager::FlushManager,
+    fs::{FileSystem, OsFileSystem},
+    journal::{
+        batch_reader::JournalBatchReader, manager::JournalManager, reader::JournalReader, Journal,
+        JournalId,
+    },
+    keyspace::{KeyspaceInner, Partitions},
+    range_tombstone::RangeTombstone,
+    recovery::recover_partitions_in,
+    snapshot_tracker::SnapshotTracker,
+    write_buffer_manager::WriteBufferManager,
+    HashMap, JournalEncryptionProvider, Keyspace, PartitionHandle,
+};
+use lsm_tree::{AbstractTree, AnyTree, SeqNo, SequenceNumberCounter, Tree, TreeId};
+use std::{
+    fs::{create_dir, create_dir_all, remove_dir_all},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+};
+use std_semaphore::Semaphore;
+
+/// Folder in the secondary's directory that holds the mirrors of the primary's partitions
+const MIRRORS_FOLDER: &str = "mirrors";
+
+/// How often mirroring the partitions is attempted, as it may race
+/// with flushes and compactions of the primary
+const MIRROR_ATTEMPTS: usize = 5;
+
+#[derive(Default)]
+struct State {
+    /// Number of the last mirror that was created
+    generation: u64,
+
+    /// Folder of the mirror the partitions currently use
+    mirror: Option<PathBuf>,
+
+    /// Maps trees to the tree that recovered their current segments
+    ///
+    /// Segments are registered in the descriptor table
+    /// with the ID of the tree that recovered them.
+    segment_owners: HashMap<TreeId, TreeId>,
+}
+
+/// State of a read-only secondary instance
+///
+/// Recovering an LSM-tree deletes files that are not referenced by its manifest,
+/// which may be segments the primary is writing at that moment, so the secondary
+/// never opens the primary's partitions. Instead, the partitions are mirrored into the
+/// secondary's directory (hard linking segment and blob files), and recovered from there.
+/// The primary's journals are then replayed read-only, to recover its memtables.
+///
+/// When catching up, a new mirror is created, and the recovered state is moved
+/// into the existing partition handles.
+pub struct Secondary {
+    /// Directory of the primary keyspace
+    primary_path: PathBuf,
+
+    state: Mutex<State>,
+}
+
+/// Opens a secondary instance of the keyspace at `primary_path`.
+pub fn open(config: Config, primary_path: PathBuf) -> crate::Result<Keyspace> {
+    log::info!(
+        "Opening secondary of keyspace at {} in {}",
+        primary_path.display(),
+        config.path.display()
+    );
+
+    // NOTE: Secondaries follow a primary in the OS file system
+    let fs: Arc<dyn FileSystem> = Arc::new(OsFileSystem);
+
+    Keyspace::check_version(&*fs, &primary_path)?;
+
+    // IMPORTANT: The working directory is cleaned up, so it must not be a keyspace
+    if config.path.join(FJALL_MARKER).try_exists()? {
+        log::error!(
+            "Cannot use keyspace at {} as working directory of secondary",
+            config.path.display()
+        );
+        return Err(crate::Error::Io(std::io::ErrorKind::AlreadyExists.into()));
+    }
+
+    let mirrors_folder = config.path.join(MIRRORS_FOLDER);
+
+    if mirrors_folder.try_exists()? {
+        remove_dir_all(&mirrors_folder)?;
+    }
+    create_dir_all(&mirrors_folder)?;
+
+    let journals = open_journals(
+        &primary_path.join(JOURNALS_FOLDER),
+        config.journal_encryption.as_ref(),
+    )?;
+
+    let Some(active_journal) = journals.last() else {
+        log::error!("Keyspace at {} has no journal", primary_path.display());
+        return Err(crate::Error::Io(std::io::ErrorKind::NotFound.into()));
+    };
+
+    let journal = Journal::open_read_only(fs.clone(), &active_journal.path)?;
+    let journal_manager = JournalManager::from_active(journal.path(), fs);
+
+    let inner = KeyspaceInner {
+        config,
+        journal: Arc::new(journal),
+        partitions: Arc::new(RwLock::new(Partitions::with_capacity_and_hasher(
+            10,
+            xxhash_rust::xxh3::Xxh3Builder::new(),
+        ))),
+        seqno: SequenceNumberCounter::default(),
+        flush_manager: Arc::new(RwLock::new(FlushManager::new())),
+        journal_manager: Arc::new(RwLock::new(journal_manager)),
+        flush_semaphore: Arc::new(Semaphore::new(0)),
+        compaction_manager: CompactionManager::default(),
+        stop_signal: lsm_tree::stop_signal::StopSignal::default(),
+        active_background_threads: Arc::default(),
+        write_buffer_manager: WriteBufferManager::default(),
+        is_poisoned: Arc::default(),
+        snapshot_tracker: SnapshotTracker::default(),
+        maintenance_lock: Arc::default(),
+        skipped_batches: Mutex::default(),
+        secondary: Some(Secondary {
+            primary_path,
+            state: Mutex::default(),
+        }),
+        lock_file: None,
+    };
+
+    let keyspace = Keyspace(Arc::new(inner));
+    keyspace.try_catch_up()?;
+
+    #[cfg(feature = "__internal_whitebox")]
+    crate::drop::increment_drop_counter();
+
+    Ok(keyspace)
+}
+
+impl Secondary {
+    /// Catches up with the primary.
+    #[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+    pub fn catch_up(&self, keyspace: &Keyspace) -> crate::Result<()> {
+        let mut state = self.state.lock().expect("lock is poisoned");
+
+        log::debug!(
+            "Catching up with primary at {}",
+            self.primary_path.display()
+        );
+
+        // NOTE: The journals are opened before the partitions are mirrored, so if a journal
+        // is evicted in between, its data has been flushed, and is in the mirrored segments
+        let journals = open_journals(
+            &self.primary_path.join(JOURNALS_FOLDER),
+            keyspace.config.journal_encryption.as_ref(),
+        )?;
+
+        let mirrors_folder = keyspace.config.path.join(MIRRORS_FOLDER);
+        let primary_partitions_folder = self.primary_path.join(PARTITIONS_FOLDER);
+
+        let mut attempt = 1;
+
+        let (mirror, recovered) = loop {
+            state.generation += 1;
+            let folder = mirrors_folder.join(state.generation.to_string());
+
+            match mirror_partitions(keyspace, &primary_partitions_folder, &folder) {
+                Ok(partitions) => break (folder, partitions),
+                Err(e) => {
+                    if let Err(e) = remove_dir_all(&folder) {
+                        log::warn!("Failed to clean up mirror at {}: {e:?}", folder.display());
+                    }
+
+                    if attempt == MIRROR_ATTEMPTS {
+                        return Err(e);
+                    }
+
+                    log::debug!("Mirroring partitions failed, retrying: {e:?}");
+                    attempt += 1;
+                }
+            }
+        };
+
+        replay_journals(keyspace, &recovered, journals, &primary_partitions_folder)?;
+
+        let mut partitions = keyspace.partitions.write().expect("lock is poisoned");
+
+        for handle in partitions.values() {
+            if !recovered.contains_key(&handle.name) {
+                log::debug!("Partition {:?} was deleted by primary", handle.name);
+
+                handle
+                    .is_deleted
+                    .store(true, std::sync::atomic::Ordering::Release);
+            }
+        }
+
+        partitions.retain(|name, _| recovered.contains_key(name));
+
+        for (name, recovered_handle) in recovered {
+            let is_adopted = partitions
+                .get(&name)
+                .is_some_and(|handle| adopt(handle, &recovered_handle, &mut state.segment_owners));
+
+            if !is_adopted {
+                // NOTE: The partition was recreated with another tree type
+                if let Some(handle) = partitions.insert(name, recovered_handle) {
+                    handle
+                        .is_deleted
+                        .store(true, std::sync::atomic::Ordering::Release);
+                }
+            }
+        }
+
+        drop(partitions);
+
+        if let Some(previous_mirror) = state.mirror.replace(mirror) {
+            if let Err(e) = remove_dir_all(&previous_mirror) {
+                log::warn!(
+                    "Failed to clean up mirror at {}: {e:?}",
+                    previous_mirror.display()
+                );
+            }
+        }
+
+        log::debug!(
+            "Caught up with primary, seqno is now {}",
+            keyspace.seqno.get()
+        );
+
+        Ok(())
+    }
+}
+
+/// Opens the journals of the primary, oldest first.
+///
+/// Journals that are evicted before they are opened are skipped.
+#[allow(clippy::expect_used)]
+fn open_journals(
+    folder: &Path,
+    encryption: Option<&Arc<dyn JournalEncryptionProvider>>,
+) -> crate::Result<Vec<JournalReader>> {
+    let mut journal_ids = vec![];
+
+    for dirent in std::fs::read_dir(folder)? {
+        let file_name = dirent?.file_name();
+        let file_name = file_name.to_str().expect("should be utf-8");
+
+        let journal_id = file_name
+            .strip_suffix(".sealed")
+            .unwrap_or(file_name)
+            .parse::<JournalId>()
+            .expect("should be valid journal ID");
+
+        journal_ids.push(journal_id);
+    }
+
+    journal_ids.sort_unstable();
+    journal_ids.dedup();
+
+    let mut journals = Vec::with_capacity(journal_ids.len());
+
+    'journals: for journal_id in journal_ids {
+        // NOTE: The active journal may have been sealed in the meantime
+        for path in [
+            folder.join(journal_id.to_string()),
+            folder.join(format!("{journal_id}.sealed")),
+        ] {
+            match JournalReader::new_read_only(&path) {
+                Ok(reader) => {
+                    journals.push(reader.with_encryption(encryption.cloned()));
+                    continue 'journals;
+                }
+                Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        log::debug!("Journal {journal_id} was evicted by primary, skipping");
+    }
+
+    Ok(journals)
+}
+
+/// Returns the tree that holds the segments of the partition.
+fn index_tree(tree: &AnyTree) -> &Tree {
+    match tree {
+        AnyTree::Standard(tree) => tree,
+        AnyTree::Blob(tree) => &tree.index.0,
+    }
+}
+
+/// Closes the file descriptors of the segments of a tree that is discarded.
+#[allow(clippy::expect_used)]
+fn close_segments(tree: &Tree) {
+    for segment in tree.levels.read().expect("lock is poisoned").iter() {
+        tree.config
+            .descriptor_table
+            .remove((tree.id, segment.metadata.id).into());
+    }
+}
+
+/// Mirrors the partitions of the primary into the given folder, and recovers them from there.
+fn mirror_partitions(
+    keyspace: &Keyspace,
+    primary_partitions_folder: &Path,
+    folder: &Path,
+) -> crate::Result<Partitions> {
+    create_dir(folder)?;
+
+    for dirent in std::fs::read_dir(primary_partitions_folder)? {
+        let dirent = dirent?;
+        let partition_path = dirent.path();
+
+        if partition_path.join(PARTITION_DELETED_MARKER).try_exists()? {
+            continue;
+        }
+
+        copy_folder(&partition_path, &folder.join(dirent.file_name()), false)?;
+    }
+
+    let mut partitions = Partitions::default();
+
+    // NOTE: Partitions that are not fully initialized are deleted from the mirror
+    if let Err(e) = recover_partitions_in(keyspace, folder, &mut partitions) {
+        for partition in partitions.values() {
+            close_segments(index_tree(&partition.tree));
+
+            if let Some(tree) = partition.expiry.tree() {
+                close_segments(tree);
+            }
+        }
+
+        return Err(e);
+    }
+
+    Ok(partitions)
+}
+
+/// Replays the primary's journals into the recovered partitions.
+///
+/// Batches that have already been flushed to the partition's segments are skipped.
+///
+/// Large values that were written after the partitions were mirrored
+/// are linked from the primary's partitions.
+fn replay_journals(
+    keyspace: &Keyspace,
+    partitions: &Partitions,
+    journals: Vec<JournalReader>,
+    primary_partitions_folder: &Path,
+) -> crate::Result<()> {
+    let persisted_seqnos = partitions
+        .iter()
+        .map(|(name, handle)| (name.clone(), handle.tree.get_highest_persisted_seqno()))
+        .collect::<HashMap<_, _>>();
+
+    let get_partition = |name: &PartitionKey, seqno: SeqNo| {
+        let persisted_seqno = persisted_seqnos.get(name)?;
+
+        if persisted_seqno.is_some_and(|x| seqno <= x) {
+            return None;
+        }
+
+        partitions.get(name)
+    };
+
+    for journal in journals {
+        log::trace!("Replaying journal of primary at {}", journal.path.display());
+
+        // NOTE: The primary may be writing the last batch, which is skipped then
+        let reader = JournalBatchReader::new(journal, keyspace.config.journal_recovery_mode);
+
+        for batch in reader {
+            let batch = batch?;
+
+            keyspace
+                .seqno
+                .fetch_max(batch.seqno + 1, std::sync::atomic::Ordering::AcqRel);
+
+            for expiry in batch.expiries {
+                if let Some(partition) = get_partition(&expiry.partition, batch.seqno) {
+                    partition
+                        .expiry
+                        .insert(&expiry.key, batch.seqno, expiry.expires_at)?;
+                }
+            }
+
+            for item in batch.items {
+                if let Some(partition) = get_partition(&item.partition, batch.seqno) {
+                    let tree = &partition.tree;
+
+                    match item.value_type {
+                        lsm_tree::ValueType::Value => {
+                            partition.large_values.link_missing(
+                                &primary_partitions_folder.join(&*item.partition),
+                                &item.value,
+                            )?;
+
+                            tree.insert(item.key, item.value, batch.seqno);
+                        }
+                        lsm_tree::ValueType::Tombstone => {
+                            tree.remove(item.key, batch.seqno);
+                        }
+                        lsm_tree::ValueType::WeakTombstone => {
+                            tree.remove_weak(item.key, batch.seqno);
+                        }
+                    }
+                }
+            }
+
+            for merge in batch.merges {
+                if let Some(partition) = get_partition(&merge.partition, batch.seqno) {
+                    partition.merges.register(merge.key.clone(), batch.seqno);
+                    partition.tree.insert(merge.key, merge.operand, batch.seqno);
+                }
+            }
+
+            for tombstone in batch.range_tombstones {
+                if let Some(partition) = partitions.get(&tombstone.partition) {
+                    partition.add_range_tombstone(RangeTombstone {
+                        start: tombstone.start,
+                        end: tombstone.end,
+                        seqno: batch.seqno,
+                    })?;
+                }
+            }
+        }
+    }
+
+    for partition in partitions.values() {
+        let maybe_next_seqno = partition
+            .tree
+            .get_highest_seqno()
+            .map(|x| x + 1)
+            .unwrap_or_default();
+
+        keyspace
+            .seqno
+            .fetch_max(maybe_next_seqno, std::sync::atomic::Ordering::AcqRel);
+    }
+
+    Ok(())
+}
+
+/// Replaces the segments of a tree by the ones of a tree recovered from a newer mirror.
+///
+/// `f` is called while holding the level manifest lock, so no reads are in progress.
+#[allow(clippy::expect_used, clippy::significant_drop_tightening)]
+fn swap_segments<F: FnOnce()>(
+    tree: &Tree,
+    recovered: &Tree,
+    segment_owners: &mut HashMap<TreeId, TreeId>,
+    f: F,
+) {
+    let mut levels = tree.levels.write().expect("lock is poisoned");
+    let mut recovered_levels = recovered.levels.write().expect("lock is poisoned");
+
+    std::mem::swap(&mut *levels, &mut *recovered_levels);
+    f();
+
+    // NOTE: Reads hold the level manifest lock, so the replaced segments
+    // are not accessed anymore, and their file descriptors can be closed
+    let owner = segment_owners
+        .insert(tree.id, recovered.id)
+        .unwrap_or(tree.id);
+
+    for segment in recovered_levels.iter() {
+        tree.config
+            .descriptor_table
+            .remove((owner, segment.metadata.id).into());
+    }
+}
+
+/// Moves the state of a partition recovered from a newer mirror into an existing partition handle.
+///
+/// Returns `false` if the partition uses another tree type.
+#[allow(clippy::expect_used)]
+fn adopt(
+    handle: &PartitionHandle,
+    recovered: &PartitionHandle,
+    segment_owners: &mut HashMap<TreeId, TreeId>,
+) -> bool {
+    let is_same_tree_type = matches!(
+        (&handle.tree, &recovered.tree),
+        (AnyTree::Standard(_), AnyTree::Standard(_)) | (AnyTree::Blob(_), AnyTree::Blob(_))
+    );
+
+    if !is_same_tree_type {
+        return false;
+    }
+
+    // NOTE: Expiry timestamps, range tombstones and large values are replaced first,
+    // so new items are never visible without them
+    if let Some(recovered_tree) = recovered.expiry.tree() {
+        let tree = handle.expiry.get_or_set(recovered_tree);
+
+        if tree.id != recovered_tree.id {
+            swap_segments(tree, recovered_tree, segment_owners, || {});
+
+            std::mem::swap(
+                &mut *tree.lock_active_memtable(),
+                &mut *recovered_tree.lock_active_memtable(),
+            );
+        }
+    }
+
+    handle.range_tombstones.replace(&recovered.range_tombstones);
+    handle.large_values.replace(&recovered.large_values);
+
+    match (&handle.tree, &recovered.tree) {
+        (AnyTree::Standard(tree), AnyTree::Standard(recovered_tree)) => {
+            swap_segments(tree, recovered_tree, segment_owners, || {});
+        }
+        (AnyTree::Blob(tree), AnyTree::Blob(recovered_tree)) => {
+            swap_segments(&tree.index, &recovered_tree.index, segment_owners, || {
+                std::mem::swap(
+                    &mut *tree
+                        .blobs
+                        .manifest
+                        .segments
+                        .write()
+                        .expect("lock is poisoned"),
+                    &mut *recovered_tree
+                        .blobs
+                        .manifest
+                        .segments
+                        .write()
+                        .expect("lock is poisoned"),
+                );
+            });
+        }
+        _ => {}
+    }
+
+    handle.merges.replace_operands(&recovered.merges, || {
+        std::mem::swap(
+            &mut *handle.tree.lock_active_memtable(),
+            &mut *recovered.tree.lock_active_memtable(),
+        );
+    });
+
+    true
+}
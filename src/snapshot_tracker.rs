@@ -57,6 +57,13 @@ impl SnapshotTrackerInner {
         }
     }
 
+    /// Returns `true` if there is an open snapshot that cannot see writes with the given seqno.
+    pub fn has_open_snapshot_before(&self, seqno: Instant) -> bool {
+        self.data
+            .iter()
+            .any(|x| *x.key() <= seqno && *x.value() > 0)
+    }
+
     pub fn get_seqno_safe_to_gc(&self) -> Instant {
         *self.lowest_freed_instant.read().expect("lock is poisoned")
     }
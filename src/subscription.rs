@@ -0,0 +1,261 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{
+    batch::PartitionKey,
+    encryption::JournalEncryptionProvider,
+    journal::{
+        batch_reader::{Batch, JournalBatchReader},
+        reader::JournalReader,
+    },
+    RecoveryMode,
+};
+use lsm_tree::{SeqNo, UserKey, UserValue, ValueType};
+use std::{
+    path::PathBuf,
+    sync::{mpsc::Sender, Arc, Mutex},
+};
+
+/// The kind of a committed write
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChangeKind {
+    /// The key was set to the value
+    Value,
+
+    /// The key was removed
+    Tombstone,
+
+    /// The key was removed with a weak tombstone
+    WeakTombstone,
+
+    /// The value is a merge operand for the key
+    MergeOperand,
+
+    /// All keys from the key (inclusive) up to the given end key (exclusive) were removed,
+    /// or all keys from the key on, if there is no end key
+    ///
+    /// The value is empty.
+    RangeTombstone(Option<UserKey>),
+}
+
+impl From<ValueType> for ChangeKind {
+    fn from(value: ValueType) -> Self {
+        match value {
+            ValueType::Value => Self::Value,
+            ValueType::Tombstone => Self::Tombstone,
+            ValueType::WeakTombstone => Self::WeakTombstone,
+        }
+    }
+}
+
+/// A committed write, in the form of `(partition, key, value, kind)`
+///
+/// Clearing a partition is emitted as a range tombstone starting at the empty key.
+pub type Change = (PartitionKey, UserKey, UserValue, ChangeKind);
+
+/// The writes of a committed batch, in the form of `(seqno, changes)`
+///
+/// See [`Keyspace::subscribe`](crate::Keyspace::subscribe).
+pub type ChangeBatch = (SeqNo, Vec<Change>);
+
+/// Batches that are committed while older batches are still being replayed,
+/// see [`Keyspace::subscribe_from`](crate::Keyspace::subscribe_from)
+///
+/// Set to `None` once replaying has finished.
+pub type Backlog = Arc<Mutex<Option<Vec<ChangeBatch>>>>;
+
+struct Subscriber {
+    sender: Sender<ChangeBatch>,
+    backlog: Option<Backlog>,
+}
+
+impl Subscriber {
+    /// Sends the batch, or buffers it, if older batches are still being replayed.
+    ///
+    /// Returns `false` if the receiver was dropped.
+    #[allow(clippy::expect_used)]
+    fn send(&self, batch: ChangeBatch) -> bool {
+        if let Some(backlog) = &self.backlog {
+            if let Some(buffered) = backlog.lock().expect("lock is poisoned").as_mut() {
+                buffered.push(batch);
+                return true;
+            }
+        }
+
+        self.sender.send(batch).is_ok()
+    }
+}
+
+/// Subscribers of committed writes
+#[derive(Default)]
+pub struct Subscribers(Vec<Subscriber>);
+
+impl Subscribers {
+    pub fn add(&mut self, sender: Sender<ChangeBatch>) {
+        self.0.push(Subscriber {
+            sender,
+            backlog: None,
+        });
+    }
+
+    /// Adds a subscriber, whose batches are buffered until [`finish_replay`] is called.
+    pub fn add_replaying(&mut self, sender: Sender<ChangeBatch>) -> Backlog {
+        let backlog = Backlog::new(Mutex::new(Some(vec![])));
+
+        self.0.push(Subscriber {
+            sender,
+            backlog: Some(backlog.clone()),
+        });
+
+        backlog
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Sends the changes of a committed batch to all subscribers.
+    ///
+    /// Subscribers whose receiver was dropped are removed.
+    pub fn publish(&mut self, seqno: SeqNo, changes: Vec<Change>) {
+        if changes.is_empty() {
+            return;
+        }
+
+        let count = self.0.len();
+        let mut changes = Some(changes);
+        let mut idx = 0;
+
+        self.0.retain(|subscriber| {
+            idx += 1;
+
+            // NOTE: The last subscriber gets the changes themselves, so a single
+            // subscriber does not need to copy them
+            let changes = if idx == count {
+                changes.take()
+            } else {
+                changes.clone()
+            };
+
+            subscriber.send((seqno, changes.unwrap_or_default()))
+        });
+    }
+}
+
+/// Returns the changes of a batch read from a journal.
+fn changes_of(batch: Batch) -> Vec<Change> {
+    let range_tombstones = batch.range_tombstones.into_iter().map(|tombstone| {
+        (
+            tombstone.partition,
+            tombstone.start,
+            UserValue::from(&[][..]),
+            ChangeKind::RangeTombstone(tombstone.end),
+        )
+    });
+
+    let items = batch
+        .items
+        .into_iter()
+        .map(|item| (item.partition, item.key, item.value, item.value_type.into()));
+
+    let merges = batch.merges.into_iter().map(|item| {
+        (
+            item.partition,
+            item.key,
+            item.operand,
+            ChangeKind::MergeOperand,
+        )
+    });
+
+    range_tombstones.chain(items).chain(merges).collect()
+}
+
+/// Sends the batches in the given journals (oldest first), starting at the given seqno.
+///
+/// Batches from `until` on are not sent, those are buffered by the subscriber, see [`finish_replay`].
+///
+/// The journals are not modified, even if they contain invalid batches.
+///
+/// Values are passed through `resolve`, so references to large values are replaced by the values.
+pub fn replay<F: Fn(&PartitionKey, &UserKey, UserValue) -> crate::Result<UserValue>>(
+    journals: &[PathBuf],
+    seqno: SeqNo,
+    until: SeqNo,
+    encryption: Option<&Arc<dyn JournalEncryptionProvider>>,
+    sender: &Sender<ChangeBatch>,
+    resolve: F,
+) -> crate::Result<()> {
+    for path in journals {
+        log::debug!("Replaying journal {} from seqno={seqno}", path.display());
+
+        // NOTE: The active journal may be sealed, and sealed journals may be evicted while replaying
+        let reader = match JournalReader::new_read_only(path) {
+            Ok(reader) => reader,
+            Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut sealed_path = path.clone().into_os_string();
+                sealed_path.push(".sealed");
+
+                match JournalReader::new_read_only(&sealed_path) {
+                    Ok(reader) => reader,
+                    Err(crate::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                        log::debug!("Journal {} was evicted, skipping", path.display());
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        let reader = JournalBatchReader::new(
+            reader.with_encryption(encryption.cloned()),
+            RecoveryMode::SkipInvalidBatches,
+        );
+
+        for batch in reader {
+            let batch = batch?;
+
+            // NOTE: The active journal may still be written to, so later batches
+            // may be incomplete, or even appear corrupt
+            if batch.seqno >= until {
+                return Ok(());
+            }
+
+            if batch.seqno < seqno {
+                continue;
+            }
+
+            let batch_seqno = batch.seqno;
+            let changes = changes_of(batch)
+                .into_iter()
+                .map(|(partition, key, value, kind)| {
+                    let value = match kind {
+                        ChangeKind::Value => resolve(&partition, &key, value)?,
+                        _ => value,
+                    };
+                    Ok((partition, key, value, kind))
+                })
+                .collect::<crate::Result<Vec<_>>>()?;
+
+            if !changes.is_empty() && sender.send((batch_seqno, changes)).is_err() {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Sends the batches that were buffered while replaying, and stops buffering.
+#[allow(clippy::expect_used)]
+pub fn finish_replay(backlog: &Backlog, sender: &Sender<ChangeBatch>) {
+    // NOTE: The lock is held while sending, so batches that are published
+    // in the meantime are sent after the buffered ones
+    let mut backlog = backlog.lock().expect("lock is poisoned");
+
+    for batch in backlog.take().unwrap_or_default() {
+        if sender.send(batch).is_err() {
+            break;
+        }
+    }
+}
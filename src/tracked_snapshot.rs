@@ -2,7 +2,9 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::snapshot_nonce::SnapshotNonce;
+use crate::{cursor::Cursor, read_filter::ReadFilter, snapshot_nonce::SnapshotNonce};
+use lsm_tree::{KvPair, UserKey, UserValue};
+use std::ops::RangeBounds;
 
 /// A snapshot captures a read-only point-in-time view of the tree at the time the snapshot was created
 ///
@@ -15,6 +17,8 @@ pub struct TrackedSnapshot {
 
     #[allow(unused)]
     nonce: SnapshotNonce,
+
+    filter: ReadFilter,
 }
 
 impl std::ops::Deref for TrackedSnapshot {
@@ -26,10 +30,161 @@ impl std::ops::Deref for TrackedSnapshot {
 }
 
 impl TrackedSnapshot {
-    pub(crate) fn new(snapshot: lsm_tree::Snapshot, nonce: SnapshotNonce) -> Self {
+    pub(crate) fn new(
+        snapshot: lsm_tree::Snapshot,
+        nonce: SnapshotNonce,
+        filter: ReadFilter,
+    ) -> Self {
         Self {
             inner: snapshot,
             nonce,
+            filter,
+        }
+    }
+
+    fn read_filter(&self) -> ReadFilter {
+        self.filter.clone()
+    }
+
+    /// Retrieves an item from the snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> crate::Result<Option<UserValue>> {
+        let Some(value) = self.inner.get(&key)? else {
+            return Ok(None);
+        };
+
+        let filter = self.read_filter();
+
+        if filter.is_deleted(key.as_ref())? {
+            return Ok(None);
+        }
+
+        Ok(Some(filter.resolve(key.as_ref(), value)?))
+    }
+
+    /// Returns `true` if the snapshot contains the specified key.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn contains_key<K: AsRef<[u8]>>(&self, key: K) -> crate::Result<bool> {
+        if !self.inner.contains_key(&key)? {
+            return Ok(false);
+        }
+
+        Ok(!self.read_filter().is_deleted(key.as_ref())?)
+    }
+
+    /// Returns an iterator that scans through the entire snapshot.
+    ///
+    /// Avoid using this function, or limit it as otherwise it may scan a lot of items.
+    #[must_use]
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
+        self.read_filter()
+            .apply_kv(self.inner.iter().map(|item| item.map_err(Into::into)))
+    }
+
+    /// Returns an iterator that scans through the entire snapshot, returning only keys.
+    ///
+    /// Avoid using this function, or limit it as otherwise it may scan a lot of items.
+    #[must_use]
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = crate::Result<UserKey>> + 'static {
+        self.read_filter().apply(
+            self.inner.keys().map(|item| item.map_err(Into::into)),
+            |key| key,
+        )
+    }
+
+    /// Returns an iterator that scans through the entire snapshot, returning only values.
+    ///
+    /// Avoid using this function, or limit it as otherwise it may scan a lot of items.
+    #[must_use]
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = crate::Result<UserValue>> + 'static {
+        self.iter().map(|item| item.map(|(_, value)| value))
+    }
+
+    /// Returns an iterator over a range of items in the snapshot.
+    ///
+    /// Avoid using full or unbounded ranges as they may scan a lot of items (unless limited).
+    pub fn range<K: AsRef<[u8]>, R: RangeBounds<K>>(
+        &self,
+        range: R,
+    ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
+        self.read_filter()
+            .apply_kv(self.inner.range(range).map(|item| item.map_err(Into::into)))
+    }
+
+    /// Returns an iterator over a prefixed set of items in the snapshot.
+    ///
+    /// Avoid using an empty prefix as it may scan a lot of items (unless limited).
+    pub fn prefix<K: AsRef<[u8]>>(
+        &self,
+        prefix: K,
+    ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
+        self.read_filter().apply_kv(
+            self.inner
+                .prefix(prefix)
+                .map(|item| item.map_err(Into::into)),
+        )
+    }
+
+    /// Opens a seekable cursor over the snapshot.
+    ///
+    /// See [`Cursor`] for more.
+    #[must_use]
+    pub fn cursor(&self) -> Cursor<'_> {
+        Cursor::new(move |bounds| self.range(bounds))
+    }
+
+    /// Returns the first key-value pair in the snapshot.
+    /// The key in this pair is the minimum key in the snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn first_key_value(&self) -> crate::Result<Option<KvPair>> {
+        self.iter().next().transpose()
+    }
+
+    /// Returns the last key-value pair in the snapshot.
+    /// The key in this pair is the maximum key in the snapshot.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn last_key_value(&self) -> crate::Result<Option<KvPair>> {
+        self.iter().next_back().transpose()
+    }
+
+    /// Returns `true` if the snapshot is empty.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn is_empty(&self) -> crate::Result<bool> {
+        self.first_key_value().map(|x| x.is_none())
+    }
+
+    /// Scans the entire snapshot, returning the amount of items.
+    ///
+    /// ###### Caution
+    ///
+    /// This operation scans the entire snapshot: O(n) complexity!
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn len(&self) -> crate::Result<usize> {
+        let mut count = 0;
+
+        for kv in self.iter() {
+            let _ = kv?;
+            count += 1;
         }
+
+        Ok(count)
     }
 }
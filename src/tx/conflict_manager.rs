@@ -0,0 +1,177 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use crate::{batch::PartitionKey, HashMap};
+use lsm_tree::UserKey;
+use std::{
+    collections::HashSet,
+    ops::{Bound, RangeBounds},
+};
+
+type KeyRange = (Bound<UserKey>, Bound<UserKey>);
+
+fn to_owned_bound<K: AsRef<[u8]>>(bound: Bound<&K>) -> Bound<UserKey> {
+    match bound {
+        Bound::Included(k) => Bound::Included(k.as_ref().into()),
+        Bound::Excluded(k) => Bound::Excluded(k.as_ref().into()),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
+/// Reads of a transaction in a single partition
+#[derive(Default)]
+struct ReadSet {
+    /// Keys that were read using point reads
+    keys: HashSet<UserKey>,
+
+    /// Key ranges that were scanned
+    ranges: Vec<KeyRange>,
+}
+
+impl ReadSet {
+    fn contains(&self, key: &UserKey) -> bool {
+        self.keys.contains(key) || self.ranges.iter().any(|range| range.contains(key))
+    }
+
+    /// Returns `true` if any read touched the key range `[start, end)`.
+    fn overlaps(&self, start: &UserKey, end: Option<&UserKey>) -> bool {
+        let in_range = |key: &UserKey| key >= start && end.map_or(true, |end| key < end);
+
+        if self.keys.iter().any(in_range) {
+            return true;
+        }
+
+        self.ranges.iter().any(|(lo, hi)| {
+            let starts_before_end = match (lo, end) {
+                (_, None) | (Bound::Unbounded, _) => true,
+                (Bound::Included(lo) | Bound::Excluded(lo), Some(end)) => lo < end,
+            };
+
+            let ends_after_start = match hi {
+                Bound::Unbounded => true,
+                Bound::Included(hi) => hi >= start,
+                Bound::Excluded(hi) => hi > start,
+            };
+
+            starts_before_end && ends_after_start
+        })
+    }
+}
+
+/// Tracks the read set of a transaction, used to detect
+/// read-write conflicts with concurrently committed transactions
+#[derive(Default)]
+pub struct ConflictManager {
+    reads: HashMap<PartitionKey, ReadSet>,
+}
+
+impl ConflictManager {
+    fn partition(&mut self, partition: &PartitionKey) -> &mut ReadSet {
+        self.reads.entry(partition.clone()).or_default()
+    }
+
+    /// Marks a single key as read.
+    pub fn mark_read(&mut self, partition: &PartitionKey, key: &[u8]) {
+        self.partition(partition).keys.insert(key.into());
+    }
+
+    /// Marks a range of keys as read.
+    pub fn mark_range<K: AsRef<[u8]>, R: RangeBounds<K>>(
+        &mut self,
+        partition: &PartitionKey,
+        range: &R,
+    ) {
+        let start = to_owned_bound(range.start_bound());
+        let end = to_owned_bound(range.end_bound());
+        self.partition(partition).ranges.push((start, end));
+    }
+
+    /// Marks the entire partition as read.
+    pub fn mark_full_scan(&mut self, partition: &PartitionKey) {
+        self.partition(partition)
+            .ranges
+            .push((Bound::Unbounded, Bound::Unbounded));
+    }
+
+    /// Returns `true` if the given key in the partition was read.
+    pub fn has_conflict(&self, partition: &PartitionKey, key: &UserKey) -> bool {
+        self.reads
+            .get(partition)
+            .is_some_and(|reads| reads.contains(key))
+    }
+
+    /// Returns `true` if any key in the range `[start, end)` of the partition was read.
+    pub fn has_range_conflict(
+        &self,
+        partition: &PartitionKey,
+        start: &UserKey,
+        end: Option<&UserKey>,
+    ) -> bool {
+        self.reads
+            .get(partition)
+            .is_some_and(|reads| reads.overlaps(start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_log::test;
+
+    #[test]
+    fn conflict_manager_point_read() {
+        let partition: PartitionKey = "default".into();
+        let mut cm = ConflictManager::default();
+
+        cm.mark_read(&partition, b"a");
+
+        assert!(cm.has_conflict(&partition, &UserKey::from(*b"a")));
+        assert!(!cm.has_conflict(&partition, &UserKey::from(*b"b")));
+        assert!(!cm.has_conflict(&"other".into(), &UserKey::from(*b"a")));
+    }
+
+    #[test]
+    fn conflict_manager_range_read() {
+        let partition: PartitionKey = "default".into();
+        let mut cm = ConflictManager::default();
+
+        cm.mark_range(&partition, &("b"..="d"));
+
+        assert!(!cm.has_conflict(&partition, &UserKey::from(*b"a")));
+        assert!(cm.has_conflict(&partition, &UserKey::from(*b"b")));
+        assert!(cm.has_conflict(&partition, &UserKey::from(*b"c")));
+        assert!(cm.has_conflict(&partition, &UserKey::from(*b"d")));
+        assert!(!cm.has_conflict(&partition, &UserKey::from(*b"e")));
+    }
+
+    #[test]
+    fn conflict_manager_full_scan() {
+        let partition: PartitionKey = "default".into();
+        let mut cm = ConflictManager::default();
+
+        cm.mark_full_scan(&partition);
+
+        assert!(cm.has_conflict(&partition, &UserKey::from(*b"a")));
+        assert!(cm.has_conflict(&partition, &UserKey::from(*b"zzz")));
+    }
+
+    #[test]
+    fn conflict_manager_range_write() {
+        let partition: PartitionKey = "default".into();
+        let mut cm = ConflictManager::default();
+
+        cm.mark_read(&partition, b"a");
+        cm.mark_range(&partition, &("m".."p"));
+
+        let key = |k: &[u8]| UserKey::from(k);
+
+        assert!(cm.has_range_conflict(&partition, &key(b"a"), Some(&key(b"b"))));
+        assert!(!cm.has_range_conflict(&partition, &key(b"b"), Some(&key(b"c"))));
+        assert!(cm.has_range_conflict(&partition, &key(b"o"), None));
+        assert!(!cm.has_range_conflict(&partition, &key(b"p"), None));
+        assert!(!cm.has_range_conflict(&partition, &key(b"b"), Some(&key(b"m"))));
+        assert!(cm.has_range_conflict(&partition, &key(b"b"), Some(&key(b"ma"))));
+        assert!(!cm.has_range_conflict(&"other".into(), &key(b""), None));
+    }
+}
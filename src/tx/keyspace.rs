@@ -4,17 +4,29 @@
 
 use super::{read_tx::ReadTransaction, write_tx::WriteTransaction};
 use crate::{
-    batch::PartitionKey, snapshot_nonce::SnapshotNonce, Config, Keyspace, PartitionCreateOptions,
-    PersistMode, TxPartitionHandle,
+    batch::PartitionKey, snapshot_nonce::SnapshotNonce, ChangeBatch, Config, Keyspace,
+    PartitionCreateOptions, PersistMode, TxPartitionHandle,
 };
-use std::sync::{Arc, Mutex};
+use lsm_tree::SeqNo;
+use std::sync::{mpsc::Receiver, Arc};
+
+#[cfg(feature = "single_writer_tx")]
+use std::sync::Mutex;
+
+#[cfg(feature = "ssi_tx")]
+use super::oracle::Oracle;
 
 /// Transaction keyspace
 #[derive(Clone)]
 #[allow(clippy::module_name_repetitions)]
 pub struct TransactionalKeyspace {
     inner: Keyspace,
+
+    #[cfg(feature = "single_writer_tx")]
     tx_lock: Arc<Mutex<()>>,
+
+    #[cfg(feature = "ssi_tx")]
+    pub(crate) oracle: Arc<Oracle>,
 }
 
 /// Alias for [`TransactionalKeyspace`]
@@ -24,6 +36,7 @@ pub type TxKeyspace = TransactionalKeyspace;
 impl TxKeyspace {
     /// Starts a new writeable transaction.
     #[must_use]
+    #[cfg(feature = "single_writer_tx")]
     pub fn write_tx(&self) -> WriteTransaction {
         let lock = self.tx_lock.lock().expect("lock is poisoned");
 
@@ -43,6 +56,28 @@ impl TxKeyspace {
         write_tx
     }
 
+    /// Starts a new writeable transaction.
+    ///
+    /// Transactions run concurrently and are checked for conflicts
+    /// when committing, see [`WriteTransaction::commit`].
+    #[must_use]
+    #[cfg(feature = "ssi_tx")]
+    pub fn write_tx(&self) -> WriteTransaction {
+        let guard = self.oracle.begin(&self.inner);
+
+        let mut write_tx = WriteTransaction::new(
+            self.inner.clone(),
+            SnapshotNonce::new(guard.instant, self.inner.snapshot_tracker.clone()),
+            guard,
+        );
+
+        if !self.inner.config.manual_journal_persist {
+            write_tx = write_tx.durability(Some(PersistMode::Buffer));
+        }
+
+        write_tx
+    }
+
     /// Starts a new read-only transaction.
     #[must_use]
     pub fn read_tx(&self) -> ReadTransaction {
@@ -54,6 +89,25 @@ impl TxKeyspace {
         ))
     }
 
+    /// Subscribes to committed writes, including writes of transactions.
+    ///
+    /// See [`Keyspace::subscribe`].
+    #[must_use]
+    pub fn subscribe(&self) -> Receiver<ChangeBatch> {
+        self.inner.subscribe()
+    }
+
+    /// Subscribes to committed writes, starting at the given seqno.
+    ///
+    /// See [`Keyspace::subscribe_from`].
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if an IO error occurs.
+    pub fn subscribe_from(&self, seqno: SeqNo) -> crate::Result<Receiver<ChangeBatch>> {
+        self.inner.subscribe_from(seqno)
+    }
+
     /// Flushes the active journal. The durability depends on the [`PersistMode`]
     /// used.
     ///
@@ -86,11 +140,7 @@ impl TxKeyspace {
     ///
     /// # Errors
     ///
-    /// Returns error, if an IO error occurred.
-    ///
-    /// # Panics
-    ///
-    /// Panics if the partition name includes characters other than: a-z A-Z 0-9 _ -
+    /// Returns error, if an IO error occurred, or the partition name is invalid.
     pub fn open_partition(
         &self,
         name: &str,
@@ -100,7 +150,12 @@ impl TxKeyspace {
 
         Ok(TxPartitionHandle {
             inner: partition,
+
+            #[cfg(feature = "single_writer_tx")]
             tx_lock: self.tx_lock.clone(),
+
+            #[cfg(feature = "ssi_tx")]
+            keyspace: self.clone(),
         })
     }
 
@@ -153,14 +208,19 @@ impl TxKeyspace {
     ///
     /// # Errors
     ///
-    /// Returns error, if an IO error occurred.
+    /// Returns error, if an IO error occurred, or the keyspace is locked by another process.
     pub fn open(config: Config) -> crate::Result<Self> {
         let inner = Keyspace::create_or_recover(config)?;
         inner.start_background_threads();
 
         Ok(Self {
             inner,
+
+            #[cfg(feature = "single_writer_tx")]
             tx_lock: Arc::default(),
+
+            #[cfg(feature = "ssi_tx")]
+            oracle: Arc::default(),
         })
     }
 }
@@ -5,6 +5,12 @@
 pub mod keyspace;
 pub mod partition;
 
+#[cfg(feature = "ssi_tx")]
+mod conflict_manager;
+
+#[cfg(feature = "ssi_tx")]
+mod oracle;
+
 #[allow(clippy::module_name_repetitions)]
 pub mod read_tx;
 
@@ -0,0 +1,228 @@
+// Copyright (c) 2024-present, fjall-rs
+// This source code is licensed under both the Apache 2.0 and MIT License
+// (found in the LICENSE-* files in the repository)
+
+use super::conflict_manager::ConflictManager;
+use crate::{batch::PartitionKey, HashMap, Instant, Keyspace};
+use lsm_tree::{SeqNo, UserKey};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex, MutexGuard, RwLock},
+};
+
+/// Writes of a committed transaction in a single partition
+#[derive(Default)]
+pub struct PartitionWrites {
+    /// Keys that were written or removed
+    pub keys: Vec<UserKey>,
+
+    /// Key ranges that were removed using range tombstones
+    pub ranges: Vec<(UserKey, Option<UserKey>)>,
+}
+
+/// Writes of a committed transaction, per partition
+pub type WriteSet = HashMap<PartitionKey, PartitionWrites>;
+
+struct CommittedTx {
+    seqno: SeqNo,
+    writes: WriteSet,
+}
+
+#[derive(Default)]
+struct OracleInner {
+    /// Recently committed transactions, ordered by commit seqno
+    committed: VecDeque<CommittedTx>,
+
+    /// Write sets of transactions that passed the conflict check,
+    /// but whose batch is still being committed
+    pending: BTreeMap<u64, WriteSet>,
+
+    next_pending_id: u64,
+
+    /// Read instants of transactions that are still running
+    active: BTreeMap<Instant, usize>,
+}
+
+impl OracleInner {
+    /// Returns `true` if a transaction that committed after the given instant,
+    /// or that is still committing, wrote something the conflict manager has read.
+    fn has_conflict(&self, instant: Instant, conflict_manager: &ConflictManager) -> bool {
+        let committed = self
+            .committed
+            .iter()
+            .filter(|tx| tx.seqno >= instant)
+            .map(|tx| &tx.writes);
+
+        committed
+            .chain(self.pending.values())
+            .flat_map(|writes| writes.iter())
+            .any(|(partition, writes)| {
+                writes
+                    .keys
+                    .iter()
+                    .any(|key| conflict_manager.has_conflict(partition, key))
+                    || writes.ranges.iter().any(|(start, end)| {
+                        conflict_manager.has_range_conflict(partition, start, end.as_ref())
+                    })
+            })
+    }
+
+    /// Drops committed transactions that cannot conflict with
+    /// any running transaction anymore.
+    fn prune(&mut self) {
+        let Some(&oldest_instant) = self.active.keys().next() else {
+            self.committed.clear();
+            return;
+        };
+
+        while self
+            .committed
+            .front()
+            .is_some_and(|tx| tx.seqno < oldest_instant)
+        {
+            self.committed.pop_front();
+        }
+    }
+}
+
+/// Decides whether a transaction is allowed to commit
+///
+/// Keeps track of the write sets of recently committed transactions,
+/// so a committing transaction can check whether anything it read
+/// has been changed since it started.
+#[derive(Default)]
+pub struct Oracle {
+    inner: Mutex<OracleInner>,
+
+    /// Read locked by starting and committing transactions,
+    /// see [`Oracle::lock`]
+    gate: RwLock<()>,
+}
+
+impl Oracle {
+    #[allow(clippy::expect_used)]
+    fn inner(&self) -> MutexGuard<'_, OracleInner> {
+        self.inner.lock().expect("lock is poisoned")
+    }
+
+    /// Registers a new transaction, returning its read instant.
+    ///
+    /// The instant is taken while holding the oracle lock, so committed transactions
+    /// with a seqno from the instant on are not pruned before the new transaction ends.
+    #[allow(clippy::expect_used)]
+    pub fn begin(self: &Arc<Self>, keyspace: &Keyspace) -> TxGuard {
+        let _gate = self.gate.read().expect("lock is poisoned");
+        let mut inner = self.inner();
+
+        let instant = keyspace.instant();
+        *inner.active.entry(instant).or_default() += 1;
+        drop(inner);
+
+        TxGuard {
+            oracle: self.clone(),
+            instant,
+        }
+    }
+
+    /// Runs the given commit function if the transaction does not conflict
+    /// with any transaction that committed after it started.
+    ///
+    /// The oracle lock is only held while checking for conflicts, so commits
+    /// (and their journal syncs) run concurrently. In the meantime, the transaction's
+    /// writes are pending, and conflict with every transaction that is checked.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Conflict`] if a conflict was detected.
+    #[allow(clippy::expect_used)]
+    pub fn commit<F: FnOnce() -> crate::Result<SeqNo>>(
+        &self,
+        instant: Instant,
+        conflict_manager: &ConflictManager,
+        writes: WriteSet,
+        f: F,
+    ) -> crate::Result<()> {
+        let _gate = self.gate.read().expect("lock is poisoned");
+
+        let pending_id = {
+            let mut inner = self.inner();
+
+            if inner.has_conflict(instant, conflict_manager) {
+                return Err(crate::Error::Conflict);
+            }
+
+            let pending_id = inner.next_pending_id;
+            inner.next_pending_id += 1;
+            inner.pending.insert(pending_id, writes);
+
+            pending_id
+        };
+
+        let result = f();
+
+        let mut inner = self.inner();
+        let writes = inner.pending.remove(&pending_id).unwrap_or_default();
+
+        if let Ok(seqno) = result {
+            // NOTE: Concurrent commits may finish out of seqno order
+            let idx = inner.committed.partition_point(|tx| tx.seqno < seqno);
+            inner.committed.insert(idx, CommittedTx { seqno, writes });
+        }
+
+        inner.prune();
+        drop(inner);
+
+        result.map(|_| ())
+    }
+
+    /// Blocks other transactions from starting or committing while the lock is held.
+    ///
+    /// Waits for transactions that are committing.
+    #[allow(clippy::expect_used)]
+    pub fn lock(&self) -> impl Drop + '_ {
+        self.gate.write().expect("lock is poisoned")
+    }
+
+    fn end(&self, instant: Instant) {
+        let mut inner = self.inner();
+
+        if let Some(count) = inner.active.get_mut(&instant) {
+            *count -= 1;
+
+            if *count == 0 {
+                inner.active.remove(&instant);
+            }
+        }
+
+        inner.prune();
+    }
+}
+
+/// Keeps a transaction registered in the oracle until it is dropped
+pub struct TxGuard {
+    oracle: Arc<Oracle>,
+    pub(crate) instant: Instant,
+}
+
+impl TxGuard {
+    /// Commits the transaction through the oracle.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`crate::Error::Conflict`] if a conflict was detected.
+    pub fn commit<F: FnOnce() -> crate::Result<SeqNo>>(
+        &self,
+        conflict_manager: &ConflictManager,
+        writes: WriteSet,
+        f: F,
+    ) -> crate::Result<()> {
+        self.oracle
+            .commit(self.instant, conflict_manager, writes, f)
+    }
+}
+
+impl Drop for TxGuard {
+    fn drop(&mut self) {
+        self.oracle.end(self.instant);
+    }
+}
@@ -4,16 +4,24 @@
 
 use crate::{gc::GarbageCollection, PartitionHandle};
 use lsm_tree::{GcReport, UserValue};
-use std::{
-    path::PathBuf,
-    sync::{Arc, Mutex},
-};
+use std::path::PathBuf;
+
+#[cfg(feature = "single_writer_tx")]
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "ssi_tx")]
+use crate::{TxKeyspace, WriteTransaction};
 
 /// Access to a partition of a transactional keyspace
 #[derive(Clone)]
 pub struct TransactionalPartitionHandle {
     pub(crate) inner: PartitionHandle,
+
+    #[cfg(feature = "single_writer_tx")]
     pub(crate) tx_lock: Arc<Mutex<()>>,
+
+    #[cfg(feature = "ssi_tx")]
+    pub(crate) keyspace: TxKeyspace,
 }
 
 impl GarbageCollection for TransactionalPartitionHandle {
@@ -22,12 +30,12 @@ impl GarbageCollection for TransactionalPartitionHandle {
     }
 
     fn gc_with_space_amp_target(&self, factor: f32) -> crate::Result<u64> {
-        let _lock = self.tx_lock.lock().expect("lock is poisoned");
+        let _lock = self.lock();
         crate::gc::GarbageCollector::with_space_amp_target(self.inner(), factor)
     }
 
     fn gc_with_staleness_threshold(&self, threshold: f32) -> crate::Result<u64> {
-        let _lock = self.tx_lock.lock().expect("lock is poisoned");
+        let _lock = self.lock();
         crate::gc::GarbageCollector::with_staleness_threshold(self.inner(), threshold)
     }
 
@@ -37,6 +45,40 @@ impl GarbageCollection for TransactionalPartitionHandle {
 }
 
 impl TransactionalPartitionHandle {
+    /// Blocks write transactions from committing while the lock is held.
+    #[cfg(feature = "single_writer_tx")]
+    #[allow(clippy::expect_used)]
+    fn lock(&self) -> impl Drop + '_ {
+        self.tx_lock.lock().expect("lock is poisoned")
+    }
+
+    /// Blocks write transactions from starting or committing while the lock is held.
+    #[cfg(feature = "ssi_tx")]
+    fn lock(&self) -> impl Drop + '_ {
+        self.keyspace.oracle.lock()
+    }
+
+    /// Runs the given function in a write transaction, retrying it
+    /// until it commits without conflict.
+    #[cfg(feature = "ssi_tx")]
+    fn run_tx<T, F: Fn(&mut WriteTransaction) -> crate::Result<T>>(
+        &self,
+        f: F,
+    ) -> crate::Result<T> {
+        loop {
+            let mut tx = self.keyspace.write_tx();
+            let result = f(&mut tx)?;
+
+            match tx.commit() {
+                Ok(()) => return Ok(result),
+                Err(crate::Error::Conflict) => {
+                    log::trace!("tx: Conflict detected, retrying");
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
     /// Returns the underlying LSM-tree's path
     #[must_use]
     pub fn path(&self) -> PathBuf {
@@ -119,23 +161,33 @@ impl TransactionalPartitionHandle {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
     pub fn fetch_update<K: AsRef<[u8]>, F: Fn(Option<&UserValue>) -> Option<UserValue>>(
         &self,
         key: K,
         f: F,
     ) -> crate::Result<Option<UserValue>> {
-        let _lock = self.tx_lock.lock().expect("lock is poisoned");
+        #[cfg(feature = "ssi_tx")]
+        return self.run_tx(|tx| tx.fetch_update(self, &key, &f));
 
-        let prev = self.inner.get(&key)?;
-        let updated = f(prev.as_ref());
+        #[cfg(feature = "single_writer_tx")]
+        {
+            let _lock = self.lock();
 
-        if let Some(value) = updated {
-            self.inner.insert(&key, value)?;
-        } else if prev.is_some() {
-            self.inner.remove(&key)?;
-        }
+            let prev = self.inner.get(&key)?;
+            let updated = f(prev.as_ref());
+
+            if let Some(value) = updated {
+                self.inner.insert(&key, value)?;
+            } else if prev.is_some() {
+                self.inner.remove(&key)?;
+            }
 
-        Ok(prev)
+            Ok(prev)
+        }
     }
 
     /// Atomically updates an item and returns the new value.
@@ -185,28 +237,38 @@ impl TransactionalPartitionHandle {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
     pub fn update_fetch<K: AsRef<[u8]>, F: Fn(Option<&UserValue>) -> Option<UserValue>>(
         &self,
         key: K,
         f: F,
     ) -> crate::Result<Option<UserValue>> {
-        let _lock = self.tx_lock.lock().expect("lock is poisoned");
+        #[cfg(feature = "ssi_tx")]
+        return self.run_tx(|tx| tx.update_fetch(self, &key, &f));
 
-        let prev = self.inner.get(&key)?;
-        let updated = f(prev.as_ref());
+        #[cfg(feature = "single_writer_tx")]
+        {
+            let _lock = self.lock();
 
-        if let Some(value) = &updated {
-            self.inner.insert(&key, value)?;
-        } else if prev.is_some() {
-            self.inner.remove(&key)?;
-        }
+            let prev = self.inner.get(&key)?;
+            let updated = f(prev.as_ref());
 
-        Ok(updated)
+            if let Some(value) = &updated {
+                self.inner.insert(&key, value)?;
+            } else if prev.is_some() {
+                self.inner.remove(&key)?;
+            }
+
+            Ok(updated)
+        }
     }
 
     /// Inserts a key-value pair into the partition.
     ///
-    /// Keys may be up to 65536 bytes long, values up to 2^32 bytes.
+    /// Keys may be up to 65535 bytes long, values up to 2^32 - 1 bytes.
     /// Shorter keys and values result in better performance.
     ///
     /// If the key already exists, the item will be overwritten.
@@ -231,14 +293,27 @@ impl TransactionalPartitionHandle {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
     pub fn insert<K: AsRef<[u8]>, V: AsRef<[u8]>>(&self, key: K, value: V) -> crate::Result<()> {
-        let _lock = self.tx_lock.lock().expect("lock is poisoned");
-        self.inner.insert(key, value)
+        #[cfg(feature = "ssi_tx")]
+        return self.run_tx(|tx| {
+            tx.insert(self, &key, &value);
+            Ok(())
+        });
+
+        #[cfg(feature = "single_writer_tx")]
+        {
+            let _lock = self.lock();
+            self.inner.insert(key, value)
+        }
     }
 
     /// Removes an item from the partition.
     ///
-    /// The key may be up to 65536 bytes long.
+    /// The key may be up to 65535 bytes long.
     /// Shorter keys result in better performance.
     ///
     /// The operation will run wrapped in a transaction.
@@ -263,9 +338,22 @@ impl TransactionalPartitionHandle {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
     pub fn remove<K: AsRef<[u8]>>(&self, key: K) -> crate::Result<()> {
-        let _lock = self.tx_lock.lock().expect("lock is poisoned");
-        self.inner.remove(key)
+        #[cfg(feature = "ssi_tx")]
+        return self.run_tx(|tx| {
+            tx.remove(self, &key);
+            Ok(())
+        });
+
+        #[cfg(feature = "single_writer_tx")]
+        {
+            let _lock = self.lock();
+            self.inner.remove(key)
+        }
     }
 
     /// Retrieves an item from the partition.
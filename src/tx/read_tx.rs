@@ -2,7 +2,7 @@
 // This source code is licensed under both the Apache 2.0 and MIT License
 // (found in the LICENSE-* files in the repository)
 
-use crate::{snapshot_nonce::SnapshotNonce, TxPartitionHandle};
+use crate::{cursor::Cursor, snapshot_nonce::SnapshotNonce, TxPartitionHandle};
 use lsm_tree::{AbstractTree, KvPair, UserKey, UserValue};
 use std::ops::RangeBounds;
 
@@ -49,12 +49,22 @@ impl ReadTransaction {
         partition: &TxPartitionHandle,
         key: K,
     ) -> crate::Result<Option<UserValue>> {
-        partition
+        let Some(value) = partition
             .inner
             .tree
             .snapshot_at(self.nonce.instant)
-            .get(key)
-            .map_err(Into::into)
+            .get(&key)?
+        else {
+            return Ok(None);
+        };
+
+        let filter = partition.inner.read_filter(Some(self.nonce.instant));
+
+        if filter.is_deleted(key.as_ref())? {
+            return Ok(None);
+        }
+
+        Ok(Some(filter.resolve(key.as_ref(), value)?))
     }
 
     /// Returns `true` if the transaction's state contains the specified key.
@@ -83,12 +93,19 @@ impl ReadTransaction {
         partition: &TxPartitionHandle,
         key: K,
     ) -> crate::Result<bool> {
-        partition
+        if !partition
             .inner
             .tree
             .snapshot_at(self.nonce.instant)
-            .contains_key(key)
-            .map_err(Into::into)
+            .contains_key(&key)?
+        {
+            return Ok(false);
+        }
+
+        Ok(!partition
+            .inner
+            .read_filter(Some(self.nonce.instant))
+            .is_deleted(key.as_ref())?)
     }
 
     /// Returns the first key-value pair in the transaction's state.
@@ -250,9 +267,14 @@ impl ReadTransaction {
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
         partition
             .inner
-            .tree
-            .iter_with_seqno(self.nonce.instant, None)
-            .map(|item| Ok(item?))
+            .read_filter(Some(self.nonce.instant))
+            .apply_kv(
+                partition
+                    .inner
+                    .tree
+                    .iter_with_seqno(self.nonce.instant, None)
+                    .map(|item| Ok(item?)),
+            )
     }
 
     /// Iterates over the transaction's state, returning keys only.
@@ -263,11 +285,14 @@ impl ReadTransaction {
         &'a self,
         partition: &'a TxPartitionHandle,
     ) -> impl DoubleEndedIterator<Item = crate::Result<UserKey>> + 'static {
-        partition
-            .inner
-            .tree
-            .keys_with_seqno(self.nonce.instant, None)
-            .map(|item| Ok(item?))
+        partition.inner.read_filter(Some(self.nonce.instant)).apply(
+            partition
+                .inner
+                .tree
+                .keys_with_seqno(self.nonce.instant, None)
+                .map(|item| Ok(item?)),
+            |key| key,
+        )
     }
 
     /// Iterates over the transaction's state, returning values only.
@@ -278,11 +303,8 @@ impl ReadTransaction {
         &'a self,
         partition: &'a TxPartitionHandle,
     ) -> impl DoubleEndedIterator<Item = crate::Result<UserValue>> + 'static {
-        partition
-            .inner
-            .tree
-            .values_with_seqno(self.nonce.instant, None)
-            .map(|item| Ok(item?))
+        self.iter(partition)
+            .map(|item| item.map(|(_, value)| value))
     }
 
     /// Iterates over a range of the transaction's state.
@@ -313,9 +335,14 @@ impl ReadTransaction {
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
         partition
             .inner
-            .tree
-            .range_with_seqno(range, self.nonce.instant, None)
-            .map(|item| Ok(item?))
+            .read_filter(Some(self.nonce.instant))
+            .apply_kv(
+                partition
+                    .inner
+                    .tree
+                    .range_with_seqno(range, self.nonce.instant, None)
+                    .map(|item| Ok(item?)),
+            )
     }
 
     /// Iterates over a range of the transaction's state.
@@ -346,8 +373,21 @@ impl ReadTransaction {
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
         partition
             .inner
-            .tree
-            .prefix_with_seqno(prefix, self.nonce.instant, None)
-            .map(|item| Ok(item?))
+            .read_filter(Some(self.nonce.instant))
+            .apply_kv(
+                partition
+                    .inner
+                    .tree
+                    .prefix_with_seqno(prefix, self.nonce.instant, None)
+                    .map(|item| Ok(item?)),
+            )
+    }
+
+    /// Opens a seekable cursor over the transaction's state.
+    ///
+    /// See [`Cursor`] for more.
+    #[must_use]
+    pub fn cursor<'a>(&'a self, partition: &'a TxPartitionHandle) -> Cursor<'a> {
+        Cursor::new(move |bounds| self.range(partition, bounds))
     }
 }
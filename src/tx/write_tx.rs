@@ -3,15 +3,29 @@
 // (found in the LICENSE-* files in the repository)
 
 use crate::{
-    batch::{item::Item, PartitionKey},
+    batch::{
+        item::{Item, RangeTombstoneItem},
+        PartitionKey,
+    },
+    cursor::Cursor,
+    large_value::PendingValue,
+    limits::check_key,
+    range_tombstone::{range_to_bounds, RangeTombstone},
+    read_filter::ReadFilter,
     snapshot_nonce::SnapshotNonce,
     Batch, HashMap, Keyspace, PersistMode, TxPartitionHandle,
 };
 use lsm_tree::{AbstractTree, InternalValue, KvPair, Memtable, SeqNo, UserKey, UserValue};
-use std::{
-    ops::RangeBounds,
-    sync::{Arc, MutexGuard},
-};
+use std::{ops::RangeBounds, sync::Arc};
+
+#[cfg(feature = "single_writer_tx")]
+use std::sync::MutexGuard;
+
+#[cfg(feature = "ssi_tx")]
+use super::{conflict_manager::ConflictManager, oracle::TxGuard};
+
+#[cfg(feature = "ssi_tx")]
+use std::{marker::PhantomData, sync::Mutex};
 
 fn ignore_tombstone_value(item: InternalValue) -> Option<InternalValue> {
     if item.is_tombstone() {
@@ -21,7 +35,35 @@ fn ignore_tombstone_value(item: InternalValue) -> Option<InternalValue> {
     }
 }
 
-/// A single-writer (serialized) cross-partition transaction
+/// Marks a point inside a [`WriteTransaction`] that can be rolled back to
+///
+/// See [`WriteTransaction::savepoint`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Savepoint(usize);
+
+/// Change to the transaction's write set,
+/// used to undo writes when rolling back to a savepoint
+enum UndoEntry {
+    /// Previous state of a key
+    Write {
+        partition: PartitionKey,
+        key: UserKey,
+        prev: Option<InternalValue>,
+    },
+
+    /// A range tombstone was added to the partition
+    RangeTombstone { partition: PartitionKey },
+
+    /// A write was rejected, see `write_error`
+    WriteError,
+}
+
+/// A cross-partition transaction
+///
+/// With the `single_writer_tx` feature, write transactions are serialized.
+///
+/// With the `ssi_tx` feature, write transactions run concurrently and are checked
+/// for conflicts on commit (optimistic, serializable snapshot isolation).
 ///
 /// Use [`WriteTransaction::commit`] to commit changes to the keyspace.
 ///
@@ -32,13 +74,41 @@ pub struct WriteTransaction<'a> {
     keyspace: Keyspace,
     memtables: HashMap<PartitionKey, Arc<Memtable>>,
 
+    /// Range tombstones that will be written on commit
+    range_tombstones: HashMap<PartitionKey, Vec<RangeTombstone>>,
+
+    /// Undo log, only kept once a savepoint has been created
+    undo_log: Option<Vec<UndoEntry>>,
+
+    /// First write that exceeded the size limits, which fails the commit
+    write_error: Option<crate::Error>,
+
+    /// Values that are too large for the journal and LSM-tree,
+    /// and have been written to their own files, in the form of `(partition, file, value)`
+    ///
+    /// The transaction's memtables store the files' references instead.
+    /// Files that are not committed are deleted when the transaction is dropped.
+    large_values: Vec<(PartitionKey, PendingValue, UserValue)>,
+
     nonce: SnapshotNonce,
 
+    #[cfg(feature = "single_writer_tx")]
     #[allow(unused)]
     tx_lock: MutexGuard<'a, ()>,
+
+    /// Read set, used for conflict detection
+    #[cfg(feature = "ssi_tx")]
+    conflict_manager: Mutex<ConflictManager>,
+
+    #[cfg(feature = "ssi_tx")]
+    tx_guard: TxGuard,
+
+    #[cfg(feature = "ssi_tx")]
+    phantom: PhantomData<&'a ()>,
 }
 
 impl<'a> WriteTransaction<'a> {
+    #[cfg(feature = "single_writer_tx")]
     pub(crate) fn new(
         keyspace: Keyspace,
         tx_lock: MutexGuard<'a, ()>,
@@ -47,12 +117,60 @@ impl<'a> WriteTransaction<'a> {
         Self {
             keyspace,
             memtables: HashMap::default(),
+            range_tombstones: HashMap::default(),
+            undo_log: None,
+            write_error: None,
+            large_values: Vec::new(),
             tx_lock,
             nonce,
             durability: None,
         }
     }
 
+    #[cfg(feature = "ssi_tx")]
+    pub(crate) fn new(keyspace: Keyspace, nonce: SnapshotNonce, tx_guard: TxGuard) -> Self {
+        Self {
+            keyspace,
+            memtables: HashMap::default(),
+            range_tombstones: HashMap::default(),
+            undo_log: None,
+            write_error: None,
+            large_values: Vec::new(),
+            nonce,
+            durability: None,
+            conflict_manager: Mutex::default(),
+            tx_guard,
+            phantom: PhantomData,
+        }
+    }
+
+    #[allow(clippy::unused_self, unused_variables, clippy::expect_used)]
+    fn mark_read(&self, partition: &PartitionKey, key: &[u8]) {
+        #[cfg(feature = "ssi_tx")]
+        self.conflict_manager
+            .lock()
+            .expect("lock is poisoned")
+            .mark_read(partition, key);
+    }
+
+    #[allow(clippy::unused_self, unused_variables, clippy::expect_used)]
+    fn mark_range<K: AsRef<[u8]>, R: RangeBounds<K>>(&self, partition: &PartitionKey, range: &R) {
+        #[cfg(feature = "ssi_tx")]
+        self.conflict_manager
+            .lock()
+            .expect("lock is poisoned")
+            .mark_range(partition, range);
+    }
+
+    #[allow(clippy::unused_self, unused_variables, clippy::expect_used)]
+    fn mark_full_scan(&self, partition: &PartitionKey) {
+        #[cfg(feature = "ssi_tx")]
+        self.conflict_manager
+            .lock()
+            .expect("lock is poisoned")
+            .mark_full_scan(partition);
+    }
+
     /// Sets the durability level.
     #[must_use]
     pub fn durability(mut self, mode: Option<PersistMode>) -> Self {
@@ -60,6 +178,146 @@ impl<'a> WriteTransaction<'a> {
         self
     }
 
+    /// Creates a savepoint, marking the current state of the transaction.
+    ///
+    /// Writes made after the savepoint can be discarded using [`WriteTransaction::rollback_to`],
+    /// without rolling back the entire transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, Keyspace, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(folder).open_transactional()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// let mut tx = keyspace.write_tx();
+    /// tx.insert(&partition, "a", "abc");
+    ///
+    /// let savepoint = tx.savepoint();
+    /// tx.insert(&partition, "a", "def");
+    /// tx.insert(&partition, "b", "def");
+    ///
+    /// tx.rollback_to(savepoint);
+    /// tx.commit()?;
+    ///
+    /// assert_eq!(b"abc", &*partition.get("a")?.unwrap());
+    /// assert!(partition.get("b")?.is_none());
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    #[must_use]
+    pub fn savepoint(&mut self) -> Savepoint {
+        Savepoint(self.undo_log.get_or_insert_with(Vec::new).len())
+    }
+
+    /// Discards all writes made after the given savepoint was created.
+    ///
+    /// The savepoint (and any savepoint created before it) stays valid,
+    /// so the transaction can be rolled back to it again later.
+    /// Savepoints created after the given savepoint become invalid.
+    ///
+    /// Writes that were rejected after the savepoint (e.g. because the key is too large)
+    /// are discarded as well, so they no longer fail the commit.
+    ///
+    /// Reads are not rolled back, so (with the `ssi_tx` feature) keys read after the savepoint
+    /// are still considered for conflict detection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the savepoint is invalid, because the transaction was already
+    /// rolled back to an earlier savepoint, or the savepoint was created by another transaction.
+    pub fn rollback_to(&mut self, savepoint: Savepoint) {
+        let undo_log = self.undo_log.get_or_insert_with(Vec::new);
+
+        assert!(savepoint.0 <= undo_log.len(), "savepoint is invalid");
+
+        for entry in undo_log.drain(savepoint.0..).rev() {
+            match entry {
+                UndoEntry::Write {
+                    partition,
+                    key,
+                    prev,
+                } => {
+                    let Some(memtable) = self.memtables.get(&partition) else {
+                        continue;
+                    };
+
+                    if let Some(prev) = prev {
+                        memtable.insert(prev);
+                    } else {
+                        // NOTE: Only the key & seqno are used for lookup, so the value type doesn't matter
+                        let lookup = InternalValue::new_tombstone(key, SeqNo::MAX);
+                        memtable.items.remove(&lookup.key);
+                    }
+                }
+                UndoEntry::RangeTombstone { partition } => {
+                    if let Some(tombstones) = self.range_tombstones.get_mut(&partition) {
+                        tombstones.pop();
+                    }
+                }
+                UndoEntry::WriteError => {
+                    self.write_error = None;
+                }
+            }
+        }
+    }
+
+    /// Returns `true` if the key was deleted by a range tombstone of this transaction.
+    fn is_range_deleted(&self, partition: &PartitionKey, key: &[u8]) -> bool {
+        self.range_tombstones
+            .get(partition)
+            .is_some_and(|tombstones| tombstones.iter().any(|x| x.contains_key(key)))
+    }
+
+    fn read_filter(&self, partition: &TxPartitionHandle) -> ReadFilter {
+        ReadFilter::new(
+            &partition.inner,
+            Some(self.nonce.instant),
+            self.memtables.get(&partition.inner.name).cloned(),
+        )
+        .with_pending(
+            self.range_tombstones
+                .get(&partition.inner.name)
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+        )
+    }
+
+    /// Remembers a rejected write, so the transaction fails on commit.
+    fn reject_write(&mut self, error: crate::Error) {
+        log::debug!("Rejecting write in transaction: {error:?}");
+
+        if self.write_error.is_some() {
+            return;
+        }
+
+        // NOTE: Remember when the write was rejected,
+        // so rolling back to an earlier savepoint discards the error
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.push(UndoEntry::WriteError);
+        }
+
+        self.write_error = Some(error);
+    }
+
+    fn write(&mut self, partition: &TxPartitionHandle, item: InternalValue) {
+        let memtable = self
+            .memtables
+            .entry(partition.inner.name.clone())
+            .or_default();
+
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.push(UndoEntry::Write {
+                partition: partition.inner.name.clone(),
+                prev: memtable.get(&item.key.user_key, None),
+                key: item.key.user_key.clone(),
+            });
+        }
+
+        memtable.insert(item);
+    }
+
     /// Removes an item and returns its value if it existed.
     ///
     /// The operation will run wrapped in a transaction.
@@ -282,15 +540,19 @@ impl<'a> WriteTransaction<'a> {
     ) -> crate::Result<Option<UserValue>> {
         if let Some(memtable) = self.memtables.get(&partition.inner.name) {
             if let Some(item) = memtable.get(&key, None) {
-                return Ok(ignore_tombstone_value(item).map(|x| x.value));
+                return ignore_tombstone_value(item)
+                    .map(|x| partition.inner.large_values.resolve(key.as_ref(), x.value))
+                    .transpose();
             }
         }
 
-        partition
-            .inner
-            .snapshot_at(self.nonce.instant)
-            .get(key)
-            .map_err(Into::into)
+        if self.is_range_deleted(&partition.inner.name, key.as_ref()) {
+            return Ok(None);
+        }
+
+        self.mark_read(&partition.inner.name, key.as_ref());
+
+        partition.inner.snapshot_at(self.nonce.instant).get(key)
     }
 
     /// Returns `true` if the transaction's state contains the specified key.
@@ -335,11 +597,16 @@ impl<'a> WriteTransaction<'a> {
             }
         }
 
+        if self.is_range_deleted(&partition.inner.name, key.as_ref()) {
+            return Ok(false);
+        }
+
+        self.mark_read(&partition.inner.name, key.as_ref());
+
         partition
             .inner
             .snapshot_at(self.nonce.instant)
             .contains_key(key)
-            .map_err(Into::into)
     }
 
     /// Returns the first key-value pair in the transaction's state.
@@ -480,14 +747,18 @@ impl<'a> WriteTransaction<'a> {
         &'b self,
         partition: &'b TxPartitionHandle,
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
-        partition
-            .inner
-            .tree
-            .iter_with_seqno(
-                self.nonce.instant,
-                self.memtables.get(&partition.inner.name).cloned(),
-            )
-            .map(|item| item.map_err(Into::into))
+        self.mark_full_scan(&partition.inner.name);
+
+        self.read_filter(partition).apply_kv(
+            partition
+                .inner
+                .tree
+                .iter_with_seqno(
+                    self.nonce.instant,
+                    self.memtables.get(&partition.inner.name).cloned(),
+                )
+                .map(|item| item.map_err(Into::into)),
+        )
     }
 
     /// Iterates over the transaction's state, returning keys only.
@@ -498,11 +769,7 @@ impl<'a> WriteTransaction<'a> {
         &'a self,
         partition: &'a TxPartitionHandle,
     ) -> impl DoubleEndedIterator<Item = crate::Result<UserKey>> + 'static {
-        partition
-            .inner
-            .tree
-            .keys_with_seqno(self.nonce.instant, None)
-            .map(|item| item.map_err(Into::into))
+        self.iter(partition).map(|item| item.map(|(key, _)| key))
     }
 
     /// Iterates over the transaction's state, returning values only.
@@ -513,11 +780,8 @@ impl<'a> WriteTransaction<'a> {
         &'a self,
         partition: &'a TxPartitionHandle,
     ) -> impl DoubleEndedIterator<Item = crate::Result<UserValue>> + 'static {
-        partition
-            .inner
-            .tree
-            .values_with_seqno(self.nonce.instant, None)
-            .map(|item| item.map_err(Into::into))
+        self.iter(partition)
+            .map(|item| item.map(|(_, value)| value))
     }
 
     /// Iterates over a range of the transaction's state.
@@ -549,15 +813,19 @@ impl<'a> WriteTransaction<'a> {
         partition: &'b TxPartitionHandle,
         range: R,
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
-        partition
-            .inner
-            .tree
-            .range_with_seqno(
-                range,
-                self.nonce.instant,
-                self.memtables.get(&partition.inner.name).cloned(),
-            )
-            .map(|item| item.map_err(Into::into))
+        self.mark_range(&partition.inner.name, &range);
+
+        self.read_filter(partition).apply_kv(
+            partition
+                .inner
+                .tree
+                .range_with_seqno(
+                    range,
+                    self.nonce.instant,
+                    self.memtables.get(&partition.inner.name).cloned(),
+                )
+                .map(|item| item.map_err(Into::into)),
+        )
     }
 
     /// Iterates over a range of the transaction's state.
@@ -589,21 +857,43 @@ impl<'a> WriteTransaction<'a> {
         partition: &'b TxPartitionHandle,
         prefix: K,
     ) -> impl DoubleEndedIterator<Item = crate::Result<KvPair>> + 'static {
-        partition
-            .inner
-            .tree
-            .prefix_with_seqno(
-                prefix,
-                self.nonce.instant,
-                self.memtables.get(&partition.inner.name).cloned(),
-            )
-            .map(|item| item.map_err(Into::into))
+        self.mark_range(
+            &partition.inner.name,
+            &lsm_tree::range::prefix_to_range(prefix.as_ref()),
+        );
+
+        self.read_filter(partition).apply_kv(
+            partition
+                .inner
+                .tree
+                .prefix_with_seqno(
+                    prefix,
+                    self.nonce.instant,
+                    self.memtables.get(&partition.inner.name).cloned(),
+                )
+                .map(|item| item.map_err(Into::into)),
+        )
+    }
+
+    /// Opens a seekable cursor over the transaction's state.
+    ///
+    /// The cursor sees the transaction's own writes, but it borrows the transaction,
+    /// so the transaction cannot be written to while the cursor is alive.
+    ///
+    /// See [`Cursor`] for more.
+    #[must_use]
+    pub fn cursor<'b>(&'b self, partition: &'b TxPartitionHandle) -> Cursor<'b> {
+        Cursor::new(move |bounds| self.range(partition, bounds))
     }
 
     /// Inserts a key-value pair into the partition.
     ///
-    /// Keys may be up to 65536 bytes long, values up to 2^32 bytes.
+    /// Keys may be up to 65535 bytes long.
     /// Shorter keys and values result in better performance.
+    /// If the key is too large, the write is discarded and committing fails.
+    ///
+    /// Values of 4 GiB or more are written to their own files right away,
+    /// which are deleted again if the transaction is not committed.
     ///
     /// If the key already exists, the item will be overwritten.
     ///
@@ -638,23 +928,47 @@ impl<'a> WriteTransaction<'a> {
         key: K,
         value: V,
     ) {
-        self.memtables
-            .entry(partition.inner.name.clone())
-            .or_default()
-            .insert(lsm_tree::InternalValue::from_components(
+        if let Err(e) = check_key(key.as_ref()) {
+            return self.reject_write(e);
+        }
+
+        let value = match partition
+            .inner
+            .write_large_value(key.as_ref(), value.as_ref())
+        {
+            Ok(Some(large_value)) => {
+                let reference = large_value.reference.clone();
+
+                self.large_values.push((
+                    partition.inner.name.clone(),
+                    large_value,
+                    value.as_ref().into(),
+                ));
+
+                reference
+            }
+            Ok(None) => value.as_ref().into(),
+            Err(e) => return self.reject_write(e),
+        };
+
+        self.write(
+            partition,
+            lsm_tree::InternalValue::from_components(
                 key.as_ref(),
-                value.as_ref(),
+                value,
                 // NOTE: Just take the max seqno, which should never be reached
                 // that way, the write is definitely always the newest
                 SeqNo::MAX,
                 lsm_tree::ValueType::Value,
-            ));
+            ),
+        );
     }
 
     /// Removes an item from the partition.
     ///
-    /// The key may be up to 65536 bytes long.
+    /// The key may be up to 65535 bytes long.
     /// Shorter keys result in better performance.
+    /// If the key is too large, the write is discarded and committing fails.
     ///
     /// # Examples
     ///
@@ -686,15 +1000,105 @@ impl<'a> WriteTransaction<'a> {
     ///
     /// Will return `Err` if an IO error occurs.
     pub fn remove<K: AsRef<[u8]>>(&mut self, partition: &TxPartitionHandle, key: K) {
-        self.memtables
-            .entry(partition.inner.name.clone())
-            .or_default()
-            .insert(lsm_tree::InternalValue::new_tombstone(
+        if let Err(e) = check_key(key.as_ref()) {
+            return self.reject_write(e);
+        }
+
+        self.write(
+            partition,
+            lsm_tree::InternalValue::new_tombstone(
                 key.as_ref(),
                 // NOTE: Just take the max seqno, which should never be reached
                 // that way, the write is definitely always the newest
                 SeqNo::MAX,
-            ));
+            ),
+        );
+    }
+
+    /// Removes all items in the given key range from the partition.
+    ///
+    /// The items are deleted using a single range tombstone when the transaction is committed.
+    /// Writes of this transaction inside the range that happened before are discarded,
+    /// writes that happen afterwards are kept.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use fjall::{Config, Keyspace, PartitionCreateOptions};
+    /// #
+    /// # let folder = tempfile::tempdir()?;
+    /// # let keyspace = Config::new(folder).open_transactional()?;
+    /// # let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    /// partition.insert("a", "abc")?;
+    /// partition.insert("b", "abc")?;
+    ///
+    /// let mut tx = keyspace.write_tx();
+    /// tx.remove_range(&partition, "a"..="b");
+    /// tx.insert(&partition, "b", "def");
+    ///
+    /// // Read-your-own-write
+    /// assert!(tx.get(&partition, "a")?.is_none());
+    /// assert_eq!(b"def", &*tx.get(&partition, "b")?.unwrap());
+    ///
+    /// tx.commit()?;
+    ///
+    /// assert!(partition.get("a")?.is_none());
+    /// assert_eq!(b"def", &*partition.get("b")?.unwrap());
+    /// #
+    /// # Ok::<(), fjall::Error>(())
+    /// ```
+    pub fn remove_range<K: AsRef<[u8]>, R: RangeBounds<K>>(
+        &mut self,
+        partition: &TxPartitionHandle,
+        range: R,
+    ) {
+        let Some((start, end)) = range_to_bounds(&range) else {
+            return;
+        };
+
+        if let Err(e) = check_key(&start).and_then(|()| end.as_deref().map_or(Ok(()), check_key)) {
+            return self.reject_write(e);
+        }
+
+        // NOTE: Just take the max seqno, so the tombstone covers everything
+        // the transaction can see - on commit, it gets the batch seqno
+        let tombstone = RangeTombstone {
+            start,
+            end,
+            seqno: SeqNo::MAX,
+        };
+
+        // IMPORTANT: Remove previous writes of this transaction inside the range,
+        // because the tombstone does not cover items of its own batch
+        if let Some(memtable) = self.memtables.get(&partition.inner.name) {
+            let covered = memtable
+                .iter()
+                .filter(|item| tombstone.contains_key(&item.key.user_key))
+                .collect::<Vec<_>>();
+
+            for item in covered {
+                memtable.items.remove(&item.key);
+
+                if let Some(undo_log) = &mut self.undo_log {
+                    undo_log.push(UndoEntry::Write {
+                        partition: partition.inner.name.clone(),
+                        key: item.key.user_key.clone(),
+                        prev: Some(item),
+                    });
+                }
+            }
+        }
+
+        if let Some(undo_log) = &mut self.undo_log {
+            undo_log.push(UndoEntry::RangeTombstone {
+                partition: partition.inner.name.clone(),
+            });
+        }
+
+        self.range_tombstones
+            .entry(partition.inner.name.clone())
+            .or_default()
+            .push(tombstone);
     }
 
     /// Commits the transaction.
@@ -702,7 +1106,52 @@ impl<'a> WriteTransaction<'a> {
     /// # Errors
     ///
     /// Will return `Err` if an IO error occurs.
-    pub fn commit(self) -> crate::Result<()> {
+    ///
+    /// With the `ssi_tx` feature, will return [`crate::Error::Conflict`] if another
+    /// transaction has committed a write to a key (or range) that was read by
+    /// this transaction after it was started. In that case, nothing is written,
+    /// and the transaction should be retried.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a lock is poisoned.
+    #[allow(clippy::expect_used)]
+    pub fn commit(mut self) -> crate::Result<()> {
+        if let Some(e) = self.write_error.take() {
+            return Err(e);
+        }
+
+        #[cfg(feature = "ssi_tx")]
+        if self.memtables.values().all(|memtable| memtable.is_empty())
+            && self.range_tombstones.values().all(Vec::is_empty)
+        {
+            // NOTE: Read-only transactions are always serializable
+            return Ok(());
+        }
+
+        #[cfg(feature = "ssi_tx")]
+        let writes = {
+            let mut writes = super::oracle::WriteSet::default();
+
+            for (partition_key, memtable) in &self.memtables {
+                writes
+                    .entry(partition_key.clone())
+                    .or_default()
+                    .keys
+                    .extend(memtable.iter().map(|item| item.key.user_key));
+            }
+
+            for (partition_key, tombstones) in &self.range_tombstones {
+                writes
+                    .entry(partition_key.clone())
+                    .or_default()
+                    .ranges
+                    .extend(tombstones.iter().map(|x| (x.start.clone(), x.end.clone())));
+            }
+
+            writes
+        };
+
         let mut batch = Batch::new(self.keyspace).durability(self.durability);
 
         /*
@@ -731,10 +1180,43 @@ impl<'a> WriteTransaction<'a> {
             }
         }
 
+        // NOTE: Large values that have been overwritten within the transaction are dropped,
+        // which deletes their files
+        batch.large_values = std::mem::take(&mut self.large_values)
+            .into_iter()
+            .filter(|(partition_key, large_value, _)| {
+                batch.data.iter().any(|item| {
+                    item.partition == *partition_key && item.value == large_value.reference
+                })
+            })
+            .collect();
+
+        for (partition_key, tombstones) in self.range_tombstones {
+            for tombstone in tombstones {
+                batch.range_tombstones.push(RangeTombstoneItem {
+                    partition: partition_key.clone(),
+                    start: tombstone.start,
+                    end: tombstone.end,
+                });
+            }
+        }
+
         // TODO: instead of using batch, write batch::commit as a generic function that takes
         // a impl Iterator<BatchItem>
         // that way, we don't have to move the memtable(s) into the batch first to commit
-        batch.commit()
+        #[cfg(feature = "single_writer_tx")]
+        return batch.commit();
+
+        #[cfg(feature = "ssi_tx")]
+        {
+            let conflict_manager = self
+                .conflict_manager
+                .into_inner()
+                .expect("lock is poisoned");
+
+            self.tx_guard
+                .commit(&conflict_manager, writes, || batch.commit_with_seqno())
+        }
     }
 
     /// More explicit alternative to dropping the transaction
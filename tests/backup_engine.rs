@@ -0,0 +1,115 @@
+use fjall::{BackupEngine, Config, PartitionCreateOptions};
+use test_log::test;
+
+#[test]
+fn backup_engine() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+    let restore_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let mut backups = BackupEngine::open(&backup_folder)?;
+    assert!(backups.list_backups()?.is_empty());
+
+    for x in 0..100u32 {
+        partition.insert(x.to_be_bytes(), "old")?;
+    }
+    partition.rotate_memtable_and_wait()?;
+    partition.insert("a", "1")?;
+
+    let first = backups.create_backup(&keyspace)?;
+
+    partition.insert("a", "2")?;
+    partition.rotate_memtable_and_wait()?;
+
+    let second = backups.create_backup(&keyspace)?;
+    assert_eq!(vec![1, 2], [first, second]);
+
+    let infos = backups.list_backups()?;
+    assert_eq!(2, infos.len());
+    assert!(infos.iter().all(|x| x.size > 0 && x.file_count > 0));
+
+    // NOTE: The first segment is shared between both backups
+    let shared_file_count = std::fs::read_dir(backup_folder.path().join("shared"))?.count();
+    assert!(shared_file_count < infos.iter().map(|x| x.file_count).sum());
+
+    for (id, expected) in [(first, b"1"), (second, b"2")] {
+        backups.verify(id)?;
+
+        let path = restore_folder.path().join(id.to_string());
+        backups.restore(id, &path)?;
+
+        let keyspace = Config::new(&path).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        assert_eq!(expected, &*partition.get("a")?.unwrap());
+        assert_eq!(101, partition.len()?);
+    }
+
+    backups.purge_old_backups(1)?;
+    assert_eq!(
+        vec![second],
+        backups
+            .list_backups()?
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<Vec<_>>()
+    );
+    assert!(matches!(
+        backups.restore(first, restore_folder.path().join("purged")),
+        Err(fjall::Error::BackupNotFound(1))
+    ));
+
+    let path = restore_folder.path().join("after_purge");
+    backups.restore(second, &path)?;
+    let keyspace = Config::new(&path).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(101, partition.len()?);
+
+    // NOTE: Backup IDs keep increasing
+    let mut backups = BackupEngine::open(&backup_folder)?;
+    assert_eq!(3, backups.create_backup(&keyspace)?);
+    backups.delete_backup(3)?;
+    backups.delete_backup(second)?;
+    assert!(backups.list_backups()?.is_empty());
+    assert_eq!(
+        0,
+        std::fs::read_dir(backup_folder.path().join("shared"))?.count()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn backup_engine_corrupted() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+    let restore_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    partition.insert("a", "abc")?;
+    partition.rotate_memtable_and_wait()?;
+
+    let mut backups = BackupEngine::open(&backup_folder)?;
+    let id = backups.create_backup(&keyspace)?;
+
+    for dirent in std::fs::read_dir(backup_folder.path().join("shared"))? {
+        let path = dirent?.path();
+        let mut bytes = std::fs::read(&path)?;
+        bytes[0] = !bytes[0];
+        std::fs::write(&path, bytes)?;
+    }
+
+    assert!(matches!(
+        backups.verify(id),
+        Err(fjall::Error::BackupCorrupted(_))
+    ));
+    assert!(matches!(
+        backups.restore(id, restore_folder.path().join("restored")),
+        Err(fjall::Error::BackupCorrupted(_))
+    ));
+
+    Ok(())
+}
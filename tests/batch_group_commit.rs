@@ -0,0 +1,55 @@
+use fjall::{Config, PartitionCreateOptions, PersistMode};
+use test_log::test;
+
+const THREADS: usize = 8;
+const BATCHES: usize = 25;
+
+#[test]
+fn batch_group_commit() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        std::thread::scope(|scope| {
+            let threads = (0..THREADS)
+                .map(|thread| {
+                    let keyspace = &keyspace;
+                    let partition = &partition;
+
+                    scope.spawn(move || -> fjall::Result<()> {
+                        for i in 0..BATCHES {
+                            let mode = if i % 2 == 0 {
+                                PersistMode::SyncData
+                            } else {
+                                PersistMode::SyncAll
+                            };
+
+                            let mut batch = keyspace.batch().durability(Some(mode));
+                            batch.insert(partition, format!("{thread}:{i}:a"), "abc");
+                            batch.insert(partition, format!("{thread}:{i}:b"), "def");
+                            batch.commit()?;
+                        }
+
+                        Ok(())
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            threads
+                .into_iter()
+                .try_for_each(|thread| thread.join().expect("thread should not panic"))
+        })?;
+
+        assert_eq!(THREADS * BATCHES * 2, partition.len()?);
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        assert_eq!(THREADS * BATCHES * 2, partition.len()?);
+    }
+
+    Ok(())
+}
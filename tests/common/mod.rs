@@ -0,0 +1,48 @@
+// NOTE: Not every test uses every helper
+#![allow(dead_code)]
+
+/// Returns the position of the first occurrence of `needle` in `bytes`.
+pub fn find(bytes: &[u8], needle: &[u8]) -> usize {
+    bytes
+        .windows(needle.len())
+        .position(|window| window == needle)
+        .expect("should find value")
+}
+
+/// Merge operator that appends the operands to the existing value
+pub struct Append;
+
+impl fjall::MergeOperator for Append {
+    fn merge(&self, _: &[u8], existing: Option<&[u8]>, operands: &[&[u8]]) -> Vec<u8> {
+        let mut value = existing.unwrap_or_default().to_vec();
+
+        for operand in operands {
+            value.extend_from_slice(operand);
+        }
+
+        value
+    }
+}
+
+/// Copies a fixture folder, so opening it does not write to the repository
+pub fn copy_fixture(src: &std::path::Path, dest: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dest)?;
+
+    for dirent in std::fs::read_dir(src)? {
+        let dirent = dirent?;
+        let dest = dest.join(dirent.file_name());
+
+        // NOTE: Tests that open the fixtures in place leave their lock file behind
+        if dirent.file_name() == "LOCK" {
+            continue;
+        }
+
+        if dirent.file_type()?.is_dir() {
+            copy_fixture(&dirent.path(), &dest)?;
+        } else {
+            std::fs::copy(dirent.path(), dest)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,160 @@
+use fjall::{
+    journal::{restore_to, JournalArchive},
+    Config, PartitionCreateOptions,
+};
+use test_log::test;
+
+fn wait_for_eviction(keyspace: &fjall::Keyspace) {
+    while keyspace.journal_count() > 1 {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+}
+
+fn archived_journals(path: &std::path::Path) -> fjall::Result<Vec<String>> {
+    let mut names = std::fs::read_dir(path)?
+        .map(|dirent| Ok(dirent?.file_name().to_string_lossy().to_string()))
+        .filter(|name| name.as_ref().map_or(true, |name| name.ends_with(".sealed")))
+        .collect::<fjall::Result<Vec<_>>>()?;
+    names.sort();
+    Ok(names)
+}
+
+#[test]
+fn journal_archive_restore() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let archive_folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+    let checkpoint_path = backup_folder.path().join("checkpoint");
+
+    let keyspace = Config::new(&folder)
+        .journal_archive(JournalArchive::new(&archive_folder))
+        .open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "1")?;
+    keyspace.checkpoint(&checkpoint_path)?;
+
+    partition.insert("b", "2")?;
+    partition.rotate_memtable_and_wait()?;
+    wait_for_eviction(&keyspace);
+
+    partition.insert("c", "3")?;
+    let target_seqno = keyspace.instant() - 1;
+
+    partition.insert("d", "4")?;
+    partition.remove("a")?;
+    partition.rotate_memtable_and_wait()?;
+    wait_for_eviction(&keyspace);
+
+    assert_eq!(
+        vec!["0.sealed", "1.sealed"],
+        archived_journals(archive_folder.path())?,
+    );
+
+    restore_to(&checkpoint_path, &archive_folder, target_seqno)?;
+
+    let restored = Config::new(&checkpoint_path).open()?;
+    let partition = restored.open_partition("default", PartitionCreateOptions::default())?;
+
+    assert_eq!(3, partition.len()?);
+    assert!(partition.contains_key("a")?);
+    assert!(partition.contains_key("b")?);
+    assert!(partition.contains_key("c")?);
+    assert!(!partition.contains_key("d")?);
+
+    Ok(())
+}
+
+#[test]
+fn journal_archive_restore_missing_journal() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let archive_folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+    let checkpoint_path = backup_folder.path().join("checkpoint");
+
+    let keyspace = Config::new(&folder)
+        .journal_archive(JournalArchive::new(&archive_folder))
+        .open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    keyspace.checkpoint(&checkpoint_path)?;
+
+    for key in ["a", "b"] {
+        partition.insert(key, "abc")?;
+        partition.rotate_memtable_and_wait()?;
+        wait_for_eviction(&keyspace);
+    }
+
+    std::fs::remove_file(archive_folder.path().join("0.sealed"))?;
+
+    assert!(matches!(
+        restore_to(&checkpoint_path, &archive_folder, keyspace.instant()),
+        Err(fjall::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound,
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn journal_archive_max_size() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let archive_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder)
+        .journal_archive(JournalArchive::new(&archive_folder).max_size(1))
+        .open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for key in ["a", "b", "c"] {
+        partition.insert(key, "abc")?;
+        partition.rotate_memtable_and_wait()?;
+        wait_for_eviction(&keyspace);
+    }
+
+    // NOTE: The newest journal is always kept
+    assert_eq!(vec!["2.sealed"], archived_journals(archive_folder.path())?);
+
+    // NOTE: Archived journals are not preallocated
+    assert!(std::fs::metadata(archive_folder.path().join("2.sealed"))?.len() < 1_024);
+
+    Ok(())
+}
+
+#[test]
+fn journal_archive_max_size_keeps_checkpoint_journals() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let archive_folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+    let checkpoint_path = backup_folder.path().join("checkpoint");
+
+    let keyspace = Config::new(&folder)
+        .journal_archive(JournalArchive::new(&archive_folder).max_size(1))
+        .open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "abc")?;
+    partition.rotate_memtable_and_wait()?;
+    wait_for_eviction(&keyspace);
+
+    keyspace.checkpoint(&checkpoint_path)?;
+
+    for key in ["b", "c"] {
+        partition.insert(key, "abc")?;
+        partition.rotate_memtable_and_wait()?;
+        wait_for_eviction(&keyspace);
+    }
+
+    // NOTE: Journals from the one that was active during the checkpoint on are kept
+    assert_eq!(
+        vec!["1.sealed", "2.sealed"],
+        archived_journals(archive_folder.path())?,
+    );
+
+    restore_to(&checkpoint_path, &archive_folder, keyspace.instant())?;
+
+    let restored = Config::new(&checkpoint_path).open()?;
+    let partition = restored.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(3, partition.len()?);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use fjall::{CompressionType, Config, PartitionCreateOptions};
+use test_log::test;
+
+fn recover_with(compression: CompressionType) -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let document = r#"{"name":"fjall","tags":["lsm","kv","rust"]}"#.repeat(1_000);
+
+    {
+        let keyspace = Config::new(&folder)
+            .journal_compression(compression)
+            .journal_compression_threshold(1_024)
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        for key in 0..10_u32 {
+            partition.insert(key.to_be_bytes(), &document)?;
+        }
+        partition.insert("small", "value")?;
+
+        let mut batch = keyspace.batch();
+        batch.insert(&partition, "batch-a", &document);
+        batch.insert(&partition, "batch-b", &document);
+        batch.remove(&partition, 0_u32.to_be_bytes());
+        batch.commit()?;
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert!(partition.get(0_u32.to_be_bytes())?.is_none());
+
+        for key in 1..10_u32 {
+            assert_eq!(
+                document.as_bytes(),
+                &*partition.get(key.to_be_bytes())?.unwrap()
+            );
+        }
+
+        assert_eq!(b"value", &*partition.get("small")?.unwrap());
+        assert_eq!(document.as_bytes(), &*partition.get("batch-a")?.unwrap());
+        assert_eq!(document.as_bytes(), &*partition.get("batch-b")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn journal_compression_none() -> fjall::Result<()> {
+    recover_with(CompressionType::None)
+}
+
+#[test]
+#[cfg(feature = "lz4")]
+fn journal_compression_lz4() -> fjall::Result<()> {
+    recover_with(CompressionType::Lz4)
+}
+
+#[test]
+#[cfg(feature = "miniz")]
+fn journal_compression_miniz() -> fjall::Result<()> {
+    recover_with(CompressionType::Miniz(6))
+}
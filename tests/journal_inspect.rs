@@ -0,0 +1,102 @@
+mod common;
+
+use common::find;
+use fjall::{
+    journal::{inspect, Entry},
+    Config, PartitionCreateOptions, PersistMode, RecoveryError, ValueType,
+};
+use test_log::test;
+
+#[test]
+fn journal_inspect() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let path = folder.path().join("journals").join("0");
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "value-a")?;
+
+        let mut batch = keyspace.batch();
+        batch.insert(&partition, "b", "value-b");
+        batch.remove(&partition, "c");
+        batch.commit()?;
+
+        partition.remove_range("x".."y")?;
+
+        keyspace.persist(PersistMode::SyncAll)?;
+    }
+
+    let journal_len = std::fs::metadata(&path)?.len();
+
+    let batches = inspect(&path)?.collect::<fjall::Result<Vec<_>>>()?;
+    assert_eq!(3, batches.len());
+    assert!(batches.iter().all(|batch| batch.is_valid()));
+    assert_eq!(
+        vec![0, 1, 2],
+        batches.iter().map(|b| b.seqno).collect::<Vec<_>>()
+    );
+
+    let first = batches.first().unwrap();
+    assert_eq!(0, first.offset);
+    assert_eq!(first.expected_checksum, Some(first.checksum));
+    assert_eq!(
+        vec![Entry::Item {
+            partition: "default".into(),
+            key: "a".into(),
+            value: "value-a".into(),
+            value_type: ValueType::Value,
+        }],
+        first.entries,
+    );
+
+    let second = batches.get(1).unwrap();
+    assert_eq!(first.end_offset, second.offset);
+    assert_eq!(2, second.item_count);
+    assert_eq!(2, second.entries.len());
+
+    assert!(matches!(
+        batches.get(2).unwrap().entries.first(),
+        Some(Entry::RangeTombstone { .. })
+    ));
+
+    // NOTE: Inspecting does not modify the journal
+    assert_eq!(journal_len, std::fs::metadata(&path)?.len());
+
+    Ok(())
+}
+
+#[test]
+fn journal_inspect_invalid_batch() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let path = folder.path().join("journals").join("0");
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "value-a")?;
+        partition.insert("b", "value-b")?;
+        partition.insert("c", "value-c")?;
+
+        keyspace.persist(PersistMode::SyncAll)?;
+    }
+
+    let mut bytes = std::fs::read(&path)?;
+    let pos = find(&bytes, b"value-b");
+    bytes[pos] ^= 0xFF;
+    std::fs::write(&path, &bytes)?;
+
+    let batches = inspect(&path)?.collect::<fjall::Result<Vec<_>>>()?;
+    assert_eq!(
+        vec![None, Some(RecoveryError::ChecksumMismatch), None],
+        batches.iter().map(|batch| batch.error).collect::<Vec<_>>(),
+    );
+
+    let invalid = batches.get(1).unwrap();
+    assert_ne!(invalid.expected_checksum, Some(invalid.checksum));
+    assert!(invalid.offset <= pos as u64 && (pos as u64) < invalid.end_offset);
+
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use fjall::{fs::MemoryFileSystem, Config, KvSeparationOptions, PartitionCreateOptions};
+use std::sync::Arc;
+use test_log::test;
+
+#[test]
+fn keyspace_checkpoint() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+    let checkpoint_path = backup_folder.path().join("checkpoint");
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    let blobs = keyspace.open_partition(
+        "blobs",
+        PartitionCreateOptions::default()
+            .with_kv_separation(KvSeparationOptions::default().separation_threshold(1)),
+    )?;
+
+    for x in 0..100u32 {
+        partition.insert(x.to_be_bytes(), "old")?;
+        blobs.insert(x.to_be_bytes(), "old")?;
+    }
+    partition.rotate_memtable_and_wait()?;
+    blobs.rotate_memtable_and_wait()?;
+
+    partition.insert("a", "abc")?;
+    partition.remove_range(0u32.to_be_bytes()..10u32.to_be_bytes())?;
+    blobs.insert("b", "def")?;
+
+    keyspace.checkpoint(&checkpoint_path)?;
+
+    // NOTE: Changes after the checkpoint are not part of it
+    partition.insert("a", "new")?;
+    blobs.remove("b")?;
+    partition.rotate_memtable_and_wait()?;
+    blobs.rotate_memtable_and_wait()?;
+    keyspace.delete_partition(partition)?;
+
+    {
+        let checkpoint = Config::new(&checkpoint_path).open()?;
+        assert_eq!(2, checkpoint.partition_count());
+
+        let partition = checkpoint.open_partition("default", PartitionCreateOptions::default())?;
+        let blobs = checkpoint.open_partition("blobs", PartitionCreateOptions::default())?;
+
+        assert_eq!(b"abc", &*partition.get("a")?.unwrap());
+        assert_eq!(91, partition.len()?);
+        assert!(partition.get(5u32.to_be_bytes())?.is_none());
+        assert_eq!(b"def", &*blobs.get("b")?.unwrap());
+        assert_eq!(101, blobs.len()?);
+
+        // NOTE: The checkpoint is a regular keyspace
+        partition.insert("c", "c")?;
+        partition.rotate_memtable_and_wait()?;
+        assert_eq!(92, partition.len()?);
+    }
+
+    assert!(blobs.get("b")?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_checkpoint_exists() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+
+    assert!(matches!(
+        keyspace.checkpoint(backup_folder.path()),
+        Err(fjall::Error::Io(_))
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_checkpoint_file_system() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let backup_folder = tempfile::tempdir()?;
+    let checkpoint_path = backup_folder.path().join("checkpoint");
+
+    let fs = Arc::new(MemoryFileSystem::new());
+
+    let keyspace = Config::new(&folder).file_system(fs.clone()).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "abc")?;
+    partition.rotate_memtable_and_wait()?;
+    partition.insert("b", "def")?;
+    partition.remove_range("a".."b")?;
+
+    keyspace.checkpoint(&checkpoint_path)?;
+
+    partition.insert("c", "ghi")?;
+
+    let checkpoint = Config::new(&checkpoint_path).file_system(fs).open()?;
+    let partition = checkpoint.open_partition("default", PartitionCreateOptions::default())?;
+
+    assert!(partition.get("a")?.is_none());
+    assert_eq!(b"def", &*partition.get("b")?.unwrap());
+    assert_eq!(1, partition.len()?);
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+#![cfg(feature = "journal_encryption")]
+
+use fjall::{Aes256GcmProvider, Config, EncryptionKey, PartitionCreateOptions};
+use std::sync::Arc;
+use test_log::test;
+
+fn keys(key_id: u32) -> Option<EncryptionKey> {
+    match key_id {
+        1 => Some([1; 32]),
+        2 => Some([2; 32]),
+        _ => None,
+    }
+}
+
+fn contains(bytes: &[u8], needle: &[u8]) -> bool {
+    bytes.windows(needle.len()).any(|window| window == needle)
+}
+
+#[test]
+fn keyspace_encryption() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder)
+            .journal_encryption(Arc::new(Aes256GcmProvider::new(1, keys)))
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "secret-value")?;
+        partition.insert("b", "x".repeat(10_000))?;
+    }
+
+    let journal = std::fs::read(folder.path().join("journals").join("0"))?;
+    assert!(!contains(&journal, b"secret-value"));
+
+    let config = std::fs::read(
+        folder
+            .path()
+            .join("partitions")
+            .join("default")
+            .join("config"),
+    )?;
+    assert!(config.starts_with(b"FJE"));
+
+    // NOTE: Encrypted data cannot be read without the key
+    assert!(matches!(
+        Config::new(&folder).open(),
+        Err(fjall::Error::Encryption)
+    ));
+    assert!(matches!(
+        Config::new(&folder)
+            .journal_encryption(Arc::new(Aes256GcmProvider::new(3, |_| Some([3; 32]))))
+            .open(),
+        Err(fjall::Error::Encryption)
+    ));
+
+    // Rotate key
+    {
+        let keyspace = Config::new(&folder)
+            .journal_encryption(Arc::new(Aes256GcmProvider::new(2, keys)))
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(b"secret-value", &*partition.get("a")?.unwrap());
+        assert_eq!(10_000, partition.get("b")?.unwrap().len());
+
+        partition.insert("c", "another-secret")?;
+    }
+
+    {
+        let keyspace = Config::new(&folder)
+            .journal_encryption(Arc::new(Aes256GcmProvider::new(2, keys)))
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(3, partition.len()?);
+        assert_eq!(b"another-secret", &*partition.get("c")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_encryption_sealed_journal() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    // NOTE: Without flush workers, sealed journals are kept
+    let config = || {
+        Config::new(&folder)
+            .flush_workers(0)
+            .journal_encryption(Arc::new(Aes256GcmProvider::new(1, keys)))
+    };
+
+    {
+        let keyspace = config().open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "secret-value")?;
+        partition.rotate_memtable()?;
+        partition.insert("b", "secret-value")?;
+        assert_eq!(2, keyspace.journal_count());
+        assert_eq!(2, keyspace.journal_count());
+    }
+
+    {
+        let keyspace = config().open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(b"secret-value", &*partition.get("a")?.unwrap());
+        assert_eq!(b"secret-value", &*partition.get("b")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_encryption_plaintext() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        partition.insert("a", "not-so-secret-value")?;
+    }
+
+    // NOTE: Unencrypted data is not silently accepted
+    assert!(matches!(
+        Config::new(&folder)
+            .journal_encryption(Arc::new(Aes256GcmProvider::new(1, keys)))
+            .open(),
+        Err(fjall::Error::Encryption)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,175 @@
+use fjall::{
+    fs::{FaultInjectingFileSystem, FileSystem, MemoryFileSystem},
+    journal::JournalArchive,
+    Config, PartitionCreateOptions, PersistMode,
+};
+use std::sync::Arc;
+use test_log::test;
+
+#[test]
+fn keyspace_memory_file_system() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let fs = Arc::new(MemoryFileSystem::new());
+
+    {
+        let keyspace = Config::new(&folder).file_system(fs.clone()).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "1")?;
+        partition.rotate_memtable_and_wait()?;
+        partition.insert("b", "2")?;
+        partition.insert("c", "3")?;
+        partition.remove_range("c"..)?;
+    }
+
+    // NOTE: Only the partitions' LSM-trees are stored in the OS file system
+    assert!(!folder.path().join("journals").try_exists()?);
+    assert!(!folder.path().join("LOCK").try_exists()?);
+    assert!(!folder
+        .path()
+        .join("partitions")
+        .join("default")
+        .join("config")
+        .try_exists()?);
+    assert!(!folder
+        .path()
+        .join("partitions")
+        .join("default")
+        .join("range_tombstones")
+        .try_exists()?);
+    assert!(fs.exists(
+        &folder
+            .path()
+            .join("partitions")
+            .join("default")
+            .join("range_tombstones")
+    )?);
+    assert!(folder
+        .path()
+        .join("partitions")
+        .join("default")
+        .try_exists()?);
+
+    let keyspace = Config::new(&folder).file_system(fs).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(2, partition.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_file_system_crash() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let fs = Arc::new(FaultInjectingFileSystem::new(Arc::new(
+        MemoryFileSystem::new(),
+    )));
+
+    {
+        let keyspace = Config::new(&folder).file_system(fs.clone()).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "1")?;
+        keyspace.persist(PersistMode::SyncAll)?;
+
+        partition.insert("b", "2")?;
+        keyspace.persist(PersistMode::Buffer)?;
+
+        fs.crash();
+    }
+
+    {
+        let keyspace = Config::new(&folder).file_system(fs.clone()).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert!(partition.contains_key("a")?);
+        assert!(!partition.contains_key("b")?);
+
+        // NOTE: The journal can still be written to after the crash
+        partition.insert("c", "3")?;
+        keyspace.persist(PersistMode::SyncAll)?;
+
+        fs.crash();
+    }
+
+    let keyspace = Config::new(&folder).file_system(fs).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert!(partition.contains_key("a")?);
+    assert!(!partition.contains_key("b")?);
+    assert!(partition.contains_key("c")?);
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_file_system_sync_failure() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let fs = Arc::new(FaultInjectingFileSystem::new(Arc::new(
+        MemoryFileSystem::new(),
+    )));
+
+    let keyspace = Config::new(&folder).file_system(fs.clone()).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "1")?;
+
+    fs.fail_syncs(true);
+    assert!(matches!(
+        keyspace.persist(PersistMode::SyncAll),
+        Err(fjall::Error::Poisoned)
+    ));
+
+    fs.fail_syncs(false);
+    assert!(matches!(
+        keyspace.persist(PersistMode::SyncAll),
+        Err(fjall::Error::Poisoned)
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_memory_file_system_lock_and_delete() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let archive_folder = tempfile::tempdir()?;
+    let fs = Arc::new(MemoryFileSystem::new());
+
+    let config = Config::new(&folder)
+        .file_system(fs.clone())
+        .journal_archive(JournalArchive::new(archive_folder.path().join("archive")));
+
+    {
+        let keyspace = config.clone().open()?;
+
+        assert!(matches!(
+            Config::new(&folder).file_system(fs.clone()).open(),
+            Err(fjall::Error::Locked)
+        ));
+
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        partition.insert("a", "1")?;
+        partition.rotate_memtable_and_wait()?;
+
+        let deleted = keyspace.open_partition("deleted", PartitionCreateOptions::default())?;
+        deleted.insert("a", "1")?;
+        keyspace.delete_partition(deleted)?;
+
+        while keyspace.journal_count() > 1 {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    // NOTE: The archive is stored in the keyspace's file system
+    assert!(!archive_folder.path().join("archive").try_exists()?);
+    assert!(fs.exists(&archive_folder.path().join("archive").join("0.sealed"))?);
+
+    let keyspace = config.open()?;
+    assert_eq!(1, keyspace.partition_count());
+    assert!(!fs.exists(&folder.path().join("partitions").join("deleted"))?);
+    assert!(!folder
+        .path()
+        .join("partitions")
+        .join("deleted")
+        .try_exists()?);
+
+    Ok(())
+}
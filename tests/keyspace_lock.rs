@@ -0,0 +1,72 @@
+use fjall::{Config, PartitionCreateOptions};
+use test_log::test;
+
+#[test]
+fn keyspace_lock() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    partition.insert("a", "abc")?;
+
+    assert!(matches!(
+        Config::new(&folder).open(),
+        Err(fjall::Error::Locked)
+    ));
+
+    drop(partition);
+    drop(keyspace);
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(1, partition.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_lock_opt_out() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let _keyspace = Config::new(&folder).open()?;
+
+    // NOTE: Keyspaces that can be written to cannot opt out of the lock
+    assert!(matches!(
+        Config::new(&folder).lock_file(false).open(),
+        Err(fjall::Error::Locked)
+    ));
+
+    let unlocked = Config::new(&folder)
+        .lock_file(false)
+        .read_only(true)
+        .open()?;
+    assert_eq!(0, unlocked.partition_count());
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_lock_read_only() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+
+    assert!(matches!(
+        Config::new(&folder).read_only(true).open(),
+        Err(fjall::Error::Locked)
+    ));
+
+    drop(keyspace);
+
+    let reader = Config::new(&folder).read_only(true).open()?;
+    let _other_reader = Config::new(&folder).read_only(true).open()?;
+
+    assert!(matches!(
+        Config::new(&folder).open(),
+        Err(fjall::Error::Locked)
+    ));
+
+    drop(reader);
+
+    Ok(())
+}
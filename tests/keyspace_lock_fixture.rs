@@ -0,0 +1,36 @@
+mod common;
+
+use common::copy_fixture;
+use fjall::{Config, PartitionCreateOptions};
+use std::path::Path;
+use test_log::test;
+
+#[test]
+fn keyspace_lock_v2_fixture() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    copy_fixture(Path::new("test_fixture/v2_keyspace"), folder.path())?;
+
+    // NOTE: Keyspaces written before the lock file existed do not have one
+    assert!(!folder.path().join("LOCK").try_exists()?);
+
+    // NOTE: Read-only opens do not create the lock file
+    {
+        let keyspace = Config::new(&folder).read_only(true).open()?;
+        let partition = keyspace.open_partition("default1", PartitionCreateOptions::default())?;
+        assert_eq!(6, partition.len()?);
+    }
+    assert!(!folder.path().join("LOCK").try_exists()?);
+
+    let keyspace = Config::new(&folder).open()?;
+    assert!(folder.path().join("LOCK").try_exists()?);
+
+    assert!(matches!(
+        Config::new(&folder).open(),
+        Err(fjall::Error::Locked)
+    ));
+
+    let partition = keyspace.open_partition("default1", PartitionCreateOptions::default())?;
+    assert_eq!(6, partition.len()?);
+
+    Ok(())
+}
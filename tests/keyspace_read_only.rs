@@ -0,0 +1,128 @@
+use fjall::{Config, PartitionCreateOptions};
+use test_log::test;
+
+#[test]
+fn keyspace_read_only() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "1")?;
+        partition.rotate_memtable_and_wait()?;
+        partition.insert("b", "2")?;
+        partition.insert("c", "3")?;
+        partition.remove_range("c"..)?;
+
+        let deleted = keyspace.open_partition("deleted", PartitionCreateOptions::default())?;
+        keyspace.delete_partition(deleted)?;
+    }
+
+    let partitions_folder = folder.path().join("partitions");
+    std::fs::create_dir(partitions_folder.join("uninitialized"))?;
+    std::fs::create_dir(partitions_folder.join("deleted"))?;
+    std::fs::write(partitions_folder.join("deleted").join(".deleted"), "")?;
+
+    let journals = std::fs::read_dir(folder.path().join("journals"))?.count();
+
+    {
+        let keyspace = Config::new(&folder).read_only(true).open()?;
+        assert_eq!(1, keyspace.partition_count());
+
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        assert_eq!(2, partition.len()?);
+        assert!(!partition.contains_key("c")?);
+
+        assert!(matches!(
+            partition.insert("d", "4"),
+            Err(fjall::Error::ReadOnly)
+        ));
+        assert!(matches!(
+            keyspace.open_partition("other", PartitionCreateOptions::default()),
+            Err(fjall::Error::ReadOnly)
+        ));
+    }
+
+    assert!(partitions_folder.join("uninitialized").try_exists()?);
+    assert!(partitions_folder.join("deleted").try_exists()?);
+    assert_eq!(
+        journals,
+        std::fs::read_dir(folder.path().join("journals"))?.count()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_read_only_missing() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let path = folder.path().join("keyspace");
+
+    assert!(Config::new(&path).read_only(true).open().is_err());
+    assert!(!path.try_exists()?);
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_read_only(path: &std::path::Path, read_only: bool) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let is_dir = std::fs::metadata(path)?.is_dir();
+
+    // NOTE: Directories need to be writable (again) to change their contents' permissions
+    if is_dir && !read_only {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    if is_dir {
+        for dirent in std::fs::read_dir(path)? {
+            set_read_only(&dirent?.path(), read_only)?;
+        }
+    }
+
+    let mode = match (is_dir, read_only) {
+        (true, true) => 0o555,
+        (true, false) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    };
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[test]
+#[cfg(unix)]
+fn keyspace_read_only_directory() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "1")?;
+        partition.rotate_memtable_and_wait()?;
+        partition.insert("b", "2")?;
+    }
+
+    set_read_only(folder.path(), true)?;
+
+    let result = (|| {
+        let keyspace = Config::new(&folder).read_only(true).open()?;
+
+        // NOTE: Read-only keyspaces share the lock
+        let other = Config::new(&folder).read_only(true).open()?;
+
+        for keyspace in [keyspace, other] {
+            let partition =
+                keyspace.open_partition("default", PartitionCreateOptions::default())?;
+            assert_eq!(2, partition.len()?);
+        }
+
+        Ok::<_, fjall::Error>(())
+    })();
+
+    set_read_only(folder.path(), false)?;
+
+    result
+}
@@ -0,0 +1,201 @@
+mod common;
+
+use common::find;
+use fjall::{Config, PartitionCreateOptions, PersistMode, RecoveryError, RecoveryMode};
+use test_log::test;
+
+fn mangle_active_journal(folder: &std::path::Path, needle: &[u8]) -> fjall::Result<()> {
+    let path = folder.join("journals").join("0");
+
+    let mut bytes = std::fs::read(&path)?;
+    let pos = find(&bytes, needle);
+    bytes[pos] ^= 0xFF;
+    std::fs::write(&path, &bytes)?;
+
+    Ok(())
+}
+
+/// Simulates a write that did not reach the disk completely
+fn tear_active_journal(folder: &std::path::Path, needle: &[u8]) -> fjall::Result<()> {
+    let path = folder.join("journals").join("0");
+
+    let mut bytes = std::fs::read(&path)?;
+    let pos = find(&bytes, needle);
+    bytes[pos..].fill(0);
+    std::fs::write(&path, &bytes)?;
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_recovery_skip_invalid_batches() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "value-a")?;
+        partition.insert("b", "value-b")?;
+        partition.insert("c", "value-c")?;
+
+        keyspace.persist(PersistMode::SyncAll)?;
+    }
+
+    mangle_active_journal(folder.path(), b"value-b")?;
+
+    assert!(matches!(
+        Config::new(&folder).open(),
+        Err(fjall::Error::JournalRecovery(
+            RecoveryError::ChecksumMismatch
+        ))
+    ));
+
+    {
+        let keyspace = Config::new(&folder)
+            .journal_recovery_mode(RecoveryMode::SkipInvalidBatches)
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(b"value-a", &*partition.get("a")?.unwrap());
+        assert!(partition.get("b")?.is_none());
+        assert_eq!(b"value-c", &*partition.get("c")?.unwrap());
+
+        let skipped = keyspace.skipped_batches();
+        assert_eq!(1, skipped.len());
+        assert_eq!(RecoveryError::ChecksumMismatch, skipped[0].error);
+        assert!(skipped[0].offset > 0);
+
+        // NOTE: The skipped batch is kept in a copy of the journal
+        assert_eq!(
+            folder.path().join("salvaged_journals").join("0"),
+            skipped[0].path,
+        );
+        let bytes = std::fs::read(&skipped[0].path)?;
+        let range = skipped[0].offset as usize..(skipped[0].offset + skipped[0].len) as usize;
+        find(&bytes[range], b"alue-b");
+
+        partition.insert("d", "value-d")?;
+        keyspace.persist(PersistMode::SyncAll)?;
+    }
+
+    // NOTE: The skipped batch was removed from the journal,
+    // so other recovery modes do not stop at it anymore
+    for recovery_mode in [
+        RecoveryMode::SkipInvalidBatches,
+        RecoveryMode::TolerateCorruptTail,
+        RecoveryMode::AbsoluteConsistency,
+    ] {
+        let keyspace = Config::new(&folder)
+            .journal_recovery_mode(recovery_mode)
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(b"value-a", &*partition.get("a")?.unwrap());
+        assert!(partition.get("b")?.is_none());
+        assert_eq!(b"value-c", &*partition.get("c")?.unwrap());
+        assert_eq!(b"value-d", &*partition.get("d")?.unwrap());
+
+        assert!(keyspace.skipped_batches().is_empty());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_recovery_no_skipped_batches() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        partition.insert("a", "a")?;
+    }
+
+    let keyspace = Config::new(&folder)
+        .journal_recovery_mode(RecoveryMode::SkipInvalidBatches)
+        .open()?;
+    assert!(keyspace.skipped_batches().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_recovery_absolute_consistency() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "value-a")?;
+        partition.insert("b", "value-b")?;
+
+        keyspace.persist(PersistMode::SyncAll)?;
+    }
+
+    for _ in 0..3 {
+        let keyspace = Config::new(&folder)
+            .journal_recovery_mode(RecoveryMode::AbsoluteConsistency)
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        assert_eq!(2, partition.len()?);
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        partition.insert("c", "value-c")?;
+        keyspace.persist(PersistMode::SyncAll)?;
+    }
+
+    tear_active_journal(folder.path(), b"value-c")?;
+
+    for _ in 0..3 {
+        assert!(matches!(
+            Config::new(&folder)
+                .journal_recovery_mode(RecoveryMode::AbsoluteConsistency)
+                .open(),
+            Err(fjall::Error::JournalRecovery(
+                RecoveryError::MissingTerminator
+            ))
+        ));
+    }
+
+    // NOTE: The default mode discards the incomplete batch
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(2, partition.len()?);
+    assert!(partition.get("c")?.is_none());
+
+    let skipped = keyspace.skipped_batches();
+    assert_eq!(1, skipped.len());
+    assert_eq!(RecoveryError::MissingTerminator, skipped[0].error);
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_recovery_absolute_consistency_checksum() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        partition.insert("a", "value-a")?;
+        keyspace.persist(PersistMode::SyncAll)?;
+    }
+
+    mangle_active_journal(folder.path(), b"value-a")?;
+
+    assert!(matches!(
+        Config::new(&folder)
+            .journal_recovery_mode(RecoveryMode::AbsoluteConsistency)
+            .open(),
+        Err(fjall::Error::JournalRecovery(
+            RecoveryError::ChecksumMismatch
+        ))
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use fjall::{Config, KvSeparationOptions, PartitionCreateOptions};
+use test_log::test;
+
+#[test]
+fn secondary_catch_up() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let secondary_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "1")?;
+    partition.rotate_memtable_and_wait()?;
+    partition.insert("b", "2")?;
+
+    let secondary = Config::new(&secondary_folder).open_secondary(&folder)?;
+    let secondary_partition =
+        secondary.open_partition("default", PartitionCreateOptions::default())?;
+
+    assert_eq!(2, secondary_partition.len()?);
+    assert_eq!(keyspace.instant(), secondary.instant());
+
+    partition.insert("c", "3")?;
+    partition.remove("a")?;
+    partition.rotate_memtable_and_wait()?;
+    partition.insert("d", "4")?;
+    partition.remove_range("b".."c")?;
+
+    assert_eq!(2, secondary_partition.len()?);
+
+    secondary.try_catch_up()?;
+
+    assert_eq!(2, secondary_partition.len()?);
+    assert!(secondary_partition.contains_key("c")?);
+    assert!(secondary_partition.contains_key("d")?);
+    assert_eq!(keyspace.instant(), secondary.instant());
+
+    let fjall::AnyTree::Standard(tree) = &partition.tree else {
+        unreachable!();
+    };
+    tree.major_compact(u64::MAX, u64::MAX)?;
+    secondary.try_catch_up()?;
+
+    assert_eq!(2, secondary_partition.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn secondary_read_only() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let secondary_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    partition.insert("a", "1")?;
+
+    let secondary = Config::new(&secondary_folder).open_secondary(&folder)?;
+    let secondary_partition =
+        secondary.open_partition("default", PartitionCreateOptions::default())?;
+
+    assert!(matches!(
+        secondary_partition.insert("b", "2"),
+        Err(fjall::Error::ReadOnly)
+    ));
+    assert!(matches!(
+        secondary_partition.remove("a"),
+        Err(fjall::Error::ReadOnly)
+    ));
+
+    let mut batch = secondary.batch();
+    batch.insert(&secondary_partition, "b", "2");
+    assert!(matches!(batch.commit(), Err(fjall::Error::ReadOnly)));
+
+    assert!(matches!(
+        secondary.open_partition("other", PartitionCreateOptions::default()),
+        Err(fjall::Error::ReadOnly)
+    ));
+    assert!(matches!(
+        secondary.delete_partition(secondary_partition.clone()),
+        Err(fjall::Error::ReadOnly)
+    ));
+
+    assert_eq!(1, secondary_partition.len()?);
+    assert_eq!(1, partition.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn secondary_partitions_created_and_deleted() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let secondary_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    partition.insert("a", "1")?;
+
+    let secondary = Config::new(&secondary_folder).open_secondary(&folder)?;
+    assert_eq!(1, secondary.partition_count());
+    let secondary_partition =
+        secondary.open_partition("default", PartitionCreateOptions::default())?;
+
+    let other = keyspace.open_partition("other", PartitionCreateOptions::default())?;
+    other.insert("b", "2")?;
+    keyspace.delete_partition(partition)?;
+
+    secondary.try_catch_up()?;
+
+    assert!(!secondary.partition_exists("default"));
+    assert!(matches!(
+        secondary_partition.insert("a", "1"),
+        Err(fjall::Error::PartitionDeleted)
+    ));
+
+    let secondary_other = secondary.open_partition("other", PartitionCreateOptions::default())?;
+    assert_eq!(1, secondary_other.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn secondary_blob_partition() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let secondary_folder = tempfile::tempdir()?;
+
+    let big_value = "a".repeat(10_000);
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition(
+        "blobs",
+        PartitionCreateOptions::default().with_kv_separation(KvSeparationOptions::default()),
+    )?;
+
+    partition.insert("a", &big_value)?;
+    partition.rotate_memtable_and_wait()?;
+
+    let secondary = Config::new(&secondary_folder).open_secondary(&folder)?;
+    let secondary_partition = secondary.open_partition(
+        "blobs",
+        PartitionCreateOptions::default().with_kv_separation(KvSeparationOptions::default()),
+    )?;
+
+    assert_eq!(
+        big_value.as_bytes(),
+        &*secondary_partition.get("a")?.unwrap()
+    );
+
+    partition.insert("b", &big_value)?;
+    partition.rotate_memtable_and_wait()?;
+
+    secondary.try_catch_up()?;
+
+    assert_eq!(
+        big_value.as_bytes(),
+        &*secondary_partition.get("a")?.unwrap()
+    );
+    assert_eq!(
+        big_value.as_bytes(),
+        &*secondary_partition.get("b")?.unwrap()
+    );
+
+    Ok(())
+}
+
+#[test]
+fn secondary_rejects_keyspace_folder() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let other_folder = tempfile::tempdir()?;
+
+    let _keyspace = Config::new(&folder).open()?;
+    let _other = Config::new(&other_folder).open()?;
+
+    assert!(Config::new(&other_folder).open_secondary(&folder).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use common::Append;
+use fjall::{ChangeBatch, ChangeKind, Config, PartitionCreateOptions};
+use std::sync::{mpsc::Receiver, Arc};
+use test_log::test;
+
+/// Collects the received batches as `(seqno, [(key, kind)])`
+fn drain(receiver: &Receiver<ChangeBatch>) -> Vec<(u64, Vec<(String, ChangeKind)>)> {
+    receiver
+        .try_iter()
+        .map(|(seqno, changes)| {
+            let changes = changes
+                .into_iter()
+                .map(|(_, key, _, kind)| (String::from_utf8_lossy(&key).to_string(), kind))
+                .collect();
+
+            (seqno, changes)
+        })
+        .collect()
+}
+
+#[test]
+fn keyspace_subscribe() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition(
+        "default",
+        PartitionCreateOptions::default().with_merge_operator(Arc::new(Append)),
+    )?;
+
+    partition.insert("before", "abc")?;
+
+    let receiver = keyspace.subscribe();
+
+    partition.insert("a", "abc")?;
+    partition.remove("a")?;
+
+    let mut batch = keyspace.batch();
+    batch.insert(&partition, "b", "def");
+    batch.remove(&partition, "c");
+    batch.commit()?;
+
+    partition.merge("m", "1")?;
+    partition.remove_range("x".."y")?;
+
+    let mut batch = keyspace.batch();
+    batch.remove_range(&partition, "y"..);
+    batch.insert(&partition, "z", "ghi");
+    batch.commit()?;
+
+    partition.clear()?;
+
+    // NOTE: Empty batches are not sent
+    keyspace.batch().commit()?;
+
+    assert_eq!(
+        vec![
+            (1, vec![("a".into(), ChangeKind::Value)]),
+            (2, vec![("a".into(), ChangeKind::Tombstone)]),
+            (
+                3,
+                vec![
+                    ("b".into(), ChangeKind::Value),
+                    ("c".into(), ChangeKind::Tombstone)
+                ]
+            ),
+            (4, vec![("m".into(), ChangeKind::MergeOperand)]),
+            (
+                5,
+                vec![("x".into(), ChangeKind::RangeTombstone(Some("y".into())))]
+            ),
+            (
+                6,
+                vec![
+                    ("y".into(), ChangeKind::RangeTombstone(None)),
+                    ("z".into(), ChangeKind::Value)
+                ]
+            ),
+            (7, vec![("".into(), ChangeKind::RangeTombstone(None))]),
+        ],
+        drain(&receiver),
+    );
+
+    drop(receiver);
+    partition.insert("after", "abc")?;
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_subscribe_from() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).flush_workers(0).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "abc")?;
+        partition.insert("b", "abc")?;
+        partition.rotate_memtable()?;
+        partition.insert("c", "abc")?;
+        assert_eq!(2, keyspace.journal_count());
+    }
+
+    let keyspace = Config::new(&folder).flush_workers(0).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let receiver = keyspace.subscribe_from(1)?;
+    partition.insert("d", "abc")?;
+
+    assert_eq!(
+        vec![
+            (1, vec![("b".into(), ChangeKind::Value)]),
+            (2, vec![("c".into(), ChangeKind::Value)]),
+            (3, vec![("d".into(), ChangeKind::Value)]),
+        ],
+        drain(&receiver),
+    );
+
+    Ok(())
+}
+
+#[test]
+fn keyspace_subscribe_from_concurrent() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for idx in 0..1_000u32 {
+        partition.insert(idx.to_be_bytes(), "abc")?;
+    }
+
+    let writer = std::thread::spawn({
+        let partition = partition.clone();
+
+        move || -> fjall::Result<()> {
+            for idx in 1_000..2_000u32 {
+                partition.insert(idx.to_be_bytes(), "abc")?;
+            }
+            Ok(())
+        }
+    });
+
+    let receiver = keyspace.subscribe_from(0)?;
+
+    writer.join().expect("should join")?;
+
+    // NOTE: Every batch is received exactly once, in commit order
+    let seqnos = receiver
+        .try_iter()
+        .map(|(seqno, _)| seqno)
+        .collect::<Vec<_>>();
+    assert_eq!((0..2_000).collect::<Vec<_>>(), seqnos);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
+fn tx_keyspace_subscribe() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let receiver = keyspace.subscribe();
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&partition, "a", "abc");
+    tx.insert(&partition, "b", "abc");
+    tx.commit()?;
+
+    partition.insert("c", "abc")?;
+
+    let batches = drain(&receiver);
+    assert_eq!(2, batches.len());
+    assert_eq!(
+        vec![
+            ("a".into(), ChangeKind::Value),
+            ("b".into(), ChangeKind::Value)
+        ],
+        batches.first().unwrap().1,
+    );
+    assert_eq!(
+        vec![("c".into(), ChangeKind::Value)],
+        batches.get(1).unwrap().1,
+    );
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use fjall::{Config, KvSeparationOptions, PartitionCreateOptions};
+use test_log::test;
+
+#[test]
+fn partition_clear() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        for x in 0..100u32 {
+            partition.insert(x.to_be_bytes(), x.to_string())?;
+        }
+        partition.rotate_memtable_and_wait()?;
+
+        for x in 100..200u32 {
+            partition.insert(x.to_be_bytes(), x.to_string())?;
+        }
+
+        assert_eq!(1, partition.segment_count());
+        assert_eq!(200, partition.len()?);
+
+        partition.clear()?;
+
+        assert!(partition.is_empty()?);
+        assert!(partition.get(5u32.to_be_bytes())?.is_none());
+        assert_eq!(0, partition.segment_count());
+        assert_eq!(0, partition.disk_space());
+
+        // NOTE: Sealed journals must not be held back by the dropped segments
+        for _ in 0..100 {
+            if keyspace.journal_count() == 1 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        assert_eq!(1, keyspace.journal_count());
+
+        // NOTE: The handle stays usable
+        partition.insert("a", "a")?;
+        assert_eq!(1, partition.len()?);
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(1, partition.len()?);
+        assert!(partition.contains_key("a")?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_clear_recover_from_journal() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).flush_workers(0).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        for x in 0..100u32 {
+            partition.insert(x.to_be_bytes(), x.to_string())?;
+        }
+
+        partition.clear()?;
+        partition.insert("a", "a")?;
+
+        assert_eq!(1, partition.len()?);
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        // NOTE: The journal still contains the old data,
+        // but it must not be resurrected
+        assert_eq!(1, partition.len()?);
+        assert!(partition.contains_key("a")?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_clear_snapshot() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for x in 0..100u32 {
+        partition.insert(x.to_be_bytes(), x.to_string())?;
+    }
+    partition.rotate_memtable_and_wait()?;
+
+    let snapshot = partition.snapshot();
+
+    partition.clear()?;
+    assert!(partition.is_empty()?);
+
+    // NOTE: The snapshot still needs the data
+    assert_eq!(100, snapshot.len()?);
+    assert_eq!(1, partition.segment_count());
+
+    drop(snapshot);
+
+    // NOTE: Clearing again now reclaims the disk space
+    partition.clear()?;
+    assert_eq!(0, partition.segment_count());
+
+    Ok(())
+}
+
+#[test]
+fn partition_clear_blob() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition(
+        "default",
+        PartitionCreateOptions::default().with_kv_separation(KvSeparationOptions::default()),
+    )?;
+
+    for x in 0..100u32 {
+        partition.insert(x.to_be_bytes(), x.to_string().repeat(1_000))?;
+    }
+    partition.rotate_memtable_and_wait()?;
+
+    if let fjall::AnyTree::Blob(tree) = &partition.tree {
+        assert_eq!(1, tree.blobs.segment_count());
+    } else {
+        panic!("should be blob tree");
+    }
+
+    partition.clear()?;
+
+    assert!(partition.is_empty()?);
+    assert_eq!(0, partition.disk_space());
+
+    if let fjall::AnyTree::Blob(tree) = &partition.tree {
+        assert_eq!(0, tree.blobs.segment_count());
+    } else {
+        panic!("should be blob tree");
+    }
+
+    partition.insert("a", "a".repeat(10_000))?;
+    assert_eq!(1, partition.len()?);
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+mod common;
+
+use common::Append;
+use fjall::{
+    compaction::{CompactionFilter, Decision},
+    Config, KvSeparationOptions, PartitionCreateOptions,
+};
+use std::sync::Arc;
+use test_log::test;
+
+/// Drops items marked as deleted, and upgrades items of the old format
+struct Migrate;
+
+impl CompactionFilter for Migrate {
+    fn filter(&self, _: &[u8], value: &[u8]) -> Decision {
+        if value == b"deleted" {
+            Decision::Remove
+        } else if let Some(value) = value.strip_prefix(b"v1:") {
+            let mut upgraded = b"v2:".to_vec();
+            upgraded.extend_from_slice(value);
+            Decision::Change(upgraded)
+        } else {
+            Decision::Keep
+        }
+    }
+}
+
+fn options() -> PartitionCreateOptions {
+    PartitionCreateOptions::default().with_compaction_filter(Arc::new(Migrate))
+}
+
+#[test]
+fn partition_compaction_filter() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", options())?;
+
+    partition.insert("a", "v1:a")?;
+    partition.insert("b", "deleted")?;
+    partition.insert("c", "v2:c")?;
+
+    // NOTE: The filter only runs once items are written to disk
+    assert_eq!(b"v1:a", &*partition.get("a")?.unwrap());
+    assert_eq!(b"deleted", &*partition.get("b")?.unwrap());
+
+    partition.rotate_memtable_and_wait()?;
+
+    assert_eq!(b"v2:a", &*partition.get("a")?.unwrap());
+    assert!(partition.get("b")?.is_none());
+    assert_eq!(b"v2:c", &*partition.get("c")?.unwrap());
+    assert_eq!(2, partition.len()?);
+
+    // NOTE: Removed items are dropped, and each changed item is written once
+    assert_eq!(2, partition.approximate_len());
+
+    Ok(())
+}
+
+#[test]
+fn partition_compaction_filter_no_resurrect() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", options())?;
+
+    partition.insert("a", "old")?;
+    partition.rotate_memtable_and_wait()?;
+
+    partition.insert("a", "deleted")?;
+    partition.rotate_memtable_and_wait()?;
+
+    // NOTE: The older version must not be revived
+    assert!(partition.get("a")?.is_none());
+
+    if let fjall::AnyTree::Standard(tree) = &partition.tree {
+        tree.major_compact(u64::MAX, u64::MAX)?;
+    }
+
+    assert!(partition.get("a")?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn partition_compaction_filter_reopen() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", options())?;
+        partition.insert("a", "v1:a")?;
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", options())?;
+
+        partition.insert("b", "deleted")?;
+        partition.rotate_memtable_and_wait()?;
+
+        assert_eq!(b"v2:a", &*partition.get("a")?.unwrap());
+        assert!(partition.get("b")?.is_none());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_compaction_filter_merge() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition =
+        keyspace.open_partition("default", options().with_merge_operator(Arc::new(Append)))?;
+
+    partition.merge("a", "v1:")?;
+    partition.merge("a", "x")?;
+    partition.merge("b", "dele")?;
+    partition.merge("b", "ted")?;
+    partition.rotate_memtable_and_wait()?;
+
+    // NOTE: The filter sees the merged values
+    assert_eq!(b"v2:x", &*partition.get("a")?.unwrap());
+    assert!(partition.get("b")?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn partition_compaction_filter_blob() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition(
+        "default",
+        options().with_kv_separation(KvSeparationOptions::default().separation_threshold(1)),
+    )?;
+
+    partition.insert("a", "v1:a")?;
+    partition.insert("b", "deleted")?;
+    partition.rotate_memtable_and_wait()?;
+
+    assert_eq!(b"v2:a", &*partition.get("a")?.unwrap());
+    assert!(partition.get("b")?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn partition_compaction_filter_existing_segment() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "v1:a")?;
+        partition.insert("b", "deleted")?;
+        partition.rotate_memtable_and_wait()?;
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", options())?;
+
+        partition.insert("c", "v1:c")?;
+        partition.rotate_memtable_and_wait()?;
+
+        // NOTE: Items that were flushed before the filter was set are filtered when compacted
+        assert_eq!(b"v1:a", &*partition.get("a")?.unwrap());
+        assert_eq!(b"deleted", &*partition.get("b")?.unwrap());
+        assert_eq!(b"v2:c", &*partition.get("c")?.unwrap());
+
+        partition.major_compact()?;
+
+        assert_eq!(b"v2:a", &*partition.get("a")?.unwrap());
+        assert!(partition.get("b")?.is_none());
+        assert_eq!(b"v2:c", &*partition.get("c")?.unwrap());
+
+        // NOTE: Filtering again does not change anything
+        partition.rotate_memtable_and_wait()?;
+        partition.major_compact()?;
+
+        assert_eq!(b"v2:a", &*partition.get("a")?.unwrap());
+        assert!(partition.get("b")?.is_none());
+        assert_eq!(b"v2:c", &*partition.get("c")?.unwrap());
+        assert_eq!(2, partition.len()?);
+    }
+
+    Ok(())
+}
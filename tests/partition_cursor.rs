@@ -0,0 +1,120 @@
+use fjall::{Config, PartitionCreateOptions};
+use test_log::test;
+
+#[test]
+fn partition_cursor() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for key in ["a", "c", "e", "g"] {
+        partition.insert(key, key)?;
+    }
+    partition.rotate_memtable_and_wait()?;
+
+    partition.insert("b", "b")?;
+    partition.remove("e")?;
+
+    let mut cursor = partition.cursor();
+    assert!(!cursor.is_valid());
+
+    let mut keys = vec![];
+    cursor.seek_to_first()?;
+    while cursor.is_valid() {
+        keys.push(cursor.key().unwrap().to_vec());
+        cursor.next()?;
+    }
+    assert_eq!(
+        vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec(), b"g".to_vec()],
+        keys
+    );
+
+    assert!(cursor.seek("d")?);
+    assert_eq!(b"g", &**cursor.key().unwrap());
+    assert!(cursor.prev()?);
+    assert_eq!(b"c", &**cursor.value().unwrap());
+
+    assert!(cursor.seek_for_prev("f")?);
+    assert_eq!(b"c", &**cursor.key().unwrap());
+    assert!(cursor.prev()?);
+    assert_eq!(b"b", &**cursor.key().unwrap());
+
+    assert!(cursor.seek_to_last()?);
+    assert!(!cursor.next()?);
+    assert!(!cursor.seek("h")?);
+
+    Ok(())
+}
+
+#[test]
+fn partition_cursor_snapshot() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "old")?;
+    let snapshot = partition.snapshot();
+
+    let mut cursor = partition.cursor();
+    partition.insert("a", "new")?;
+    partition.insert("b", "b")?;
+
+    // NOTE: The cursor is pinned to the moment it was opened
+    assert!(cursor.seek_to_first()?);
+    assert_eq!(b"old", &**cursor.value().unwrap());
+    assert!(!cursor.next()?);
+
+    let mut cursor = snapshot.cursor();
+    assert!(cursor.seek("a")?);
+    assert_eq!(b"old", &**cursor.value().unwrap());
+    assert!(!cursor.next()?);
+
+    let mut cursor = partition.cursor();
+    assert!(cursor.seek_to_last()?);
+    assert_eq!(b"b", &**cursor.key().unwrap());
+    assert!(cursor.prev()?);
+    assert_eq!(b"new", &**cursor.value().unwrap());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
+fn tx_cursor() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "a")?;
+    partition.insert("c", "c")?;
+
+    let read_tx = keyspace.read_tx();
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&partition, "b", "b");
+    tx.remove(&partition, "c");
+
+    {
+        let mut cursor = tx.cursor(&partition);
+        assert!(cursor.seek("a")?);
+        assert!(cursor.next()?);
+        assert_eq!(b"b", &**cursor.key().unwrap());
+        assert!(!cursor.next()?);
+    }
+
+    tx.commit()?;
+
+    let mut cursor = read_tx.cursor(&partition);
+    assert!(cursor.seek("b")?);
+    assert_eq!(b"c", &**cursor.key().unwrap());
+
+    let read_tx = keyspace.read_tx();
+    let mut cursor = read_tx.cursor(&partition);
+    assert!(cursor.seek_for_prev("c")?);
+    assert_eq!(b"b", &**cursor.key().unwrap());
+
+    Ok(())
+}
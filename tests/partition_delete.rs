@@ -69,7 +69,7 @@ fn partition_delete() -> fjall::Result<()> {
 }
 
 #[test]
-#[cfg(feature = "single_writer_tx")]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
 fn tx_partition_delete() -> fjall::Result<()> {
     let folder = tempfile::tempdir()?;
 
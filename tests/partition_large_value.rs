@@ -0,0 +1,184 @@
+use fjall::{
+    replication::{ChannelTransport, Primary, Replica},
+    Config, PartitionCreateOptions, PartitionHandle, TxKeyspace,
+};
+use test_log::test;
+
+const THRESHOLD: u64 = 1_000;
+
+fn large_value_count(partition: &PartitionHandle) -> fjall::Result<usize> {
+    let folder = partition.path().join("large_values");
+
+    if !folder.try_exists()? {
+        return Ok(0);
+    }
+
+    Ok(std::fs::read_dir(folder)?.count())
+}
+
+#[test]
+fn partition_large_value() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let large_value = "a".repeat(THRESHOLD as usize + 1);
+
+    {
+        let keyspace = Config::new(&folder)
+            .large_value_threshold(THRESHOLD)
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", &large_value)?;
+        partition.insert("b", "b")?;
+
+        let mut batch = keyspace.batch();
+        batch.insert(&partition, "c", &large_value);
+        batch.commit()?;
+
+        assert_eq!(2, large_value_count(&partition)?);
+        assert!(partition.disk_space() > 2 * THRESHOLD);
+
+        assert_eq!(large_value.as_bytes(), &*partition.get("a")?.unwrap());
+        assert_eq!(b"b", &*partition.get("b")?.unwrap());
+        assert_eq!(large_value.as_bytes(), &*partition.get("c")?.unwrap());
+
+        let values = partition
+            .iter()
+            .map(|kv| kv.map(|(_, v)| v.len()))
+            .collect::<fjall::Result<Vec<_>>>()?;
+        assert_eq!(vec![large_value.len(), 1, large_value.len()], values);
+    }
+
+    {
+        let keyspace = Config::new(&folder)
+            .large_value_threshold(THRESHOLD)
+            .open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(large_value.as_bytes(), &*partition.get("a")?.unwrap());
+        assert_eq!(b"b", &*partition.get("b")?.unwrap());
+        assert_eq!(large_value.as_bytes(), &*partition.get("c")?.unwrap());
+
+        partition.rotate_memtable_and_wait()?;
+        assert_eq!(large_value.as_bytes(), &*partition.get("a")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_large_value_overwrite() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder)
+        .large_value_threshold(THRESHOLD)
+        .open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "a".repeat(THRESHOLD as usize + 1))?;
+    partition.insert("b", "b".repeat(THRESHOLD as usize + 1))?;
+    partition.rotate_memtable_and_wait()?;
+
+    partition.insert("a", "a")?;
+    partition.remove("b")?;
+    partition.rotate_memtable_and_wait()?;
+    assert_eq!(2, large_value_count(&partition)?);
+
+    partition.major_compact()?;
+    assert_eq!(0, large_value_count(&partition)?);
+
+    assert_eq!(b"a", &*partition.get("a")?.unwrap());
+    assert!(partition.get("b")?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn partition_large_value_tx() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let large_value = "a".repeat(THRESHOLD as usize + 1);
+
+    let keyspace: TxKeyspace = Config::new(&folder)
+        .large_value_threshold(THRESHOLD)
+        .open_transactional()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&partition, "a", &large_value);
+    assert_eq!(large_value.as_bytes(), &*tx.get(&partition, "a")?.unwrap());
+    drop(tx);
+
+    assert_eq!(0, large_value_count(&partition.inner())?);
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&partition, "a", &large_value);
+    tx.commit()?;
+
+    assert_eq!(1, large_value_count(&partition.inner())?);
+    assert_eq!(large_value.as_bytes(), &*partition.get("a")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn partition_large_value_checkpoint() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let checkpoint_folder = tempfile::tempdir()?;
+    let checkpoint_path = checkpoint_folder.path().join("checkpoint");
+
+    let large_value = "a".repeat(THRESHOLD as usize + 1);
+
+    let keyspace = Config::new(&folder)
+        .large_value_threshold(THRESHOLD)
+        .open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", &large_value)?;
+    keyspace.checkpoint(&checkpoint_path)?;
+
+    let keyspace = Config::new(&checkpoint_path).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(large_value.as_bytes(), &*partition.get("a")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn partition_large_value_replication() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let replica_folder = tempfile::tempdir()?;
+
+    let large_value = "a".repeat(THRESHOLD as usize + 1);
+
+    let keyspace = Config::new(&folder)
+        .large_value_threshold(THRESHOLD)
+        .open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    partition.insert("a", &large_value)?;
+
+    let (primary_transport, replica_transport) = ChannelTransport::pair();
+    let primary = Primary::new(keyspace.clone());
+    std::thread::spawn(move || primary.serve(primary_transport));
+
+    let mut replica = Replica::open(Config::new(&replica_folder), replica_transport)?;
+    let subscription = replica.keyspace().subscribe();
+
+    partition.insert("b", &large_value)?;
+    assert!(replica.apply_next()?);
+    assert!(replica.apply_next()?);
+
+    let replicated = replica
+        .keyspace()
+        .open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(large_value.as_bytes(), &*replicated.get("a")?.unwrap());
+    assert_eq!(large_value.as_bytes(), &*replicated.get("b")?.unwrap());
+
+    for (_, changes) in subscription.try_iter() {
+        for (_, _, value, _) in changes {
+            assert_eq!(large_value.as_bytes(), &*value);
+        }
+    }
+
+    Ok(())
+}
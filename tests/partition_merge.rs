@@ -0,0 +1,216 @@
+mod common;
+
+use common::Append;
+use fjall::{Config, KvSeparationOptions, PartitionCreateOptions};
+use std::sync::Arc;
+use test_log::test;
+
+fn options() -> PartitionCreateOptions {
+    PartitionCreateOptions::default().with_merge_operator(Arc::new(Append))
+}
+
+#[test]
+fn partition_merge() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", options())?;
+
+    partition.merge("a", "1")?;
+    partition.merge("a", "2")?;
+    partition.insert("b", "x")?;
+    partition.merge("b", "y")?;
+    partition.merge("c", "3")?;
+
+    assert_eq!(b"12", &*partition.get("a")?.unwrap());
+    assert_eq!(b"xy", &*partition.get("b")?.unwrap());
+    assert_eq!(b"3", &*partition.get("c")?.unwrap());
+
+    let items = partition
+        .iter()
+        .map(|x| x.map(|(_, v)| v.to_vec()))
+        .collect::<fjall::Result<Vec<_>>>()?;
+    assert_eq!(vec![b"12".to_vec(), b"xy".to_vec(), b"3".to_vec()], items);
+
+    let (_, value) = partition.last_key_value()?.unwrap();
+    assert_eq!(b"3", &*value);
+
+    partition.insert("a", "new")?;
+    assert_eq!(b"new", &*partition.get("a")?.unwrap());
+
+    partition.remove("b")?;
+    partition.merge("b", "z")?;
+    assert_eq!(b"z", &*partition.get("b")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn partition_merge_flush() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", options())?;
+
+    partition.insert("a", "0")?;
+    partition.merge("a", "1")?;
+    partition.rotate_memtable_and_wait()?;
+
+    assert_eq!(b"01", &*partition.get("a")?.unwrap());
+
+    partition.merge("a", "2")?;
+    partition.merge("a", "3")?;
+    assert_eq!(b"0123", &*partition.get("a")?.unwrap());
+
+    partition.rotate_memtable_and_wait()?;
+    assert_eq!(b"0123", &*partition.get("a")?.unwrap());
+
+    assert_eq!(2, partition.segment_count());
+    assert_eq!(1, partition.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn partition_merge_snapshot() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", options())?;
+
+    partition.merge("a", "1")?;
+    let snapshot = partition.snapshot();
+
+    partition.merge("a", "2")?;
+    assert_eq!(b"12", &*partition.get("a")?.unwrap());
+    assert_eq!(b"1", &*snapshot.get("a")?.unwrap());
+
+    partition.rotate_memtable_and_wait()?;
+    assert_eq!(b"12", &*partition.get("a")?.unwrap());
+    assert_eq!(b"1", &*snapshot.get("a")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn partition_merge_range_delete() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", options())?;
+
+    partition.merge("a", "1")?;
+    partition.remove_range("a"..="z")?;
+    partition.merge("a", "2")?;
+    assert_eq!(b"2", &*partition.get("a")?.unwrap());
+
+    partition.rotate_memtable_and_wait()?;
+    assert_eq!(b"2", &*partition.get("a")?.unwrap());
+
+    partition.clear()?;
+    partition.merge("a", "3")?;
+    assert_eq!(b"3", &*partition.get("a")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn partition_merge_recover() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", options())?;
+
+        partition.insert("a", "0")?;
+        partition.merge("a", "1")?;
+        partition.rotate_memtable_and_wait()?;
+
+        partition.merge("a", "2")?;
+        partition.merge("b", "3")?;
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", options())?;
+
+        assert_eq!(b"012", &*partition.get("a")?.unwrap());
+        assert_eq!(b"3", &*partition.get("b")?.unwrap());
+
+        partition.rotate_memtable_and_wait()?;
+        assert_eq!(b"012", &*partition.get("a")?.unwrap());
+        assert_eq!(b"3", &*partition.get("b")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_merge_recover_sealed() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).flush_workers(0).open()?;
+        let partition = keyspace.open_partition("default", options())?;
+
+        partition.merge("a", "1")?;
+        partition.rotate_memtable()?;
+        partition.merge("a", "2")?;
+
+        assert_eq!(2, keyspace.journal_count());
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", options())?;
+
+        assert_eq!(b"12", &*partition.get("a")?.unwrap());
+
+        partition.rotate_memtable_and_wait()?;
+        assert_eq!(b"12", &*partition.get("a")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_merge_blob() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition(
+        "default",
+        options().with_kv_separation(KvSeparationOptions::default().separation_threshold(1)),
+    )?;
+
+    partition.insert("a", "0")?;
+    partition.merge("a", "1")?;
+    assert_eq!(b"01", &*partition.get("a")?.unwrap());
+
+    partition.rotate_memtable_and_wait()?;
+    assert_eq!(b"01", &*partition.get("a")?.unwrap());
+
+    partition.merge("a", "2")?;
+    assert_eq!(b"012", &*partition.get("a")?.unwrap());
+
+    let (_, value) = partition.first_key_value()?.unwrap();
+    assert_eq!(b"012", &*value);
+
+    Ok(())
+}
+
+#[test]
+fn partition_merge_missing_operator() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    assert!(matches!(
+        partition.merge("a", "1"),
+        Err(fjall::Error::MissingMergeOperator)
+    ));
+    assert!(partition.get("a")?.is_none());
+
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use fjall::{Config, KvSeparationOptions, PartitionCreateOptions};
+use test_log::test;
+
+#[test]
+fn partition_range_delete() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for key in ["a", "b", "c", "d", "e"] {
+        partition.insert(key, key)?;
+    }
+
+    let snapshot = partition.snapshot();
+
+    partition.remove_range("b"..="d")?;
+
+    assert_eq!(2, partition.len()?);
+    assert!(partition.contains_key("a")?);
+    assert!(partition.get("b")?.is_none());
+    assert!(!partition.contains_key("c")?);
+    assert!(partition.get("d")?.is_none());
+    assert!(partition.contains_key("e")?);
+    assert_eq!(
+        vec![b"e".to_vec(), b"a".to_vec()],
+        partition
+            .keys()
+            .rev()
+            .map(|x| x.map(|k| k.to_vec()))
+            .collect::<fjall::Result<Vec<_>>>()?,
+    );
+    assert_eq!(0, partition.prefix("c").count());
+    assert_eq!(1, partition.range("b"..).count());
+
+    // NOTE: Snapshot was taken before the range delete
+    assert_eq!(5, snapshot.len()?);
+    assert!(snapshot.contains_key("c")?);
+
+    // NOTE: Later writes are not covered
+    partition.insert("c", "c2")?;
+    assert_eq!(b"c2", &*partition.get("c")?.unwrap());
+    assert_eq!(3, partition.len()?);
+
+    partition.remove_range::<&str, _>(..)?;
+    assert!(partition.is_empty()?);
+    assert!(partition.first_key_value()?.is_none());
+    assert!(partition.last_key_value()?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn partition_range_delete_flushed() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition(
+        "default",
+        PartitionCreateOptions::default().with_kv_separation(KvSeparationOptions::default()),
+    )?;
+
+    for x in 0..100u32 {
+        partition.insert(x.to_be_bytes(), x.to_string().repeat(1_000))?;
+    }
+    partition.rotate_memtable_and_wait()?;
+
+    partition.remove_range(10u32.to_be_bytes()..90u32.to_be_bytes())?;
+    assert_eq!(20, partition.len()?);
+
+    for x in 0..100u32 {
+        partition.insert(x.to_be_bytes(), "new")?;
+    }
+    partition.rotate_memtable_and_wait()?;
+
+    assert_eq!(100, partition.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn partition_range_delete_recover() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        for key in ["a", "b", "c", "d", "e"] {
+            partition.insert(key, key)?;
+        }
+        partition.rotate_memtable_and_wait()?;
+
+        partition.remove_range("b"..="d")?;
+        partition.insert("c", "c2")?;
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(3, partition.len()?);
+        assert!(partition.get("b")?.is_none());
+        assert_eq!(b"c2", &*partition.get("c")?.unwrap());
+
+        // NOTE: Seqno needs to be recovered past the range tombstone,
+        // otherwise new writes would still be covered
+        partition.insert("b", "b2")?;
+        assert_eq!(b"b2", &*partition.get("b")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn batch_range_delete() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+        let other = keyspace.open_partition("other", PartitionCreateOptions::default())?;
+
+        for key in ["a", "b", "c", "d"] {
+            partition.insert(key, key)?;
+            other.insert(key, key)?;
+        }
+
+        let mut batch = keyspace.batch();
+        batch.insert(&partition, "bb", "discarded");
+        batch.insert(&other, "bb", "bb");
+        batch.remove_range(&partition, "b".."d");
+        batch.insert(&partition, "c", "c2");
+        batch.commit()?;
+
+        assert_eq!(3, partition.len()?);
+        assert!(partition.get("b")?.is_none());
+        assert!(partition.get("bb")?.is_none());
+        assert_eq!(b"c2", &*partition.get("c")?.unwrap());
+        assert_eq!(5, other.len()?);
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert_eq!(3, partition.len()?);
+        assert!(partition.get("b")?.is_none());
+        assert_eq!(b"c2", &*partition.get("c")?.unwrap());
+    }
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
+fn tx_range_delete() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for key in ["a", "b", "c", "d"] {
+        partition.insert(key, key)?;
+    }
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&partition, "bb", "discarded");
+
+    let savepoint = tx.savepoint();
+    tx.remove_range(&partition, "a"..="c");
+    assert_eq!(1, tx.len(&partition)?);
+
+    tx.rollback_to(savepoint);
+    assert_eq!(5, tx.len(&partition)?);
+
+    tx.remove_range(&partition, "b"..);
+    tx.insert(&partition, "c", "c2");
+
+    assert!(tx.get(&partition, "b")?.is_none());
+    assert!(tx.get(&partition, "bb")?.is_none());
+    assert_eq!(b"c2", &*tx.get(&partition, "c")?.unwrap());
+    assert_eq!(2, tx.len(&partition)?);
+
+    // NOTE: Not committed yet
+    assert_eq!(4, keyspace.read_tx().len(&partition)?);
+
+    tx.commit()?;
+
+    let read_tx = keyspace.read_tx();
+    assert_eq!(2, read_tx.len(&partition)?);
+    assert!(read_tx.get(&partition, "d")?.is_none());
+    assert_eq!(b"c2", &*read_tx.get(&partition, "c")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "ssi_tx")]
+fn tx_range_delete_conflict() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("c", "c")?;
+
+    let mut tx1 = keyspace.write_tx();
+    let mut tx2 = keyspace.write_tx();
+
+    assert!(tx1.get(&partition, "c")?.is_some());
+    tx1.insert(&partition, "x", "x");
+
+    tx2.remove_range(&partition, "b".."d");
+    tx2.commit()?;
+
+    assert!(matches!(tx1.commit(), Err(fjall::Error::Conflict)));
+
+    Ok(())
+}
+
+#[test]
+fn partition_range_delete_compaction() -> fjall::Result<()> {
+    use fjall::AbstractTree;
+    use std::time::Duration;
+
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for x in 0..100u32 {
+        partition.insert(x.to_be_bytes(), [0; 1_000])?;
+    }
+    partition.rotate_memtable_and_wait()?;
+
+    partition.remove_range(..90u32.to_be_bytes())?;
+    partition.insert("a", "a")?;
+    partition.rotate_memtable_and_wait()?;
+
+    assert_eq!(11, partition.len()?);
+    let disk_space = partition.disk_space();
+
+    // NOTE: Compaction runs after the flush, and writes point tombstones for the covered items
+    for _ in 0..100 {
+        if partition.tree.active_memtable_size() > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    partition.rotate_memtable_and_wait()?;
+
+    let fjall::AnyTree::Standard(tree) = &partition.tree else {
+        unreachable!();
+    };
+    tree.major_compact(u64::MAX, u64::MAX)?;
+
+    assert_eq!(11, partition.len()?);
+    assert!(partition.disk_space() < disk_space / 5);
+
+    // NOTE: Later writes are not covered
+    partition.insert(0u32.to_be_bytes(), "new")?;
+    assert_eq!(12, partition.len()?);
+
+    Ok(())
+}
@@ -0,0 +1,194 @@
+use fjall::{AbstractTree, Config, KvSeparationOptions, PartitionCreateOptions};
+use std::time::Duration;
+use test_log::test;
+
+const EXPIRED: Duration = Duration::ZERO;
+const LONG: Duration = Duration::from_secs(3_600);
+
+#[test]
+fn partition_ttl() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "a")?;
+    partition.insert_with_ttl("b", "b", EXPIRED)?;
+    partition.insert_with_ttl("c", "c", LONG)?;
+    partition.insert_with_ttl("d", "d", EXPIRED)?;
+
+    assert!(partition.get("b")?.is_none());
+    assert!(!partition.contains_key("d")?);
+    assert_eq!(b"c", &*partition.get("c")?.unwrap());
+
+    assert_eq!(2, partition.len()?);
+    assert_eq!(2, partition.range("a"..="z").count());
+    assert_eq!(0, partition.prefix("b").count());
+    assert_eq!(2, partition.keys().count());
+
+    // NOTE: Overwriting discards the expiry
+    partition.insert("b", "b2")?;
+    assert_eq!(b"b2", &*partition.get("b")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn partition_ttl_expires() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert_with_ttl("a", "a", Duration::from_millis(100))?;
+    assert!(partition.contains_key("a")?);
+
+    std::thread::sleep(Duration::from_millis(150));
+    assert!(!partition.contains_key("a")?);
+    assert!(partition.is_empty()?);
+
+    Ok(())
+}
+
+#[test]
+fn partition_ttl_batch() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let mut batch = keyspace.batch();
+    batch.insert_with_ttl(&partition, "a", "a", EXPIRED);
+    batch.insert_with_ttl(&partition, "b", "b", LONG);
+    batch.insert_with_ttl(&partition, "c", "c", EXPIRED);
+    batch.insert(&partition, "c", "c2");
+    batch.commit()?;
+
+    assert!(partition.get("a")?.is_none());
+    assert_eq!(b"b", &*partition.get("b")?.unwrap());
+    assert_eq!(b"c2", &*partition.get("c")?.unwrap());
+
+    Ok(())
+}
+
+#[test]
+fn partition_ttl_recover() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert_with_ttl("a", "a", EXPIRED)?;
+        partition.insert_with_ttl("b", "b", LONG)?;
+        partition.rotate_memtable_and_wait()?;
+
+        partition.insert_with_ttl("c", "c", EXPIRED)?;
+        partition.insert_with_ttl("d", "d", LONG)?;
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert!(partition.get("a")?.is_none());
+        assert!(partition.get("b")?.is_some());
+        assert!(partition.get("c")?.is_none());
+        assert!(partition.get("d")?.is_some());
+        assert_eq!(2, partition.len()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_ttl_compaction() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    for x in 0..100u32 {
+        partition.insert_with_ttl(x.to_be_bytes(), [0; 1_000], EXPIRED)?;
+    }
+    partition.insert("a", "a")?;
+    partition.rotate_memtable_and_wait()?;
+
+    assert_eq!(1, partition.len()?);
+    let disk_space = partition.disk_space();
+
+    // NOTE: Compaction runs after the flush, and writes tombstones for the expired items
+    for _ in 0..100 {
+        if partition.tree.active_memtable_size() > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    partition.rotate_memtable_and_wait()?;
+
+    let fjall::AnyTree::Standard(tree) = &partition.tree else {
+        unreachable!();
+    };
+    tree.major_compact(u64::MAX, u64::MAX)?;
+
+    assert_eq!(1, partition.len()?);
+    assert!(partition.disk_space() < disk_space / 10);
+
+    Ok(())
+}
+
+#[test]
+fn partition_ttl_blob() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition(
+        "default",
+        PartitionCreateOptions::default()
+            .with_kv_separation(KvSeparationOptions::default().separation_threshold(1)),
+    )?;
+
+    partition.insert_with_ttl("a", "a", EXPIRED)?;
+    partition.insert_with_ttl("b", "b", LONG)?;
+    partition.rotate_memtable_and_wait()?;
+
+    assert!(partition.get("a")?.is_none());
+    assert_eq!(b"b", &*partition.get("b")?.unwrap());
+    assert_eq!(1, partition.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn partition_ttl_tombstone_seqno() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert_with_ttl("a", "a", EXPIRED)?;
+    partition.insert("b", "b")?;
+    let seqno = keyspace.instant();
+
+    partition.rotate_memtable_and_wait()?;
+
+    // NOTE: Compaction runs after the flush, and writes tombstones for the expired items
+    for _ in 0..100 {
+        if partition.tree.active_memtable_size() > 0 {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let fjall::AnyTree::Standard(tree) = &partition.tree else {
+        unreachable!();
+    };
+
+    // NOTE: The tombstone takes a new seqno, so it cannot collide with another write
+    let tombstone = tree.get_internal_entry("a", false, None)?.unwrap();
+    assert!(tombstone.is_tombstone());
+    assert!(tombstone.key.seqno >= seqno);
+
+    Ok(())
+}
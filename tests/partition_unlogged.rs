@@ -0,0 +1,123 @@
+use fjall::{journal::inspect, Config, PartitionCreateOptions, PersistMode};
+use test_log::test;
+
+#[test]
+fn partition_unlogged_recover() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let logged = keyspace.open_partition("logged", PartitionCreateOptions::default())?;
+        let unlogged = keyspace.open_partition(
+            "unlogged",
+            PartitionCreateOptions::default().journaling(false),
+        )?;
+
+        unlogged.insert("flushed", "abc")?;
+        unlogged.rotate_memtable_and_wait()?;
+        assert_eq!(1, keyspace.journal_count());
+
+        logged.insert("a", "abc")?;
+        unlogged.insert("b", "abc")?;
+
+        let mut batch = keyspace.batch();
+        batch.insert(&logged, "c", "abc");
+        batch.insert(&unlogged, "d", "abc");
+        batch.commit()?;
+
+        keyspace.persist(PersistMode::SyncAll)?;
+
+        let journaled_partitions = inspect(folder.path().join("journals").join("0"))?
+            .map(|batch| Ok(batch?.entries))
+            .collect::<fjall::Result<Vec<_>>>()?
+            .into_iter()
+            .flatten()
+            .map(|entry| entry.partition().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["logged", "logged"], journaled_partitions);
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let logged = keyspace.open_partition("logged", PartitionCreateOptions::default())?;
+        let unlogged = keyspace.open_partition("unlogged", PartitionCreateOptions::default())?;
+
+        assert!(logged.contains_key("a")?);
+        assert!(logged.contains_key("c")?);
+
+        // NOTE: Only flushed data of unlogged partitions survives
+        assert!(unlogged.contains_key("flushed")?);
+        assert!(!unlogged.contains_key("b")?);
+        assert!(!unlogged.contains_key("d")?);
+
+        // NOTE: The partition stays unlogged, even if opened with default options
+        unlogged.insert("e", "abc")?;
+    }
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let unlogged = keyspace.open_partition("unlogged", PartitionCreateOptions::default())?;
+        assert!(!unlogged.contains_key("e")?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn partition_unlogged_journal_eviction() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let logged = keyspace.open_partition("logged", PartitionCreateOptions::default())?;
+    let unlogged = keyspace.open_partition(
+        "unlogged",
+        PartitionCreateOptions::default().journaling(false),
+    )?;
+
+    unlogged.insert("a", "abc")?;
+    logged.insert("a", "abc")?;
+    logged.rotate_memtable_and_wait()?;
+
+    // NOTE: The unlogged partition's memtable does not hold back the sealed journal
+    for _ in 0..100 {
+        if keyspace.journal_count() == 1 {
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+    assert_eq!(1, keyspace.journal_count());
+
+    assert!(unlogged.contains_key("a")?);
+
+    Ok(())
+}
+
+#[test]
+fn partition_unlogged_journal_does_not_grow() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let unlogged = keyspace.open_partition(
+        "unlogged",
+        PartitionCreateOptions::default().journaling(false),
+    )?;
+
+    let journal_path = folder.path().join("journals").join("0");
+    let journal_size = std::fs::metadata(&journal_path)?.len();
+
+    unlogged.insert("a", "abc")?;
+    unlogged.remove("a")?;
+
+    let mut batch = keyspace.batch().durability(Some(PersistMode::SyncAll));
+    batch.insert(&unlogged, "b", "abc");
+    batch.remove(&unlogged, "c");
+    batch.commit()?;
+
+    keyspace.persist(PersistMode::SyncAll)?;
+
+    assert_eq!(journal_size, std::fs::metadata(&journal_path)?.len());
+    assert_eq!(0, inspect(&journal_path)?.count());
+    assert!(unlogged.contains_key("b")?);
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+mod common;
+
+use common::Append;
+use fjall::{
+    replication::{ChannelTransport, Primary, Replica, ReplicationTransport, TcpTransport},
+    Config, Keyspace, PartitionCreateOptions,
+};
+use std::sync::Arc;
+use test_log::test;
+
+fn serve(keyspace: &Keyspace, transport: ChannelTransport) {
+    let primary = Primary::new(keyspace.clone());
+    std::thread::spawn(move || primary.serve(transport));
+}
+
+fn apply<T: ReplicationTransport>(replica: &mut Replica<T>, count: usize) -> fjall::Result<()> {
+    for _ in 0..count {
+        assert!(replica.apply_next()?);
+    }
+    Ok(())
+}
+
+#[test]
+fn replication_stream() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let replica_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    let merged = keyspace.open_partition(
+        "merged",
+        PartitionCreateOptions::default().with_merge_operator(Arc::new(Append)),
+    )?;
+
+    partition.insert("a", "1")?;
+    partition.insert("b", "2")?;
+
+    let (primary_transport, replica_transport) = ChannelTransport::pair();
+    serve(&keyspace, primary_transport);
+
+    let mut replica = Replica::open(Config::new(&replica_folder), replica_transport)?;
+    let replicated_merged = replica.keyspace().open_partition(
+        "merged",
+        PartitionCreateOptions::default().with_merge_operator(Arc::new(Append)),
+    )?;
+
+    partition.insert("c", "3")?;
+    partition.remove("a")?;
+    partition.remove_range("b".."c")?;
+    merged.merge("m", "x")?;
+    merged.merge("m", "y")?;
+
+    let mut batch = keyspace.batch();
+    batch.insert(&partition, "d", "4");
+    batch.insert(&merged, "n", "z");
+    batch.commit()?;
+
+    apply(&mut replica, 8)?;
+
+    let replicated = replica
+        .keyspace()
+        .open_partition("default", PartitionCreateOptions::default())?;
+
+    assert_eq!(2, replicated.len()?);
+    assert!(replicated.contains_key("c")?);
+    assert!(replicated.contains_key("d")?);
+    assert_eq!(b"xy", &*replicated_merged.get("m")?.unwrap());
+    assert_eq!(b"z", &*replicated_merged.get("n")?.unwrap());
+
+    assert_eq!(keyspace.instant(), replica.keyspace().instant());
+
+    Ok(())
+}
+
+#[test]
+fn replication_resume() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let replica_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "1")?;
+    partition.insert("b", "2")?;
+
+    {
+        let (primary_transport, replica_transport) = ChannelTransport::pair();
+        serve(&keyspace, primary_transport);
+
+        let mut replica = Replica::open(Config::new(&replica_folder), replica_transport)?;
+        apply(&mut replica, 2)?;
+    }
+
+    partition.insert("c", "3")?;
+
+    let (primary_transport, replica_transport) = ChannelTransport::pair();
+    serve(&keyspace, primary_transport);
+
+    // NOTE: Only the missing batch is sent
+    let mut replica = Replica::open(Config::new(&replica_folder), replica_transport)?;
+    apply(&mut replica, 1)?;
+
+    let replicated = replica
+        .keyspace()
+        .open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(3, replicated.len()?);
+
+    partition.insert("d", "4")?;
+    apply(&mut replica, 1)?;
+    assert_eq!(4, replicated.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn replication_checkpoint() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let replica_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "1")?;
+    partition.rotate_memtable_and_wait()?;
+    partition.insert("b", "2")?;
+
+    // NOTE: Once the first journal is deleted, a new replica cannot catch up from the journals
+    while keyspace.journal_count() > 1 {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+
+    let (primary_transport, replica_transport) = ChannelTransport::pair();
+    serve(&keyspace, primary_transport);
+
+    let mut replica = Replica::open(Config::new(&replica_folder), replica_transport)?;
+
+    let replicated = replica
+        .keyspace()
+        .open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(2, replicated.len()?);
+
+    partition.insert("c", "3")?;
+    apply(&mut replica, 1)?;
+    assert_eq!(3, replicated.len()?);
+
+    assert_eq!(keyspace.instant(), replica.keyspace().instant());
+
+    Ok(())
+}
+
+#[test]
+fn replication_replica_read_only() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let replica_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let (primary_transport, replica_transport) = ChannelTransport::pair();
+    serve(&keyspace, primary_transport);
+
+    let mut replica = Replica::open(Config::new(&replica_folder), replica_transport)?;
+    let replicated = replica
+        .keyspace()
+        .open_partition("default", PartitionCreateOptions::default())?;
+
+    assert!(matches!(
+        replicated.insert("a", "local"),
+        Err(fjall::Error::ReadOnly)
+    ));
+    assert!(matches!(
+        replicated.remove("a"),
+        Err(fjall::Error::ReadOnly)
+    ));
+
+    let mut batch = replica.keyspace().batch();
+    batch.insert(&replicated, "a", "local");
+    assert!(matches!(batch.commit(), Err(fjall::Error::ReadOnly)));
+
+    partition.insert("a", "1")?;
+    apply(&mut replica, 1)?;
+    assert_eq!(Some("1".as_bytes().into()), replicated.get("a")?);
+
+    Ok(())
+}
+
+#[test]
+fn replication_tcp() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let replica_folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    partition.insert("a", "1")?;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    let primary = Primary::new(keyspace.clone());
+    std::thread::spawn(move || -> fjall::Result<()> {
+        let (stream, _) = listener.accept()?;
+        primary.serve(TcpTransport::new(stream))
+    });
+
+    let mut replica = Replica::open(Config::new(&replica_folder), TcpTransport::connect(addr)?)?;
+
+    partition.insert("b", "2")?;
+    apply(&mut replica, 2)?;
+
+    let replicated = replica
+        .keyspace()
+        .open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(2, replicated.len()?);
+
+    Ok(())
+}
+
+#[test]
+fn replication_tcp_frame_too_large() -> fjall::Result<()> {
+    use std::io::Write;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    std::thread::spawn(move || -> std::io::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+
+        // NOTE: Announce a frame of 4 GiB, but never send it
+        stream.write_all(&u32::MAX.to_be_bytes())?;
+        stream.write_all(&[0; 16])?;
+        Ok(())
+    });
+
+    let mut transport = TcpTransport::connect(addr)?.with_max_frame_size(1_024);
+    assert!(transport.recv().is_err());
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 #[test_log::test]
-#[cfg(feature = "single_writer_tx")]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
 fn tx_ryow() -> fjall::Result<()> {
     use fjall::{Config, PartitionCreateOptions};
 
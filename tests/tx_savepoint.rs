@@ -0,0 +1,112 @@
+#[test_log::test]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
+fn tx_savepoint() -> fjall::Result<()> {
+    use fjall::{Config, PartitionCreateOptions};
+
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+
+    let tree = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    let tree2 = keyspace.open_partition("other", PartitionCreateOptions::default())?;
+
+    tree.insert("a", "old")?;
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&tree, "b", "b");
+
+    let sp1 = tx.savepoint();
+    tx.remove(&tree, "a");
+    tx.insert(&tree, "b", "b2");
+    tx.insert(&tree2, "c", "c");
+
+    let sp2 = tx.savepoint();
+    tx.insert(&tree, "d", "d");
+    assert!(tx.contains_key(&tree, "d")?);
+
+    tx.rollback_to(sp2);
+    assert!(!tx.contains_key(&tree, "d")?);
+    assert!(!tx.contains_key(&tree, "a")?);
+    assert_eq!(b"b2", &*tx.get(&tree, "b")?.unwrap());
+
+    tx.rollback_to(sp1);
+    assert_eq!(b"old", &*tx.get(&tree, "a")?.unwrap());
+    assert_eq!(b"b", &*tx.get(&tree, "b")?.unwrap());
+    assert!(!tx.contains_key(&tree2, "c")?);
+
+    // NOTE: Savepoint stays valid after rolling back to it
+    tx.insert(&tree, "e", "e");
+    tx.rollback_to(sp1);
+    assert!(!tx.contains_key(&tree, "e")?);
+
+    tx.commit()?;
+
+    assert_eq!(b"old", &*tree.get("a")?.unwrap());
+    assert_eq!(b"b", &*tree.get("b")?.unwrap());
+    assert!(tree.get("d")?.is_none());
+    assert!(tree.get("e")?.is_none());
+    assert!(keyspace.read_tx().is_empty(&tree2)?);
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
+#[should_panic(expected = "savepoint is invalid")]
+fn tx_savepoint_invalid() {
+    use fjall::{Config, PartitionCreateOptions};
+
+    let folder = tempfile::tempdir().unwrap();
+
+    let keyspace = Config::new(&folder).open_transactional().unwrap();
+
+    let tree = keyspace
+        .open_partition("default", PartitionCreateOptions::default())
+        .unwrap();
+
+    let mut tx = keyspace.write_tx();
+
+    let sp1 = tx.savepoint();
+    tx.insert(&tree, "a", "a");
+    let sp2 = tx.savepoint();
+
+    tx.rollback_to(sp1);
+    tx.rollback_to(sp2);
+}
+
+#[test_log::test]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
+fn tx_savepoint_rejected_write() -> fjall::Result<()> {
+    use fjall::{Config, PartitionCreateOptions};
+
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+    let tree = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let too_large = vec![b'a'; u16::MAX as usize + 1];
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&tree, "a", "a");
+
+    let sp = tx.savepoint();
+    tx.insert(&tree, &too_large, "b");
+
+    // NOTE: The rejected write is rolled back, so the transaction can still commit
+    tx.rollback_to(sp);
+    tx.commit()?;
+    assert_eq!(b"a", &*tree.get("a")?.unwrap());
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&tree, &too_large, "b");
+
+    let sp = tx.savepoint();
+    tx.insert(&tree, "b", "b");
+
+    // NOTE: Writes rejected before the savepoint still fail the commit
+    tx.rollback_to(sp);
+    assert!(matches!(tx.commit(), Err(fjall::Error::KeyTooLarge)));
+    assert!(tree.get("b")?.is_none());
+
+    Ok(())
+}
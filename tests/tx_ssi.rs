@@ -0,0 +1,138 @@
+#[test_log::test]
+#[cfg(feature = "ssi_tx")]
+fn tx_ssi_read_write_conflict() -> fjall::Result<()> {
+    use fjall::{Config, PartitionCreateOptions};
+
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+
+    let tree = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    tree.insert("a", "0")?;
+
+    let mut tx1 = keyspace.write_tx();
+    let mut tx2 = keyspace.write_tx();
+
+    assert_eq!(b"0", &*tx1.get(&tree, "a")?.unwrap());
+    tx1.insert(&tree, "b", "1");
+
+    tx2.insert(&tree, "a", "2");
+    tx2.commit()?;
+
+    assert!(matches!(tx1.commit(), Err(fjall::Error::Conflict)));
+
+    assert_eq!(b"2", &*tree.get("a")?.unwrap());
+    assert!(tree.get("b")?.is_none());
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(feature = "ssi_tx")]
+fn tx_ssi_range_conflict() -> fjall::Result<()> {
+    use fjall::{Config, PartitionCreateOptions};
+
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+
+    let tree = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let mut tx1 = keyspace.write_tx();
+    let mut tx2 = keyspace.write_tx();
+
+    // NOTE: Phantom read, the key did not exist when tx1 scanned the range
+    assert_eq!(0, tx1.prefix(&tree, "user#").count());
+    tx1.insert(&tree, "count", "0");
+
+    tx2.insert(&tree, "user#1", "a");
+    tx2.commit()?;
+
+    assert!(matches!(tx1.commit(), Err(fjall::Error::Conflict)));
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(feature = "ssi_tx")]
+fn tx_ssi_no_conflict() -> fjall::Result<()> {
+    use fjall::{Config, PartitionCreateOptions};
+
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+
+    let tree = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    tree.insert("a", "0")?;
+
+    let mut tx1 = keyspace.write_tx();
+    let mut tx2 = keyspace.write_tx();
+
+    assert_eq!(b"0", &*tx1.get(&tree, "a")?.unwrap());
+    tx1.insert(&tree, "a", "1");
+
+    // NOTE: Blind writes never conflict
+    tx2.insert(&tree, "b", "2");
+    tx2.insert(&tree, "a", "2");
+
+    tx1.commit()?;
+    tx2.commit()?;
+
+    assert_eq!(b"2", &*tree.get("a")?.unwrap());
+    assert_eq!(b"2", &*tree.get("b")?.unwrap());
+
+    // NOTE: Transaction started after the commit, so it sees the write
+    let mut tx3 = keyspace.write_tx();
+    assert_eq!(b"2", &*tx3.get(&tree, "a")?.unwrap());
+    tx3.insert(&tree, "a", "3");
+    tx3.commit()?;
+
+    assert_eq!(b"3", &*tree.get("a")?.unwrap());
+
+    Ok(())
+}
+
+#[test_log::test]
+#[cfg(feature = "ssi_tx")]
+fn tx_ssi_concurrent_counter() -> fjall::Result<()> {
+    use fjall::{Config, PartitionCreateOptions};
+
+    const THREADS: u64 = 4;
+    const INCREMENTS: u64 = 100;
+
+    let folder = tempfile::tempdir()?;
+
+    let keyspace = Config::new(&folder).open_transactional()?;
+
+    let tree = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    tree.insert("counter", 0_u64.to_be_bytes())?;
+
+    let threads = (0..THREADS)
+        .map(|_| {
+            let tree = tree.clone();
+
+            std::thread::spawn(move || -> fjall::Result<()> {
+                for _ in 0..INCREMENTS {
+                    tree.fetch_update("counter", |prev| {
+                        let mut buf = [0; 8];
+                        buf.copy_from_slice(prev.expect("should exist"));
+                        let next = u64::from_be_bytes(buf) + 1;
+                        Some(next.to_be_bytes().into())
+                    })?;
+                }
+
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+
+    for thread in threads {
+        thread.join().expect("should join")?;
+    }
+
+    let mut buf = [0; 8];
+    buf.copy_from_slice(&tree.get("counter")?.unwrap());
+    assert_eq!(THREADS * INCREMENTS, u64::from_be_bytes(buf));
+
+    Ok(())
+}
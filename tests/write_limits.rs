@@ -0,0 +1,105 @@
+use fjall::{Config, PartitionCreateOptions};
+use test_log::test;
+
+const MAX_KEY_SIZE: usize = 65_535;
+
+#[test]
+fn write_limits_partition_name() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let keyspace = Config::new(&folder).open()?;
+
+    for name in ["", "a/b", "a b", &"a".repeat(256)] {
+        assert!(matches!(
+            keyspace.open_partition(name, PartitionCreateOptions::default()),
+            Err(fjall::Error::InvalidPartitionName),
+        ));
+    }
+
+    keyspace.open_partition(&"a".repeat(255), PartitionCreateOptions::default())?;
+    assert_eq!(1, keyspace.partition_count());
+
+    Ok(())
+}
+
+#[test]
+fn write_limits_key_size() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let too_large = vec![b'a'; MAX_KEY_SIZE + 1];
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        assert!(matches!(
+            partition.insert(&too_large, "abc"),
+            Err(fjall::Error::KeyTooLarge),
+        ));
+        assert!(matches!(
+            partition.remove(&too_large),
+            Err(fjall::Error::KeyTooLarge),
+        ));
+        assert!(matches!(
+            partition.remove_range(&too_large[..]..),
+            Err(fjall::Error::KeyTooLarge),
+        ));
+
+        // NOTE: An invalid item fails the whole batch
+        let mut batch = keyspace.batch();
+        batch.insert(&partition, "a", "abc");
+        batch.insert(&partition, &too_large, "abc");
+        assert!(matches!(batch.commit(), Err(fjall::Error::KeyTooLarge)));
+
+        partition.insert(vec![b'a'; MAX_KEY_SIZE], "abc")?;
+        assert_eq!(1, partition.len()?);
+    }
+
+    // NOTE: Rejected writes do not end up in the journal
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(1, partition.len()?);
+    assert!(partition.contains_key(vec![b'a'; MAX_KEY_SIZE])?);
+
+    Ok(())
+}
+
+#[test]
+fn write_limits_range_max_key() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let max_key = vec![u8::MAX; MAX_KEY_SIZE];
+
+    {
+        let keyspace = Config::new(&folder).open()?;
+        let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+        partition.insert("a", "abc")?;
+        partition.insert(&max_key, "abc")?;
+
+        partition.remove_range(&b"b"[..]..=&max_key[..])?;
+        assert_eq!(1, partition.len()?);
+        assert!(!partition.contains_key(&max_key)?);
+    }
+
+    let keyspace = Config::new(&folder).open()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+    assert_eq!(1, partition.len()?);
+    assert!(partition.contains_key("a")?);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(any(feature = "single_writer_tx", feature = "ssi_tx"))]
+fn write_limits_tx_key_size() -> fjall::Result<()> {
+    let folder = tempfile::tempdir()?;
+    let keyspace = Config::new(&folder).open_transactional()?;
+    let partition = keyspace.open_partition("default", PartitionCreateOptions::default())?;
+
+    let mut tx = keyspace.write_tx();
+    tx.insert(&partition, "a", "abc");
+    tx.insert(&partition, vec![b'a'; MAX_KEY_SIZE + 1], "abc");
+    assert!(matches!(tx.commit(), Err(fjall::Error::KeyTooLarge)));
+
+    assert!(partition.inner().is_empty()?);
+
+    Ok(())
+}